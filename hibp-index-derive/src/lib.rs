@@ -0,0 +1,163 @@
+#![warn(missing_docs)]
+//! `#[derive(KeyData)]` for custom key types of `hibp-index`
+//!
+//! Use through `hibp_index::data::KeyData` (feature `derive`); see there for an example.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitInt, LitStr};
+
+/// Implement `KeyData` (and the traits it needs) for a wrapper of `[u8; N]`
+///
+/// Requires a `#[key_data(name = "...", size = N)]` attribute with the key type
+/// name (stored in index headers) and the key length in bytes. Also implements
+/// `Default`, `FromStr` (hex), `Debug`, `Display`, `LowerHex` and `UpperHex`
+/// (hex), `Deref` and `AsRef` (and their mutable variants); `Clone` must be
+/// derived separately.
+#[proc_macro_derive(KeyData, attributes(key_data))]
+pub fn derive_key_data(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	match expand(&input) {
+		Ok(output) => output.into(),
+		Err(e) => e.to_compile_error().into(),
+	}
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+	let single_field = match &input.data {
+		Data::Struct(data) => {
+			matches!(&data.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1)
+		},
+		_ => false,
+	};
+	if !single_field {
+		return Err(Error::new_spanned(
+			input,
+			"KeyData can only be derived for tuple structs with a single `[u8; N]` field",
+		));
+	}
+	if !input.generics.params.is_empty() {
+		return Err(Error::new_spanned(
+			&input.generics,
+			"KeyData can't be derived for generic types",
+		));
+	}
+
+	let mut name: Option<LitStr> = None;
+	let mut size: Option<usize> = None;
+	for attr in &input.attrs {
+		if !attr.path().is_ident("key_data") {
+			continue;
+		}
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("name") {
+				let value: LitStr = meta.value()?.parse()?;
+				let valid = !value.value().is_empty()
+					&& value.value().bytes().all(|c| c.is_ascii_graphic());
+				if !valid {
+					return Err(Error::new_spanned(value, "key type name must be printable ASCII"));
+				}
+				name = Some(value);
+				Ok(())
+			} else if meta.path.is_ident("size") {
+				let value: LitInt = meta.value()?.parse()?;
+				let value_size = value.base10_parse::<usize>()?;
+				if !(1..=255).contains(&value_size) {
+					return Err(Error::new_spanned(value, "key size must be in 1..=255"));
+				}
+				size = Some(value_size);
+				Ok(())
+			} else {
+				Err(meta.error("unsupported key_data attribute (expected `name` or `size`)"))
+			}
+		})?;
+	}
+	let missing = |what: &str| {
+		Error::new(
+			Span::call_site(),
+			format!("missing `{what}` in #[key_data(name = \"...\", size = N)]"),
+		)
+	};
+	let name = name.ok_or_else(|| missing("name"))?;
+	let size = size.ok_or_else(|| missing("size"))?;
+	let hex_size = 2 * size;
+
+	let ident = &input.ident;
+	Ok(quote! {
+		impl ::core::default::Default for #ident {
+			fn default() -> Self {
+				Self([0u8; #size])
+			}
+		}
+
+		impl ::core::str::FromStr for #ident {
+			type Err = ::hibp_index::__private::hex::FromHexError;
+
+			fn from_str(hex: &str) -> ::core::result::Result<Self, Self::Err> {
+				let mut this = Self([0u8; #size]);
+				::hibp_index::__private::hex::decode_to_slice(hex, &mut this.0)?;
+				::core::result::Result::Ok(this)
+			}
+		}
+
+		impl ::core::ops::Deref for #ident {
+			type Target = [u8];
+
+			fn deref(&self) -> &Self::Target {
+				&self.0
+			}
+		}
+
+		impl ::core::ops::DerefMut for #ident {
+			fn deref_mut(&mut self) -> &mut Self::Target {
+				&mut self.0
+			}
+		}
+
+		impl ::core::convert::AsRef<[u8; #size]> for #ident {
+			fn as_ref(&self) -> &[u8; #size] {
+				&self.0
+			}
+		}
+
+		impl ::core::convert::AsMut<[u8; #size]> for #ident {
+			fn as_mut(&mut self) -> &mut [u8; #size] {
+				&mut self.0
+			}
+		}
+
+		impl ::core::fmt::Debug for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+				f.write_str(&::hibp_index::data::FixedByteArray::hex(self))
+			}
+		}
+
+		impl ::core::fmt::Display for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+				f.write_str(&::hibp_index::data::FixedByteArray::hex(self))
+			}
+		}
+
+		impl ::core::fmt::LowerHex for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+				f.write_str(&::hibp_index::data::FixedByteArray::hex(self))
+			}
+		}
+
+		impl ::core::fmt::UpperHex for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+				f.write_str(&::hibp_index::data::FixedByteArray::hex_upper(self))
+			}
+		}
+
+		impl ::hibp_index::data::FixedByteArrayImpl for #ident {
+			type ByteArray = [u8; #size];
+			type HexArray = [u8; #hex_size];
+		}
+
+		impl ::hibp_index::data::KeyData for #ident {
+			const KEY_TYPE: ::hibp_index::data::KeyType = ::hibp_index::data::KeyType::custom(#name);
+		}
+	})
+}
@@ -0,0 +1,227 @@
+//! Audit password dumps of Windows domains
+//!
+//! Checks the NT hashes of all accounts of a pwdump file (see
+//! [`pwdump`](crate::pwdump)) against an NT index and detects password
+//! reuse, i.e. accounts sharing the same NT hash (NT hashes aren't salted).
+//! Stored LM hashes of legacy environments can be checked against an LM index
+//! too (see [`AuditReport::check_lm`]):
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::audit::AuditReport;
+//! use hibp_index::checker::{PasswordChecker, DEFAULT_NT_INDEX};
+//! use hibp_index::pwdump::PwdumpEntry;
+//!
+//! let checker = PasswordChecker::open(None, Some(DEFAULT_NT_INDEX.as_ref()))?;
+//! let dump = std::fs::read_to_string("ntds.dump")?;
+//! let entries = dump.lines().map(PwdumpEntry::parse).collect::<Result<Vec<_>, _>>()?;
+//! let report = AuditReport::check(&checker, &entries)?;
+//! for account in report.pwned() {
+//!     println!("{} uses a breached password", account.user);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+
+use crate::{
+	buf_read::ReadAt,
+	checker::PasswordChecker,
+	data::{PayloadData, LM, NT},
+	errors::{LookupError, PasswordCheckError},
+	index::TypedIndex,
+	pwdump::PwdumpEntry,
+};
+
+/// Audit result of a single account
+#[derive(Clone, Debug)]
+pub struct AuditAccount {
+	/// User name (possibly with domain)
+	pub user: String,
+	/// Relative identifier
+	pub rid: u32,
+	/// NT hash
+	pub nt: NT,
+	/// Whether the NT hash was found in the index
+	pub pwned: bool,
+	/// Whether an LM hash is stored too
+	pub lm_hash: bool,
+	/// Stored LM hash
+	pub lm: Option<LM>,
+	/// Whether the LM hash was found in an LM index (see [`AuditReport::check_lm`])
+	pub lm_pwned: bool,
+	/// Index of reuse group in [`AuditReport::reuse_groups`] (if the hash is shared)
+	pub reuse_group: Option<usize>,
+}
+
+/// Accounts sharing the same NT hash (i.e. the same password)
+#[derive(Clone, Debug)]
+pub struct ReuseGroup {
+	/// Shared NT hash
+	pub nt: NT,
+	/// Whether the NT hash was found in the index
+	pub pwned: bool,
+	/// Indices of the accounts in [`AuditReport::accounts`]
+	pub accounts: Vec<usize>,
+}
+
+/// Result of an audit
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+	/// All accounts (in input order)
+	pub accounts: Vec<AuditAccount>,
+	/// Groups of accounts sharing a hash (largest first)
+	pub reuse_groups: Vec<ReuseGroup>,
+}
+
+impl AuditReport {
+	/// Check all entries with the NT index of the checker
+	pub fn check<R>(
+		checker: &PasswordChecker<R>,
+		entries: &[PwdumpEntry<'_>],
+	) -> Result<Self, PasswordCheckError>
+	where
+		R: ReadAt,
+	{
+		// same hashes are only looked up once
+		let mut lookups = HashMap::<NT, bool>::new();
+		let mut accounts = Vec::with_capacity(entries.len());
+		for entry in entries {
+			let pwned = match lookups.get(&entry.nt) {
+				Some(&pwned) => pwned,
+				None => {
+					let pwned = checker.check_nt(&entry.nt)?;
+					lookups.insert(entry.nt, pwned);
+					pwned
+				},
+			};
+			accounts.push(AuditAccount {
+				user: entry.user.to_string(),
+				rid: entry.rid,
+				nt: entry.nt,
+				pwned,
+				lm_hash: entry.has_lm_hash(),
+				lm: entry.lm,
+				lm_pwned: false,
+				reuse_group: None,
+			});
+		}
+		Ok(Self::with_reuse_groups(accounts))
+	}
+
+	fn with_reuse_groups(mut accounts: Vec<AuditAccount>) -> Self {
+		let mut by_hash = HashMap::<NT, Vec<usize>>::new();
+		for (ndx, account) in accounts.iter().enumerate() {
+			by_hash.entry(account.nt).or_default().push(ndx);
+		}
+		let mut reuse_groups: Vec<ReuseGroup> = by_hash
+			.into_iter()
+			.filter(|(_, members)| members.len() > 1)
+			.map(|(nt, members)| ReuseGroup {
+				nt,
+				pwned: accounts[members[0]].pwned,
+				accounts: members,
+			})
+			.collect();
+		reuse_groups.sort_by(|a, b| b.accounts.len().cmp(&a.accounts.len()).then(a.nt.cmp(&b.nt)));
+		for (group_ndx, group) in reuse_groups.iter().enumerate() {
+			for &ndx in &group.accounts {
+				accounts[ndx].reuse_group = Some(group_ndx);
+			}
+		}
+		Self { accounts, reuse_groups }
+	}
+
+	/// Check stored LM hashes of all accounts with an LM index
+	///
+	/// Accounts found are marked as [`lm_pwned`](AuditAccount::lm_pwned) and
+	/// [`pwned`](AuditAccount::pwned).
+	pub fn check_lm<P, R>(&mut self, index: &TypedIndex<LM, P, R>) -> Result<(), LookupError>
+	where
+		P: PayloadData,
+		R: ReadAt,
+	{
+		let mut lookups = HashMap::<LM, bool>::new();
+		for account in &mut self.accounts {
+			let Some(lm) = account.lm else {
+				continue;
+			};
+			let lm_pwned = match lookups.get(&lm) {
+				Some(&lm_pwned) => lm_pwned,
+				None => {
+					let lm_pwned = index.lookup(&lm)?.is_some();
+					lookups.insert(lm, lm_pwned);
+					lm_pwned
+				},
+			};
+			account.lm_pwned = lm_pwned;
+			account.pwned |= lm_pwned;
+		}
+		for group in &mut self.reuse_groups {
+			group.pwned = group.accounts.iter().any(|&ndx| self.accounts[ndx].pwned);
+		}
+		Ok(())
+	}
+
+	/// Accounts with breached passwords
+	pub fn pwned(&self) -> impl Iterator<Item = &AuditAccount> {
+		self.accounts.iter().filter(|account| account.pwned)
+	}
+
+	/// Accounts of a reuse group
+	pub fn group_accounts<'a>(
+		&'a self,
+		group: &'a ReuseGroup,
+	) -> impl Iterator<Item = &'a AuditAccount> + 'a {
+		group.accounts.iter().map(|&ndx| &self.accounts[ndx])
+	}
+
+	/// Write human readable report
+	pub fn write_text<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+		let lm_hashes = self.accounts.iter().filter(|account| account.lm_hash).count();
+		let lm_pwned = self.accounts.iter().filter(|account| account.lm_pwned).count();
+		let reused = self.reuse_groups.iter().map(|group| group.accounts.len()).sum::<usize>();
+		writeln!(w, "Accounts: {}", self.accounts.len())?;
+		writeln!(w, "Pwned: {}", self.pwned().count())?;
+		writeln!(w, "Sharing a password: {} (in {} groups)", reused, self.reuse_groups.len())?;
+		writeln!(w, "With LM hash: {} ({} pwned)", lm_hashes, lm_pwned)?;
+		writeln!(w)?;
+		writeln!(w, "Pwned accounts:")?;
+		for account in self.pwned() {
+			writeln!(w, "\t{}", account.user)?;
+		}
+		writeln!(w)?;
+		writeln!(w, "Password reuse:")?;
+		for group in &self.reuse_groups {
+			let pwned = if group.pwned { " (pwned)" } else { "" };
+			writeln!(w, "\t{} accounts{}:", group.accounts.len(), pwned)?;
+			for account in self.group_accounts(group) {
+				writeln!(w, "\t\t{}", account.user)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Write report as tab separated values (one line per account, with header)
+	///
+	/// Columns: user, rid, NT hash, pwned (0/1), LM hash stored (0/1), reuse
+	/// group (empty if the hash isn't shared).
+	pub fn write_tsv<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+		writeln!(w, "user\trid\tnt\tpwned\tlm_hash\treuse_group")?;
+		for account in &self.accounts {
+			writeln!(
+				w,
+				"{}\t{}\t{}\t{}\t{}\t{}",
+				account.user,
+				account.rid,
+				account.nt,
+				account.pwned as u8,
+				account.lm_hash as u8,
+				account.reuse_group.map(|group| group.to_string()).unwrap_or_default(),
+			)?;
+		}
+		Ok(())
+	}
+}
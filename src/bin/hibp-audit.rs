@@ -0,0 +1,77 @@
+extern crate hibp_index;
+
+use hibp_index::audit::AuditReport;
+use hibp_index::checker::{PasswordChecker, DEFAULT_NT_INDEX};
+use hibp_index::data::{NoPayload, LM};
+use hibp_index::index::TypedIndex;
+use hibp_index::pwdump::PwdumpEntry;
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Audit accounts of a pwdump / secretsdump file (user:rid:lmhash:nthash:::)
+///
+/// Reports accounts with breached passwords (NT hash found in the index) and
+/// groups of accounts sharing the same password.
+struct Cli {
+	#[arg(long, default_value = DEFAULT_NT_INDEX)]
+	/// NT index to check hashes with
+	index: PathBuf,
+
+	#[arg(long)]
+	/// LM index to check stored (legacy) LM hashes with
+	lm_index: Option<PathBuf>,
+
+	#[arg(long)]
+	/// Write tab separated values (one line per account) instead of a text report
+	tsv: bool,
+
+	/// Dump to audit (default: stdin)
+	input: Option<PathBuf>,
+}
+
+fn main() -> anyhow::Result<()> {
+	let cli = <Cli as clap::Parser>::parse();
+	let checker = PasswordChecker::open(None, Some(&cli.index))?;
+	let dump = match &cli.input {
+		Some(path) => fs::read_to_string(path)?,
+		None => {
+			let mut dump = String::new();
+			io::stdin().read_to_string(&mut dump)?;
+			dump
+		},
+	};
+	let mut entries = Vec::new();
+	for (line_no, line) in dump.lines().enumerate() {
+		if line.trim().is_empty() {
+			continue;
+		}
+		let entry =
+			PwdumpEntry::parse(line).map_err(|e| anyhow::anyhow!("line {}: {}", line_no + 1, e))?;
+		entries.push(entry);
+	}
+	let mut report = AuditReport::check(&checker, &entries)?;
+	if let Some(path) = &cli.lm_index {
+		let lm_index = TypedIndex::<LM, NoPayload, _>::open(fs::File::open(path)?)?;
+		report.check_lm(&lm_index)?;
+	}
+	let stdout = io::stdout().lock();
+	if cli.tsv {
+		report.write_tsv(stdout)?;
+	} else {
+		report.write_text(stdout)?;
+	}
+	Ok(())
+}
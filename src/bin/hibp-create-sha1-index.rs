@@ -1,21 +1,118 @@
 extern crate hibp_index;
 
 use hibp_index::data::{NoPayload, SHA1};
-use hibp_index::index::{Depth, TypedBuilder};
+use hibp_index::import::{SplitHibpImport, UnorderedHibpImport};
+use hibp_index::index::{AtomicFile, Depth, IndexOptions, TypedBuilder};
+use hibp_index::range_dir::RangeDir;
 
+use chrono::TimeZone;
 use std::fs;
-use std::io::{BufRead, BufReader, BufWriter};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Build hibp-sha1.index from the SHA-1 hashes ordered by hash
+struct Cli {
+	#[arg(default_value = "pwned-passwords-sha1-ordered-by-hash-v7.txt")]
+	/// Input file (`-` for stdin); gzip, zstd and xz compressed input is detected
+	input: PathBuf,
+
+	#[arg(long, conflicts_with_all = ["unordered", "split"])]
+	/// Input is a directory of the official downloader (one `ABCDE.txt` file per 5 hex digit prefix)
+	ranges: bool,
+
+	#[arg(long, conflicts_with = "unordered")]
+	/// Input is a directory with files split by the first hash byte (`00.txt` to `FF.txt`)
+	split: bool,
+
+	#[arg(long)]
+	/// Input is not ordered by hash (e.g. ordered by prevalence); sort it with temporary files
+	unordered: bool,
+
+	#[arg(long, conflicts_with_all = ["ranges", "split", "unordered"])]
+	/// Input is a plaintext wordlist (one password per line); hash, sort (with temporary files) and count the passwords
+	wordlist: bool,
+
+	#[arg(long, default_value = ".")]
+	/// Directory for the temporary files of `--unordered` and `--wordlist`
+	temp_dir: PathBuf,
+
+	#[arg(long)]
+	/// Replace an existing output file
+	force: bool,
+}
 
 fn main() -> anyhow::Result<()> {
-	let input = BufReader::new(fs::File::open("pwned-passwords-sha1-ordered-by-hash-v7.txt")?);
-	let output = BufWriter::new(
-		fs::OpenOptions::new().write(true).create_new(true).open("hibp-sha1.index")?,
-	);
-	let mut builder =
-		TypedBuilder::<SHA1, NoPayload, _>::create(output, "pwned-passwords v7", Depth::DEPTH20)?;
-	for line in input.lines() {
-		builder.add_entry_from_hibp_line(&line?)?;
+	let cli = <Cli as clap::Parser>::parse();
+	let mut options = IndexOptions::v1();
+	options.checksum = true;
+	if cli.input != Path::new("-") {
+		let mtime = fs::metadata(&cli.input)?.modified()?.duration_since(std::time::UNIX_EPOCH)?;
+		if let Some(mtime) = chrono::Utc.timestamp_opt(mtime.as_secs() as i64, 0).single() {
+			options.metadata.set_mtime(mtime);
+		}
+		if let Some(source) = cli.input.file_name() {
+			options.metadata.set_source(&source.to_string_lossy());
+		}
+	}
+	let output = Path::new("hibp-sha1.index");
+	let mut atomic_file = AtomicFile::create_locked(output)?;
+	if !cli.force {
+		atomic_file = atomic_file.no_overwrite()?;
+	}
+	let description = if cli.wordlist { "wordlist" } else { "pwned-passwords v7" };
+	let mut builder = TypedBuilder::<SHA1, NoPayload, _>::create_with_atomic_file(
+		atomic_file,
+		description,
+		Depth::DEPTH20,
+		&options,
+	)?;
+	let mut last_report = Instant::now();
+	builder.set_progress(move |progress| {
+		if last_report.elapsed() >= Duration::from_secs(1) {
+			last_report = Instant::now();
+			eprint!(
+				"\r{} entries, {} MiB written, prefix {}",
+				progress.entries,
+				progress.bytes >> 20,
+				progress.prefix.hex()
+			);
+		}
+	});
+	if cli.ranges {
+		RangeDir::open(&cli.input)?.add_entries(&mut builder, |_count| NoPayload)?;
+	} else if cli.split {
+		SplitHibpImport::new(&cli.input).add_entries(&mut builder, |_count| NoPayload)?;
+	} else if cli.wordlist {
+		let input = hibp_index::input::open(&cli.input)?;
+		UnorderedHibpImport::new(&cli.temp_dir).add_wordlist(
+			input,
+			SHA1::hash,
+			&mut builder,
+			|_count| NoPayload,
+		)?;
+	} else if cli.unordered {
+		let input = hibp_index::input::open(&cli.input)?;
+		UnorderedHibpImport::new(&cli.temp_dir)
+			.add_entries(input, &mut builder, |_count| NoPayload)?;
+	} else {
+		let input = hibp_index::input::open(&cli.input)?;
+		for line in input.lines() {
+			builder.add_entry_from_hibp_line(&line?)?;
+		}
 	}
 	builder.finish()?;
+	eprintln!();
 	Ok(())
 }
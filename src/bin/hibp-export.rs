@@ -0,0 +1,70 @@
+extern crate hibp_index;
+
+use hibp_index::index::Index;
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+	/// Comma separated values with header line
+	Csv,
+	/// Parquet file (requires feature `parquet`)
+	Parquet,
+	/// Raw binary records (key followed by payload)
+	Raw,
+	/// HIBP text files split by the first key byte (`00.txt` to `FF.txt`) in the `--output` directory; payload must be a breach count
+	SplitHibp,
+}
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Export all entries of an index (hex encoded key and payload columns, or raw binary records)
+struct Cli {
+	#[arg(long, value_enum, default_value = "csv")]
+	/// Output format
+	format: Format,
+
+	#[arg(long, required_if_eq("format", "split-hibp"))]
+	/// File to write (default: stdout); directory for `--format split-hibp`
+	output: Option<PathBuf>,
+
+	/// Index to export
+	index: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+	let cli = <Cli as clap::Parser>::parse();
+	let index = Index::open(fs::File::open(&cli.index)?)?;
+	if let (Format::SplitHibp, Some(dir)) = (cli.format, &cli.output) {
+		fs::create_dir_all(dir)?;
+		let lines = hibp_index::export::write_split_hibp(&index, dir)?;
+		eprintln!("exported {} entries", lines);
+		return Ok(());
+	}
+	let output: Box<dyn io::Write + Send> = match &cli.output {
+		Some(path) => Box::new(fs::File::create(path)?),
+		None => Box::new(io::stdout()),
+	};
+	let rows = match cli.format {
+		Format::Csv => hibp_index::export::write_csv(&index, output)?,
+		Format::Raw => hibp_index::export::write_raw(&index, output)?,
+		Format::SplitHibp => unreachable!("requires output directory"),
+		#[cfg(feature = "parquet")]
+		Format::Parquet => hibp_index::export::write_parquet(&index, output)?,
+		#[cfg(not(feature = "parquet"))]
+		Format::Parquet => anyhow::bail!("built without parquet support (feature `parquet`)"),
+	};
+	eprintln!("exported {} entries", rows);
+	Ok(())
+}
@@ -0,0 +1,161 @@
+extern crate hibp_index;
+
+use hibp_index::data::{Count32, KeyData, NoPayload, PayloadData, LM, NT, SHA1, SHA512};
+use hibp_index::import::{CsvImport, HashEncoding};
+use hibp_index::index::{AtomicFile, Depth, IndexOptions, TypedBuilder};
+
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum KeyType {
+	/// SHA-1 hashes
+	Sha1,
+	/// NT hashes
+	Nt,
+	/// SHA-512 hashes
+	Sha512,
+	/// LM hashes
+	Lm,
+}
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Build an index from arbitrary CSV / TSV files
+///
+/// The input doesn't need to be sorted; duplicate hashes are merged (adding
+/// up their counts). Counts are stored as 4 byte big endian payload.
+struct Cli {
+	#[arg(long, value_enum, default_value = "sha1")]
+	/// Type of the hashes
+	key_type: KeyType,
+
+	#[arg(long, conflicts_with = "delimiter")]
+	/// Tab separated input (no quoting)
+	tsv: bool,
+
+	#[arg(long, default_value_t = ',')]
+	/// Field delimiter
+	delimiter: char,
+
+	#[arg(long)]
+	/// Skip first line
+	header: bool,
+
+	#[arg(long, default_value_t = 0)]
+	/// Column with the hash (starting at 0)
+	hash_column: usize,
+
+	#[arg(long)]
+	/// Column with the breach count (starting at 0); without it the index has no payload
+	count_column: Option<usize>,
+
+	#[arg(long)]
+	/// Hashes are base64 encoded (default: hex)
+	base64: bool,
+
+	#[arg(long, default_value_t = 20)]
+	/// Number of prefix bits in the index table
+	depth: u8,
+
+	#[arg(long, requires = "count_column")]
+	/// Store counts in per-bucket dictionaries (smaller for mostly small counts; fails for more than 256 distinct counts in a bucket)
+	payload_dictionary: bool,
+
+	#[arg(long, default_value_t = 0)]
+	/// Additional prefix bits (1-8) of tables for large buckets (0: none)
+	bucket_table_bits: u8,
+
+	#[arg(long)]
+	/// Description stored in the index (default: name of the input file)
+	description: Option<String>,
+
+	#[arg(long)]
+	/// Replace an existing output file
+	force: bool,
+
+	/// CSV / TSV file to import
+	input: PathBuf,
+
+	/// Index to create
+	output: PathBuf,
+}
+
+fn build<D, P>(cli: &Cli, import: &CsvImport, payload: fn(Option<u64>) -> P) -> anyhow::Result<()>
+where
+	D: KeyData + Ord,
+	P: PayloadData,
+{
+	let depth =
+		Depth::new(cli.depth).ok_or_else(|| anyhow::anyhow!("invalid depth {}", cli.depth))?;
+	let source = cli.input.file_name().unwrap_or_default().to_string_lossy().into_owned();
+	let description = cli.description.as_deref().unwrap_or(&source);
+	let mut options = IndexOptions::v1();
+	options.metadata.set_source(&source);
+	options.payload_dictionary = cli.payload_dictionary;
+	options.bucket_table_bits = cli.bucket_table_bits;
+	let input = fs::File::open(&cli.input)?;
+	let mut atomic_file = AtomicFile::create_locked(&cli.output)?;
+	if !cli.force {
+		atomic_file = atomic_file.no_overwrite()?;
+	}
+	let mut builder = TypedBuilder::<D, P, _>::create_with_atomic_file(
+		atomic_file,
+		description,
+		depth,
+		&options,
+	)?;
+	let entries = import.add_entries(input, &mut builder, payload)?;
+	builder.finish()?;
+	eprintln!("imported {} entries", entries);
+	Ok(())
+}
+
+fn count_payload(count: Option<u64>) -> Count32 {
+	Count32::saturating_from(count.unwrap_or(0))
+}
+
+fn main() -> anyhow::Result<()> {
+	let cli = <Cli as clap::Parser>::parse();
+	let mut import = if cli.tsv {
+		CsvImport::tsv()
+	} else {
+		let delimiter = u8::try_from(cli.delimiter)
+			.map_err(|_| anyhow::anyhow!("delimiter must be an ASCII character"))?;
+		CsvImport::csv().with_delimiter(delimiter)
+	};
+	import = import.with_hash_column(cli.hash_column);
+	if cli.header {
+		import = import.with_header();
+	}
+	if cli.base64 {
+		import = import.with_encoding(HashEncoding::Base64);
+	}
+	match (cli.key_type, cli.count_column) {
+		(KeyType::Sha1, None) => build::<SHA1, _>(&cli, &import, |_| NoPayload),
+		(KeyType::Nt, None) => build::<NT, _>(&cli, &import, |_| NoPayload),
+		(KeyType::Sha512, None) => build::<SHA512, _>(&cli, &import, |_| NoPayload),
+		(KeyType::Lm, None) => build::<LM, _>(&cli, &import, |_| NoPayload),
+		(KeyType::Sha1, Some(column)) => {
+			build::<SHA1, _>(&cli, &import.with_count_column(column), count_payload)
+		},
+		(KeyType::Nt, Some(column)) => {
+			build::<NT, _>(&cli, &import.with_count_column(column), count_payload)
+		},
+		(KeyType::Sha512, Some(column)) => {
+			build::<SHA512, _>(&cli, &import.with_count_column(column), count_payload)
+		},
+		(KeyType::Lm, Some(column)) => {
+			build::<LM, _>(&cli, &import.with_count_column(column), count_payload)
+		},
+	}
+}
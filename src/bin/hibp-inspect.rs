@@ -0,0 +1,157 @@
+extern crate hibp_index;
+
+use hibp_index::index::{verify_stream, Index};
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Tool to show header and table statistics of an index database
+struct Cli {
+	/// Index file to inspect
+	index: PathBuf,
+	/// Reject suspicious headers and tables (for untrusted files)
+	#[arg(long)]
+	strict: bool,
+	/// Verify the checksum over the whole file (fails for indexes without checksum)
+	#[arg(long)]
+	verify: bool,
+	/// Check that keys are strictly increasing (unique) within each bucket
+	#[arg(long)]
+	verify_order: bool,
+	/// Only verify structure, key order and checksum reading the file once from start to end
+	///
+	/// Requires an index with leading table; use `-` as index to read from stdin
+	/// (e.g. while downloading it).
+	#[arg(long, conflicts_with_all = ["strict", "verify", "verify_order"])]
+	stream: bool,
+}
+
+struct BucketStats {
+	buckets: u64,
+	empty: u64,
+	invalid: u64,
+	min: u64,
+	max: u64,
+	median: u64,
+}
+
+impl BucketStats {
+	fn new<R>(index: &Index<R>) -> Self {
+		let entry_size = index.entry_size() as u64;
+		let mut invalid = 0;
+		let mut entries: Vec<u64> = index
+			.bucket_ranges()
+			.filter_map(|range| {
+				let length = range.end - range.start;
+				if !length.is_multiple_of(entry_size) {
+					invalid += 1;
+					None
+				} else {
+					Some(length / entry_size)
+				}
+			})
+			.collect();
+		entries.sort_unstable();
+		Self {
+			buckets: index.bucket_ranges().len() as u64,
+			empty: entries.iter().take_while(|&&n| n == 0).count() as u64,
+			invalid,
+			min: entries.first().copied().unwrap_or(0),
+			max: entries.last().copied().unwrap_or(0),
+			median: entries.get(entries.len() / 2).copied().unwrap_or(0),
+		}
+	}
+}
+
+fn stream_verification(cli: &Cli) -> anyhow::Result<()> {
+	let verification = if cli.index.as_os_str() == "-" {
+		verify_stream(io::stdin().lock())?
+	} else {
+		verify_stream(fs::File::open(&cli.index)?)?
+	};
+	println!("file:          {}", cli.index.display());
+	println!("flags:         {}", verification.capabilities.flags());
+	println!("key type:      {}", verification.key_type.name());
+	println!("description:   {}", verification.description);
+	println!("depth:         {} bits", verification.depth.as_u8());
+	println!("entries:       {}", verification.entries);
+	if verification.checksum {
+		println!("checksum:      ok");
+	}
+	println!("structure:     ok");
+	Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+	let cli = <Cli as clap::Parser>::parse();
+	if cli.stream {
+		return stream_verification(&cli);
+	}
+	let file = fs::File::open(&cli.index)?;
+	let file_size = file.metadata()?.len();
+	let index = if cli.strict { Index::open_strict(file)? } else { Index::open(file)? };
+	let stats = BucketStats::new(&index);
+
+	println!("file:          {}", cli.index.display());
+	println!("file size:     {} bytes", file_size);
+	println!("magic:         {}", index.magic());
+	println!("flags:         {}", index.capabilities().flags());
+	for (tag, value) in index.metadata().iter() {
+		println!("metadata {:3}:  {}", tag, hex::encode(value));
+	}
+	println!("key type:      {}", index.key_type().name());
+	println!("key size:      {} bytes", index.key_size());
+	println!("payload size:  {} bytes", index.payload_size());
+	println!("description:   {}", index.description());
+	if let Some(mtime) = index.mtime() {
+		println!("mtime:         {}", mtime);
+	}
+	if let Some(source) = index.source() {
+		println!("source:        {}", source);
+	}
+	println!("depth:         {} bits", index.depth().as_u8());
+	println!("table size:    {} bytes (compressed)", index.table_size());
+	println!("entry size:    {} bytes", index.entry_size());
+	if let Some(size) = index.dictionary_size() {
+		println!("dictionaries:  {} bytes", size);
+	}
+	if let Some(count) = index.bucket_table_count() {
+		println!("bucket tables: {}", count);
+	}
+	match index.entry_count() {
+		Ok(count) => println!("entries:       {}", count),
+		Err(e) => println!("entries:       unknown ({})", e),
+	}
+	println!("buckets:       {}", stats.buckets);
+	println!("empty buckets: {}", stats.empty);
+	if stats.invalid > 0 {
+		println!("invalid buckets: {}", stats.invalid);
+	}
+	if let Ok(count) = index.entry_count() {
+		println!("bucket mean:   {:.1}", count as f64 / stats.buckets as f64);
+	}
+	println!("bucket min:    {}", stats.min);
+	println!("bucket median: {}", stats.median);
+	println!("bucket max:    {}", stats.max);
+	if cli.verify {
+		index.verify_checksum()?;
+		println!("checksum:      ok");
+	}
+	if cli.verify_order {
+		index.verify_order()?;
+		println!("key order:     ok");
+	}
+	Ok(())
+}
@@ -1,21 +1,104 @@
 extern crate hibp_index;
 
-use hibp_index::data::{KeyData, NoPayload, NT, SHA1};
-use hibp_index::index::TypedIndex;
+use hibp_index::checker::{
+	InputMode, PasswordChecker, PasswordHash, DEFAULT_NT_INDEX, DEFAULT_SHA1_INDEX,
+};
+use hibp_index::config::Config;
+use hibp_index::errors::PasswordCheckError;
+use hibp_index::index::MultiIndex;
+use hibp_index::policy::{Action, Policy};
+use hibp_index::pwdump::PwdumpEntry;
 
 use std::fs;
 use std::io::{self, BufRead};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum SummaryFormat {
+	/// Human readable line
+	Text,
+	/// JSON object
+	Json,
+}
 
 struct AppConfig {
 	auto_load: bool,
 	load_sha1: bool,
-	sha1_index: &'static Path,
+	sha1_index: PathBuf,
 	load_nt: bool,
-	nt_index: &'static Path,
+	nt_index: PathBuf,
+	cache_size: Option<usize>,
 	one_shot: bool,
-	plaintext: bool,
-	no_plaintext: bool,
+	prompt: bool,
+	threshold: Option<u64>,
+	input_mode: InputMode,
+	pwdump: bool,
+	summary: Option<SummaryFormat>,
+	interactive: bool,
+	matrix: bool,
+	watch: bool,
+}
+
+impl AppConfig {
+	fn index_paths(&self) -> Vec<&Path> {
+		let sha1 = Some(self.sha1_index.as_path()).filter(|_| self.load_sha1);
+		let nt = Some(self.nt_index.as_path()).filter(|_| self.load_nt);
+		sha1.into_iter().chain(nt).collect()
+	}
+
+	fn open_checker(&self) -> anyhow::Result<PasswordChecker> {
+		let mut indexes = MultiIndex::open(self.index_paths())?;
+		if let Some(cache_size) = self.cache_size {
+			indexes.set_cache_size(cache_size);
+		}
+		Ok(PasswordChecker::from_indexes(indexes)?)
+	}
+}
+
+/// How often `--watch` checks the index files
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Modification time and size of a file
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+	let metadata = fs::metadata(path).ok()?;
+	Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Loaded indexes; reopened when their files change with `--watch`
+struct Indexes {
+	checker: PasswordChecker,
+	// versions of the index files the checker was opened with
+	versions: Vec<Option<(SystemTime, u64)>>,
+	last_check: Instant,
+}
+
+impl Indexes {
+	fn open(cfg: &AppConfig) -> anyhow::Result<Self> {
+		// get versions first: a file replaced while opening is reopened later
+		let versions = cfg.index_paths().into_iter().map(file_version).collect();
+		Ok(Self { checker: cfg.open_checker()?, versions, last_check: Instant::now() })
+	}
+
+	/// Checker to use for the next input (reopens indexes first if needed)
+	fn checker(&mut self, cfg: &AppConfig) -> &PasswordChecker {
+		if cfg.watch && self.last_check.elapsed() >= WATCH_INTERVAL {
+			self.last_check = Instant::now();
+			let versions: Vec<_> = cfg.index_paths().into_iter().map(file_version).collect();
+			// missing files are probably just being replaced: keep old indexes until they are back
+			if versions != self.versions && versions.iter().all(Option::is_some) {
+				match cfg.open_checker() {
+					Ok(checker) => {
+						eprintln!("Reopened changed index files");
+						self.checker = checker;
+						self.versions = versions;
+					},
+					Err(e) => eprintln!("Failed to reopen changed index files: {}", e),
+				}
+			}
+		}
+		&self.checker
+	}
 }
 
 fn app() -> anyhow::Result<AppConfig> {
@@ -30,8 +113,13 @@ fn app() -> anyhow::Result<AppConfig> {
 {all-args}{after-help}
 "
 	))]
+	#[command(group(clap::ArgGroup::new("single").args(["oneshot", "prompt"])))]
 	/// Tool to lookup SHA-1/NT hashes in index database
 	struct Cli {
+		#[arg(long)]
+		/// Load index paths and cache size from TOML configuration file
+		config: Option<PathBuf>,
+
 		#[arg(long)]
 		/// Load SHA-1 index; disable auto-loading NT index
 		sha1: bool,
@@ -44,6 +132,14 @@ fn app() -> anyhow::Result<AppConfig> {
 		/// Only test single password; exit code 0 signals password is fine (not included in index)
 		oneshot: bool,
 
+		#[arg(long)]
+		/// Like --oneshot, but read the password from the terminal (not echoed) instead of stdin
+		prompt: bool,
+
+		#[arg(long, requires("single"))]
+		/// With --oneshot / --prompt: only fail if the password was seen at least N times (requires index with breach counts; others count every match once)
+		threshold: Option<u64>,
+
 		#[arg(long)]
 		/// Treat every input line as plaintext password
 		plaintext: bool,
@@ -51,29 +147,67 @@ fn app() -> anyhow::Result<AppConfig> {
 		#[arg(long = "no-plaintext", conflicts_with("plaintext"))]
 		/// Every input line must be a hash (either SHA-1 or NT)
 		no_plaintext: bool,
+
+		#[arg(long, conflicts_with_all(["sha1", "single", "plaintext", "no_plaintext"]))]
+		/// Input is a pwdump / secretsdump file (user:rid:lmhash:nthash:::); checks NT hashes per user
+		pwdump: bool,
+
+		#[arg(long, value_enum, num_args(0..=1), default_missing_value("text"), conflicts_with("single"))]
+		/// Print totals to stderr after processing input; invalid lines are counted and skipped instead of aborting
+		summary: Option<SummaryFormat>,
+
+		#[arg(long, conflicts_with_all(["single", "pwdump", "summary"]))]
+		/// Repeatedly prompt for passwords (not echoed) and print whether they were found; stop with empty input
+		interactive: bool,
+
+		#[arg(long, conflicts_with_all(["single", "pwdump", "interactive"]))]
+		/// Check passwords with all loaded indexes and print a table (line number and result per index)
+		matrix: bool,
+
+		#[arg(long, conflicts_with("single"))]
+		/// Reopen indexes when their files change (e.g. replaced by a rebuild) while processing input
+		watch: bool,
 	}
 
 	let cli = <Cli as clap::Parser>::parse();
+	let config = match &cli.config {
+		Some(path) => {
+			Config::load(path).map_err(|e| anyhow::anyhow!("config {:?}: {}", path, e))?
+		},
+		None => Config::default(),
+	};
 
 	let mut cfg = AppConfig {
 		auto_load: true,
 		load_sha1: false,
-		sha1_index: Path::new("hibp-sha1.index"),
+		sha1_index: config.sha1_index.unwrap_or_else(|| PathBuf::from(DEFAULT_SHA1_INDEX)),
 		load_nt: false,
-		nt_index: Path::new("hibp-ntlm.index"),
-		one_shot: cli.oneshot,
-		plaintext: cli.plaintext,
-		no_plaintext: cli.no_plaintext,
+		nt_index: config.nt_index.unwrap_or_else(|| PathBuf::from(DEFAULT_NT_INDEX)),
+		cache_size: config.cache_size,
+		one_shot: cli.oneshot || cli.prompt,
+		prompt: cli.prompt,
+		threshold: cli.threshold,
+		input_mode: if cli.plaintext {
+			InputMode::Plaintext
+		} else if cli.no_plaintext {
+			InputMode::HashOnly
+		} else {
+			InputMode::Auto
+		},
+		pwdump: cli.pwdump,
+		summary: cli.summary,
+		interactive: cli.interactive,
+		matrix: cli.matrix,
+		watch: cli.watch,
 	};
 	if cli.sha1 {
 		cfg.auto_load = false;
 		cfg.load_sha1 = true;
 	}
-	if cli.nt {
+	if cli.nt || cli.pwdump {
 		cfg.auto_load = false;
 		cfg.load_nt = true;
 	}
-	// TODO: make filenames configurable
 	if cfg.auto_load {
 		if !cfg.load_sha1 && cfg.sha1_index.is_file() {
 			cfg.load_sha1 = true;
@@ -88,82 +222,213 @@ fn app() -> anyhow::Result<AppConfig> {
 	Ok(cfg)
 }
 
-fn open_index<D>(path: &Path) -> anyhow::Result<TypedIndex<D, NoPayload, fs::File>>
-where
-	D: KeyData,
-{
-	Ok(TypedIndex::<D, NoPayload, _>::open(fs::File::open(path)?)?)
+#[derive(Default)]
+struct Summary {
+	lines: u64,
+	found: u64,
+	not_found: u64,
+	parse_errors: u64,
 }
 
-fn check<D>(
-	cfg: &AppConfig,
-	index: &TypedIndex<D, NoPayload, fs::File>,
-	hash: &D,
-) -> anyhow::Result<()>
-where
-	D: KeyData + std::fmt::Display,
-{
-	let is_present = index.lookup(hash)?.is_some();
-	if cfg.one_shot {
-		std::process::exit(if is_present { 1 } else { 0 });
+impl Summary {
+	fn add(&mut self, is_present: bool) {
+		if is_present {
+			self.found += 1;
+		} else {
+			self.not_found += 1;
+		}
 	}
+
+	fn print(&self, format: SummaryFormat, elapsed: Duration) {
+		match format {
+			SummaryFormat::Text => eprintln!(
+				"{} lines processed: {} found, {} not found, {} parse errors ({:.3}s)",
+				self.lines,
+				self.found,
+				self.not_found,
+				self.parse_errors,
+				elapsed.as_secs_f64()
+			),
+			SummaryFormat::Json => eprintln!(
+				r#"{{"lines":{},"found":{},"not_found":{},"parse_errors":{},"elapsed_secs":{:.3}}}"#,
+				self.lines,
+				self.found,
+				self.not_found,
+				self.parse_errors,
+				elapsed.as_secs_f64()
+			),
+		}
+	}
+}
+
+fn check(hash: &PasswordHash, is_present: bool) {
+	let key_type = hash.key_type().name();
 	if is_present {
-		println!("Found {}: {}", D::KEY_TYPE.name(), hash);
+		println!("Found {}: {}", key_type, hash);
 	} else {
-		println!("Not found {}: {}", D::KEY_TYPE.name(), hash);
+		println!("Not found {}: {}", key_type, hash);
+	}
+}
+
+fn check_pwdump(
+	cfg: &AppConfig,
+	indexes: &mut Indexes,
+	summary: &mut Summary,
+) -> anyhow::Result<()> {
+	for (line_no, line) in io::stdin().lock().lines().enumerate() {
+		let line = line?;
+		let checker = indexes.checker(cfg);
+		if line.trim().is_empty() {
+			continue;
+		}
+		summary.lines += 1;
+		let entry = match PwdumpEntry::parse(&line) {
+			Ok(entry) => entry,
+			Err(e) if cfg.summary.is_some() => {
+				eprintln!("line {}: {}", line_no + 1, e);
+				summary.parse_errors += 1;
+				continue;
+			},
+			Err(e) => anyhow::bail!("line {}: {}", line_no + 1, e),
+		};
+		let is_present = checker.check_nt(&entry.nt)?;
+		summary.add(is_present);
+		if is_present {
+			println!("Found NT for {}: {}", entry.user, entry.nt);
+		} else {
+			println!("Not found NT for {}: {}", entry.user, entry.nt);
+		}
 	}
 	Ok(())
 }
 
-#[allow(clippy::upper_case_acronyms)]
-enum Input {
-	SHA1(SHA1),
-	NT(NT),
+/// Check single input and exit; exit code 1 signals password was found (at least `threshold` times)
+fn check_oneshot(cfg: &AppConfig, checker: &PasswordChecker, input: &str) -> anyhow::Result<()> {
+	let hash = checker.parse_input(input, cfg.input_mode)?;
+	let reject = match cfg.threshold {
+		Some(threshold) => {
+			let verdict = Policy::reject_at(threshold).evaluate(checker.lookup_count(&hash)?);
+			verdict.action == Action::Reject
+		},
+		None => checker.check_hash(&hash)?,
+	};
+	std::process::exit(if reject { 1 } else { 0 });
 }
 
-impl Input {
-	fn new(cfg: &AppConfig, line: String) -> anyhow::Result<Self> {
-		if !cfg.plaintext {
-			if cfg.load_sha1 {
-				if let Ok(sha1) = line.parse::<SHA1>() {
-					return Ok(Self::SHA1(sha1));
-				}
-			}
-			if cfg.load_nt {
-				if let Ok(nt) = line.parse::<NT>() {
-					return Ok(Self::NT(nt));
-				}
-			}
+fn interactive(cfg: &AppConfig, indexes: &mut Indexes) -> anyhow::Result<()> {
+	loop {
+		let input = match rpassword::prompt_password("Password: ") {
+			Ok(input) => input,
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+			Err(e) => return Err(e.into()),
+		};
+		if input.is_empty() {
+			break;
 		}
-		if !cfg.no_plaintext {
-			// fallback: treat as plaintext
-			if cfg.load_sha1 {
-				let sha1 = SHA1::hash(line.as_bytes());
-				return Ok(Self::SHA1(sha1));
-			}
-			if cfg.load_nt {
-				let nt = NT::hash(&line);
-				return Ok(Self::NT(nt));
+		let checker = indexes.checker(cfg);
+		let hash = match checker.parse_input(&input, cfg.input_mode) {
+			Ok(hash) => hash,
+			Err(e @ PasswordCheckError::PlaintextNotAllowed) => {
+				eprintln!("{}", e);
+				continue;
+			},
+			Err(e) => return Err(e.into()),
+		};
+		// don't print the hash: it identifies the password
+		let key_type = hash.key_type();
+		match checker.lookup_count(&hash)? {
+			Some(count) if checker.has_breach_counts(key_type) => {
+				println!("Found in {} index (seen {} times)", key_type.name(), count)
+			},
+			Some(_) => println!("Found in {} index", key_type.name()),
+			None => println!("Not found in {} index", key_type.name()),
+		}
+	}
+	Ok(())
+}
+
+fn check_lines(
+	cfg: &AppConfig,
+	indexes: &mut Indexes,
+	summary: &mut Summary,
+) -> anyhow::Result<()> {
+	for (line_no, line) in io::stdin().lock().lines().enumerate() {
+		let line = line?;
+		let checker = indexes.checker(cfg);
+		summary.lines += 1;
+		if cfg.one_shot {
+			return check_oneshot(cfg, checker, &line);
+		}
+		let result = if cfg.matrix {
+			checker.check_input_all(&line, cfg.input_mode)
+		} else {
+			checker.check_input(&line, cfg.input_mode).map(|result| vec![result])
+		};
+		let results = match result {
+			Err(e @ PasswordCheckError::PlaintextNotAllowed) if cfg.summary.is_some() => {
+				eprintln!("line {}: {}", line_no + 1, e);
+				summary.parse_errors += 1;
+				continue;
+			},
+			result => result?,
+		};
+		summary.add(results.iter().any(|(_, is_present)| *is_present));
+		if cfg.matrix {
+			print_matrix_row(checker, line_no + 1, &results);
+		} else {
+			for (hash, is_present) in &results {
+				check(hash, *is_present);
 			}
-			anyhow::bail!("Can't handle input - no index available");
 		}
-		anyhow::bail!("Can't handle input - plaintext input not allowed");
 	}
+	Ok(())
+}
+
+fn print_matrix_header(checker: &PasswordChecker) {
+	print!("line");
+	for key_type in checker.key_types() {
+		print!("\t{}", key_type.name());
+	}
+	println!();
+}
+
+/// Result per loaded index; `-` for indexes not matching the type of a hash input
+fn print_matrix_row(checker: &PasswordChecker, line: usize, results: &[(PasswordHash, bool)]) {
+	print!("{}", line);
+	for key_type in checker.key_types() {
+		let result = results.iter().find(|(hash, _)| hash.key_type() == key_type);
+		let result = match result {
+			Some((_, true)) => "found",
+			Some((_, false)) => "not found",
+			None => "-",
+		};
+		print!("\t{}", result);
+	}
+	println!();
 }
 
 fn main() -> anyhow::Result<()> {
 	let cfg = app()?;
-	let sha1_index = if cfg.load_sha1 { Some(open_index::<SHA1>(cfg.sha1_index)?) } else { None };
-	let nt_index = if cfg.load_nt { Some(open_index::<NT>(cfg.nt_index)?) } else { None };
-	for line in io::stdin().lock().lines() {
-		match Input::new(&cfg, line?)? {
-			Input::SHA1(sha1) => {
-				check(&cfg, sha1_index.as_ref().expect("SHA1 index required"), &sha1)?;
-			},
-			Input::NT(nt) => {
-				check(&cfg, nt_index.as_ref().expect("NT index required"), &nt)?;
-			},
-		}
+	let mut indexes = Indexes::open(&cfg)?;
+	if cfg.interactive {
+		return interactive(&cfg, &mut indexes);
+	}
+	if cfg.prompt {
+		let password = rpassword::prompt_password("Password: ")?;
+		return check_oneshot(&cfg, &indexes.checker, &password);
+	}
+	let start = Instant::now();
+	let mut summary = Summary::default();
+	if cfg.matrix {
+		print_matrix_header(&indexes.checker);
+	}
+	if cfg.pwdump {
+		check_pwdump(&cfg, &mut indexes, &mut summary)?;
+	} else {
+		check_lines(&cfg, &mut indexes, &mut summary)?;
+	}
+	if let Some(format) = cfg.summary {
+		summary.print(format, start.elapsed());
 	}
 	Ok(())
 }
@@ -0,0 +1,45 @@
+extern crate hibp_index;
+
+use hibp_index::index::{reindex, Depth, Index};
+
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Copy an index into a new index with a different table depth
+///
+/// Use `hibp-inspect` to check the bucket statistics of an index first.
+struct Cli {
+	#[arg(long)]
+	/// Number of prefix bits in the table of the new index
+	depth: u8,
+
+	/// Index to copy
+	input: PathBuf,
+
+	/// Index to create
+	output: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+	let cli = <Cli as clap::Parser>::parse();
+	let depth =
+		Depth::new(cli.depth).ok_or_else(|| anyhow::anyhow!("invalid depth {}", cli.depth))?;
+	if cli.output.exists() {
+		anyhow::bail!("Output file {:?} already exists", cli.output);
+	}
+	let index = Index::open(fs::File::open(&cli.input)?)?;
+	let entries = reindex(&index, &cli.output, depth)?;
+	eprintln!("{} entries written", entries);
+	Ok(())
+}
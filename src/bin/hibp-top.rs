@@ -0,0 +1,82 @@
+extern crate hibp_index;
+
+use hibp_index::checker::DEFAULT_SHA1_INDEX;
+use hibp_index::data::{Count32, Count64, KeyData, KnownKeyType, NT, SHA1};
+use hibp_index::index::{Index, TypedIndex};
+use hibp_index::policy::BreachCount;
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Show the most frequent hashes of an index with breach counts
+///
+/// Requires an index with 4 or 8 byte (big endian) counts as payload.
+struct Cli {
+	#[arg(long, default_value_t = 20)]
+	/// Number of hashes to show
+	top: usize,
+
+	#[arg(long)]
+	/// Label hashes with matching passwords from this wordlist (one password per line)
+	wordlist: Option<PathBuf>,
+
+	#[arg(default_value = DEFAULT_SHA1_INDEX)]
+	/// Index to scan
+	index: PathBuf,
+}
+
+fn run<D, P>(cli: &Cli, index: Index<fs::File>, hash: fn(&str) -> D) -> anyhow::Result<()>
+where
+	D: KeyData + Ord + std::hash::Hash,
+	P: BreachCount,
+{
+	let index = TypedIndex::<D, P, _>::from_index(index)?;
+	let top = index.top_k(cli.top)?;
+	let mut labels = HashMap::<D, String>::new();
+	if let Some(wordlist) = &cli.wordlist {
+		let wanted: HashSet<&D> = top.iter().map(|(key, _)| key).collect();
+		for line in BufReader::new(fs::File::open(wordlist)?).lines() {
+			let password = line?;
+			let key = hash(&password);
+			if wanted.contains(&key) && !labels.contains_key(&key) {
+				labels.insert(key, password);
+			}
+		}
+	}
+	for (rank, (key, count)) in top.iter().enumerate() {
+		let label = labels.get(key).map(String::as_str).unwrap_or("");
+		println!("{}\t{}\t{}\t{}", rank + 1, count, key.hex(), label);
+	}
+	Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+	let cli = <Cli as clap::Parser>::parse();
+	let index = Index::open(fs::File::open(&cli.index)?)?;
+	let payload_size = index.payload_size();
+	match (index.key_type().as_known(), payload_size) {
+		(Some(KnownKeyType::SHA1), 4) => {
+			run::<SHA1, Count32>(&cli, index, |password| SHA1::hash(password.as_bytes()))
+		},
+		(Some(KnownKeyType::SHA1), 8) => {
+			run::<SHA1, Count64>(&cli, index, |password| SHA1::hash(password.as_bytes()))
+		},
+		(Some(KnownKeyType::NT), 4) => run::<NT, Count32>(&cli, index, NT::hash),
+		(Some(KnownKeyType::NT), 8) => run::<NT, Count64>(&cli, index, NT::hash),
+		(Some(_), _) => anyhow::bail!("index has no counts (payload size {})", payload_size),
+		(None, _) => anyhow::bail!("unsupported key type {}", index.key_type().name()),
+	}
+}
@@ -0,0 +1,244 @@
+extern crate hibp_index;
+
+use hibp_index::checker::{DEFAULT_NT_INDEX, DEFAULT_SHA1_INDEX};
+use hibp_index::config::Config;
+use hibp_index::data::{KeyData, NoPayload, NT, SHA1};
+use hibp_index::index::{AtomicFile, Depth, IndexOptions, TypedBuilder};
+use hibp_index::online::{RangeClient, RangeUpdater, RetryPolicy, DEFAULT_RANGE_API_URL};
+
+use chrono::TimeZone;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(clap::Parser)]
+#[command(author, version)]
+#[command(help_template(
+	"\
+{before-help}{name} {version}
+{author-with-newline}{about-with-newline}
+{usage-heading} {usage}
+
+{all-args}{after-help}
+"
+))]
+/// Mirror ranges of the Pwned Passwords API and (re)build an index from them
+///
+/// Only ranges that changed since the last run are downloaded; the index is
+/// only rebuilt if any range changed since it was built (or the index doesn't
+/// exist yet). A run interrupted within the last day resumes where it stopped.
+struct Cli {
+	#[arg(long)]
+	/// Load settings from TOML configuration file (options given here override them)
+	config: Option<PathBuf>,
+
+	#[arg(long)]
+	/// Download NT hashes (aka NTLM) instead of SHA-1 hashes
+	nt: bool,
+
+	#[arg(long)]
+	/// Directory to store ranges in (default: hibp-ranges)
+	dir: Option<PathBuf>,
+
+	#[arg(long)]
+	/// Index to build (default: hibp-sha1.index or hibp-ntlm.index)
+	output: Option<PathBuf>,
+
+	#[arg(long)]
+	/// Base URL of range API (default: https://api.pwnedpasswords.com/range/)
+	base_url: Option<String>,
+
+	#[arg(long)]
+	/// Maximum number of attempts per range (default: 5)
+	attempts: Option<u32>,
+
+	#[arg(long)]
+	/// Maximum number of requests per second
+	rate_limit: Option<u32>,
+
+	#[arg(long)]
+	/// Download all ranges straight into the index (without mirroring them in --dir)
+	stream: bool,
+
+	#[arg(long)]
+	/// Number of concurrent downloads with --stream (default: 4)
+	workers: Option<usize>,
+
+	#[arg(long)]
+	/// Depth of the built index (default: 20)
+	depth: Option<u8>,
+
+	#[arg(long, conflicts_with = "stream")]
+	/// Check all ranges again instead of resuming an interrupted run
+	restart: bool,
+}
+
+/// Command line options merged with configuration file and defaults
+struct Settings {
+	nt: bool,
+	dir: PathBuf,
+	output: PathBuf,
+	base_url: String,
+	attempts: u32,
+	rate_limit: Option<u32>,
+	stream: bool,
+	restart: bool,
+	workers: usize,
+	depth: Depth,
+}
+
+impl Settings {
+	fn new(cli: Cli) -> anyhow::Result<Self> {
+		let config = match &cli.config {
+			Some(path) => {
+				Config::load(path).map_err(|e| anyhow::anyhow!("config {:?}: {}", path, e))?
+			},
+			None => Config::default(),
+		};
+		let ranges = config.ranges;
+		let depth = match cli.depth {
+			Some(depth) => {
+				Depth::new(depth).ok_or_else(|| anyhow::anyhow!("Invalid depth {}", depth))?
+			},
+			None => ranges.depth().unwrap_or(Depth::DEPTH20),
+		};
+		let output = match cli.output {
+			Some(output) => output,
+			None if cli.nt => config.nt_index.unwrap_or_else(|| PathBuf::from(DEFAULT_NT_INDEX)),
+			None => config.sha1_index.unwrap_or_else(|| PathBuf::from(DEFAULT_SHA1_INDEX)),
+		};
+		Ok(Self {
+			nt: cli.nt,
+			dir: cli.dir.or(ranges.dir).unwrap_or_else(|| PathBuf::from("hibp-ranges")),
+			output,
+			base_url: cli
+				.base_url
+				.or(ranges.base_url)
+				.unwrap_or_else(|| DEFAULT_RANGE_API_URL.to_string()),
+			attempts: cli
+				.attempts
+				.or(ranges.attempts)
+				.unwrap_or(RetryPolicy::default().max_attempts),
+			rate_limit: cli.rate_limit.or(ranges.rate_limit),
+			stream: cli.stream,
+			restart: cli.restart,
+			workers: cli.workers.or(ranges.workers).unwrap_or(4),
+			depth,
+		})
+	}
+}
+
+fn index_options(settings: &Settings) -> anyhow::Result<IndexOptions> {
+	let mut options = IndexOptions::v1();
+	let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+	if let Some(mtime) = chrono::Utc.timestamp_opt(now.as_secs() as i64, 0).single() {
+		options.metadata.set_mtime(mtime);
+	}
+	options.metadata.set_source(&settings.base_url);
+	Ok(options)
+}
+
+fn stream<D: KeyData + Send>(
+	settings: &Settings,
+	client: &RangeClient,
+	output: &Path,
+) -> anyhow::Result<()> {
+	let mut builder = TypedBuilder::<D, NoPayload, _>::create_with_atomic_file(
+		AtomicFile::create_locked(output)?,
+		"pwned-passwords range API",
+		settings.depth,
+		&index_options(settings)?,
+	)?;
+	let mut last_report = Instant::now();
+	builder.set_progress(move |progress| {
+		if last_report.elapsed() >= Duration::from_secs(1) {
+			last_report = Instant::now();
+			eprint!(
+				"\r{} entries, {} MiB written, prefix {}",
+				progress.entries,
+				progress.bytes >> 20,
+				progress.prefix.hex()
+			);
+		}
+	});
+	client.build_index(builder, settings.workers)?;
+	eprintln!();
+	eprintln!("built {:?}", output);
+	Ok(())
+}
+
+fn run<D: KeyData + Send>(settings: &Settings) -> anyhow::Result<()> {
+	let output = &settings.output;
+	let mut client = RangeClient::with_base_url(&settings.base_url)
+		.with_retry(RetryPolicy { max_attempts: settings.attempts, ..RetryPolicy::default() });
+	if let Some(rate_limit) = settings.rate_limit {
+		client = client.with_rate_limit(rate_limit);
+	}
+	if settings.stream {
+		return stream::<D>(settings, &client, output);
+	}
+	fs::create_dir_all(&settings.dir)?;
+	let mut updater = RangeUpdater::<D>::open(client, &settings.dir)?;
+	if settings.restart {
+		updater.clear_checkpoint()?;
+	}
+	let mut last_report = Instant::now();
+	updater.set_progress(move |progress| {
+		if last_report.elapsed() >= Duration::from_secs(1) {
+			last_report = Instant::now();
+			let eta = progress.eta().map_or(0, |eta| eta.as_secs());
+			eprint!(
+				"\r{}/{} ranges ({} downloaded, {} failed), {} MiB fetched, prefix {}, ETA {}:{:02}:{:02}",
+				progress.completed,
+				progress.total.unwrap_or(0),
+				progress.changed,
+				progress.failed,
+				progress.bytes >> 20,
+				progress.prefix.hex(),
+				eta / 3600,
+				eta / 60 % 60,
+				eta % 60,
+			);
+		}
+	});
+	let summary = updater.update_all()?;
+	eprintln!();
+	eprintln!(
+		"{} ranges checked ({} resumed), {} downloaded",
+		summary.checked, summary.resumed, summary.changed
+	);
+	if !summary.is_complete() {
+		for failure in &summary.failures {
+			eprintln!("range {} failed: {}", failure.prefix.hex(), failure.error);
+		}
+		anyhow::bail!("{} ranges failed; run again to retry them", summary.failures.len());
+	}
+	// manifest is only written if ranges changed
+	let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+	let up_to_date = match (modified(output), modified(&updater.manifest_path())) {
+		(Some(built), Some(changed)) => built > changed,
+		_ => false,
+	};
+	if up_to_date {
+		eprintln!("{:?} is up to date", output);
+		return Ok(());
+	}
+	let builder = TypedBuilder::<D, NoPayload, _>::create_with_atomic_file(
+		AtomicFile::create_locked(output)?,
+		"pwned-passwords range API",
+		settings.depth,
+		&index_options(settings)?,
+	)?;
+	updater.build_index(builder)?;
+	eprintln!("built {:?}", output);
+	Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+	let settings = Settings::new(<Cli as clap::Parser>::parse())?;
+	if settings.nt {
+		run::<NT>(&settings)
+	} else {
+		run::<SHA1>(&settings)
+	}
+}
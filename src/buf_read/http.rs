@@ -0,0 +1,101 @@
+use super::{FileLen, ReadAt};
+use std::io;
+use wasm_bindgen::JsValue;
+use web_sys::{XmlHttpRequest, XmlHttpRequestResponseType};
+
+fn js_error(context: &str, value: JsValue) -> io::Error {
+	io::Error::other(format!("{}: {:?}", context, value))
+}
+
+/// Remote file read with HTTP range requests in the browser (`wasm32`)
+///
+/// Supports opening and querying an index hosted on a web server directly in
+/// the browser, without sending passwords or hashes anywhere (only byte
+/// ranges of the index are requested).
+///
+/// The index reader needs synchronous reads; as `fetch` is asynchronous only
+/// this uses synchronous `XMLHttpRequest`, which browsers only allow (with
+/// binary responses) in web workers. The server must support range requests
+/// (and allow the `Range` header for cross-origin requests).
+pub struct HttpFile {
+	url: String,
+	len: u64,
+	position: u64,
+}
+
+impl HttpFile {
+	/// Open remote file; requests file size with a `HEAD` request
+	pub fn open(url: &str) -> io::Result<Self> {
+		let xhr = XmlHttpRequest::new().map_err(|e| js_error("XMLHttpRequest", e))?;
+		xhr.open_with_async("HEAD", url, false).map_err(|e| js_error("HEAD", e))?;
+		xhr.send().map_err(|e| js_error("HEAD", e))?;
+		let status = xhr.status().map_err(|e| js_error("HEAD", e))?;
+		if status != 200 {
+			return Err(io::Error::other(format!("HEAD {}: status {}", url, status)));
+		}
+		let len = xhr
+			.get_response_header("Content-Length")
+			.map_err(|e| js_error("HEAD", e))?
+			.and_then(|len| len.parse::<u64>().ok())
+			.ok_or_else(|| io::Error::other(format!("HEAD {}: missing Content-Length", url)))?;
+		Ok(Self { url: url.to_string(), len, position: 0 })
+	}
+
+	/// URL of remote file
+	pub fn url(&self) -> &str {
+		&self.url
+	}
+}
+
+impl ReadAt for HttpFile {
+	fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+		if offset >= self.len || buf.is_empty() {
+			return Ok(0);
+		}
+		let end = std::cmp::min(self.len, offset + buf.len() as u64);
+		let xhr = XmlHttpRequest::new().map_err(|e| js_error("XMLHttpRequest", e))?;
+		xhr.open_with_async("GET", &self.url, false).map_err(|e| js_error("GET", e))?;
+		xhr.set_response_type(XmlHttpRequestResponseType::Arraybuffer);
+		xhr.set_request_header("Range", &format!("bytes={}-{}", offset, end - 1))
+			.map_err(|e| js_error("GET", e))?;
+		xhr.send().map_err(|e| js_error("GET", e))?;
+		let status = xhr.status().map_err(|e| js_error("GET", e))?;
+		if status != 206 {
+			return Err(io::Error::other(format!(
+				"GET {}: status {} (range requests not supported?)",
+				self.url, status
+			)));
+		}
+		let response = js_sys::Uint8Array::new(&xhr.response().map_err(|e| js_error("GET", e))?);
+		let got = std::cmp::min(response.length() as usize, buf.len());
+		response.subarray(0, got as u32).copy_to(&mut buf[..got]);
+		Ok(got)
+	}
+}
+
+impl FileLen for HttpFile {
+	fn file_len(&self) -> io::Result<u64> {
+		Ok(self.len)
+	}
+}
+
+impl io::Read for HttpFile {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let got = self.read_at(buf, self.position)?;
+		self.position += got as u64;
+		Ok(got)
+	}
+}
+
+impl io::Seek for HttpFile {
+	fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+		let position = match pos {
+			io::SeekFrom::Start(pos) => Some(pos),
+			io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+			io::SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+		};
+		self.position = position
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek position"))?;
+		Ok(self.position)
+	}
+}
@@ -1,30 +1,91 @@
 //! Seekable buffered reader
 
+#[cfg(feature = "wasm")]
+mod http;
+mod page_cache;
 mod read_at;
+mod seek_reader;
 
-pub use self::read_at::{FileLen, ReadAt};
+#[cfg(feature = "wasm")]
+pub use self::http::HttpFile;
+pub use self::{
+	read_at::{FileLen, KnownLen, ReadAt},
+	seek_reader::SeekReader,
+};
 
-use cached::{Cached, SizedCache};
+use self::page_cache::PageCache;
 use std::io;
 
 const PAGE_SIZE_BITS: u32 = 13;
-const PAGE_SIZE: usize = 1 << PAGE_SIZE_BITS;
+/// Size of pages read from the underlying reader (8 KiB)
+pub const PAGE_SIZE: usize = 1 << PAGE_SIZE_BITS;
+/// Default cache size of readers used by index lookups (16 pages, 128 KiB)
+pub const DEFAULT_CACHE_SIZE: usize = 16 * PAGE_SIZE;
+/// Maximum number of pages read at once for sequential access (1 MiB)
+const MAX_READAHEAD_PAGES: u64 = 128;
+
+/// Counters of a [`BufReader`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct CacheStats {
+	/// Pages read from the underlying reader (including readahead)
+	pub pages_loaded: u64,
+	/// Page accesses served without reading (from the cache or readahead buffer)
+	pub cache_hits: u64,
+	/// Bytes read from the underlying reader
+	pub bytes_read: u64,
+}
 
 /// Seekable buffered reader; underlying reader should support parallel reading at different file positions
 ///
 /// The underlying reader is a shared reference, which is why it needs to support
 /// some sort of "parallel" reading.
+///
+/// Pages are cached up to a total size (least recently used pages are
+/// evicted first).
+///
+/// Sequential access (e.g. scanning many buckets) is detected: if a page
+/// is loaded directly after the pages of the previous read, more pages are
+/// read at once (doubling up to 1 MiB, but not more than the cache size)
+/// into a separate readahead buffer. In total a reader therefore uses up
+/// to twice the cache size.
+///
+/// Reading and [`seek_from_start`](BufReader::seek_from_start) only need
+/// [`ReadAt`]; [`io::Seek`] is only implemented for readers that also know
+/// their size ([`FileLen`]), as required by `SeekFrom::End`.
 pub struct BufReader<'a, R> {
-	cache: SizedCache<u64, Vec<u8>>,
+	cache: PageCache,
 	position: u64,
 	reader: &'a R,
+	// first page after the previous read
+	next_page: u64,
+	// number of pages of the previous read
+	readahead: u64,
+	// data of the last readahead, starting at file offset `ahead_offset`
+	ahead: Vec<u8>,
+	ahead_offset: u64,
+	stats: CacheStats,
 }
 
 impl<'a, R: ReadAt> BufReader<'a, R> {
 	/// Create new reader with given number of pages as buffer
 	pub fn new(reader: &'a R, cache_capacity: usize) -> Self {
-		let cache = SizedCache::with_size(cache_capacity);
-		Self { cache, position: 0, reader }
+		Self::with_cache_size(reader, cache_capacity.saturating_mul(PAGE_SIZE))
+	}
+
+	/// Create new reader caching pages up to a total of `cache_size` bytes
+	///
+	/// At least the current page is always cached.
+	pub fn with_cache_size(reader: &'a R, cache_size: usize) -> Self {
+		Self {
+			cache: PageCache::new(cache_size),
+			position: 0,
+			reader,
+			next_page: 0,
+			readahead: 0,
+			ahead: Vec::new(),
+			ahead_offset: 0,
+			stats: CacheStats::default(),
+		}
 	}
 
 	/// Seek to absolut position from file start
@@ -44,17 +105,49 @@ impl<'a, R: ReadAt> BufReader<'a, R> {
 		let page = self.position >> PAGE_SIZE_BITS;
 		let page_offset = page << PAGE_SIZE_BITS;
 		let offset = (self.position - page_offset) as usize;
-		if self.cache.cache_get(&page).is_none() {
-			let mut buf = Vec::new();
+		let ahead_end = self.ahead_offset + self.ahead.len() as u64;
+		if self.position >= self.ahead_offset && self.position < ahead_end {
+			self.stats.cache_hits += 1;
+			return Ok(&self.ahead[(self.position - self.ahead_offset) as usize..]);
+		}
+		if !self.cache.contains(page) {
+			let max_pages = (self.cache.max_bytes() / PAGE_SIZE).max(1) as u64;
+			let pages = if page == self.next_page {
+				(self.readahead * 2).clamp(1, MAX_READAHEAD_PAGES.min(max_pages))
+			} else {
+				1
+			};
+			self.readahead = pages;
+			self.next_page = page + pages;
+			if pages > 1 {
+				// sequential access: serve from readahead buffer, bypassing the cache
+				self.ahead.resize(pages as usize * PAGE_SIZE, 0);
+				let got = self.reader.read_at_till_eof(&mut self.ahead, page_offset)?;
+				self.ahead.truncate(got);
+				self.ahead_offset = page_offset;
+				self.stats.pages_loaded += got.div_ceil(PAGE_SIZE) as u64;
+				self.stats.bytes_read += got as u64;
+				return Ok(self.ahead.get(offset..).unwrap_or_default());
+			}
 
-			buf.resize(PAGE_SIZE, 0);
+			let mut buf = vec![0u8; PAGE_SIZE];
 			let got = self.reader.read_at_till_eof(&mut buf, page_offset)?;
 			buf.truncate(got);
+			self.stats.pages_loaded += 1;
+			self.stats.bytes_read += got as u64;
 
-			self.cache.cache_set(page, buf);
+			self.cache.insert(page, buf);
+		} else {
+			self.stats.cache_hits += 1;
 		}
-		// lookup twice to avoid borrowing issues
-		Ok(&self.cache.cache_get(&page).expect("just inserted")[offset..])
+		Ok(&self.cache.get(page).expect("just inserted")[offset..])
+	}
+}
+
+impl<R> BufReader<'_, R> {
+	/// Counters since the reader was created
+	pub fn stats(&self) -> CacheStats {
+		self.stats
 	}
 }
 
@@ -68,6 +161,16 @@ impl<R: ReadAt> io::Read for BufReader<'_, R> {
 	}
 }
 
+impl<R: ReadAt> io::BufRead for BufReader<'_, R> {
+	fn fill_buf(&mut self) -> io::Result<&[u8]> {
+		self.load_page()
+	}
+
+	fn consume(&mut self, amount: usize) {
+		self.position += amount as u64;
+	}
+}
+
 fn checked_opt<T>(value: Option<T>, msg: &'static str) -> io::Result<T> {
 	match value {
 		Some(v) => Ok(v),
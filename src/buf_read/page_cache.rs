@@ -0,0 +1,54 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Cache of file pages bounded by their total size; evicts least recently used pages
+pub(super) struct PageCache {
+	max_bytes: usize,
+	bytes: usize,
+	/// last use and data by page number
+	pages: HashMap<u64, (u64, Vec<u8>)>,
+	/// page number by last use
+	lru: BTreeMap<u64, u64>,
+	clock: u64,
+}
+
+impl PageCache {
+	pub(super) fn new(max_bytes: usize) -> Self {
+		Self { max_bytes, bytes: 0, pages: HashMap::new(), lru: BTreeMap::new(), clock: 0 }
+	}
+
+	pub(super) fn max_bytes(&self) -> usize {
+		self.max_bytes
+	}
+
+	pub(super) fn contains(&self, page: u64) -> bool {
+		self.pages.contains_key(&page)
+	}
+
+	/// Get page data and mark page as most recently used
+	pub(super) fn get(&mut self, page: u64) -> Option<&[u8]> {
+		let (last_use, data) = self.pages.get_mut(&page)?;
+		self.lru.remove(last_use);
+		self.clock += 1;
+		*last_use = self.clock;
+		self.lru.insert(self.clock, page);
+		Some(data)
+	}
+
+	/// Insert (not yet cached) page, evicting least recently used pages to stay within budget
+	///
+	/// The new page is always inserted, even if it alone exceeds the budget.
+	pub(super) fn insert(&mut self, page: u64, data: Vec<u8>) {
+		debug_assert!(!self.contains(page));
+		while self.bytes + data.len() > self.max_bytes {
+			let Some((_, oldest)) = self.lru.pop_first() else {
+				break;
+			};
+			let (_, evicted) = self.pages.remove(&oldest).expect("page of lru entry");
+			self.bytes -= evicted.len();
+		}
+		self.clock += 1;
+		self.bytes += data.len();
+		self.lru.insert(self.clock, page);
+		self.pages.insert(page, (self.clock, data));
+	}
+}
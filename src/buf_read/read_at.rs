@@ -33,11 +33,63 @@ pub trait ReadAt {
 }
 
 /// Need file size to support `SeekFrom::End`
+///
+/// Readers that can only seek can be wrapped in [`SeekReader`](super::SeekReader);
+/// readers with a size known from elsewhere in [`KnownLen`].
+///
+/// Only opening an index (and verifying its checksum) needs the size;
+/// lookups and scans of an opened index only need [`ReadAt`].
 pub trait FileLen {
 	/// Size of file
 	fn file_len(&self) -> io::Result<u64>;
 }
 
+/// Provides [`FileLen`] for a reader with a size known in advance
+///
+/// E.g. from a `Content-Length` header, a container format or a fixed
+/// layout; the size is not checked against the reader.
+///
+/// ```
+/// use hibp_index::buf_read::{FileLen, KnownLen};
+///
+/// let reader = KnownLen::new(&b"hello world"[..], 11);
+/// assert_eq!(reader.file_len().unwrap(), 11);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KnownLen<R> {
+	reader: R,
+	len: u64,
+}
+
+impl<R> KnownLen<R> {
+	/// Wrap reader with given size
+	pub fn new(reader: R, len: u64) -> Self {
+		Self { reader, len }
+	}
+
+	/// Wrapped reader
+	pub fn get_ref(&self) -> &R {
+		&self.reader
+	}
+
+	/// Return wrapped reader
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+impl<R: ReadAt> ReadAt for KnownLen<R> {
+	fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+		self.reader.read_at(buf, offset)
+	}
+}
+
+impl<R> FileLen for KnownLen<R> {
+	fn file_len(&self) -> io::Result<u64> {
+		Ok(self.len)
+	}
+}
+
 impl FileLen for std::fs::File {
 	fn file_len(&self) -> io::Result<u64> {
 		Ok(self.metadata()?.len())
@@ -48,7 +100,7 @@ impl FileLen for std::fs::File {
 mod unix_impl {
 	use std::os::unix::fs::FileExt;
 
-	impl<F: FileExt> super::ReadAt for F {
+	impl super::ReadAt for std::fs::File {
 		fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
 			FileExt::read_at(self, buf, offset)
 		}
@@ -59,9 +111,31 @@ mod unix_impl {
 mod windows_impl {
 	use std::os::windows::fs::FileExt;
 
-	impl<F: FileExt> super::ReadAt for F {
+	impl super::ReadAt for std::fs::File {
 		fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
 			FileExt::seek_read(self, buf, offset)
 		}
 	}
 }
+
+// forward through (smart) pointers, e.g. to share files between threads
+// (`Arc<File>`) or to hide the reader type (`Box<dyn ReadAt + Send + Sync>`)
+macro_rules! forward_pointer_impls {
+	($($pointer:ty),*) => {
+		$(
+			impl<T: ReadAt + ?Sized> ReadAt for $pointer {
+				fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+					(**self).read_at(buf, offset)
+				}
+			}
+
+			impl<T: FileLen + ?Sized> FileLen for $pointer {
+				fn file_len(&self) -> io::Result<u64> {
+					(**self).file_len()
+				}
+			}
+		)*
+	};
+}
+
+forward_pointer_impls!(&T, Box<T>, std::sync::Arc<T>);
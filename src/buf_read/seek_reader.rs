@@ -0,0 +1,60 @@
+use std::io::{self, Read, Seek};
+use std::sync::{Mutex, OnceLock};
+
+use super::{FileLen, ReadAt};
+
+/// Provides [`ReadAt`] and [`FileLen`] for readers that can only [`Read`] and [`Seek`]
+///
+/// E.g. archive members, in-memory buffers ([`io::Cursor`]) or network
+/// streams with seek emulation. Reads are serialized (seek and read while
+/// holding a lock); the length is determined once by seeking to the end.
+///
+/// ```
+/// use hibp_index::buf_read::{FileLen, ReadAt, SeekReader};
+///
+/// let reader = SeekReader::new(std::io::Cursor::new(b"hello world".to_vec()));
+/// let mut buf = [0u8; 5];
+/// assert_eq!(reader.read_at(&mut buf, 6).unwrap(), 5);
+/// assert_eq!(&buf, b"world");
+/// assert_eq!(reader.file_len().unwrap(), 11);
+/// ```
+#[derive(Debug)]
+pub struct SeekReader<R> {
+	reader: Mutex<R>,
+	len: OnceLock<u64>,
+}
+
+impl<R> SeekReader<R> {
+	/// Wrap reader
+	pub fn new(reader: R) -> Self {
+		Self { reader: Mutex::new(reader), len: OnceLock::new() }
+	}
+
+	/// Return wrapped reader (position is unspecified)
+	pub fn into_inner(self) -> R {
+		self.reader.into_inner().unwrap_or_else(|e| e.into_inner())
+	}
+
+	fn lock(&self) -> std::sync::MutexGuard<'_, R> {
+		// reader state is reset by seeking before every read
+		self.reader.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+impl<R: Read + Seek> ReadAt for SeekReader<R> {
+	fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+		let mut reader = self.lock();
+		reader.seek(io::SeekFrom::Start(offset))?;
+		reader.read(buf)
+	}
+}
+
+impl<R: Seek> FileLen for SeekReader<R> {
+	fn file_len(&self) -> io::Result<u64> {
+		if let Some(len) = self.len.get() {
+			return Ok(*len);
+		}
+		let len = self.lock().seek(io::SeekFrom::End(0))?;
+		Ok(*self.len.get_or_init(|| len))
+	}
+}
@@ -0,0 +1,501 @@
+//! Check passwords (or their hashes) against SHA-1 and/or NT indexes
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::checker::PasswordChecker;
+//!
+//! let checker = PasswordChecker::open_default()?;
+//! if checker.check_plaintext("password")? {
+//!     println!("password was found in breaches");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use crate::{
+	buf_read::ReadAt,
+	data::{FixedByteArray, KeyData, KnownKeyType, NoPayload, NT, SHA1},
+	errors::{IndexOpenError, MultiLookupError, PasswordCheckError},
+	index::{Index, MultiIndex, TypedIndex},
+	policy::raw_breach_count,
+};
+
+/// Default file name of SHA-1 index (as created by `hibp-create-sha1-index`)
+pub const DEFAULT_SHA1_INDEX: &str = "hibp-sha1.index";
+/// Default file name of NT index (as created by `hibp-create-ntlm-index`)
+pub const DEFAULT_NT_INDEX: &str = "hibp-ntlm.index";
+
+/// How to interpret input strings (see [`PasswordChecker::parse_input`])
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum InputMode {
+	/// Hex encoded hash (of a loaded index type) or plaintext password
+	#[default]
+	Auto,
+	/// Always treat input as plaintext password
+	Plaintext,
+	/// Input must be a hex encoded hash (of a loaded index type)
+	HashOnly,
+}
+
+/// Hash to lookup, one of the supported key types
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum PasswordHash {
+	/// SHA-1 hash
+	SHA1(SHA1),
+	/// NT hash
+	NT(NT),
+}
+
+impl PasswordHash {
+	/// Key type of the hash
+	pub fn key_type(&self) -> KnownKeyType {
+		match self {
+			Self::SHA1(_) => KnownKeyType::SHA1,
+			Self::NT(_) => KnownKeyType::NT,
+		}
+	}
+}
+
+impl std::fmt::Display for PasswordHash {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::SHA1(hash) => hash.fmt(f),
+			Self::NT(hash) => hash.fmt(f),
+		}
+	}
+}
+
+/// Owns SHA-1 and/or NT indexes and checks passwords against them
+///
+/// Input parsing and password hashing on top of a [`MultiIndex`] (which
+/// routes the lookups). Plaintext passwords are checked with the SHA-1 index
+/// if loaded, otherwise with the NT index.
+pub struct PasswordChecker<R = fs::File> {
+	indexes: MultiIndex<R>,
+}
+
+/// Open index file, checking its key type
+fn open_index<D: KeyData>(path: &Path) -> Result<Index<fs::File>, PasswordCheckError> {
+	Ok(TypedIndex::<D, NoPayload, _>::open(fs::File::open(path)?)?.into_index())
+}
+
+impl PasswordChecker<fs::File> {
+	/// Open given index files
+	pub fn open(
+		sha1_index: Option<&Path>,
+		nt_index: Option<&Path>,
+	) -> Result<Self, PasswordCheckError> {
+		let mut indexes = MultiIndex::new();
+		if let Some(path) = sha1_index {
+			indexes.insert(open_index::<SHA1>(path)?);
+		}
+		if let Some(path) = nt_index {
+			indexes.insert(open_index::<NT>(path)?);
+		}
+		Self::from_indexes(indexes)
+	}
+
+	/// Open [`DEFAULT_SHA1_INDEX`] and [`DEFAULT_NT_INDEX`] in the current
+	/// directory (if they exist; at least one is required)
+	pub fn open_default() -> Result<Self, PasswordCheckError> {
+		let sha1_index = Path::new(DEFAULT_SHA1_INDEX);
+		let nt_index = Path::new(DEFAULT_NT_INDEX);
+		Self::open(Some(sha1_index).filter(|p| p.is_file()), Some(nt_index).filter(|p| p.is_file()))
+	}
+}
+
+impl<R> PasswordChecker<R> {
+	/// Checker without any index
+	pub fn new() -> Self {
+		Self { indexes: MultiIndex::new() }
+	}
+
+	/// Checker using already opened indexes
+	///
+	/// All indexes must be SHA-1 or NT indexes (readable without payload key);
+	/// at least one is required.
+	pub fn from_indexes(indexes: MultiIndex<R>) -> Result<Self, PasswordCheckError> {
+		for index in indexes.iter() {
+			let key_size = match index.key_type().as_known() {
+				Some(KnownKeyType::SHA1) => SHA1::SIZE,
+				Some(KnownKeyType::NT) => NT::SIZE,
+				_ => {
+					return Err(PasswordCheckError::UnsupportedKeyType {
+						key_type: index.key_type().clone(),
+					})
+				},
+			};
+			if index.key_size() as usize != key_size {
+				return Err(IndexOpenError::KeySizeMismatch {
+					expected: key_size as u8,
+					found: index.key_size(),
+				}
+				.into());
+			}
+			if index.needs_payload_key() {
+				return Err(IndexOpenError::MissingPayloadKey.into());
+			}
+		}
+		if indexes.is_empty() {
+			return Err(PasswordCheckError::NoIndex);
+		}
+		Ok(Self { indexes })
+	}
+
+	/// Loaded indexes
+	pub fn indexes(&self) -> &MultiIndex<R> {
+		&self.indexes
+	}
+
+	/// SHA-1 index (if loaded)
+	pub fn sha1_index(&self) -> Option<&Index<R>> {
+		self.indexes.get(&KnownKeyType::SHA1)
+	}
+
+	/// NT index (if loaded)
+	pub fn nt_index(&self) -> Option<&Index<R>> {
+		self.indexes.get(&KnownKeyType::NT)
+	}
+
+	fn has_index(&self, key_type: KnownKeyType) -> bool {
+		self.indexes.get(&key_type).is_some()
+	}
+
+	/// Key types of loaded indexes (SHA-1 first)
+	pub fn key_types(&self) -> impl Iterator<Item = KnownKeyType> + '_ {
+		[KnownKeyType::SHA1, KnownKeyType::NT]
+			.into_iter()
+			.filter(|&key_type| self.has_index(key_type))
+	}
+
+	/// Whether no index is loaded
+	pub fn is_empty(&self) -> bool {
+		self.indexes.is_empty()
+	}
+
+	/// Hash plaintext password (SHA-1 if SHA-1 index is loaded, otherwise NT)
+	pub fn hash_plaintext(&self, password: &str) -> Result<PasswordHash, PasswordCheckError> {
+		if self.has_index(KnownKeyType::SHA1) {
+			Ok(PasswordHash::SHA1(SHA1::hash(password.as_bytes())))
+		} else if self.has_index(KnownKeyType::NT) {
+			Ok(PasswordHash::NT(NT::hash(password)))
+		} else {
+			Err(PasswordCheckError::NoIndex)
+		}
+	}
+
+	/// Parse input line as hash or plaintext password
+	///
+	/// Hex encoded hashes are only recognized for loaded index types; with
+	/// [`InputMode::Auto`] anything else is treated as plaintext password.
+	pub fn parse_input(
+		&self,
+		input: &str,
+		mode: InputMode,
+	) -> Result<PasswordHash, PasswordCheckError> {
+		if let Some(hash) = self.parse_hash(input, mode)? {
+			return Ok(hash);
+		}
+		self.hash_plaintext(input)
+	}
+
+	/// Hashes of plaintext password for all loaded indexes (SHA-1 first)
+	pub fn hash_plaintext_all(&self, password: &str) -> Vec<PasswordHash> {
+		let mut hashes = Vec::new();
+		if self.has_index(KnownKeyType::SHA1) {
+			hashes.push(PasswordHash::SHA1(SHA1::hash(password.as_bytes())));
+		}
+		if self.has_index(KnownKeyType::NT) {
+			hashes.push(PasswordHash::NT(NT::hash(password)));
+		}
+		hashes
+	}
+
+	/// Parse input line as hash or plaintext password (see [`PasswordChecker::parse_input`])
+	///
+	/// Unlike [`PasswordChecker::parse_input`] plaintext passwords are hashed
+	/// for all loaded indexes (see [`PasswordChecker::hash_plaintext_all`]).
+	pub fn parse_input_all(
+		&self,
+		input: &str,
+		mode: InputMode,
+	) -> Result<Vec<PasswordHash>, PasswordCheckError> {
+		if let Some(hash) = self.parse_hash(input, mode)? {
+			return Ok(vec![hash]);
+		}
+		if self.is_empty() {
+			return Err(PasswordCheckError::NoIndex);
+		}
+		Ok(self.hash_plaintext_all(input))
+	}
+
+	/// Parse hash (unless mode is [`InputMode::Plaintext`]); `None` if input should be treated as plaintext
+	fn parse_hash(
+		&self,
+		input: &str,
+		mode: InputMode,
+	) -> Result<Option<PasswordHash>, PasswordCheckError> {
+		if mode != InputMode::Plaintext {
+			if self.has_index(KnownKeyType::SHA1) {
+				if let Ok(sha1) = input.parse::<SHA1>() {
+					return Ok(Some(PasswordHash::SHA1(sha1)));
+				}
+			}
+			if self.has_index(KnownKeyType::NT) {
+				if let Ok(nt) = input.parse::<NT>() {
+					return Ok(Some(PasswordHash::NT(nt)));
+				}
+			}
+		}
+		if mode == InputMode::HashOnly {
+			if self.is_empty() {
+				return Err(PasswordCheckError::NoIndex);
+			}
+			return Err(PasswordCheckError::PlaintextNotAllowed);
+		}
+		Ok(None)
+	}
+}
+
+impl<R> Default for PasswordChecker<R> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<R> PasswordChecker<R>
+where
+	R: ReadAt,
+{
+	/// Set SHA-1 index
+	pub fn with_sha1(mut self, index: TypedIndex<SHA1, NoPayload, R>) -> Self {
+		self.indexes.insert(index.into_index());
+		self
+	}
+
+	/// Set NT index
+	pub fn with_nt(mut self, index: TypedIndex<NT, NoPayload, R>) -> Self {
+		self.indexes.insert(index.into_index());
+		self
+	}
+
+	/// Set page cache size (in bytes) of loaded indexes (see [`Index::set_cache_size`](crate::index::Index::set_cache_size))
+	pub fn set_cache_size(&mut self, cache_size: usize) {
+		self.indexes.set_cache_size(cache_size);
+	}
+
+	/// Lookup key in index of its key type; returns payload if found
+	fn lookup<D: KeyData>(
+		&self,
+		key_type: KnownKeyType,
+		key: &D,
+	) -> Result<Option<Vec<u8>>, PasswordCheckError> {
+		self.indexes.lookup(key).map_err(|e| match e {
+			MultiLookupError::LookupError(e) => e.into(),
+			// key sizes are checked when adding indexes
+			MultiLookupError::MissingIndex { .. } | MultiLookupError::InvalidKeyLength { .. } => {
+				PasswordCheckError::MissingIndex { key_type }
+			},
+		})
+	}
+
+	/// Payload of hash in index of its type
+	fn lookup_payload(&self, hash: &PasswordHash) -> Result<Option<Vec<u8>>, PasswordCheckError> {
+		match hash {
+			PasswordHash::SHA1(hash) => self.lookup(KnownKeyType::SHA1, hash),
+			PasswordHash::NT(hash) => self.lookup(KnownKeyType::NT, hash),
+		}
+	}
+
+	/// Whether SHA-1 hash is contained in SHA-1 index
+	pub fn check_sha1(&self, hash: &SHA1) -> Result<bool, PasswordCheckError> {
+		Ok(self.lookup(KnownKeyType::SHA1, hash)?.is_some())
+	}
+
+	/// Whether NT hash is contained in NT index
+	pub fn check_nt(&self, hash: &NT) -> Result<bool, PasswordCheckError> {
+		Ok(self.lookup(KnownKeyType::NT, hash)?.is_some())
+	}
+
+	/// Whether hash is contained in index of its type
+	pub fn check_hash(&self, hash: &PasswordHash) -> Result<bool, PasswordCheckError> {
+		Ok(self.lookup_payload(hash)?.is_some())
+	}
+
+	/// Whether the index for key type stores breach counts (see [`PasswordChecker::lookup_count`])
+	pub fn has_breach_counts(&self, key_type: KnownKeyType) -> bool {
+		let payload_size = self.indexes.get(&key_type).map(|index| index.payload_size());
+		matches!(payload_size, Some(4 | 8))
+	}
+
+	/// Breach count of hash (`None` if not found)
+	///
+	/// Indexes with 4 or 8 byte payloads are expected to store breach counts
+	/// (see [`raw_breach_count`]); entries of other indexes count as seen once.
+	pub fn lookup_count(&self, hash: &PasswordHash) -> Result<Option<u64>, PasswordCheckError> {
+		let payload = self.lookup_payload(hash)?;
+		Ok(payload.map(|payload| raw_breach_count(&payload).unwrap_or(1)))
+	}
+
+	/// Whether hash of plaintext password is contained in index (see [`PasswordChecker::hash_plaintext`])
+	pub fn check_plaintext(&self, password: &str) -> Result<bool, PasswordCheckError> {
+		self.check_hash(&self.hash_plaintext(password)?)
+	}
+
+	/// Parse (see [`PasswordChecker::parse_input`]) and check input
+	pub fn check_input(
+		&self,
+		input: &str,
+		mode: InputMode,
+	) -> Result<(PasswordHash, bool), PasswordCheckError> {
+		let hash = self.parse_input(input, mode)?;
+		let found = self.check_hash(&hash)?;
+		Ok((hash, found))
+	}
+
+	/// Parse (see [`PasswordChecker::parse_input_all`]) and check input with all loaded indexes
+	///
+	/// Returns result for each hash; hex encoded hashes are only checked
+	/// with the index of their type.
+	pub fn check_input_all(
+		&self,
+		input: &str,
+		mode: InputMode,
+	) -> Result<Vec<(PasswordHash, bool)>, PasswordCheckError> {
+		self.parse_input_all(input, mode)?
+			.into_iter()
+			.map(|hash| {
+				let found = self.check_hash(&hash)?;
+				Ok((hash, found))
+			})
+			.collect()
+	}
+}
+
+/// How [`HybridChecker`] uses the online range API for stale indexes
+#[cfg(feature = "online")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum StaleMode {
+	/// Only use the online API (local results are ignored)
+	#[default]
+	Fallback,
+	/// Ask the online API only for hashes not found locally
+	CrossCheck,
+}
+
+/// Where the result of a [`HybridChecker`] lookup came from
+#[cfg(feature = "online")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum LookupSource {
+	/// Local index
+	Local,
+	/// Online range API
+	Online,
+}
+
+/// Result of a [`HybridChecker`] lookup
+#[cfg(feature = "online")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HybridResult {
+	/// Whether the hash was found
+	pub found: bool,
+	/// Breach count (only known from the online API)
+	pub count: Option<u64>,
+	/// Source of the result
+	pub source: LookupSource,
+}
+
+/// Checks local indexes first, using the online range API if they are stale
+///
+/// An index is stale if its data is older than the configured maximum age
+/// (see [`Index::mtime`](crate::index::Index::mtime)); indexes without
+/// mtime are always stale. Hashes of a type without local index are always
+/// checked online.
+#[cfg(feature = "online")]
+pub struct HybridChecker<R = fs::File> {
+	local: PasswordChecker<R>,
+	client: crate::online::RangeClient,
+	max_age: std::time::Duration,
+	stale_mode: StaleMode,
+}
+
+#[cfg(feature = "online")]
+impl<R> HybridChecker<R>
+where
+	R: ReadAt,
+{
+	/// Create checker; indexes with data older than `max_age` are stale
+	pub fn new(
+		local: PasswordChecker<R>,
+		client: crate::online::RangeClient,
+		max_age: std::time::Duration,
+	) -> Self {
+		Self { local, client, max_age, stale_mode: StaleMode::default() }
+	}
+
+	/// Set how to handle stale indexes
+	pub fn with_stale_mode(mut self, stale_mode: StaleMode) -> Self {
+		self.stale_mode = stale_mode;
+		self
+	}
+
+	/// Local indexes
+	pub fn local(&self) -> &PasswordChecker<R> {
+		&self.local
+	}
+
+	fn is_stale(&self, mtime: Option<chrono::DateTime<chrono::Utc>>) -> bool {
+		let Some(mtime) = mtime else {
+			return true;
+		};
+		let now = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map_or(0, |d| d.as_secs() as i64);
+		now.saturating_sub(mtime.timestamp()) > self.max_age.as_secs() as i64
+	}
+
+	/// Whether the local index for hashes of given key type is missing or stale
+	pub fn needs_online(&self, key_type: KnownKeyType) -> bool {
+		let mtime = self.local.indexes.get(&key_type).map(|index| index.mtime());
+		match mtime {
+			None => true,
+			Some(mtime) => self.is_stale(mtime),
+		}
+	}
+
+	fn check_online(&self, hash: &PasswordHash) -> Result<HybridResult, PasswordCheckError> {
+		let count = match hash {
+			PasswordHash::SHA1(hash) => self.client.lookup_count(hash)?,
+			PasswordHash::NT(hash) => self.client.lookup_count(hash)?,
+		};
+		Ok(HybridResult { found: count.is_some(), count, source: LookupSource::Online })
+	}
+
+	/// Check hash locally and/or online
+	pub fn check_hash(&self, hash: &PasswordHash) -> Result<HybridResult, PasswordCheckError> {
+		if !self.needs_online(hash.key_type()) {
+			let found = self.local.check_hash(hash)?;
+			return Ok(HybridResult { found, count: None, source: LookupSource::Local });
+		}
+		let has_local = self.local.has_index(hash.key_type());
+		if has_local && self.stale_mode == StaleMode::CrossCheck && self.local.check_hash(hash)? {
+			return Ok(HybridResult { found: true, count: None, source: LookupSource::Local });
+		}
+		self.check_online(hash)
+	}
+
+	/// Hash plaintext password (see [`PasswordChecker::hash_plaintext`]) and check it
+	///
+	/// Without any local index the password is hashed with SHA-1.
+	pub fn check_plaintext(&self, password: &str) -> Result<HybridResult, PasswordCheckError> {
+		let hash = match self.local.hash_plaintext(password) {
+			Err(PasswordCheckError::NoIndex) => PasswordHash::SHA1(SHA1::hash(password.as_bytes())),
+			hash => hash?,
+		};
+		self.check_hash(&hash)
+	}
+}
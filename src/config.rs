@@ -0,0 +1,99 @@
+//! Configuration file shared by the binaries (`--config`)
+//!
+//! All settings are optional; command line options override them. Relative
+//! paths are resolved relative to the directory of the configuration file.
+//!
+//! ```toml
+//! # index files (default: hibp-sha1.index / hibp-ntlm.index)
+//! sha1_index = "/srv/hibp/hibp-sha1.index"
+//! nt_index = "/srv/hibp/hibp-ntlm.index"
+//! # page cache size (in bytes) of each lookup
+//! cache_size = 1048576
+//!
+//! # hibp-update-ranges
+//! [ranges]
+//! dir = "/srv/hibp/ranges"
+//! base_url = "https://api.pwnedpasswords.com/range/"
+//! attempts = 5
+//! rate_limit = 50
+//! workers = 8
+//! depth = 20
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{errors::ConfigError, index::Depth};
+
+/// Settings of all binaries
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+	/// SHA-1 index file
+	pub sha1_index: Option<PathBuf>,
+	/// NT index file
+	pub nt_index: Option<PathBuf>,
+	/// Page cache size (in bytes) of each lookup (see [`Index::set_cache_size`](crate::index::Index::set_cache_size))
+	pub cache_size: Option<usize>,
+	/// Settings for mirroring the range API
+	#[serde(default)]
+	pub ranges: RangesConfig,
+}
+
+/// Settings for mirroring the range API (`[ranges]` section)
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RangesConfig {
+	/// Directory to store ranges in
+	pub dir: Option<PathBuf>,
+	/// Base URL of range API
+	pub base_url: Option<String>,
+	/// Maximum number of attempts per range
+	pub attempts: Option<u32>,
+	/// Maximum number of requests per second
+	pub rate_limit: Option<u32>,
+	/// Number of concurrent downloads
+	pub workers: Option<usize>,
+	/// Depth of the built index
+	depth: Option<u8>,
+}
+
+impl RangesConfig {
+	/// Depth of the built index
+	pub fn depth(&self) -> Option<Depth> {
+		// validated in `Config::load`
+		self.depth.and_then(Depth::new)
+	}
+}
+
+impl Config {
+	/// Load configuration file
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+		let path = path.as_ref();
+		let mut config = Self::parse(&fs::read_to_string(path)?)?;
+		if let Some(base) = path.parent() {
+			config.resolve_paths(base);
+		}
+		Ok(config)
+	}
+
+	/// Parse configuration (relative paths are kept as they are)
+	pub fn parse(data: &str) -> Result<Self, ConfigError> {
+		let config: Self = toml::from_str(data)?;
+		if let Some(depth) = config.ranges.depth {
+			if Depth::new(depth).is_none() {
+				return Err(ConfigError::InvalidDepth { depth });
+			}
+		}
+		Ok(config)
+	}
+
+	fn resolve_paths(&mut self, base: &Path) {
+		let paths = [&mut self.sha1_index, &mut self.nt_index, &mut self.ranges.dir];
+		for path in paths.into_iter().flatten() {
+			if path.is_relative() {
+				*path = base.join(&*path);
+			}
+		}
+	}
+}
@@ -0,0 +1,87 @@
+use core::fmt;
+
+use super::{FixedByteArrayImpl, PayloadData};
+
+macro_rules! count_payload {
+	($(#[$meta:meta])* $name:ident, $int:ty, $bytes:literal) => {
+		$(#[$meta])*
+		#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+		pub struct $name(pub [u8; $bytes]);
+
+		impl $name {
+			/// Count with the given value
+			pub fn new(value: $int) -> Self {
+				Self(value.to_be_bytes())
+			}
+
+			/// Stored count
+			pub fn value(&self) -> u64 {
+				<$int>::from_be_bytes(self.0) as u64
+			}
+
+			/// Count with the given value; values too large are stored as maximum value
+			pub fn saturating_from(value: u64) -> Self {
+				Self::new(value.min(<$int>::MAX as u64) as $int)
+			}
+		}
+
+		impl From<$int> for $name {
+			fn from(value: $int) -> Self {
+				Self::new(value)
+			}
+		}
+
+		impl AsRef<[u8; $bytes]> for $name {
+			fn as_ref(&self) -> &[u8; $bytes] {
+				&self.0
+			}
+		}
+
+		impl AsMut<[u8; $bytes]> for $name {
+			fn as_mut(&mut self) -> &mut [u8; $bytes] {
+				&mut self.0
+			}
+		}
+
+		impl fmt::Debug for $name {
+			fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				f.debug_tuple(stringify!($name)).field(&self.value()).finish()
+			}
+		}
+
+		impl fmt::Display for $name {
+			fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+				fmt::Display::fmt(&self.value(), f)
+			}
+		}
+
+		impl FixedByteArrayImpl for $name {
+			type ByteArray = [u8; $bytes];
+			type HexArray = [u8; 2 * $bytes];
+		}
+
+		impl PayloadData for $name {
+			const COUNTS: bool = true;
+		}
+	};
+}
+
+count_payload!(
+	/// Breach count payload: 4 bytes, big endian (unsigned)
+	///
+	/// ```
+	/// use hibp_index::data::Count32;
+	///
+	/// assert_eq!(Count32::new(3).0, [0, 0, 0, 3]);
+	/// assert_eq!(Count32::saturating_from(1 << 40).value(), u32::MAX as u64);
+	/// ```
+	Count32,
+	u32,
+	4
+);
+count_payload!(
+	/// Breach count payload: 8 bytes, big endian (unsigned)
+	Count64,
+	u64,
+	8
+);
@@ -1,6 +1,25 @@
 use super::seal_trait::U8Array;
 use super::FixedByteArray;
 
+/// Case of hex digits `a`-`f`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum HexCase {
+	/// Lower case (default)
+	#[default]
+	Lower,
+	/// Upper case (as used by the HIBP API and downloads)
+	Upper,
+}
+
+impl HexCase {
+	fn apply(self, digits: &mut [u8]) {
+		match self {
+			Self::Lower => digits.make_ascii_lowercase(),
+			Self::Upper => digits.make_ascii_uppercase(),
+		}
+	}
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 /// Hex representation of a byte array
 pub struct Hex<D> {
@@ -15,6 +34,14 @@ impl Hex<()> {
 	}
 }
 
+impl<D: AsRef<[u8]> + AsMut<[u8]>> Hex<D> {
+	/// Render hex digits in given case
+	pub fn with_case(mut self, case: HexCase) -> Self {
+		case.apply(self.str_data.as_mut());
+		self
+	}
+}
+
 impl<D: AsRef<[u8]>> Hex<D> {
 	fn _raw(&self) -> &[u8] {
 		self.str_data.as_ref()
@@ -22,11 +49,11 @@ impl<D: AsRef<[u8]>> Hex<D> {
 
 	/// String representation
 	pub fn as_str(&self) -> &str {
-		std::str::from_utf8(self._raw()).expect("ascii")
+		core::str::from_utf8(self._raw()).expect("ascii")
 	}
 }
 
-impl<D: AsRef<[u8]>> std::ops::Deref for Hex<D> {
+impl<D: AsRef<[u8]>> core::ops::Deref for Hex<D> {
 	type Target = str;
 
 	fn deref(&self) -> &Self::Target {
@@ -52,8 +79,8 @@ impl<D: AsRef<[u8]>> AsRef<D> for Hex<D> {
 	}
 }
 
-impl<D: AsRef<[u8]>> std::fmt::Display for Hex<D> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<D: AsRef<[u8]>> core::fmt::Display for Hex<D> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		f.write_str(self.as_str())
 	}
 }
@@ -80,9 +107,8 @@ fn nibble_low(byte: u8) -> u8 {
 }
 
 impl HexRange<()> {
-	pub(super) fn new<A: FixedByteArray>(arr: &A, start: u32, end: u32) -> HexRange<A::HexArray> {
-		let data = arr.data();
-		let mut str_data = A::HexArray::zeroed();
+	pub(super) fn new<H: U8Array>(data: &[u8], start: u32, end: u32) -> HexRange<H> {
+		let mut str_data = H::zeroed();
 		let target = str_data.as_mut();
 		let mut len = 0;
 
@@ -114,6 +140,14 @@ impl HexRange<()> {
 	}
 }
 
+impl<D: AsRef<[u8]> + AsMut<[u8]>> HexRange<D> {
+	/// Render hex digits in given case
+	pub fn with_case(mut self, case: HexCase) -> Self {
+		case.apply(&mut self.str_data.as_mut()[..self.len]);
+		self
+	}
+}
+
 impl<D: AsRef<[u8]>> HexRange<D> {
 	fn _raw(&self) -> &[u8] {
 		&self.str_data.as_ref()[..self.len]
@@ -121,11 +155,11 @@ impl<D: AsRef<[u8]>> HexRange<D> {
 
 	/// String representation
 	pub fn as_str(&self) -> &str {
-		std::str::from_utf8(self._raw()).expect("ascii")
+		core::str::from_utf8(self._raw()).expect("ascii")
 	}
 }
 
-impl<D: AsRef<[u8]>> std::ops::Deref for HexRange<D> {
+impl<D: AsRef<[u8]>> core::ops::Deref for HexRange<D> {
 	type Target = str;
 
 	fn deref(&self) -> &Self::Target {
@@ -145,8 +179,8 @@ impl<D: AsRef<[u8]>> AsRef<[u8]> for HexRange<D> {
 	}
 }
 
-impl<D: AsRef<[u8]>> std::fmt::Display for HexRange<D> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<D: AsRef<[u8]>> core::fmt::Display for HexRange<D> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		f.write_str(self.as_str())
 	}
 }
@@ -1,4 +1,7 @@
-use std::borrow::Cow;
+use alloc::{
+	borrow::Cow,
+	string::{String, ToString},
+};
 
 use crate::errors::KeyTypeParseError;
 
@@ -13,18 +16,33 @@ pub enum KnownKeyType {
 	///
 	/// -> md4 hash of UTF16-LE encoded data
 	NT,
+	/// SHA-512 hash data
+	SHA512,
+	/// LM hash data
+	///
+	/// -> legacy LAN Manager hash (DES based, uppercased password)
+	LM,
 }
 
 impl KnownKeyType {
 	// for deref to KeyType
 	const KT_SHA1: KeyType = KeyType(InnerKeyType::Known(KnownKeyType::SHA1));
 	const KT_NT: KeyType = KeyType(InnerKeyType::Known(KnownKeyType::NT));
+	const KT_SHA512: KeyType = KeyType(InnerKeyType::Known(KnownKeyType::SHA512));
+	const KT_LM: KeyType = KeyType(InnerKeyType::Known(KnownKeyType::LM));
+
+	/// Key type (usable in constants, e.g. for [`KeyData::KEY_TYPE`](super::KeyData::KEY_TYPE))
+	pub const fn key_type(self) -> KeyType {
+		KeyType(InnerKeyType::Known(self))
+	}
 
 	/// Fixed length of key values with our type
 	pub fn key_bytes_length(self) -> u8 {
 		match self {
 			Self::SHA1 => 20,
 			Self::NT => 16,
+			Self::SHA512 => 64,
+			Self::LM => 16,
 		}
 	}
 
@@ -35,6 +53,8 @@ impl KnownKeyType {
 		match self {
 			Self::SHA1 => "sha1",
 			Self::NT => "nt",
+			Self::SHA512 => "sha512",
+			Self::LM => "lm",
 		}
 	}
 }
@@ -45,18 +65,20 @@ impl From<KnownKeyType> for KeyType {
 	}
 }
 
-impl std::ops::Deref for KnownKeyType {
+impl core::ops::Deref for KnownKeyType {
 	type Target = KeyType;
 
 	fn deref(&self) -> &Self::Target {
 		match self {
 			Self::SHA1 => &Self::KT_SHA1,
 			Self::NT => &Self::KT_NT,
+			Self::SHA512 => &Self::KT_SHA512,
+			Self::LM => &Self::KT_LM,
 		}
 	}
 }
 
-impl std::cmp::PartialEq<KeyType> for KnownKeyType {
+impl core::cmp::PartialEq<KeyType> for KnownKeyType {
 	fn eq(&self, other: &KeyType) -> bool {
 		KeyType(InnerKeyType::Known(*self)) == *other
 	}
@@ -79,15 +101,68 @@ impl KeyType {
 	pub const SHA1: KnownKeyType = KnownKeyType::SHA1;
 	/// NT hash key type
 	pub const NT: KnownKeyType = KnownKeyType::NT;
+	/// SHA-512 key type
+	pub const SHA512: KnownKeyType = KnownKeyType::SHA512;
+	/// LM hash key type
+	pub const LM: KnownKeyType = KnownKeyType::LM;
 
 	fn from_known(input: &str) -> Option<KnownKeyType> {
 		match input {
 			"SHA-1" | "sha1" => Some(Self::SHA1),
 			"NTLM" | "NT" | "nt" => Some(Self::NT),
+			"SHA-512" | "sha512" => Some(Self::SHA512),
+			"LM" | "lm" => Some(Self::LM),
 			_ => None,
 		}
 	}
 
+	// const version of `from_known` (only the serialized names)
+	const fn from_known_name(input: &str) -> Option<KnownKeyType> {
+		const fn eq(a: &[u8], b: &[u8]) -> bool {
+			if a.len() != b.len() {
+				return false;
+			}
+			let mut i = 0;
+			while i < a.len() {
+				if a[i] != b[i] {
+					return false;
+				}
+				i += 1;
+			}
+			true
+		}
+		let input = input.as_bytes();
+		if eq(input, b"sha1") {
+			Some(Self::SHA1)
+		} else if eq(input, b"nt") {
+			Some(Self::NT)
+		} else if eq(input, b"sha512") {
+			Some(Self::SHA512)
+		} else if eq(input, b"lm") {
+			Some(Self::LM)
+		} else {
+			None
+		}
+	}
+
+	/// Key type with a name defined outside this crate (usable in constants)
+	///
+	/// Names of known key types map to the known type; panics (at compile time
+	/// in constants) if the name isn't a valid key type name.
+	pub const fn custom(name: &'static str) -> Self {
+		if let Some(known) = Self::from_known_name(name) {
+			return known.key_type();
+		}
+		let bytes = name.as_bytes();
+		assert!(!bytes.is_empty(), "empty key type name");
+		let mut i = 0;
+		while i < bytes.len() {
+			assert!(bytes[i].is_ascii_graphic(), "key type name must be printable ASCII");
+			i += 1;
+		}
+		Self(InnerKeyType::Unknown(Cow::Borrowed(name)))
+	}
+
 	/// Return known key type - if type is known.
 	pub fn as_known(&self) -> Option<KnownKeyType> {
 		match self.0 {
@@ -122,7 +197,7 @@ impl KeyType {
 	}
 }
 
-impl std::str::FromStr for KeyType {
+impl core::str::FromStr for KeyType {
 	type Err = KeyTypeParseError;
 
 	fn from_str(key_type: &str) -> Result<Self, Self::Err> {
@@ -134,7 +209,7 @@ impl std::str::FromStr for KeyType {
 	}
 }
 
-impl std::convert::TryFrom<&'static str> for KeyType {
+impl core::convert::TryFrom<&'static str> for KeyType {
 	type Error = KeyTypeParseError;
 
 	fn try_from(key_type: &'static str) -> Result<Self, Self::Error> {
@@ -142,7 +217,7 @@ impl std::convert::TryFrom<&'static str> for KeyType {
 	}
 }
 
-impl std::convert::TryFrom<String> for KeyType {
+impl core::convert::TryFrom<String> for KeyType {
 	type Error = KeyTypeParseError;
 
 	fn try_from(key_type: String) -> Result<Self, Self::Error> {
@@ -0,0 +1,128 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
+
+use super::FixedByteArray;
+
+/// Storing LM hash
+///
+/// The (legacy) LAN Manager hash: the uppercased password in the OEM
+/// codepage, padded (or truncated) to 14 bytes, split into two halves that
+/// are used as DES keys to encrypt the constant `KGS!@#$%`.
+///
+/// LM hashes are trivial to crack; they are only supported to audit legacy
+/// Windows environments still storing them.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LM(pub [u8; 16]);
+
+#[cfg(feature = "lm")]
+fn des_key(half: &[u8]) -> [u8; 8] {
+	// spread 56 key bits over 8 bytes (lowest bit is parity and ignored)
+	let mut key = [
+		half[0] >> 1,
+		((half[0] & 0x01) << 6) | (half[1] >> 2),
+		((half[1] & 0x03) << 5) | (half[2] >> 3),
+		((half[2] & 0x07) << 4) | (half[3] >> 4),
+		((half[3] & 0x0f) << 3) | (half[4] >> 5),
+		((half[4] & 0x1f) << 2) | (half[5] >> 6),
+		((half[5] & 0x3f) << 1) | (half[6] >> 7),
+		half[6] & 0x7f,
+	];
+	for byte in &mut key {
+		*byte <<= 1;
+	}
+	key
+}
+
+impl LM {
+	/// Calculate hash of plaintext
+	///
+	/// Characters without a single byte representation (i.e. outside
+	/// Latin-1) are replaced by `?`; only the first 14 characters are used.
+	#[cfg(feature = "lm")]
+	pub fn hash(password: &str) -> Self {
+		use des::cipher::{BlockEncrypt, KeyInit};
+
+		let mut oem = [0u8; 14];
+		for (slot, c) in oem.iter_mut().zip(password.chars().flat_map(char::to_uppercase)) {
+			*slot = u8::try_from(c).unwrap_or(b'?');
+		}
+		let mut this = Self([0u8; 16]);
+		for (half, out) in oem.chunks(7).zip(this.0.chunks_mut(8)) {
+			let cipher = des::Des::new(&des_key(half).into());
+			let mut block = (*b"KGS!@#$%").into();
+			cipher.encrypt_block(&mut block);
+			out.copy_from_slice(&block);
+		}
+		this
+	}
+}
+
+impl FromStr for LM {
+	type Err = hex::FromHexError;
+
+	fn from_str(hex: &str) -> Result<Self, Self::Err> {
+		let mut this = Self([0u8; 16]);
+		hex::decode_to_slice(hex, &mut this.0)?;
+		Ok(this)
+	}
+}
+
+impl Deref for LM {
+	type Target = [u8];
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl DerefMut for LM {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl AsRef<[u8; 16]> for LM {
+	fn as_ref(&self) -> &[u8; 16] {
+		&self.0
+	}
+}
+
+impl AsMut<[u8; 16]> for LM {
+	fn as_mut(&mut self) -> &mut [u8; 16] {
+		&mut self.0
+	}
+}
+
+impl fmt::Debug for LM {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::Display for LM {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::LowerHex for LM {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::UpperHex for LM {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex_upper())
+	}
+}
+
+impl crate::data::FixedByteArrayImpl for LM {
+	type ByteArray = [u8; 16];
+	type HexArray = [u8; 32];
+}
+
+impl crate::data::KeyData for LM {
+	const KEY_TYPE: crate::data::KeyType = crate::data::KnownKeyType::LM.key_type();
+}
@@ -1,18 +1,41 @@
 //! Various types representing "data" (keys, payload, related)
+mod count;
 mod hex;
 mod key_type;
+mod lm;
 mod nt;
 mod prefix;
 mod sha1;
+mod sha512;
 
+pub(crate) use self::prefix::split_prefix_str;
 pub use self::{
-	hex::{Hex, HexRange},
+	count::{Count32, Count64},
+	hex::{Hex, HexCase, HexRange},
 	key_type::{KeyType, KnownKeyType},
+	lm::LM,
 	nt::NT,
 	prefix::{Prefix, Suffix},
 	sha1::SHA1,
+	sha512::SHA512,
 };
 
+/// Derive [`KeyData`](trait@KeyData) for custom key types
+///
+/// ```
+/// use hibp_index::data::{KeyData, KeyType};
+///
+/// #[derive(Clone, PartialEq, Eq, KeyData)]
+/// #[key_data(name = "md5", size = 16)]
+/// pub struct MD5(pub [u8; 16]);
+///
+/// assert_eq!(MD5::KEY_TYPE, KeyType::custom("md5"));
+/// let key: MD5 = "5f4dcc3b5aa765d61d8327deb882cf99".parse().unwrap();
+/// assert_eq!(key.to_string(), "5f4dcc3b5aa765d61d8327deb882cf99");
+/// ```
+#[cfg(feature = "derive")]
+pub use hibp_index_derive::KeyData;
+
 /// Both keys (hashes) and payload are stored as raw bytestrings with fixed length
 ///
 /// Provide traits to handle those in a generic way.
@@ -73,14 +96,63 @@ pub trait FixedByteArray: FixedByteArrayImpl {
 		hex::Hex::new(self)
 	}
 
+	/// Like [`hex`](FixedByteArray::hex), but with upper case hex digits (like HIBP uses)
+	///
+	/// Key types also implement [`fmt::UpperHex`](core::fmt::UpperHex) (and
+	/// [`fmt::LowerHex`](core::fmt::LowerHex)):
+	///
+	/// ```
+	/// use hibp_index::data::{FixedByteArray, NT};
+	///
+	/// let key = NT::hash("password");
+	/// assert_eq!(key.hex_upper().as_str(), "8846F7EAEE8FB117AD06BDD830B7586C");
+	/// assert_eq!(format!("{:X}", key), "8846F7EAEE8FB117AD06BDD830B7586C");
+	/// assert_eq!(format!("{:x}", key), "8846f7eaee8fb117ad06bdd830b7586c");
+	/// ```
+	fn hex_upper(&self) -> hex::Hex<Self::HexArray> {
+		self.hex().with_case(HexCase::Upper)
+	}
+
 	/// Returns an `impl std::fmt::Display` showing the hex digits of the data in the given bit range
 	///
 	/// Shows all hex digits that contain at least one bit to be shown (but doesn't mask the other bits;
-	/// that might change though).
+	/// see [`hex_bit_range_masked`](FixedByteArray::hex_bit_range_masked)).
 	fn hex_bit_range(&self, start: u32, end: u32) -> hex::HexRange<Self::HexArray> {
 		assert!(start <= end);
 		assert!(end <= Self::SIZE as u32 * 8);
-		hex::HexRange::new(self, start, end)
+		hex::HexRange::new(self.data(), start, end)
+	}
+
+	/// Like [`hex_bit_range`](FixedByteArray::hex_bit_range), but bits outside the range are shown as zero
+	///
+	/// ```
+	/// use hibp_index::data::{FixedByteArray, SHA1};
+	///
+	/// let key: SHA1 = "21bd12dc183f740ee76f27b78eb39c8ad972a757".parse().unwrap();
+	/// assert_eq!(key.hex_bit_range(0, 18).as_str(), "21bd1");
+	/// assert_eq!(key.hex_bit_range_masked(0, 18).as_str(), "21bd0");
+	/// assert_eq!(key.hex_bit_range_masked(6, 16).as_str(), "1bd");
+	/// ```
+	fn hex_bit_range_masked(&self, start: u32, end: u32) -> hex::HexRange<Self::HexArray> {
+		assert!(start <= end);
+		assert!(end <= Self::SIZE as u32 * 8);
+		let mut masked = <Self::ByteArray as seal_trait::U8Array>::zeroed();
+		let masked_data = masked.as_mut();
+		masked_data.copy_from_slice(self.data());
+		for (ndx, octet) in masked_data.iter_mut().enumerate() {
+			let octet_start = ndx as u32 * 8;
+			if octet_start + 8 <= start || octet_start >= end {
+				*octet = 0;
+				continue;
+			}
+			if start > octet_start {
+				*octet &= 0xff >> (start - octet_start);
+			}
+			if end < octet_start + 8 {
+				*octet &= !(0xff >> (end - octet_start));
+			}
+		}
+		hex::HexRange::new(masked_data, start, end)
 	}
 }
 
@@ -90,8 +162,10 @@ impl<T: FixedByteArrayImpl> FixedByteArray for T {}
 pub trait KeyData: FixedByteArray {
 	/// Key type
 	///
-	/// For types that store hash data for a certain hash we really should know the key type.
-	const KEY_TYPE: KnownKeyType;
+	/// For types that store hash data for a certain hash we really should know the key type;
+	/// use [`KnownKeyType::key_type`] for types known to this crate and [`KeyType::custom`]
+	/// otherwise (or `#[derive(KeyData)]` with feature `derive`).
+	const KEY_TYPE: KeyType;
 
 	/// Build prefix with given number of bits
 	fn prefix(&self, bits: u32) -> Prefix<Self> {
@@ -112,7 +186,13 @@ pub trait KeyData: FixedByteArray {
 /// Explicitly mark `FixedByteArray` to be used as payload.
 ///
 /// When reading a file excessive data will be truncated!
-pub trait PayloadData: FixedByteArray {}
+pub trait PayloadData: FixedByteArray {
+	/// Payload is a (password) count
+	///
+	/// Typed builders mark new (format v1) indexes with
+	/// [`IndexFlags::COUNTS`](crate::index::IndexFlags::COUNTS).
+	const COUNTS: bool = false;
+}
 
 /// `PayloadData` type with zero length (and no data)
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
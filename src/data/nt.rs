@@ -1,14 +1,15 @@
-use byteorder::{WriteBytesExt, LE};
-use std::fmt;
-use std::ops::{Deref, DerefMut};
-use std::str::FromStr;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
 
 use super::FixedByteArray;
 
 fn utf16le(data: &str) -> Vec<u8> {
 	let mut result = Vec::new();
 	for c in data.encode_utf16() {
-		result.write_u16::<LE>(c).unwrap();
+		result.extend_from_slice(&c.to_le_bytes());
 	}
 	result
 }
@@ -30,6 +31,25 @@ impl NT {
 		this.0.copy_from_slice(&dig);
 		this
 	}
+
+	/// Calculate hash of plaintext given as raw bytes
+	///
+	/// Valid UTF-8 is hashed like [`NT::hash`]; otherwise each byte is
+	/// decoded as Latin-1 (ISO 8859-1) character, which is what most legacy
+	/// (e.g. rockyou-style) wordlists with invalid UTF-8 use.
+	///
+	/// ```
+	/// use hibp_index::data::NT;
+	///
+	/// assert_eq!(NT::hash_bytes("café".as_bytes()), NT::hash("café"));
+	/// assert_eq!(NT::hash_bytes(b"caf\xe9"), NT::hash("café"));
+	/// ```
+	pub fn hash_bytes(password: &[u8]) -> Self {
+		match core::str::from_utf8(password) {
+			Ok(password) => Self::hash(password),
+			Err(_) => Self::hash(&password.iter().map(|&b| char::from(b)).collect::<String>()),
+		}
+	}
 }
 
 impl FromStr for NT {
@@ -80,11 +100,23 @@ impl fmt::Display for NT {
 	}
 }
 
+impl fmt::LowerHex for NT {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::UpperHex for NT {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex_upper())
+	}
+}
+
 impl crate::data::FixedByteArrayImpl for NT {
 	type ByteArray = [u8; 16];
 	type HexArray = [u8; 32];
 }
 
 impl crate::data::KeyData for NT {
-	const KEY_TYPE: crate::data::KnownKeyType = crate::data::KnownKeyType::NT;
+	const KEY_TYPE: crate::data::KeyType = crate::data::KnownKeyType::NT.key_type();
 }
@@ -1,8 +1,27 @@
-use std::cmp::Ordering;
+use core::cmp::Ordering;
 
 use hex::FromHexError;
 
 use super::{HexRange, KeyData};
+use crate::errors::PrefixParseError;
+
+/// Split `"hex/bits"` (or just `"hex"`, using all bits of the hex digits)
+pub(crate) fn split_prefix_str(s: &str) -> Result<(&str, u32), PrefixParseError> {
+	match s.split_once('/') {
+		Some((hex_digits, bits)) => {
+			// `u32::from_str` would accept a leading `+`
+			if bits.is_empty() || !bits.bytes().all(|c| c.is_ascii_digit()) {
+				return Err(PrefixParseError::InvalidBits);
+			}
+			let bits = bits.parse().map_err(|_| PrefixParseError::InvalidBits)?;
+			Ok((hex_digits, bits))
+		},
+		None => {
+			let bits = u32::try_from(4 * s.len()).map_err(|_| PrefixParseError::InvalidBits)?;
+			Ok((s, bits))
+		},
+	}
+}
 
 /// Prefix of key data bitstring
 #[derive(Clone)]
@@ -68,7 +87,7 @@ where
 
 	/// Show hex digits of prefix
 	pub fn hex(&self) -> HexRange<D::HexArray> {
-		self.key.hex_bit_range(0, self.bits)
+		self.key.hex_bit_range_masked(0, self.bits)
 	}
 
 	/// Recombine prefix with suffix data from hexadecimal input
@@ -88,7 +107,7 @@ where
 			}
 			let shared_octet_str = [b'0', suffix_str[0]];
 			let mut shared_octet: u8 = 0;
-			hex::decode_to_slice(shared_octet_str, std::slice::from_mut(&mut shared_octet))?;
+			hex::decode_to_slice(shared_octet_str, core::slice::from_mut(&mut shared_octet))?;
 			key_data[start] |= mask_bits & shared_octet;
 			hex::decode_to_slice(&suffix_str[1..], &mut key_data[start + 1..])?;
 		} else if start == D::SIZE {
@@ -98,7 +117,7 @@ where
 			}
 		} else {
 			let mut shared_octet: u8 = 0;
-			hex::decode_to_slice(&suffix_str[..2], std::slice::from_mut(&mut shared_octet))?;
+			hex::decode_to_slice(&suffix_str[..2], core::slice::from_mut(&mut shared_octet))?;
 			key_data[start] |= mask_bits & shared_octet;
 			hex::decode_to_slice(&suffix_str[2..], &mut key_data[start + 1..])?;
 		}
@@ -137,15 +156,46 @@ where
 	}
 }
 
-impl<D> std::fmt::Debug for Prefix<D>
+impl<D> core::fmt::Debug for Prefix<D>
 where
 	D: KeyData,
 {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "{}/{}", self.hex(), self.bits)
 	}
 }
 
+/// Parse `"hex/bits"` (the [`Debug`](core::fmt::Debug) format) or just hex digits
+///
+/// The number of hex digits must match the number of bits (bits after the
+/// prefix in the last digit are ignored); without `/bits` all bits of the
+/// hex digits are used (e.g. 20 bits for the 5 hex digits of the HIBP range API).
+///
+/// ```
+/// use hibp_index::data::{Prefix, SHA1};
+///
+/// let prefix: Prefix<SHA1> = "21BD1/18".parse().unwrap();
+/// assert_eq!(prefix.bits(), 18);
+/// assert_eq!(format!("{:?}", prefix), "21bd0/18");
+/// let prefix: Prefix<SHA1> = "21BD1".parse().unwrap();
+/// assert_eq!(format!("{:?}", prefix), "21bd1/20");
+/// assert!("21BD1/21".parse::<Prefix<SHA1>>().is_err());
+/// ```
+impl<D> core::str::FromStr for Prefix<D>
+where
+	D: KeyData,
+{
+	type Err = PrefixParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (hex_digits, bits) = split_prefix_str(s)?;
+		if bits as usize > D::SIZE * 8 {
+			return Err(PrefixParseError::InvalidBits);
+		}
+		Self::new_from_hex(hex_digits.as_bytes(), bits).map_err(PrefixParseError::InvalidHex)
+	}
+}
+
 /// Suffix of key data bitstring
 #[derive(Clone)]
 pub struct Suffix<D> {
@@ -192,15 +242,15 @@ where
 
 	/// Show hex digits of suffix
 	pub fn hex(&self) -> HexRange<D::HexArray> {
-		self.key.hex_bit_range(self.prefix_bits, D::SIZE as u32 * 8)
+		self.key.hex_bit_range_masked(self.prefix_bits, D::SIZE as u32 * 8)
 	}
 }
 
-impl<D> std::fmt::Debug for Suffix<D>
+impl<D> core::fmt::Debug for Suffix<D>
 where
 	D: KeyData,
 {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "<0../{}>{}", self.prefix_bits, self.hex())
 	}
 }
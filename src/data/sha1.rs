@@ -1,6 +1,6 @@
-use std::fmt;
-use std::ops::{Deref, DerefMut};
-use std::str::FromStr;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
 
 use super::FixedByteArray;
 
@@ -67,11 +67,23 @@ impl fmt::Display for SHA1 {
 	}
 }
 
+impl fmt::LowerHex for SHA1 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::UpperHex for SHA1 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex_upper())
+	}
+}
+
 impl crate::data::FixedByteArrayImpl for SHA1 {
 	type ByteArray = [u8; 20];
 	type HexArray = [u8; 40];
 }
 
 impl crate::data::KeyData for SHA1 {
-	const KEY_TYPE: crate::data::KnownKeyType = crate::data::KnownKeyType::SHA1;
+	const KEY_TYPE: crate::data::KeyType = crate::data::KnownKeyType::SHA1.key_type();
 }
@@ -0,0 +1,96 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::str::FromStr;
+
+use super::FixedByteArray;
+
+/// SHA-512 hash data
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SHA512(pub [u8; 64]);
+
+impl SHA512 {
+	/// Calculate SHA-512 hash of plaintext data
+	pub fn hash(data: &[u8]) -> Self {
+		use sha2::Digest;
+		let dig = sha2::Sha512::digest(data);
+		let mut this = Self([0u8; 64]);
+		this.0.copy_from_slice(&dig);
+		this
+	}
+}
+
+// arrays only implement `Default` up to 32 elements
+impl Default for SHA512 {
+	fn default() -> Self {
+		Self([0u8; 64])
+	}
+}
+
+impl FromStr for SHA512 {
+	type Err = hex::FromHexError;
+
+	fn from_str(hex: &str) -> Result<Self, Self::Err> {
+		let mut this = Self([0u8; 64]);
+		hex::decode_to_slice(hex, &mut this.0)?;
+		Ok(this)
+	}
+}
+
+impl Deref for SHA512 {
+	type Target = [u8];
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl DerefMut for SHA512 {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl AsRef<[u8; 64]> for SHA512 {
+	fn as_ref(&self) -> &[u8; 64] {
+		&self.0
+	}
+}
+
+impl AsMut<[u8; 64]> for SHA512 {
+	fn as_mut(&mut self) -> &mut [u8; 64] {
+		&mut self.0
+	}
+}
+
+impl fmt::Debug for SHA512 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::Display for SHA512 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::LowerHex for SHA512 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex())
+	}
+}
+
+impl fmt::UpperHex for SHA512 {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.hex_upper())
+	}
+}
+
+impl crate::data::FixedByteArrayImpl for SHA512 {
+	type ByteArray = [u8; 64];
+	type HexArray = [u8; 128];
+}
+
+impl crate::data::KeyData for SHA512 {
+	const KEY_TYPE: crate::data::KeyType = crate::data::KnownKeyType::SHA512.key_type();
+}
@@ -0,0 +1,120 @@
+//! Indexes of breached email addresses
+//!
+//! Email addresses are normalized (see [`normalize`]) and stored as SHA-1
+//! hash of the normalized address (see [`email_key`]), with a payload per
+//! address; e.g. a bitmap of the breaches it appeared in. Lookups then work
+//! offline like password checks:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::data::{Count64, SHA1};
+//! use hibp_index::email::{add_emails, email_key};
+//! use hibp_index::index::{Depth, TypedBuilder, TypedIndex};
+//!
+//! // bit `n` of the payload: address appeared in breach `n`
+//! let records = [("Alice@Example.com", Count64::new(1 << 0)), ("alice@example.com ", Count64::new(1 << 3))];
+//! let mut builder =
+//!     TypedBuilder::<SHA1, Count64, _>::create_atomic("emails.index", "breached emails", Depth::DEPTH20)?;
+//! add_emails(records, &mut builder, |bitmap, other| *bitmap = Count64::new(bitmap.value() | other.value()))?;
+//! builder.finish()?;
+//!
+//! let index = TypedIndex::<SHA1, Count64, _>::open(std::fs::File::open("emails.index")?)?;
+//! let breaches = index.lookup(&email_key("alice@example.com").unwrap())?;
+//! assert_eq!(breaches.map(|bitmap| bitmap.value()), Some(0b1001));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io;
+
+use crate::{
+	data::{PayloadData, SHA1},
+	errors::BuilderAddError,
+	index::TypedBuilder,
+};
+
+/// Normalize email address (`None` if it doesn't look like one)
+///
+/// Removes surrounding whitespace and converts the address to lower case;
+/// the address needs a single `@` with non-empty local part and domain (and
+/// no whitespace). Provider specific aliases (e.g. `+tag` suffixes) are kept,
+/// as they are different accounts for most providers.
+///
+/// ```
+/// use hibp_index::email::normalize;
+///
+/// assert_eq!(normalize(" Alice@Example.COM\n").as_deref(), Some("alice@example.com"));
+/// assert_eq!(normalize("alice+news@example.com").as_deref(), Some("alice+news@example.com"));
+/// assert_eq!(normalize("alice"), None);
+/// assert_eq!(normalize("alice@@example.com"), None);
+/// ```
+pub fn normalize(email: &str) -> Option<String> {
+	let email = email.trim();
+	let (local, domain) = email.split_once('@')?;
+	if local.is_empty()
+		|| domain.is_empty()
+		|| domain.contains('@')
+		|| email.contains(char::is_whitespace)
+	{
+		return None;
+	}
+	Some(email.to_lowercase())
+}
+
+/// Key of an email address: SHA-1 hash of the normalized address
+pub fn email_key(email: &str) -> Option<SHA1> {
+	Some(SHA1::hash(normalize(email)?.as_bytes()))
+}
+
+/// Statistics of [`add_emails`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct EmailImportStats {
+	/// Number of added entries (distinct addresses)
+	pub added: u64,
+	/// Number of records merged into another record of the same address
+	pub merged: u64,
+	/// Number of skipped records with invalid addresses
+	pub invalid: u64,
+}
+
+/// Add records of email addresses and their payload to builder
+///
+/// Records don't need to be ordered (they are collected in memory and sorted
+/// by key); `merge` combines the payloads of records with the same
+/// (normalized) address, e.g. by or-ing breach bitmaps. Records with invalid
+/// addresses are skipped (and counted).
+pub fn add_emails<P, W, I, S, F>(
+	records: I,
+	builder: &mut TypedBuilder<SHA1, P, W>,
+	mut merge: F,
+) -> Result<EmailImportStats, BuilderAddError>
+where
+	P: PayloadData,
+	W: io::Write + io::Seek,
+	I: IntoIterator<Item = (S, P)>,
+	S: AsRef<str>,
+	F: FnMut(&mut P, &P),
+{
+	let mut stats = EmailImportStats::default();
+	let mut entries = Vec::new();
+	for (email, payload) in records {
+		match email_key(email.as_ref()) {
+			Some(key) => entries.push((key, payload)),
+			None => stats.invalid += 1,
+		}
+	}
+	entries.sort_by_key(|(key, _)| *key);
+	entries.dedup_by(|(key, payload), (prev_key, prev_payload)| {
+		if key != prev_key {
+			return false;
+		}
+		merge(prev_payload, payload);
+		stats.merged += 1;
+		true
+	});
+	for (key, payload) in &entries {
+		builder.add_entry(key, payload)?;
+	}
+	stats.added = entries.len() as u64;
+	Ok(stats)
+}
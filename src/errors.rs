@@ -1,5 +1,10 @@
 //! Error types
 
+#[cfg(feature = "std")]
+use crate::data::KnownKeyType;
+use crate::{data::KeyType, index::IndexFlags};
+use alloc::string::String;
+
 /// Error when parsing key types
 #[derive(thiserror::Error, Debug)]
 pub enum KeyTypeParseError {
@@ -8,7 +13,19 @@ pub enum KeyTypeParseError {
 	Invalid(String),
 }
 
+/// Error when parsing prefixes (see [`Prefix`](crate::data::Prefix) and [`LimPrefix`](crate::index::LimPrefix))
+#[derive(thiserror::Error, Debug)]
+pub enum PrefixParseError {
+	/// Number of bits after `/` isn't a number or too large
+	#[error("invalid prefix length")]
+	InvalidBits,
+	/// Invalid hex digits or number of hex digits not matching the number of bits
+	#[error("invalid prefix hex digits: {0}")]
+	InvalidHex(hex::FromHexError),
+}
+
 /// Error when creating a new index
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum BuilderCreateError {
 	/// IO write error
@@ -26,9 +43,71 @@ pub enum BuilderCreateError {
 	/// Parameters too long for header
 	#[error("Header too big")]
 	HeaderTooBig,
+	/// Options require a newer format version
+	#[error("Index options require format v1")]
+	RequiresV1,
+	/// Table after the header requires knowing the table in advance
+	#[error("Leading table requires two-pass builder")]
+	RequiresTwoPass,
+	/// Compression level not supported by table compression
+	#[error("Invalid table compression level {level}")]
+	InvalidCompressionLevel {
+		/// the invalid level
+		level: u32,
+	},
+	/// Payload dictionary not supported by builder or with other options
+	#[error("Payload dictionary requires seekable builder without payload encryption")]
+	PayloadDictionaryUnsupported,
+	/// Bucket tables not supported by builder, with other options or for the key size
+	#[error("Bucket tables require seekable builder without payload dictionary, and 1 to 8 bits within the key")]
+	BucketTablesUnsupported,
+}
+
+/// Error when adding entries to a new index
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum BuilderAddError {
+	/// IO write error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Entries must be added in strictly increasing order
+	#[error(
+		"entry #{line_hint} out of order: {} not greater than previous entry {}",
+		hex::encode(key),
+		hex::encode(previous)
+	)]
+	OutOfOrder {
+		/// previous (accepted) key
+		previous: Vec<u8>,
+		/// rejected key
+		key: Vec<u8>,
+		/// number of the rejected entry (starting at 1); matches the line
+		/// number for inputs with one entry per line
+		line_hint: u64,
+	},
+	/// Entries don't match the bucket counts of the first pass of a two-pass build
+	#[error("entry #{line_hint} doesn't match entry counts of first pass")]
+	CountMismatch {
+		/// number of the rejected entry (starting at 1); if there were fewer
+		/// entries than counted this is the number of entries plus one
+		line_hint: u64,
+	},
+	/// Raw record input ended within a record
+	#[error("raw input ends within record #{record}")]
+	TruncatedRecord {
+		/// number of the incomplete record (starting at 1)
+		record: u64,
+	},
+	/// Bucket needs more payloads than its dictionary can store
+	#[error("entry #{line_hint}: more than 256 distinct payloads in bucket")]
+	DictionaryFull {
+		/// number of the rejected entry (starting at 1)
+		line_hint: u64,
+	},
 }
 
 /// Error when opening index
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum IndexOpenError {
 	/// IO read error
@@ -46,9 +125,192 @@ pub enum IndexOpenError {
 	/// Invalid header
 	#[error("invalid/unknown header format")]
 	InvalidHeader,
+	/// Index uses features not supported by this implementation
+	#[error("unsupported index features: {flags}")]
+	UnsupportedFlags {
+		/// unsupported flags
+		flags: IndexFlags,
+	},
+	/// Index uses encrypted payloads but no key was given
+	#[error("index payloads are encrypted, key required")]
+	MissingPayloadKey,
+	/// Payload key doesn't match index
+	#[cfg(feature = "encryption")]
+	#[error("wrong payload key")]
+	WrongPayloadKey,
+	/// Payload key given for index without encrypted payloads
+	#[cfg(feature = "encryption")]
+	#[error("index payloads are not encrypted")]
+	PayloadNotEncrypted,
+	/// Index can't be read from a stream, table is stored at the end
+	#[error("index table not stored after header")]
+	TableNotLeading,
+	/// Index rejected by strict validation (see [`Index::open_strict`](crate::index::Index::open_strict))
+	#[error("rejected by strict validation: {reason}")]
+	StrictValidation {
+		/// failed check
+		reason: &'static str,
+	},
+	/// Index contains different key type than requested
+	#[error("key type mismatch: expected {}, found {}", expected.name(), found.name())]
+	KeyTypeMismatch {
+		/// requested key type
+		expected: KeyType,
+		/// key type stored in index
+		found: KeyType,
+	},
+	/// Index uses different key size than requested key type
+	#[error("key size mismatch: expected {expected} bytes, found {found} bytes")]
+	KeySizeMismatch {
+		/// key size of requested key type
+		expected: u8,
+		/// key size stored in index
+		found: u8,
+	},
+	/// Index payload is smaller than requested payload type
+	#[error("payload too small: need {expected} bytes, found {found} bytes")]
+	PayloadTooSmall {
+		/// payload size of requested payload type
+		expected: usize,
+		/// payload size stored in index
+		found: u8,
+	},
+}
+
+/// Error when parsing pwdump line (see [`PwdumpEntry`](crate::pwdump::PwdumpEntry))
+#[derive(thiserror::Error, Debug)]
+pub enum PwdumpParseError {
+	/// Line doesn't match `user:rid:lmhash:nthash:::`
+	#[error("invalid pwdump line (expected user:rid:lmhash:nthash:::)")]
+	InvalidFormat,
+	/// Invalid relative identifier
+	#[error("invalid RID")]
+	InvalidRid,
+	/// Invalid NT hash
+	#[error("invalid NT hash")]
+	InvalidNtHash,
+	/// Invalid LM hash
+	#[error("invalid LM hash")]
+	InvalidLmHash,
+}
+
+/// Error when opening in-memory index (see [`SliceIndex`](crate::index::SliceIndex))
+#[derive(thiserror::Error, Debug)]
+pub enum SliceIndexError {
+	/// Invalid key type
+	#[error("key-type error: {0}")]
+	KeyTypeError(#[from] KeyTypeParseError),
+	/// Invalid key length
+	#[error("invalid key / table depth length")]
+	InvalidKeyLength,
+	/// Invalid header
+	#[error("invalid/unknown header format")]
+	InvalidHeader,
+	/// Index uses features not supported by this implementation
+	#[error("unsupported index features: {flags}")]
+	UnsupportedFlags {
+		/// unsupported flags
+		flags: IndexFlags,
+	},
+	/// Table missing, not decompressable or inconsistent with index data
+	#[error("invalid table")]
+	InvalidTable,
+	/// Index contains different key type than requested
+	#[error("key type mismatch: expected {}, found {}", expected.name(), found.name())]
+	KeyTypeMismatch {
+		/// requested key type
+		expected: KeyType,
+		/// key type stored in index
+		found: KeyType,
+	},
+	/// Index payload is smaller than requested payload type
+	#[error("payload too small: need {expected} bytes, found {found} bytes")]
+	PayloadTooSmall {
+		/// payload size of requested payload type
+		expected: usize,
+		/// payload size stored in index
+		found: u8,
+	},
+}
+
+/// Error when verifying the checksum of an index
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum ChecksumError {
+	/// IO read error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Index was written without checksum
+	#[error("index has no checksum")]
+	MissingChecksum,
+	/// Stored checksum doesn't match index content
+	#[error("checksum mismatch")]
+	Mismatch,
+}
+
+/// Error when verifying an index read from a stream (see [`verify_stream`](crate::index::verify_stream))
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum StreamVerifyError {
+	/// IO read error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Invalid header or table
+	#[error("{0}")]
+	Open(#[from] IndexOpenError),
+	/// Table doesn't cover the data between table and end of file
+	#[error("table doesn't cover data after table")]
+	InvalidTable,
+	/// Bucket size isn't a multiple of the entry size
+	#[error("size of bucket {bucket} not a multiple of entry size")]
+	InvalidSegmentLength {
+		/// index of the bucket
+		bucket: usize,
+	},
+	/// Stored key suffix contains bits of the prefix
+	#[error("invalid key in bucket {bucket} (entry {entry})")]
+	InvalidKey {
+		/// index of the bucket
+		bucket: usize,
+		/// number of entry in index (starting at 1)
+		entry: u64,
+	},
+	/// Entries not in strictly increasing order
+	#[error("entry {entry} in bucket {bucket} out of order")]
+	OutOfOrder {
+		/// index of the bucket
+		bucket: usize,
+		/// number of entry in index (starting at 1)
+		entry: u64,
+	},
+	/// Unexpected data after the table (and checksum)
+	#[error("trailing data after end of index")]
+	TrailingData,
+	/// Stored checksum doesn't match index content
+	#[error("checksum mismatch")]
+	ChecksumMismatch,
+}
+
+/// Error when opening index with signature verification
+#[cfg(feature = "signing")]
+#[derive(thiserror::Error, Debug)]
+pub enum IndexVerifyError {
+	/// IO read error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Signature doesn't match index content or key
+	#[error("invalid index signature")]
+	InvalidSignature,
+	/// Index file changed size while verifying
+	#[error("index file was modified while verifying")]
+	Modified,
+	/// Error opening the (verified) index
+	#[error("{0}")]
+	OpenError(#[from] IndexOpenError),
 }
 
 /// Error when looking up entry in index
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum LookupError {
 	/// IO read error
@@ -57,12 +319,207 @@ pub enum LookupError {
 	/// Invalid segment length
 	#[error("Invalid length of segment containing key (not a multiple of entry size)")]
 	InvalidSegmentLength,
+	/// Payload dictionary too large or entry refers to payload not in the dictionary of its bucket
+	#[error("Invalid payload dictionary or dictionary index")]
+	InvalidDictionary,
+	/// Bucket table has invalid size or entry indices outside of its bucket
+	#[error("Invalid bucket table")]
+	InvalidBucketTable,
+}
+
+/// Error when looking up keys in a HIBP text file (see [`TextIndex`](crate::index::TextIndex))
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum TextLookupError {
+	/// IO read error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Line is not a `HASH:count` line (or too long)
+	#[error("invalid line at offset {offset}")]
+	InvalidLine {
+		/// file offset of the line
+		offset: u64,
+	},
+}
+
+/// Error when reading a range directory (see [`range_dir`](crate::range_dir))
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum RangeDirError {
+	/// IO error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Adding entries to index failed
+	#[error("index build error: {0}")]
+	BuilderAddError(#[from] BuilderAddError),
+	/// File of range is missing
+	#[error("range {prefix} missing")]
+	MissingRange {
+		/// hex prefix of range
+		prefix: String,
+	},
+	/// Line is not a `SUFFIX:count` line
+	#[error("range {prefix}: invalid line {line:?}")]
+	InvalidLine {
+		/// hex prefix of range
+		prefix: String,
+		/// the invalid line
+		line: String,
+	},
+}
+
+/// Error of the breach API client (see [`BreachClient`](crate::online::BreachClient))
+#[cfg(feature = "breaches")]
+#[derive(thiserror::Error, Debug)]
+pub enum BreachApiError {
+	/// Request failed
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Response isn't valid JSON (or doesn't match the breach model)
+	#[error("invalid response: {0}")]
+	InvalidResponse(#[from] serde_json::Error),
+	/// Searching accounts needs an API key
+	#[error("missing API key")]
+	MissingApiKey,
+}
+
+/// Error when auditing the order of entries (see [`Index::verify_order`](crate::index::Index::verify_order))
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum OrderError {
+	/// Reading entries failed
+	#[error("lookup error: {0}")]
+	LookupError(#[from] LookupError),
+	/// Stored key suffix contains bits of the bucket prefix
+	#[error("key at offset {offset} in bucket {prefix:?} contains bits of the prefix")]
+	InvalidKey {
+		/// prefix of the bucket
+		prefix: crate::index::LimPrefix,
+		/// file offset of the entry
+		offset: u64,
+	},
+	/// Key equal to the previous key in the bucket
+	#[error("duplicate key at offset {offset} in bucket {prefix:?}")]
+	Duplicate {
+		/// prefix of the bucket
+		prefix: crate::index::LimPrefix,
+		/// file offset of the entry
+		offset: u64,
+	},
+	/// Key less than the previous key in the bucket
+	#[error("key at offset {offset} in bucket {prefix:?} less than previous key")]
+	OutOfOrder {
+		/// prefix of the bucket
+		prefix: crate::index::LimPrefix,
+		/// file offset of the entry
+		offset: u64,
+	},
+}
+
+/// Error when looking up keys in a [`MultiIndex`](crate::index::MultiIndex)
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum MultiLookupError {
+	/// Lookup in index failed
+	#[error("lookup error: {0}")]
+	LookupError(#[from] LookupError),
+	/// No index for key type loaded
+	#[error("no index for key type {} loaded", key_type.name())]
+	MissingIndex {
+		/// requested key type
+		key_type: KeyType,
+	},
+	/// Key length doesn't match key size of index
+	#[error("invalid key length {length} (index expects {expected})")]
+	InvalidKeyLength {
+		/// length of given key
+		length: usize,
+		/// key size of index
+		expected: u8,
+	},
+}
+
+/// Error when exporting an index (see [`export`](crate::export))
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum ExportError {
+	/// IO error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Reading entries failed
+	#[error("lookup error: {0}")]
+	LookupError(#[from] LookupError),
+	/// Payloads are encrypted and no key was set
+	#[error("payloads are encrypted")]
+	EncryptedPayload,
+	/// Payload is not a breach count (4 or 8 byte big endian)
+	#[error("payload is not a breach count")]
+	MissingCount,
+	/// Writing Parquet file failed
+	#[cfg(feature = "parquet")]
+	#[error("parquet error: {0}")]
+	ParquetError(#[from] parquet::errors::ParquetError),
+}
+
+/// Error when importing CSV / TSV files (see [`import`](crate::import))
+#[cfg(feature = "cli")]
+#[derive(thiserror::Error, Debug)]
+pub enum CsvImportError {
+	/// Reading or parsing the input failed
+	#[error("CSV error: {0}")]
+	CsvError(#[from] csv::Error),
+	/// Line has not enough columns
+	#[error("line {line}: missing column {column}")]
+	MissingColumn {
+		/// line number (starting at 1)
+		line: u64,
+		/// requested column (starting at 0)
+		column: usize,
+	},
+	/// Hash column doesn't contain a valid hash
+	#[error("line {line}: invalid hash {value:?}")]
+	InvalidHash {
+		/// line number (starting at 1)
+		line: u64,
+		/// content of the hash column
+		value: String,
+	},
+	/// Count column doesn't contain a valid number
+	#[error("line {line}: invalid count {value:?}")]
+	InvalidCount {
+		/// line number (starting at 1)
+		line: u64,
+		/// content of the count column
+		value: String,
+	},
+	/// Adding entries to index failed
+	#[error("index build error: {0}")]
+	BuilderAddError(#[from] BuilderAddError),
+}
+
+/// Error when loading a configuration file (see [`config`](crate::config))
+#[cfg(feature = "cli")]
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+	/// IO read error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Invalid TOML or unknown / invalid setting
+	#[error("parse error: {0}")]
+	ParseError(#[from] toml::de::Error),
+	/// Invalid index depth
+	#[error("Invalid depth {depth}")]
+	InvalidDepth {
+		/// the invalid depth value
+		depth: u8,
+	},
 }
 
 /// Table read error
 ///
 /// The table is the part of the index that tells us where keys with a given
 /// prefix are stored.
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum TableReadError {
 	/// IO read error
@@ -80,9 +537,126 @@ pub enum TableReadError {
 	/// Table offsets decreasing
 	#[error("Table offsets not increasing")]
 	InvalidTableOffsets,
+	/// Compressed table size doesn't fit file or table contents
+	#[error("Suspicious compressed table size")]
+	SuspiciousTableSize,
+}
+
+/// Error when checking passwords (see [`PasswordChecker`](crate::checker::PasswordChecker))
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum PasswordCheckError {
+	/// IO error opening index
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Opening index failed
+	#[error("index open error: {0}")]
+	OpenError(#[from] IndexOpenError),
+	/// Lookup failed
+	#[error("lookup error: {0}")]
+	LookupError(#[from] LookupError),
+	/// No index loaded
+	#[error("no index available")]
+	NoIndex,
+	/// No index for key type of hash loaded
+	#[error("no {} index available", key_type.name())]
+	MissingIndex {
+		/// key type of hash
+		key_type: KnownKeyType,
+	},
+	/// Input isn't a hash, and plaintext input not allowed
+	#[error("input not a supported hash (plaintext input not allowed)")]
+	PlaintextNotAllowed,
+	/// Index has a key type other than SHA-1 or NT
+	#[error("unsupported key type {}", key_type.name())]
+	UnsupportedKeyType {
+		/// key type of index
+		key_type: KeyType,
+	},
+}
+
+/// Error when updating a range mirror (see [`RangeUpdater`](crate::online::RangeUpdater))
+#[cfg(feature = "online")]
+#[derive(thiserror::Error, Debug)]
+pub enum RangeUpdateError {
+	/// IO error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Fetching or reading range failed
+	#[error("range error: {0}")]
+	LookupError(#[from] LookupError),
+	/// Adding entries to index failed
+	#[error("index build error: {0}")]
+	BuilderAddError(#[from] BuilderAddError),
+	/// Invalid line in manifest
+	#[error("invalid manifest line: {line:?}")]
+	InvalidManifest {
+		/// the invalid line
+		line: String,
+	},
+	/// Downloading range failed (after all retries)
+	#[error("range {prefix} failed: {error}")]
+	DownloadFailed {
+		/// hex prefix of range
+		prefix: String,
+		/// the error
+		#[source]
+		error: LookupError,
+	},
+	/// Range wasn't downloaded yet
+	#[error("range {prefix} missing")]
+	MissingRange {
+		/// hex prefix of range
+		prefix: String,
+	},
+}
+
+/// Error when building a [`KeyFilter`](crate::filter::KeyFilter)
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum FilterBuildError {
+	/// Reading keys from index failed
+	#[error("lookup error: {0}")]
+	LookupError(#[from] LookupError),
+	/// Only 8 and 16 bits per key are supported
+	#[error("unsupported number of bits per key: {0} (supported: 8, 16)")]
+	InvalidBitsPerKey(u8),
+	/// Too many keys for a single filter
+	#[error("too many keys")]
+	TooManyKeys,
+	/// No working seed found (e.g. because of duplicate keys)
+	#[error("filter construction failed (duplicate keys?)")]
+	ConstructionFailed,
+}
+
+/// Error when reading a [`KeyFilter`](crate::filter::KeyFilter)
+#[cfg(feature = "std")]
+#[derive(thiserror::Error, Debug)]
+pub enum FilterReadError {
+	/// IO read error
+	#[error("IO error: {0}")]
+	IOError(#[from] std::io::Error),
+	/// Invalid header
+	#[error("invalid/unknown header format")]
+	InvalidHeader,
+	/// Invalid key type
+	#[error("key-type error: {0}")]
+	KeyTypeError(#[from] KeyTypeParseError),
+	/// Filter contains different key type than requested
+	#[error("key type mismatch: expected {}, found {}", expected.name(), found.name())]
+	KeyTypeMismatch {
+		/// requested key type
+		expected: KeyType,
+		/// key type stored in filter
+		found: KeyType,
+	},
+	/// Data after the fingerprints
+	#[error("trailing data after filter")]
+	TrailingData,
 }
 
 /// Error when opening hash list
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum HashListCreateError {
 	/// IO read error
@@ -94,6 +668,7 @@ pub enum HashListCreateError {
 }
 
 /// Error when opening hash list
+#[cfg(feature = "std")]
 #[derive(thiserror::Error, Debug)]
 pub enum HashListOpenError {
 	/// IO read error
@@ -0,0 +1,183 @@
+//! Export index entries for other tools
+//!
+//! Keys and payloads are exported hex encoded, one row per entry (in key
+//! order), with the columns `key` and `payload` (the latter is omitted for
+//! indexes without payload); [`write_raw`] writes the binary records
+//! instead. [`write_split_hibp`] writes breach counts in the HIBP text format,
+//! split by the first byte of the keys.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::index::Index;
+//!
+//! let index = Index::open(std::fs::File::open("hibp-sha1.index")?)?;
+//! let rows = hibp_index::export::write_csv(&index, std::io::stdout().lock())?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{buf_read::ReadAt, errors::ExportError, import::split_file_name, index::Index};
+
+/// Write entries as CSV (with header line); returns number of rows
+pub fn write_csv<R, W>(index: &Index<R>, writer: W) -> Result<u64, ExportError>
+where
+	R: ReadAt,
+	W: io::Write,
+{
+	if index.needs_payload_key() {
+		return Err(ExportError::EncryptedPayload);
+	}
+	let mut writer = io::BufWriter::new(writer);
+	let with_payload = index.payload_size() > 0;
+	if with_payload {
+		writeln!(writer, "key,payload")?;
+	} else {
+		writeln!(writer, "key")?;
+	}
+	let mut rows = 0;
+	index.for_each_entry(|key, payload| -> Result<(), ExportError> {
+		if with_payload {
+			writeln!(writer, "{},{}", hex::encode(key), hex::encode(payload))?;
+		} else {
+			writeln!(writer, "{}", hex::encode(key))?;
+		}
+		rows += 1;
+		Ok(())
+	})?;
+	writer.flush()?;
+	Ok(rows)
+}
+
+/// Write entries as raw records (`key || payload`, no separators); returns number of records
+///
+/// Records have a fixed size (key size plus payload size) and are ordered by
+/// key; the output can be read back with
+/// [`TypedBuilder::add_raw_records`](crate::index::TypedBuilder::add_raw_records).
+pub fn write_raw<R, W>(index: &Index<R>, writer: W) -> Result<u64, ExportError>
+where
+	R: ReadAt,
+	W: io::Write,
+{
+	if index.needs_payload_key() {
+		return Err(ExportError::EncryptedPayload);
+	}
+	let mut writer = io::BufWriter::new(writer);
+	let mut records = 0;
+	index.for_each_entry(|key, payload| -> Result<(), ExportError> {
+		writer.write_all(key)?;
+		writer.write_all(payload)?;
+		records += 1;
+		Ok(())
+	})?;
+	writer.flush()?;
+	Ok(records)
+}
+
+/// Write entries as HIBP text files split by the first key byte; returns number of lines
+///
+/// Creates the 256 files `00.txt` to `FF.txt` in `dir` (overwriting existing
+/// files) with `HASH:count` lines (upper case hex, ordered by hash), as read
+/// by [`SplitHibpImport`](crate::import::SplitHibpImport). The payload must
+/// be a breach count (4 or 8 byte big endian, e.g.
+/// [`Count32`](crate::data::Count32)).
+pub fn write_split_hibp<R>(index: &Index<R>, dir: &Path) -> Result<u64, ExportError>
+where
+	R: ReadAt,
+{
+	if index.needs_payload_key() {
+		return Err(ExportError::EncryptedPayload);
+	}
+	if !matches!(index.payload_size(), 4 | 8) {
+		return Err(ExportError::MissingCount);
+	}
+	let create = |first: u8| -> io::Result<io::BufWriter<fs::File>> {
+		Ok(io::BufWriter::new(fs::File::create(dir.join(split_file_name(first)))?))
+	};
+	// file of the current first byte; files before it are complete
+	let mut current = (0u8, create(0)?);
+	let mut lines = 0;
+	index.for_each_entry(|key, payload| -> Result<(), ExportError> {
+		while key[0] != current.0 {
+			current.1.flush()?;
+			let next = current.0 + 1;
+			current = (next, create(next)?);
+		}
+		let count = payload.iter().fold(0u64, |count, &byte| (count << 8) | byte as u64);
+		writeln!(current.1, "{}:{}", hex::encode_upper(key), count)?;
+		lines += 1;
+		Ok(())
+	})?;
+	current.1.flush()?;
+	for first in current.0 as u16 + 1..=255 {
+		create(first as u8)?.flush()?;
+	}
+	Ok(lines)
+}
+
+/// Write entries as (uncompressed) Parquet file; returns number of rows
+///
+/// Both columns are required `BYTE_ARRAY` columns with `STRING` annotation.
+#[cfg(feature = "parquet")]
+pub fn write_parquet<R, W>(index: &Index<R>, writer: W) -> Result<u64, ExportError>
+where
+	R: ReadAt,
+	W: io::Write + Send,
+{
+	use parquet::{
+		data_type::{ByteArray, ByteArrayType},
+		file::{properties::WriterProperties, writer::SerializedFileWriter},
+		schema::parser::parse_message_type,
+	};
+	use std::sync::Arc;
+
+	/// Rows per row group
+	const ROW_GROUP_SIZE: usize = 1 << 20;
+
+	if index.needs_payload_key() {
+		return Err(ExportError::EncryptedPayload);
+	}
+	let with_payload = index.payload_size() > 0;
+	let schema = if with_payload {
+		"message entries { required binary key (STRING); required binary payload (STRING); }"
+	} else {
+		"message entries { required binary key (STRING); }"
+	};
+	let schema = Arc::new(parse_message_type(schema)?);
+	let properties = Arc::new(WriterProperties::builder().build());
+	let mut writer = SerializedFileWriter::new(writer, schema, properties)?;
+
+	let mut columns: [Vec<ByteArray>; 2] = Default::default();
+	let mut write_row_group = |columns: &mut [Vec<ByteArray>; 2]| -> Result<(), ExportError> {
+		let mut row_group = writer.next_row_group()?;
+		let mut values = columns.iter_mut();
+		while let Some(mut column) = row_group.next_column()? {
+			let values = values.next().expect("at most two columns");
+			column.typed::<ByteArrayType>().write_batch(values, None, None)?;
+			column.close()?;
+			values.clear();
+		}
+		row_group.close()?;
+		Ok(())
+	};
+	let mut rows = 0;
+	index.for_each_entry(|key, payload| -> Result<(), ExportError> {
+		columns[0].push(hex::encode(key).into_bytes().into());
+		if with_payload {
+			columns[1].push(hex::encode(payload).into_bytes().into());
+		}
+		rows += 1;
+		if columns[0].len() >= ROW_GROUP_SIZE {
+			write_row_group(&mut columns)?;
+		}
+		Ok(())
+	})?;
+	if !columns[0].is_empty() {
+		write_row_group(&mut columns)?;
+	}
+	writer.close()?;
+	Ok(rows)
+}
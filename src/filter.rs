@@ -0,0 +1,318 @@
+//! Approximate membership filters of index keys
+//!
+//! A [`KeyFilter`] is an xor filter over all keys of an index: it answers
+//! "not contained" exactly, but "contained" with a small false positive rate
+//! (`2^-bits_per_key`), using about `1.23 * bits_per_key` bits per key (e.g.
+//! about 1.2 GB with 8 bits per key for the SHA-1 hashes of Pwned Passwords,
+//! instead of about 20 GB for the index). Memory
+//! constrained services can use it to pre-screen passwords, and only query
+//! the full index (or the online API) for the few filter hits.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::{data::{NoPayload, SHA1}, filter::KeyFilter, index::TypedIndex};
+//!
+//! let index = TypedIndex::<SHA1, NoPayload, _>::open(std::fs::File::open("hibp-sha1.index")?)?;
+//! index.build_filter(8)?.write(std::fs::File::create("hibp-sha1.filter")?)?;
+//!
+//! let filter = KeyFilter::<SHA1>::read(std::fs::File::open("hibp-sha1.filter")?)?;
+//! let maybe_pwned = filter.contains(&SHA1::hash(b"password"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! File format: a line with the magic `xor-filter-v0`, a line with the key
+//! type, then (big-endian) the fingerprint size in bits (`u8`), the seed
+//! (`u64`), the number of fingerprints in each of the three blocks (`u32`)
+//! and all fingerprints (little-endian if wider than a byte).
+
+use byteorder::{ReadBytesExt, WriteBytesExt, BE};
+use std::io::{self, BufRead, Read, Write};
+use std::marker::PhantomData;
+
+use crate::{
+	data::{KeyData, KeyType},
+	errors::{FilterBuildError, FilterReadError},
+};
+
+const XOR_FILTER_V0_MAGIC: &str = "xor-filter-v0";
+const XOR_FILTER_V0_HEADER_LIMIT: u64 = 4096;
+// construction fails for some seeds
+const MAX_ATTEMPTS: u32 = 64;
+
+fn mix64(mut h: u64) -> u64 {
+	// finalizer of MurmurHash3
+	h ^= h >> 33;
+	h = h.wrapping_mul(0xff51afd7ed558ccd);
+	h ^= h >> 33;
+	h = h.wrapping_mul(0xc4ceb9fe1a85ec53);
+	h ^ (h >> 33)
+}
+
+/// Hash of the key bytes (before applying the seed of a filter)
+pub(crate) fn key_hash(key: &[u8]) -> u64 {
+	key.chunks(8).fold(key.len() as u64, |h, chunk| {
+		let mut raw = [0u8; 8];
+		raw[..chunk.len()].copy_from_slice(chunk);
+		mix64(h ^ u64::from_le_bytes(raw))
+	})
+}
+
+pub(crate) fn check_bits_per_key(bits_per_key: u8) -> Result<(), FilterBuildError> {
+	if bits_per_key != 8 && bits_per_key != 16 {
+		return Err(FilterBuildError::InvalidBitsPerKey(bits_per_key));
+	}
+	Ok(())
+}
+
+/// Map a 32-bit value uniformly to `0..n`
+fn reduce(value: u32, n: u32) -> u32 {
+	((value as u64 * n as u64) >> 32) as u32
+}
+
+/// Fingerprint size and layout of the three blocks of a filter
+#[derive(Clone, Copy)]
+struct Layout {
+	bits: u8,
+	seed: u64,
+	block_length: u32,
+}
+
+impl Layout {
+	fn hash(&self, key_hash: u64) -> u64 {
+		mix64(key_hash.wrapping_add(self.seed))
+	}
+
+	fn fingerprint(&self, hash: u64) -> u16 {
+		let fingerprint = (hash ^ (hash >> 32)) as u16;
+		if self.bits == 8 {
+			fingerprint & 0xff
+		} else {
+			fingerprint
+		}
+	}
+
+	fn fingerprint_bytes(&self) -> usize {
+		self.bits as usize / 8
+	}
+
+	/// The slot of the hash in each block
+	fn slots(&self, hash: u64) -> [usize; 3] {
+		let block_length = self.block_length;
+		[
+			reduce(hash as u32, block_length) as usize,
+			(reduce(hash.rotate_left(21) as u32, block_length) + block_length) as usize,
+			(reduce(hash.rotate_left(42) as u32, block_length) + 2 * block_length) as usize,
+		]
+	}
+}
+
+/// Fingerprints of all slots (little-endian if wider than a byte)
+struct Fingerprints {
+	bytes: usize,
+	raw: Vec<u8>,
+}
+
+impl Fingerprints {
+	fn get(&self, slot: usize) -> u16 {
+		match self.bytes {
+			1 => self.raw[slot] as u16,
+			_ => u16::from_le_bytes([self.raw[2 * slot], self.raw[2 * slot + 1]]),
+		}
+	}
+
+	fn set(&mut self, slot: usize, fingerprint: u16) {
+		match self.bytes {
+			1 => self.raw[slot] = fingerprint as u8,
+			_ => self.raw[2 * slot..][..2].copy_from_slice(&fingerprint.to_le_bytes()),
+		}
+	}
+}
+
+/// Xor filter over the keys of an index (see [module documentation](self))
+pub struct KeyFilter<D> {
+	key_type: KeyType,
+	layout: Layout,
+	fingerprints: Fingerprints,
+	_marker: PhantomData<D>,
+}
+
+impl<D> KeyFilter<D>
+where
+	D: KeyData,
+{
+	/// Build filter from raw keys with 8 or 16 bits per key
+	///
+	/// Needs about 50 bytes of memory per key while building; see
+	/// [`TypedIndex::build_filter`](crate::index::TypedIndex::build_filter)
+	/// to build a filter of all keys of an index.
+	pub fn build_raw<I>(bits_per_key: u8, keys: I) -> Result<Self, FilterBuildError>
+	where
+		I: IntoIterator,
+		I::Item: AsRef<[u8]>,
+	{
+		check_bits_per_key(bits_per_key)?;
+		let key_hashes = keys.into_iter().map(|key| key_hash(key.as_ref())).collect();
+		Self::from_key_hashes(bits_per_key, key_hashes)
+	}
+
+	/// Build filter from the [`key_hash`] of all keys
+	pub(crate) fn from_key_hashes(
+		bits_per_key: u8,
+		mut key_hashes: Vec<u64>,
+	) -> Result<Self, FilterBuildError> {
+		check_bits_per_key(bits_per_key)?;
+		// duplicate hashes (duplicate keys or hash collisions) can't be peeled
+		key_hashes.sort_unstable();
+		key_hashes.dedup();
+		let block_length = u32::try_from((key_hashes.len() as u64 * 123).div_ceil(300) + 11)
+			.ok()
+			.filter(|&block_length| block_length <= u32::MAX / 3)
+			.ok_or(FilterBuildError::TooManyKeys)?;
+		let capacity = 3 * block_length as usize;
+		let mut seed = 0x9e3779b97f4a7c15u64;
+		for _ in 0..MAX_ATTEMPTS {
+			seed = mix64(seed);
+			let layout = Layout { bits: bits_per_key, seed, block_length };
+			if let Some(fingerprints) = Self::construct(layout, capacity, &key_hashes) {
+				return Ok(Self {
+					key_type: D::KEY_TYPE,
+					layout,
+					fingerprints,
+					_marker: PhantomData,
+				});
+			}
+		}
+		Err(FilterBuildError::ConstructionFailed)
+	}
+
+	/// Try building the fingerprints with the given seed
+	fn construct(layout: Layout, capacity: usize, key_hashes: &[u64]) -> Option<Fingerprints> {
+		// number of keys mapped to each slot, and the xor of their hashes
+		let mut counts = vec![0u32; capacity];
+		let mut hash_xor = vec![0u64; capacity];
+		for &key_hash in key_hashes {
+			let hash = layout.hash(key_hash);
+			for slot in layout.slots(hash) {
+				counts[slot] += 1;
+				hash_xor[slot] ^= hash;
+			}
+		}
+		// peel keys that are the only one mapped to a slot
+		let mut queue: Vec<usize> = (0..capacity).filter(|&slot| counts[slot] == 1).collect();
+		let mut peeled: Vec<(u64, usize)> = Vec::with_capacity(key_hashes.len());
+		while let Some(slot) = queue.pop() {
+			if counts[slot] != 1 {
+				continue;
+			}
+			let hash = hash_xor[slot];
+			peeled.push((hash, slot));
+			for other in layout.slots(hash) {
+				counts[other] -= 1;
+				hash_xor[other] ^= hash;
+				if counts[other] == 1 {
+					queue.push(other);
+				}
+			}
+		}
+		if peeled.len() != key_hashes.len() {
+			return None;
+		}
+		drop((counts, hash_xor));
+		// assign in reverse peeling order: the slot of each key isn't used by
+		// keys assigned before it
+		let bytes = layout.fingerprint_bytes();
+		let mut fingerprints = Fingerprints { bytes, raw: vec![0u8; capacity * bytes] };
+		for &(hash, slot) in peeled.iter().rev() {
+			let [a, b, c] = layout.slots(hash);
+			// slot is one of a, b and c; still zero
+			let fingerprint = layout.fingerprint(hash)
+				^ fingerprints.get(a)
+				^ fingerprints.get(b)
+				^ fingerprints.get(c);
+			fingerprints.set(slot, fingerprint);
+		}
+		Some(fingerprints)
+	}
+
+	/// Whether the key might be contained (false positive rate `2^-bits_per_key`)
+	pub fn contains(&self, key: &D) -> bool {
+		self.contains_raw(key.data())
+	}
+
+	/// Whether the raw key might be contained
+	pub fn contains_raw(&self, key: &[u8]) -> bool {
+		let hash = self.layout.hash(key_hash(key));
+		let [a, b, c] = self.layout.slots(hash);
+		let fingerprints = &self.fingerprints;
+		self.layout.fingerprint(hash)
+			== fingerprints.get(a) ^ fingerprints.get(b) ^ fingerprints.get(c)
+	}
+
+	/// Fingerprint size in bits
+	pub fn bits_per_key(&self) -> u8 {
+		self.layout.bits
+	}
+
+	/// Size of the fingerprints in bytes (i.e. size of the filter file without header)
+	pub fn size(&self) -> u64 {
+		self.fingerprints.raw.len() as u64
+	}
+
+	/// Write filter file
+	pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+		let mut writer = io::BufWriter::new(writer);
+		writeln!(writer, "{}", XOR_FILTER_V0_MAGIC)?;
+		writeln!(writer, "{}", self.key_type.name())?;
+		writer.write_u8(self.layout.bits)?;
+		writer.write_u64::<BE>(self.layout.seed)?;
+		writer.write_u32::<BE>(self.layout.block_length)?;
+		writer.write_all(&self.fingerprints.raw)?;
+		writer.flush()
+	}
+
+	/// Read filter file; key type must match `D`
+	pub fn read<R: io::Read>(reader: R) -> Result<Self, FilterReadError> {
+		let mut reader = io::BufReader::new(reader);
+		let mut header = (&mut reader).take(XOR_FILTER_V0_HEADER_LIMIT);
+		let mut magic = String::new();
+		let mut key_type = String::new();
+		header.read_line(&mut magic)?;
+		header.read_line(&mut key_type)?;
+		if magic.pop() != Some('\n') || key_type.pop() != Some('\n') {
+			return Err(FilterReadError::InvalidHeader);
+		}
+		if magic != XOR_FILTER_V0_MAGIC {
+			return Err(FilterReadError::InvalidHeader);
+		}
+		let key_type: KeyType = key_type.parse()?;
+		if key_type != D::KEY_TYPE {
+			return Err(FilterReadError::KeyTypeMismatch {
+				expected: D::KEY_TYPE,
+				found: key_type,
+			});
+		}
+		let bits = reader.read_u8()?;
+		if bits != 8 && bits != 16 {
+			return Err(FilterReadError::InvalidHeader);
+		}
+		let seed = reader.read_u64::<BE>()?;
+		let block_length = reader.read_u32::<BE>()?;
+		if block_length == 0 || block_length > u32::MAX / 3 {
+			return Err(FilterReadError::InvalidHeader);
+		}
+		let layout = Layout { bits, seed, block_length };
+		let size = 3 * block_length as u64 * layout.fingerprint_bytes() as u64;
+		// don't trust the header with the allocation; grow while reading
+		let mut raw = Vec::new();
+		(&mut reader).take(size).read_to_end(&mut raw)?;
+		if raw.len() as u64 != size {
+			return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+		}
+		if !reader.fill_buf()?.is_empty() {
+			return Err(FilterReadError::TrailingData);
+		}
+		let fingerprints = Fingerprints { bytes: layout.fingerprint_bytes(), raw };
+		Ok(Self { key_type, layout, fingerprints, _marker: PhantomData })
+	}
+}
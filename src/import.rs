@@ -0,0 +1,509 @@
+//! Import entries from CSV / TSV files
+//!
+//! Breach corpora come in many shapes; [`CsvImport`] (feature `cli`) describes
+//! which column holds the hash (hex or base64 encoded) and which (optional)
+//! column holds the breach count. Columns are counted starting at 0.
+//!
+//! The input doesn't need to be sorted: all entries are collected in memory,
+//! sorted and duplicates merged (adding up their counts) before they are
+//! added to a builder.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::data::{NoPayload, SHA1};
+//! use hibp_index::import::{CsvImport, HashEncoding};
+//! use hibp_index::index::{Depth, TypedBuilder};
+//!
+//! // "user;hash" lines with base64 encoded hashes
+//! let import = CsvImport::csv()
+//!     .with_delimiter(b';')
+//!     .with_hash_column(1)
+//!     .with_encoding(HashEncoding::Base64);
+//! let mut builder =
+//!     TypedBuilder::<SHA1, NoPayload, _>::create_atomic("corpus.index", "corpus", Depth::DEPTH20)?;
+//! import.add_entries(std::fs::File::open("corpus.csv")?, &mut builder, |_count| NoPayload)?;
+//! builder.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! HIBP text files that are not ordered by hash (e.g. the "ordered by
+//! prevalence" downloads) are too large to sort in memory; [`UnorderedHibpImport`]
+//! sorts them with temporary files instead. It also hashes plaintext
+//! wordlists on the fly ([`UnorderedHibpImport::add_wordlist`]).
+//!
+//! Some mirrors split the HIBP text files by the first byte of the hashes
+//! into 256 files (`00.txt` to `FF.txt`); [`SplitHibpImport`] reads them in
+//! order (and [`export::write_split_hibp`](crate::export::write_split_hibp)
+//! writes that layout).
+
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+#[cfg(feature = "cli")]
+use base64::Engine;
+
+#[cfg(feature = "cli")]
+use crate::errors::CsvImportError;
+use crate::{
+	data::{KeyData, PayloadData},
+	index::{parse_hibp_line_with_count, TypedBuilder},
+};
+
+/// Encoding of the hash column
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum HashEncoding {
+	/// Hex digits (upper or lower case)
+	#[default]
+	Hex,
+	/// Standard base64 alphabet (with padding)
+	Base64,
+}
+
+#[cfg(feature = "cli")]
+impl HashEncoding {
+	fn decode<D: KeyData>(self, value: &str) -> Option<D> {
+		let mut key = D::default();
+		match self {
+			Self::Hex => hex::decode_to_slice(value, key.data_mut()).ok()?,
+			Self::Base64 => {
+				let data = base64::engine::general_purpose::STANDARD.decode(value).ok()?;
+				if data.len() != D::SIZE {
+					return None;
+				}
+				key.data_mut().copy_from_slice(&data);
+			},
+		}
+		Some(key)
+	}
+}
+
+/// Column mapping (and dialect) of CSV / TSV input
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct CsvImport {
+	/// Field delimiter
+	pub delimiter: u8,
+	/// Whether fields can be quoted with `"`
+	pub quoting: bool,
+	/// Whether the first line is a header (and gets skipped)
+	pub has_header: bool,
+	/// Column with the hash
+	pub hash_column: usize,
+	/// Column with the breach count (if any)
+	pub count_column: Option<usize>,
+	/// Encoding of the hash column
+	pub encoding: HashEncoding,
+}
+
+#[cfg(feature = "cli")]
+impl CsvImport {
+	/// Comma separated values with optional quoting; hex hash in first column, no header
+	pub fn csv() -> Self {
+		Self {
+			delimiter: b',',
+			quoting: true,
+			has_header: false,
+			hash_column: 0,
+			count_column: None,
+			encoding: HashEncoding::Hex,
+		}
+	}
+
+	/// Tab separated values without quoting; hex hash in first column, no header
+	pub fn tsv() -> Self {
+		Self { delimiter: b'\t', quoting: false, ..Self::csv() }
+	}
+
+	/// Use a different field delimiter
+	pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+		self.delimiter = delimiter;
+		self
+	}
+
+	/// Skip first line
+	pub fn with_header(mut self) -> Self {
+		self.has_header = true;
+		self
+	}
+
+	/// Read hash from given column
+	pub fn with_hash_column(mut self, column: usize) -> Self {
+		self.hash_column = column;
+		self
+	}
+
+	/// Read breach count from given column
+	pub fn with_count_column(mut self, column: usize) -> Self {
+		self.count_column = Some(column);
+		self
+	}
+
+	/// Set encoding of the hash column
+	pub fn with_encoding(mut self, encoding: HashEncoding) -> Self {
+		self.encoding = encoding;
+		self
+	}
+
+	/// Read all entries; returns them sorted by key without duplicates
+	///
+	/// The count is `None` if no count column is configured; counts of
+	/// duplicate hashes are added up.
+	pub fn read<D, R>(&self, input: R) -> Result<Vec<(D, Option<u64>)>, CsvImportError>
+	where
+		D: KeyData + Ord,
+		R: io::Read,
+	{
+		let mut reader = csv::ReaderBuilder::new()
+			.delimiter(self.delimiter)
+			.quoting(self.quoting)
+			.has_headers(self.has_header)
+			.flexible(true)
+			.trim(csv::Trim::All)
+			.from_reader(input);
+		let mut entries = Vec::new();
+		let mut record = csv::StringRecord::new();
+		while reader.read_record(&mut record)? {
+			let line = record.position().map_or(0, |pos| pos.line());
+			let field = |column: usize| {
+				record.get(column).ok_or(CsvImportError::MissingColumn { line, column })
+			};
+			let hash = field(self.hash_column)?;
+			let key = self
+				.encoding
+				.decode::<D>(hash)
+				.ok_or_else(|| CsvImportError::InvalidHash { line, value: hash.to_string() })?;
+			let count = match self.count_column {
+				Some(column) => {
+					let count = field(column)?;
+					Some(count.parse::<u64>().map_err(|_| CsvImportError::InvalidCount {
+						line,
+						value: count.to_string(),
+					})?)
+				},
+				None => None,
+			};
+			entries.push((key, count));
+		}
+		entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+		entries.dedup_by(|(key, count), (prev_key, prev_count)| {
+			if key != prev_key {
+				return false;
+			}
+			if let (Some(count), Some(prev_count)) = (count, prev_count) {
+				*prev_count = prev_count.saturating_add(*count);
+			}
+			true
+		});
+		Ok(entries)
+	}
+
+	/// Read all entries (see [`read`](Self::read)) and add them to builder;
+	/// returns number of added entries
+	///
+	/// `payload` builds the payload for an entry from its count.
+	pub fn add_entries<D, P, W, R, F>(
+		&self,
+		input: R,
+		builder: &mut TypedBuilder<D, P, W>,
+		mut payload: F,
+	) -> Result<u64, CsvImportError>
+	where
+		D: KeyData + Ord,
+		P: PayloadData,
+		W: io::Write + io::Seek,
+		R: io::Read,
+		F: FnMut(Option<u64>) -> P,
+	{
+		let entries = self.read::<D, R>(input)?;
+		for (key, count) in &entries {
+			builder.add_entry(key, &payload(*count))?;
+		}
+		Ok(entries.len() as u64)
+	}
+}
+
+#[cfg(feature = "cli")]
+impl Default for CsvImport {
+	fn default() -> Self {
+		Self::csv()
+	}
+}
+
+/// Import HIBP text files (`HASH:count` lines) in any order
+///
+/// Entries are first distributed into temporary files by the first
+/// `shard_bits` bits of their key (the "shard"); then each shard is loaded,
+/// sorted and added to the builder. Memory usage is therefore about the size
+/// of the largest shard (key plus 8 bytes per entry). Counts of duplicate
+/// hashes are added up. Plaintext wordlists are hashed before sharding (see
+/// [`add_wordlist`](Self::add_wordlist)).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct UnorderedHibpImport {
+	/// Directory to create the temporary files in
+	pub temp_dir: PathBuf,
+	/// Number of key bits used to select the temporary file (1-10)
+	pub shard_bits: u8,
+}
+
+/// Maximum of [`UnorderedHibpImport::shard_bits`]
+///
+/// All temporary files are open while distributing the entries; 1024 files
+/// (plus input and output) might already need a raised open file limit.
+pub const MAX_SHARD_BITS: u8 = 10;
+
+impl UnorderedHibpImport {
+	/// Use temporary files in `temp_dir` with 256 shards
+	pub fn new<Q: Into<PathBuf>>(temp_dir: Q) -> Self {
+		Self { temp_dir: temp_dir.into(), shard_bits: 8 }
+	}
+
+	/// Use `2^bits` temporary files (more files: less memory per shard)
+	///
+	/// At most [`MAX_SHARD_BITS`]; importing fails with more bits.
+	pub fn with_shard_bits(mut self, bits: u8) -> Self {
+		self.shard_bits = bits;
+		self
+	}
+
+	/// Read all lines, sort them and add them to builder; returns number of added entries
+	///
+	/// `payload` builds the payload for an entry from its count.
+	pub fn add_entries<D, P, W, R, F>(
+		&self,
+		input: R,
+		builder: &mut TypedBuilder<D, P, W>,
+		payload: F,
+	) -> anyhow::Result<u64>
+	where
+		D: KeyData + Ord + std::str::FromStr,
+		<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+		P: PayloadData,
+		W: io::Write + io::Seek,
+		R: BufRead,
+		F: FnMut(u64) -> P,
+	{
+		let entries = input
+			.lines()
+			.map(|line| parse_hibp_line_with_count::<D>(&line?))
+			.filter_map(Result::transpose);
+		self.sort_entries(entries, builder, payload)
+	}
+
+	/// Hash each line of a plaintext wordlist, sort the hashes and add them to builder;
+	/// returns number of added entries
+	///
+	/// Lines don't need to be valid UTF-8 (e.g. rockyou-style lists); line
+	/// breaks (`\n` or `\r\n`) are removed and empty lines skipped before
+	/// hashing the raw bytes with `hash` (e.g. `SHA1::hash` or `NT::hash_bytes`,
+	/// which decodes invalid UTF-8 as Latin-1).
+	/// The count of an entry is the number of lines with the same hash.
+	pub fn add_wordlist<D, P, W, R, H, F>(
+		&self,
+		input: R,
+		mut hash: H,
+		builder: &mut TypedBuilder<D, P, W>,
+		payload: F,
+	) -> anyhow::Result<u64>
+	where
+		D: KeyData + Ord,
+		P: PayloadData,
+		W: io::Write + io::Seek,
+		R: BufRead,
+		H: FnMut(&[u8]) -> D,
+		F: FnMut(u64) -> P,
+	{
+		let entries = input.split(b'\n').filter_map(|line| {
+			let mut line = match line {
+				Ok(line) => line,
+				Err(e) => return Some(Err(e.into())),
+			};
+			if line.last() == Some(&b'\r') {
+				line.pop();
+			}
+			if line.is_empty() {
+				return None;
+			}
+			Some(Ok((hash(&line), 1)))
+		});
+		self.sort_entries(entries, builder, payload)
+	}
+
+	/// Sort entries (adding up the counts of duplicates) and add them to builder
+	fn sort_entries<D, P, W, I, F>(
+		&self,
+		entries: I,
+		builder: &mut TypedBuilder<D, P, W>,
+		mut payload: F,
+	) -> anyhow::Result<u64>
+	where
+		D: KeyData + Ord,
+		P: PayloadData,
+		W: io::Write + io::Seek,
+		I: IntoIterator<Item = anyhow::Result<(D, u64)>>,
+		F: FnMut(u64) -> P,
+	{
+		let bits = self.shard_bits as usize;
+		if !(1..=MAX_SHARD_BITS as usize).contains(&bits) || bits > 8 * D::SIZE {
+			anyhow::bail!("Invalid number of shard bits {}", bits);
+		}
+		let shards = ShardDir::create(&self.temp_dir, 1 << bits)?;
+
+		let mut writers = Vec::with_capacity(shards.count);
+		for shard in 0..shards.count {
+			writers.push(io::BufWriter::new(fs::File::create(shards.path(shard))?));
+		}
+		for entry in entries {
+			let (key, count) = entry?;
+			let data = key.data();
+			let lead = u16::from_be_bytes([data[0], data.get(1).copied().unwrap_or(0)]);
+			let writer = &mut writers[(lead >> (16 - bits)) as usize];
+			writer.write_all(data)?;
+			writer.write_all(&count.to_be_bytes())?;
+		}
+		for writer in writers {
+			writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+		}
+
+		let mut added = 0;
+		for shard in 0..shards.count {
+			let path = shards.path(shard);
+			let mut records = io::BufReader::new(fs::File::open(&path)?);
+			let mut entries = Vec::new();
+			loop {
+				let mut key = D::default();
+				match records.read_exact(key.data_mut()) {
+					Ok(()) => (),
+					Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+					Err(e) => return Err(e.into()),
+				}
+				let mut count = [0u8; 8];
+				records.read_exact(&mut count)?;
+				entries.push((key, u64::from_be_bytes(count)));
+			}
+			drop(records);
+			fs::remove_file(&path)?;
+			entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+			entries.dedup_by(|(key, count), (prev_key, prev_count)| {
+				if key != prev_key {
+					return false;
+				}
+				*prev_count = prev_count.saturating_add(*count);
+				true
+			});
+			for (key, count) in &entries {
+				builder.add_entry(key, &payload(*count)).context("Failed to add hash to index")?;
+			}
+			added += entries.len() as u64;
+		}
+		Ok(added)
+	}
+}
+
+/// File name of the split HIBP text file with keys starting with `first` (upper case)
+pub(crate) fn split_file_name(first: u8) -> String {
+	format!("{:02X}.txt", first)
+}
+
+/// Import HIBP text files split by the first byte of the hashes
+///
+/// The directory must contain the 256 files `00.txt` to `FF.txt` (upper or
+/// lower case hex digits) with `HASH:count` lines ordered by hash; compressed
+/// files are detected like in [`input::open`](crate::input::open).
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SplitHibpImport {
+	/// Directory with the split files
+	pub dir: PathBuf,
+}
+
+impl SplitHibpImport {
+	/// Read split files from `dir`
+	pub fn new<Q: Into<PathBuf>>(dir: Q) -> Self {
+		Self { dir: dir.into() }
+	}
+
+	/// Path of the file with the keys starting with `first`
+	pub fn path(&self, first: u8) -> io::Result<PathBuf> {
+		let upper = self.dir.join(split_file_name(first));
+		if upper.exists() {
+			return Ok(upper);
+		}
+		let lower = self.dir.join(format!("{:02x}.txt", first));
+		if lower.exists() {
+			return Ok(lower);
+		}
+		Err(io::Error::new(io::ErrorKind::NotFound, format!("Missing split file {:?}", upper)))
+	}
+
+	/// Read all files in order and add entries to builder; returns number of added entries
+	///
+	/// `payload` builds the payload for an entry from its count. Fails if a
+	/// file is missing or contains a hash with a different first byte.
+	pub fn add_entries<D, P, W, F>(
+		&self,
+		builder: &mut TypedBuilder<D, P, W>,
+		mut payload: F,
+	) -> anyhow::Result<u64>
+	where
+		D: KeyData + std::str::FromStr,
+		<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+		P: PayloadData,
+		W: io::Write + io::Seek,
+		F: FnMut(u64) -> P,
+	{
+		let mut added = 0;
+		for first in 0..=255u8 {
+			let path = self.path(first)?;
+			let input = crate::input::open(&path)?;
+			for line in input.lines() {
+				let line = line?;
+				let Some((key, count)) = parse_hibp_line_with_count::<D>(&line)? else {
+					continue;
+				};
+				if key.data()[0] != first {
+					anyhow::bail!("Hash {} in wrong file {:?}", key.hex(), path);
+				}
+				builder
+					.add_entry(&key, &payload(count))
+					.with_context(|| format!("Failed to add hash from {:?} to index", path))?;
+				added += 1;
+			}
+		}
+		Ok(added)
+	}
+}
+
+/// Temporary directory with one file per shard; removed when dropped
+struct ShardDir {
+	dir: PathBuf,
+	count: usize,
+}
+
+impl ShardDir {
+	/// Create a new directory; skips names already taken (e.g. left behind
+	/// by a crashed import)
+	fn create(temp_dir: &Path, count: usize) -> io::Result<Self> {
+		let mut attempt = 0;
+		loop {
+			let dir = temp_dir.join(format!(".hibp-sort-{}-{}", std::process::id(), attempt));
+			match fs::create_dir(&dir) {
+				Ok(()) => return Ok(Self { dir, count }),
+				Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+				Err(e) => return Err(e),
+			}
+		}
+	}
+
+	fn path(&self, shard: usize) -> PathBuf {
+		self.dir.join(format!("{:04x}", shard))
+	}
+}
+
+impl Drop for ShardDir {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.dir);
+	}
+}
@@ -0,0 +1,166 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Output file that only shows up at the target path once it is complete
+///
+/// Data is written to a temporary file in the directory of the target path;
+/// [`AtomicFile::commit`] flushes and syncs the data and then renames the
+/// temporary file to the target path (replacing an existing file, unless
+/// [`AtomicFile::no_overwrite`] was used).
+///
+/// Dropping an uncommitted file removes the temporary file.
+pub struct AtomicFile {
+	file: Option<io::BufWriter<fs::File>>,
+	temp_path: PathBuf,
+	target_path: PathBuf,
+	overwrite: bool,
+	// advisory lock on the target path (see `create_locked`); released when dropped
+	_lock: Option<fs::File>,
+}
+
+/// Hidden file name in the directory of `target_path` with given suffix
+fn sibling_path(target_path: &Path, suffix: &str) -> io::Result<PathBuf> {
+	let file_name = target_path
+		.file_name()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "missing file name"))?;
+	let mut name = OsString::from(".");
+	name.push(file_name);
+	name.push(suffix);
+	Ok(target_path.with_file_name(name))
+}
+
+impl AtomicFile {
+	/// Create temporary file for given target path
+	pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		Self::create_with_lock(path.as_ref().to_path_buf(), None)
+	}
+
+	/// Create temporary file for given target path, holding a lock on the target path
+	///
+	/// Uses an advisory lock (`flock` on Unix) on a hidden lock file next to
+	/// the target path (`.<name>.lock`, not removed afterwards); fails with
+	/// [`io::ErrorKind::WouldBlock`] if another build of the same target
+	/// path is still running. The lock is released when the file is
+	/// committed or dropped.
+	pub fn create_locked<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+		let target_path = path.as_ref().to_path_buf();
+		let lock_path = sibling_path(&target_path, ".lock")?;
+		let lock =
+			fs::OpenOptions::new().write(true).create(true).truncate(false).open(&lock_path)?;
+		match lock.try_lock() {
+			Ok(()) => (),
+			Err(fs::TryLockError::WouldBlock) => {
+				return Err(io::Error::new(
+					io::ErrorKind::WouldBlock,
+					format!("{:?} is locked by another build", target_path),
+				))
+			},
+			Err(fs::TryLockError::Error(e)) => return Err(e),
+		}
+		Self::create_with_lock(target_path, Some(lock))
+	}
+
+	fn create_with_lock(target_path: PathBuf, lock: Option<fs::File>) -> io::Result<Self> {
+		let temp_path = sibling_path(&target_path, &format!(".tmp.{}", std::process::id()))?;
+		let file = fs::OpenOptions::new().write(true).create_new(true).open(&temp_path)?;
+		Ok(Self {
+			file: Some(io::BufWriter::new(file)),
+			temp_path,
+			target_path,
+			overwrite: true,
+			_lock: lock,
+		})
+	}
+
+	/// Never replace an existing file at the target path
+	///
+	/// Fails right away if the target path already exists (when created with
+	/// [`AtomicFile::create_locked`] this check happens while holding the lock);
+	/// [`AtomicFile::commit`] then hard links the temporary file to the target
+	/// path, which fails with [`io::ErrorKind::AlreadyExists`] if the target
+	/// path was created in the meantime.
+	pub fn no_overwrite(mut self) -> io::Result<Self> {
+		if self.target_path.symlink_metadata().is_ok() {
+			return Err(io::Error::new(
+				io::ErrorKind::AlreadyExists,
+				format!("{:?} already exists", self.target_path),
+			));
+		}
+		self.overwrite = false;
+		Ok(self)
+	}
+
+	/// Path the file will be renamed to
+	pub fn target_path(&self) -> &Path {
+		&self.target_path
+	}
+
+	fn writer(&mut self) -> &mut io::BufWriter<fs::File> {
+		self.file.as_mut().expect("only taken in commit")
+	}
+
+	/// Sync data to disk and rename temporary file to target path
+	pub fn commit(mut self) -> io::Result<()> {
+		let file = self.file.take().expect("only taken in commit");
+		// the file is gone now; `Drop` won't remove the temporary file anymore
+		let synced = file
+			.into_inner()
+			.map_err(io::IntoInnerError::into_error)
+			.and_then(|file| file.sync_all());
+		if let Err(e) = synced {
+			let _ = fs::remove_file(&self.temp_path);
+			return Err(e);
+		}
+		if self.overwrite {
+			if let Err(e) = fs::rename(&self.temp_path, &self.target_path) {
+				let _ = fs::remove_file(&self.temp_path);
+				return Err(e);
+			}
+		} else {
+			let result = fs::hard_link(&self.temp_path, &self.target_path);
+			let _ = fs::remove_file(&self.temp_path);
+			result?;
+		}
+		// make rename durable; not supported on all platforms
+		#[cfg(unix)]
+		if let Some(dir) = self.target_path.parent() {
+			let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+			fs::File::open(dir)?.sync_all()?;
+		}
+		Ok(())
+	}
+}
+
+impl io::Write for AtomicFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.writer().write(buf)
+	}
+
+	fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+		self.writer().write_all(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.writer().flush()
+	}
+}
+
+impl io::Seek for AtomicFile {
+	fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+		self.writer().seek(pos)
+	}
+
+	fn stream_position(&mut self) -> io::Result<u64> {
+		self.writer().stream_position()
+	}
+}
+
+impl Drop for AtomicFile {
+	fn drop(&mut self) {
+		if self.file.take().is_some() {
+			let _ = fs::remove_file(&self.temp_path);
+		}
+	}
+}
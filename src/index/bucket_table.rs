@@ -0,0 +1,148 @@
+use std::io::{self, Read};
+use std::ops::Range;
+
+use super::{format::TableEncoding, table::Table, BucketIndex, Depth};
+use crate::{
+	buf_read::{BufReader, ReadAt},
+	errors::LookupError,
+};
+
+/// Maximum number of additional prefix bits of bucket tables
+pub(super) const BUCKET_TABLE_MAX_BITS: u8 = 8;
+
+/// Value of the `bits` additional prefix bits after the first `depth` bits of `key`
+fn sub_bucket(depth: Depth, bits: u8, key: &[u8]) -> usize {
+	let start = depth.as_u8() as usize;
+	let from = start / 8;
+	let mut raw = [0u8; 4];
+	let len = std::cmp::min(raw.len(), key.len() - from);
+	raw[..len].copy_from_slice(&key[from..][..len]);
+	let window = u32::from_be_bytes(raw) << (start % 8);
+	(window >> (32 - bits as u32)) as usize
+}
+
+/// Size (in bytes) of a bucket table with `bits` additional prefix bits
+fn table_size(bits: u8) -> usize {
+	1 + 4 * (1 << bits)
+}
+
+/// Whether `size` is a valid size (in bytes) of a bucket table (zero: no table)
+pub(super) fn valid_size(size: u64) -> bool {
+	size == 0 || (1..=BUCKET_TABLE_MAX_BITS).any(|bits| size == table_size(bits) as u64)
+}
+
+/// Collects bucket tables for buckets with many entries
+///
+/// See [`IndexFlags::BUCKET_TABLES`](super::IndexFlags::BUCKET_TABLES); the
+/// bucket tables are kept in memory until the build is finished.
+pub(super) struct BucketTableBuilder {
+	depth: Depth,
+	bits: u8,
+	// size of the bucket table of each bucket (zero if bucket has no table)
+	sizes: Vec<u64>,
+	// bucket tables of all buckets
+	tables: Vec<u8>,
+	// current bucket and number of its entries per additional prefix
+	current: Option<(BucketIndex, Vec<u64>)>,
+}
+
+impl BucketTableBuilder {
+	pub(super) fn new(depth: Depth, bits: u8) -> Self {
+		debug_assert!((1..=BUCKET_TABLE_MAX_BITS).contains(&bits));
+		Self {
+			depth,
+			bits,
+			sizes: vec![0; depth.table_entries() - 1],
+			tables: Vec::new(),
+			current: None,
+		}
+	}
+
+	/// Count entry with `key` (keys must be passed in order)
+	pub(super) fn add(&mut self, key: &[u8]) {
+		let bucket = self.depth.index(key);
+		if !matches!(&self.current, Some((current, _)) if *current == bucket) {
+			self.finish_bucket();
+			self.current = Some((bucket, vec![0; 1 << self.bits]));
+		}
+		let (_, counts) = self.current.as_mut().expect("current bucket");
+		counts[sub_bucket(self.depth, self.bits, key)] += 1;
+	}
+
+	/// Write table of current bucket if it is large enough
+	fn finish_bucket(&mut self) {
+		let Some((bucket, counts)) = self.current.take() else {
+			return;
+		};
+		let total: u64 = counts.iter().sum();
+		// scanning small buckets is cheap enough; tables store `u32` entry indices
+		if total <= counts.len() as u64 || total > u32::MAX as u64 {
+			return;
+		}
+		self.tables.push(self.bits);
+		let mut start = 0;
+		for count in counts {
+			self.tables.extend_from_slice(&(start as u32).to_be_bytes());
+			start += count;
+		}
+		self.sizes[bucket.entry()] = table_size(self.bits) as u64;
+	}
+
+	/// Write bucket tables (starting at file offset `start`) and their table
+	pub(super) fn write<W: io::Write>(
+		mut self,
+		database: &mut W,
+		start: u64,
+		encoding: TableEncoding,
+	) -> io::Result<()> {
+		self.finish_bucket();
+		database.write_all(&self.tables)?;
+		let table = Table::from_bucket_counts(self.depth, start, 1, &self.sizes);
+		table.write(database, encoding)
+	}
+}
+
+/// Narrow the range of the bucket of `key` with its bucket table (if it has one)
+///
+/// `bucket` is the file range of the entries of the bucket, `bucket_table` the
+/// file range of its bucket table (empty if there is none).
+pub(super) fn narrow<R>(
+	database: &mut BufReader<'_, R>,
+	depth: Depth,
+	key: &[u8],
+	entry_size: usize,
+	bucket: Range<u64>,
+	bucket_table: Range<u64>,
+) -> Result<Range<u64>, LookupError>
+where
+	R: ReadAt,
+{
+	if bucket_table.is_empty() {
+		return Ok(bucket);
+	}
+	database.seek_from_start(bucket_table.start);
+	let mut bits = [0u8];
+	database.read_exact(&mut bits)?;
+	let bits = bits[0];
+	if !(1..=BUCKET_TABLE_MAX_BITS).contains(&bits)
+		|| bucket_table.end - bucket_table.start != table_size(bits) as u64
+		|| depth.as_u8() as usize + bits as usize > 8 * key.len()
+	{
+		return Err(LookupError::InvalidBucketTable);
+	}
+	let sub_bucket = sub_bucket(depth, bits, key);
+	let mut indices = [0u8; 8];
+	database.seek_from_start(bucket_table.start + 1 + 4 * sub_bucket as u64);
+	let last = sub_bucket + 1 == 1 << bits;
+	database.read_exact(&mut indices[..if last { 4 } else { 8 }])?;
+	let entry_size = entry_size as u64;
+	let offset = |index: &[u8]| {
+		bucket.start + entry_size * u32::from_be_bytes(index.try_into().expect("4 bytes")) as u64
+	};
+	let start = offset(&indices[..4]);
+	let end = if last { bucket.end } else { offset(&indices[4..]) };
+	if start > end || end > bucket.end {
+		return Err(LookupError::InvalidBucketTable);
+	}
+	Ok(start..end)
+}
@@ -1,22 +1,151 @@
 use super::{
-	reader::{INDEX_V0_HEADER_LIMIT, INDEX_V0_MAGIC},
+	atomic_file::AtomicFile,
+	bucket_table::{BucketTableBuilder, BUCKET_TABLE_MAX_BITS},
+	checksum::ChecksumWriter,
+	dictionary::DictionaryBuilder,
+	format::{
+		FormatVersion, IndexFlags, IndexOptions, PayloadEncryption, TableEncoding,
+		INDEX_HEADER_LIMIT,
+	},
 	table::TableBuilder,
-	Depth,
+	Depth, LimPrefix,
 };
 use crate::{
-	data::{KeyData, KnownKeyType, NoPayload, PayloadData},
-	errors::BuilderCreateError,
+	data::{Count32, KeyData, KeyType, NoPayload, PayloadData},
+	errors::{BuilderAddError, BuilderCreateError},
 };
 use anyhow::Context;
-use byteorder::WriteBytesExt;
-use std::io;
+use std::io::{self, Seek, Write};
+use std::path::Path;
+
+/// Progress of a running build
+///
+/// Passed to the progress callback of a builder.
+#[derive(Clone, Copy, Debug)]
+pub struct BuildProgress {
+	/// Number of entries written so far
+	pub entries: u64,
+	/// Number of bytes written so far (header and entries)
+	pub bytes: u64,
+	/// Prefix of the bucket currently written
+	pub prefix: LimPrefix,
+	/// Total number of entries if known in advance (two-pass build)
+	pub total: Option<u64>,
+}
+
+/// Read buffer size for raw record input
+const RAW_INPUT_BUFFER: usize = 1 << 20;
+
+pub(super) type ProgressCallback = Box<dyn FnMut(&BuildProgress) + Send>;
+
+/// Write index header; returns size of header
+pub(super) fn write_header<W: io::Write>(
+	database: &mut W,
+	key_type: &KeyType,
+	key_bytes: u8,
+	description: &str,
+	payload_size: u8,
+	depth: Depth,
+	options: &IndexOptions,
+) -> Result<u64, BuilderCreateError> {
+	if !depth.valid_entry_layout(key_bytes, payload_size) {
+		return Err(BuilderCreateError::InvalidKeyLength);
+	}
+	if description.contains('\n') {
+		return Err(BuilderCreateError::InvalidDescription {
+			description: description.to_string(),
+		});
+	}
+	let flags = options.flags();
+	if options.version < FormatVersion::V1 && !flags.is_empty() {
+		return Err(BuilderCreateError::RequiresV1);
+	}
+	if let Some(level) = options.table_compression_level {
+		if !options.table_compression.valid_level(level) {
+			return Err(BuilderCreateError::InvalidCompressionLevel { level });
+		}
+	}
+	let mut header = Vec::new();
+	header.extend_from_slice(options.version.magic().as_bytes());
+	header.push(b'\n');
+	header.extend_from_slice(key_type.name().as_bytes());
+	header.push(b'\n');
+	header.extend_from_slice(description.as_bytes());
+	header.push(b'\n');
+	header.push(key_bytes);
+	header.push(payload_size);
+	if options.version >= FormatVersion::V1 {
+		header.extend_from_slice(&flags.bits().to_be_bytes());
+		if flags.contains(IndexFlags::METADATA) {
+			let mut metadata = Vec::new();
+			options.header_metadata().serialize(&mut metadata);
+			if metadata.len() > u16::MAX as usize {
+				return Err(BuilderCreateError::HeaderTooBig);
+			}
+			header.extend_from_slice(&(metadata.len() as u16).to_be_bytes());
+			header.extend_from_slice(&metadata);
+		}
+	}
+	if header.len() as u64 > INDEX_HEADER_LIMIT {
+		return Err(BuilderCreateError::HeaderTooBig);
+	}
+	database.write_all(&header)?;
+	Ok(header.len() as u64)
+}
+
+/// Parse key from HIBP file line
+///
+/// Returns `None` for empty lines.
+pub(super) fn parse_hibp_line<D>(line: &str) -> anyhow::Result<Option<D>>
+where
+	D: std::str::FromStr,
+	<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+{
+	if let Some(colon) = line.find(':') {
+		let hash =
+			line[..colon].parse::<D>().context("Failed to parse hash from HIBP source line")?;
+		Ok(Some(hash))
+	} else if !line.is_empty() {
+		anyhow::bail!("Invalid HIBP source line: {:?}", line);
+	} else {
+		Ok(None)
+	}
+}
+
+/// Parse key and password count from HIBP file line
+///
+/// Returns `None` for empty lines.
+pub(crate) fn parse_hibp_line_with_count<D>(line: &str) -> anyhow::Result<Option<(D, u64)>>
+where
+	D: std::str::FromStr,
+	<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+{
+	let Some(hash) = parse_hibp_line::<D>(line)? else {
+		return Ok(None);
+	};
+	let count = line[line.find(':').expect("colon") + 1..].trim_end();
+	let count = count
+		.parse::<u64>()
+		.with_context(|| format!("Failed to parse count from HIBP source line: {:?}", line))?;
+	Ok(Some((hash, count)))
+}
 
 /// Build index in database file
-struct Builder<W> {
+pub(super) struct Builder<W> {
 	key_bytes: u8,
 	payload_size: u8,
 	table: TableBuilder,
-	database: W,
+	database: ChecksumWriter<W>,
+	header_size: u64,
+	current_prefix: Option<LimPrefix>,
+	progress: Option<ProgressCallback>,
+	payload_encryption: PayloadEncryption,
+	payload_buf: Vec<u8>,
+	dictionary: Option<DictionaryBuilder>,
+	bucket_tables: Option<BucketTableBuilder>,
+	table_encoding: TableEncoding,
+	// called with database after table was written
+	on_finish: Option<fn(W) -> io::Result<()>>,
 }
 
 impl<W> Builder<W>
@@ -25,55 +154,172 @@ where
 {
 	/// Create new builder to write database
 	pub fn create(
-		mut database: W,
-		key_type: KnownKeyType,
+		database: W,
+		key_type: &KeyType,
+		key_bytes: u8,
 		description: &str,
 		payload_size: u8,
 		depth: Depth,
+		options: &IndexOptions,
 	) -> Result<Self, BuilderCreateError> {
-		let key_bytes = key_type.key_bytes_length();
-		let start = database.stream_position()?;
-		if !depth.valid_key_size(key_bytes) {
-			return Err(BuilderCreateError::InvalidKeyLength);
+		if options.leading_table {
+			return Err(BuilderCreateError::RequiresTwoPass);
 		}
-		if description.contains('\n') {
-			return Err(BuilderCreateError::InvalidDescription {
-				description: description.to_string(),
-			});
+		let options = &*options.for_new_index()?;
+		let payload_encryption = options.payload_encryption();
+		if options.payload_dictionary && payload_encryption.is_enabled() {
+			return Err(BuilderCreateError::PayloadDictionaryUnsupported);
 		}
-		database.write_all(INDEX_V0_MAGIC.as_bytes())?;
-		database.write_all(b"\n")?;
-		database.write_all(key_type.name().as_bytes())?;
-		database.write_all(b"\n")?;
-		database.write_all(description.as_bytes())?;
-		database.write_all(b"\n")?;
-		database.write_u8(key_bytes)?;
-		database.write_u8(payload_size)?;
-		let header_end = database.stream_position()?;
-		let header_size = header_end - start;
-		if header_size > INDEX_V0_HEADER_LIMIT {
-			return Err(BuilderCreateError::HeaderTooBig);
+		let bucket_table_bits = options.bucket_table_bits;
+		if bucket_table_bits != 0
+			&& (options.payload_dictionary
+				|| bucket_table_bits > BUCKET_TABLE_MAX_BITS
+				|| depth.as_u8() as usize + bucket_table_bits as usize > 8 * key_bytes as usize)
+		{
+			return Err(BuilderCreateError::BucketTablesUnsupported);
 		}
+		let mut database = ChecksumWriter::new(database, options.checksum);
+		let header_size = write_header(
+			&mut database,
+			key_type,
+			key_bytes,
+			description,
+			payload_size,
+			depth,
+			options,
+		)?;
 		let table = TableBuilder::new(depth);
-		Ok(Self { key_bytes, payload_size, table, database })
+		Ok(Self {
+			key_bytes,
+			payload_size,
+			table,
+			database,
+			header_size,
+			current_prefix: None,
+			progress: None,
+			payload_encryption,
+			payload_buf: Vec::new(),
+			dictionary: options
+				.payload_dictionary
+				.then(|| DictionaryBuilder::new(depth, payload_size)),
+			bucket_tables: (bucket_table_bits != 0)
+				.then(|| BucketTableBuilder::new(depth, bucket_table_bits)),
+			table_encoding: options.table_encoding(),
+			on_finish: None,
+		})
+	}
+
+	/// Set callback to call when a new bucket is started (and when finishing)
+	pub fn set_progress(&mut self, callback: ProgressCallback) {
+		self.progress = Some(callback);
+	}
+
+	fn report_progress(&mut self) {
+		if let (Some(callback), Some(prefix)) = (&mut self.progress, self.current_prefix) {
+			let stored_payload_size = if self.dictionary.is_some() { 1 } else { self.payload_size };
+			let entry_size = self.table.depth().entry_size(self.key_bytes, stored_payload_size);
+			let entries = self.table.entries();
+			callback(&BuildProgress {
+				entries,
+				bytes: self.header_size + entries * entry_size as u64,
+				prefix,
+				total: None,
+			});
+		}
 	}
 
 	/// Add entry to database (must be added in order)
-	pub fn add_entry(&mut self, key: &[u8], payload: &[u8]) -> io::Result<()> {
+	pub fn add_entry(&mut self, key: &[u8], payload: &[u8]) -> Result<(), BuilderAddError> {
 		assert_eq!(key.len(), self.key_bytes as usize);
 		assert_eq!(payload.len(), self.payload_size as usize);
 		self.table.write_key(&mut self.database, key)?;
-		self.database.write_all(payload)?;
+		if let Some(dictionary) = &mut self.dictionary {
+			let index = dictionary
+				.index(key, payload)
+				.ok_or(BuilderAddError::DictionaryFull { line_hint: self.table.entries() })?;
+			self.database.write_all(&[index])?;
+		} else if self.payload_encryption.is_enabled() {
+			self.payload_buf.clear();
+			self.payload_buf.extend_from_slice(payload);
+			self.payload_encryption.apply(key, &mut self.payload_buf);
+			self.database.write_all(&self.payload_buf)?;
+		} else {
+			self.database.write_all(payload)?;
+		}
+		if let Some(bucket_tables) = &mut self.bucket_tables {
+			bucket_tables.add(key);
+		}
+		if self.progress.is_some() {
+			let prefix = self.table.depth().prefix(key);
+			if self.current_prefix != Some(prefix) {
+				self.current_prefix = Some(prefix);
+				self.report_progress();
+			}
+		}
 		Ok(())
 	}
 
-	/// Write index table for database
+	/// Add entries from raw `key || payload` records (must be added in order)
+	pub fn add_raw_records<R: io::Read>(&mut self, input: R) -> Result<u64, BuilderAddError> {
+		let key_bytes = self.key_bytes as usize;
+		let mut input = io::BufReader::with_capacity(RAW_INPUT_BUFFER, input);
+		let mut record = vec![0u8; key_bytes + self.payload_size as usize];
+		let mut records = 0;
+		while !io::BufRead::fill_buf(&mut input)?.is_empty() {
+			if let Err(e) = io::Read::read_exact(&mut input, &mut record) {
+				if e.kind() == io::ErrorKind::UnexpectedEof {
+					return Err(BuilderAddError::TruncatedRecord { record: records + 1 });
+				}
+				return Err(e.into());
+			}
+			let (key, payload) = record.split_at(key_bytes);
+			self.add_entry(key, payload)?;
+			records += 1;
+		}
+		Ok(records)
+	}
+
+	/// Write payload dictionaries or bucket tables, index table (and checksum) for database
 	pub fn finish(mut self) -> io::Result<()> {
-		self.table.close(&mut self.database)?;
+		self.report_progress();
+		if let Some(dictionary) = &self.dictionary {
+			self.table.finish_entries(&mut self.database)?;
+			let start = self.database.stream_position()?;
+			dictionary.write(&mut self.database, start, self.table_encoding)?;
+		}
+		if let Some(bucket_tables) = self.bucket_tables.take() {
+			self.table.finish_entries(&mut self.database)?;
+			let start = self.database.stream_position()?;
+			bucket_tables.write(&mut self.database, start, self.table_encoding)?;
+		}
+		self.table.close(&mut self.database, self.table_encoding)?;
+		let database = self.database.finish()?;
+		if let Some(on_finish) = self.on_finish {
+			on_finish(database)?;
+		}
 		Ok(())
 	}
 }
 
+impl Builder<AtomicFile> {
+	/// Create new builder writing to a temporary file, which is renamed to
+	/// its target path when finishing the build
+	pub fn create_atomic(
+		database: AtomicFile,
+		key_type: &KeyType,
+		key_bytes: u8,
+		description: &str,
+		payload_size: u8,
+		depth: Depth,
+		options: &IndexOptions,
+	) -> Result<Self, BuilderCreateError> {
+		let mut builder =
+			Self::create(database, key_type, key_bytes, description, payload_size, depth, options)?;
+		builder.on_finish = Some(AtomicFile::commit);
+		Ok(builder)
+	}
+}
+
 /// Builder with generic types for fixed-size key and data
 pub struct TypedBuilder<D, P, W> {
 	builder: Builder<W>,
@@ -91,25 +337,138 @@ where
 		database: W,
 		description: &str,
 		depth: Depth,
+	) -> Result<Self, BuilderCreateError> {
+		Self::create_with_options(database, description, depth, &IndexOptions::default())
+	}
+
+	/// Create a new builder with header options (e.g. format version)
+	pub fn create_with_options(
+		database: W,
+		description: &str,
+		depth: Depth,
+		options: &IndexOptions,
 	) -> Result<Self, BuilderCreateError> {
 		assert!(P::SIZE < 0x100);
+		let options = &*options.for_payload::<P>();
 		Ok(Self {
-			builder: Builder::create(database, D::KEY_TYPE, description, P::SIZE as u8, depth)?,
+			builder: Builder::create(
+				database,
+				&D::KEY_TYPE,
+				D::SIZE as u8,
+				description,
+				P::SIZE as u8,
+				depth,
+				options,
+			)?,
 			_marker: std::marker::PhantomData,
 		})
 	}
 
+	/// Set callback to report progress
+	///
+	/// The callback is called whenever entries for a new bucket (prefix of the
+	/// table depth) are started, and a final time when finishing the build.
+	pub fn set_progress<F>(&mut self, callback: F)
+	where
+		F: FnMut(&BuildProgress) + Send + 'static,
+	{
+		self.builder.set_progress(Box::new(callback));
+	}
+
 	/// Add entry to database (must be added in order)
-	pub fn add_entry(&mut self, key: &D, payload: &P) -> io::Result<()> {
+	pub fn add_entry(&mut self, key: &D, payload: &P) -> Result<(), BuilderAddError> {
 		self.builder.add_entry(key.data(), payload.data())
 	}
 
+	/// Add entries from raw records (must be ordered by key); returns number of added entries
+	///
+	/// Each record is the key followed by the payload (`D::SIZE + P::SIZE`
+	/// bytes), without any separators; this avoids parsing hex for
+	/// machine-generated input.
+	pub fn add_raw_records<R: io::Read>(&mut self, input: R) -> Result<u64, BuilderAddError> {
+		self.builder.add_raw_records(input)
+	}
+
 	/// Write index table for database
+	///
+	/// Also appends the checksum if enabled in the options (see
+	/// [`TypedIndex::verify_checksum`](super::TypedIndex::verify_checksum)).
 	pub fn finish(self) -> io::Result<()> {
 		self.builder.finish()
 	}
 }
 
+impl<D, P> TypedBuilder<D, P, AtomicFile>
+where
+	D: KeyData,
+	P: PayloadData,
+{
+	/// Create a new builder writing to `path` atomically
+	///
+	/// Entries are written to a temporary file in the same directory; `finish`
+	/// syncs it to disk and renames it to `path` (replacing existing files).
+	/// If the build is aborted (builder dropped) the temporary file is removed.
+	///
+	/// Readers therefore never see an incomplete index at `path`.
+	pub fn create_atomic<Q: AsRef<Path>>(
+		path: Q,
+		description: &str,
+		depth: Depth,
+	) -> Result<Self, BuilderCreateError> {
+		Self::create_atomic_with_options(path, description, depth, &IndexOptions::default())
+	}
+
+	/// Create a new builder with header options writing to `path` atomically
+	pub fn create_atomic_with_options<Q: AsRef<Path>>(
+		path: Q,
+		description: &str,
+		depth: Depth,
+		options: &IndexOptions,
+	) -> Result<Self, BuilderCreateError> {
+		Self::create_with_atomic_file(AtomicFile::create(path)?, description, depth, options)
+	}
+
+	/// Create a new builder with header options writing to an [`AtomicFile`]
+	///
+	/// E.g. to lock the target path while building with [`AtomicFile::create_locked`].
+	pub fn create_with_atomic_file(
+		database: AtomicFile,
+		description: &str,
+		depth: Depth,
+		options: &IndexOptions,
+	) -> Result<Self, BuilderCreateError> {
+		assert!(P::SIZE < 0x100);
+		let options = &*options.for_payload::<P>();
+		Ok(Self {
+			builder: Builder::create_atomic(
+				database,
+				&D::KEY_TYPE,
+				D::SIZE as u8,
+				description,
+				P::SIZE as u8,
+				depth,
+				options,
+			)?,
+			_marker: std::marker::PhantomData,
+		})
+	}
+}
+
+#[cfg(feature = "signing")]
+impl<D, P> TypedBuilder<D, P, AtomicFile>
+where
+	D: KeyData,
+	P: PayloadData,
+{
+	/// Write index table, rename file to target path and create a detached
+	/// signature for it (see [`sign_index`](super::sign_index))
+	pub fn finish_signed(self, key: &super::SigningKey) -> io::Result<super::Signature> {
+		let path = self.builder.database.get_ref().target_path().to_path_buf();
+		self.builder.finish()?;
+		super::sign_index(io::BufReader::new(std::fs::File::open(path)?), key)
+	}
+}
+
 impl<D, W> TypedBuilder<D, NoPayload, W>
 where
 	D: KeyData + std::str::FromStr,
@@ -122,12 +481,29 @@ where
 	///
 	/// we ignore the password count (empty payload to builder)
 	pub fn add_entry_from_hibp_line(&mut self, line: &str) -> anyhow::Result<()> {
-		if let Some(colon) = line.find(':') {
-			let hash =
-				line[..colon].parse::<D>().context("Failed to parse hash from HIBP source line")?;
+		if let Some(hash) = parse_hibp_line::<D>(line)? {
 			self.add_entry(&hash, &NoPayload).context("Failed to add hash to index")?;
-		} else if !line.is_empty() {
-			anyhow::bail!("Invalid HIBP source line: {:?}", line);
+		}
+		Ok(())
+	}
+}
+
+impl<D, W> TypedBuilder<D, Count32, W>
+where
+	D: KeyData + std::str::FromStr,
+	<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+	W: io::Write + io::Seek,
+{
+	/// Add entry from HIBP file line
+	///
+	/// Each line contains the hash and the password count delimited by a colon (`:`).
+	///
+	/// The password count is stored as payload (counts above `u32::MAX` are stored as `u32::MAX`);
+	/// format v1 indexes are marked with [`IndexFlags::COUNTS`].
+	pub fn add_entry_from_hibp_line(&mut self, line: &str) -> anyhow::Result<()> {
+		if let Some((hash, count)) = parse_hibp_line_with_count::<D>(line)? {
+			self.add_entry(&hash, &Count32::saturating_from(count))
+				.context("Failed to add hash to index")?;
 		}
 		Ok(())
 	}
@@ -0,0 +1,109 @@
+use sha2::{Digest, Sha256};
+use std::io;
+
+use super::format::CHECKSUM_SIZE;
+
+/// SHA-256 of the first `len` bytes of `reader`
+pub(super) fn hash_prefix<R: io::Read>(reader: R, len: u64) -> io::Result<[u8; CHECKSUM_SIZE]> {
+	let mut hasher = Sha256::new();
+	let copied = io::copy(&mut reader.take(len), &mut hasher)?;
+	if copied != len {
+		return Err(io::ErrorKind::UnexpectedEof.into());
+	}
+	Ok(hasher.finalize().into())
+}
+
+/// Writer hashing all written data (if enabled)
+///
+/// Only supports querying the current position (`stream_position`), as
+/// the data must be written sequentially.
+pub(super) struct ChecksumWriter<W> {
+	inner: W,
+	hasher: Option<Sha256>,
+}
+
+impl<W: io::Write> ChecksumWriter<W> {
+	pub(super) fn new(inner: W, enabled: bool) -> Self {
+		Self { inner, hasher: enabled.then(Sha256::new) }
+	}
+
+	#[cfg(feature = "signing")]
+	pub(super) fn get_ref(&self) -> &W {
+		&self.inner
+	}
+
+	/// Append checksum of all written data (if enabled) and return inner writer
+	pub(super) fn finish(mut self) -> io::Result<W> {
+		if let Some(hasher) = self.hasher.take() {
+			self.inner.write_all(&hasher.finalize())?;
+		}
+		self.inner.flush()?;
+		Ok(self.inner)
+	}
+}
+
+impl<W: io::Write> io::Write for ChecksumWriter<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let written = self.inner.write(buf)?;
+		if let Some(hasher) = &mut self.hasher {
+			hasher.update(&buf[..written]);
+		}
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()
+	}
+}
+
+impl<W: io::Seek> io::Seek for ChecksumWriter<W> {
+	fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+		match pos {
+			io::SeekFrom::Current(0) => self.inner.stream_position(),
+			_ => Err(io::Error::new(io::ErrorKind::Unsupported, "can't seek in index builder")),
+		}
+	}
+}
+
+/// Reader hashing all read data except the last `CHECKSUM_SIZE` bytes
+///
+/// The last bytes of the input are held back until more data arrives, so
+/// once the input was read to the end [`ChecksumReader::finish`] returns the
+/// hash of the data before the trailing checksum and the trailing bytes.
+pub(super) struct ChecksumReader<R> {
+	inner: R,
+	hasher: Sha256,
+	// last (not yet hashed) bytes read so far
+	tail: Vec<u8>,
+}
+
+impl<R: io::Read> ChecksumReader<R> {
+	pub(super) fn new(inner: R) -> Self {
+		Self { inner, hasher: Sha256::new(), tail: Vec::with_capacity(2 * CHECKSUM_SIZE) }
+	}
+
+	/// Hash of all data except the last `CHECKSUM_SIZE` bytes, and those bytes
+	///
+	/// If less than `CHECKSUM_SIZE` bytes were read in total the trailing
+	/// bytes are shorter.
+	pub(super) fn finish(self) -> ([u8; CHECKSUM_SIZE], Vec<u8>) {
+		(self.hasher.finalize().into(), self.tail)
+	}
+}
+
+impl<R: io::Read> io::Read for ChecksumReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+		let data = &buf[..read];
+		// hash everything but the last CHECKSUM_SIZE bytes of tail + data
+		let total = self.tail.len() + data.len();
+		let hash_len = total.saturating_sub(CHECKSUM_SIZE);
+		let from_tail = std::cmp::min(hash_len, self.tail.len());
+		self.hasher.update(&self.tail[..from_tail]);
+		self.tail.drain(..from_tail);
+		let from_data = hash_len - from_tail;
+		self.hasher.update(&data[..from_data]);
+		self.tail.extend_from_slice(&data[from_data..]);
+		Ok(read)
+	}
+}
@@ -24,7 +24,7 @@ pub struct Depth(u8);
 pub(super) type BucketIndexInner = u32;
 
 impl Depth {
-	const KEY_BYTES: usize = std::mem::size_of::<BucketIndexInner>();
+	const KEY_BYTES: usize = core::mem::size_of::<BucketIndexInner>();
 	const KEY_BITS: usize = 8 * Self::KEY_BYTES;
 
 	// * must be less than bit width of BucketIndex!
@@ -39,6 +39,8 @@ impl Depth {
 	pub const DEPTH20: Self = Self(20);
 	/// Depth of 16 bits (always valid)
 	pub const DEPTH16: Self = Self(16);
+	/// Largest supported depth
+	pub(super) const MAX: Self = Self(Self::TABLE_MAX_DEPTH);
 
 	/// Create a new depth; returns `None` if depth is too large.
 	pub fn new(depth: u8) -> Option<Self> {
@@ -57,16 +59,17 @@ impl Depth {
 		self.0
 	}
 
-	pub(super) fn valid_key_size(&self, key_bytes: u8) -> bool {
+	pub(super) fn valid_entry_layout(&self, key_bytes: u8, payload_bytes: u8) -> bool {
 		if key_bytes == 0 {
 			return false;
 		}
-		if (self.0 / 8) + 1 > key_bytes {
-			// expect at least one byte after prefix
-			// implementation depends on this (design does not, but we expect long keys anyway)
+		if self.0 as usize > 8 * key_bytes as usize {
+			// prefix must fit into the key
 			return false;
 		}
-		true
+		// the stored key suffix might be empty (key fully covered by prefix),
+		// but entries still need to take up space to be counted
+		self.entry_size(key_bytes, payload_bytes) > 0
 	}
 
 	pub(super) fn table_entries(self) -> usize {
@@ -87,7 +90,7 @@ impl Depth {
 		self.prefix(key).index()
 	}
 
-	pub(super) fn prepare_key(self, key: &[u8]) -> super::KeySuffix {
+	pub(super) fn prepare_key(self, key: &[u8]) -> super::KeySuffix<'_> {
 		super::KeySuffix::new(self, key)
 	}
 
@@ -0,0 +1,76 @@
+use std::io;
+
+use super::{format::TableEncoding, table::Table, BucketIndex, Depth};
+
+/// Maximum number of distinct payloads per bucket (entries store a `u8` index)
+pub(super) const DICTIONARY_LIMIT: u64 = 0x100;
+
+/// Collects the distinct payloads of each bucket
+///
+/// See [`IndexFlags::PAYLOAD_DICTIONARY`](super::IndexFlags::PAYLOAD_DICTIONARY);
+/// the dictionaries are kept in memory until the build is finished.
+pub(super) struct DictionaryBuilder {
+	depth: Depth,
+	payload_size: usize,
+	// number of dictionary entries in each bucket
+	counts: Vec<u64>,
+	// dictionaries of all buckets
+	values: Vec<u8>,
+	// bucket of last added payload and start of its dictionary in `values`
+	current: Option<(BucketIndex, usize)>,
+}
+
+impl DictionaryBuilder {
+	pub(super) fn new(depth: Depth, payload_size: u8) -> Self {
+		Self {
+			depth,
+			payload_size: payload_size as usize,
+			counts: vec![0; depth.table_entries() - 1],
+			values: Vec::new(),
+			current: None,
+		}
+	}
+
+	/// Index of payload in the dictionary of the bucket of `key`; adds the
+	/// payload if needed
+	///
+	/// Keys must be passed in order. Returns `None` if the dictionary is full.
+	pub(super) fn index(&mut self, key: &[u8], payload: &[u8]) -> Option<u8> {
+		debug_assert_eq!(payload.len(), self.payload_size);
+		if self.payload_size == 0 {
+			// empty payloads don't need dictionary entries
+			return Some(0);
+		}
+		let bucket = self.depth.index(key);
+		let start = match self.current {
+			Some((current, start)) if current == bucket => start,
+			_ => {
+				self.current = Some((bucket, self.values.len()));
+				self.values.len()
+			},
+		};
+		let dictionary = &self.values[start..];
+		if let Some(pos) = dictionary.chunks_exact(self.payload_size).position(|v| v == payload) {
+			return Some(pos as u8);
+		}
+		let count = &mut self.counts[bucket.entry()];
+		if *count == DICTIONARY_LIMIT {
+			return None;
+		}
+		self.values.extend_from_slice(payload);
+		*count += 1;
+		Some((*count - 1) as u8)
+	}
+
+	/// Write dictionaries (starting at file offset `start`) and their table
+	pub(super) fn write<W: io::Write>(
+		&self,
+		database: &mut W,
+		start: u64,
+		encoding: TableEncoding,
+	) -> io::Result<()> {
+		database.write_all(&self.values)?;
+		let table = Table::from_bucket_counts(self.depth, start, self.payload_size, &self.counts);
+		table.write(database, encoding)
+	}
+}
@@ -0,0 +1,84 @@
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::XChaCha20;
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+/// Size of the random salt stored in each index (see [`Metadata::TAG_PAYLOAD_KEY_SALT`](super::Metadata::TAG_PAYLOAD_KEY_SALT))
+pub(super) const SALT_SIZE: usize = 16;
+
+/// Key to encrypt payload of index entries
+///
+/// Each index gets its own subkey, derived with HKDF-SHA256 from this key
+/// and a random salt stored in the index metadata, so indexes built with
+/// the same key don't share keystreams. Payloads are encrypted with
+/// XChaCha20 using the subkey; the nonce is derived from the (unique) entry
+/// key, so the payload size doesn't change. Keys are not encrypted (they
+/// are needed for lookups), and payloads are not authenticated (use
+/// checksums or signatures to detect modified files).
+#[derive(Clone)]
+pub struct PayloadKey {
+	key: [u8; 32],
+}
+
+impl PayloadKey {
+	/// Use given 256-bit key
+	pub fn new(key: [u8; 32]) -> Self {
+		Self { key }
+	}
+
+	/// Random salt for a new index
+	pub(super) fn new_salt() -> std::io::Result<[u8; SALT_SIZE]> {
+		let mut salt = [0u8; SALT_SIZE];
+		getrandom::getrandom(&mut salt)?;
+		Ok(salt)
+	}
+
+	/// Subkey of the index with given salt
+	pub(super) fn index_key(&self, salt: &[u8]) -> IndexPayloadKey {
+		let mut key = [0u8; 32];
+		Hkdf::<Sha256>::new(Some(salt), &self.key)
+			.expand(b"hibp-index payload key", &mut key)
+			.expect("valid output length");
+		IndexPayloadKey { key }
+	}
+}
+
+impl core::fmt::Debug for PayloadKey {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("PayloadKey").finish_non_exhaustive()
+	}
+}
+
+/// Subkey to encrypt the payloads of a single index (see [`PayloadKey`])
+#[derive(Clone)]
+pub(super) struct IndexPayloadKey {
+	key: [u8; 32],
+}
+
+impl IndexPayloadKey {
+	fn cipher(&self, nonce_source: &[u8]) -> XChaCha20 {
+		let mut nonce = [0u8; 24];
+		let len = core::cmp::min(nonce.len(), nonce_source.len());
+		nonce[..len].copy_from_slice(&nonce_source[..len]);
+		XChaCha20::new(&self.key.into(), &nonce.into())
+	}
+
+	/// Encrypt or decrypt (a prefix of) the payload of the entry with given key
+	pub(super) fn apply(&self, entry_key: &[u8], payload: &mut [u8]) {
+		self.cipher(entry_key).apply_keystream(payload);
+	}
+
+	/// Value stored in header to detect wrong keys
+	pub(super) fn check_value(&self) -> [u8; 8] {
+		// entry keys starting with 24 0xff bytes are practically impossible
+		let mut value = [0u8; 8];
+		self.cipher(&[0xff; 24]).apply_keystream(&mut value);
+		value
+	}
+}
+
+impl core::fmt::Debug for IndexPayloadKey {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		f.debug_struct("IndexPayloadKey").finish_non_exhaustive()
+	}
+}
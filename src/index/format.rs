@@ -0,0 +1,568 @@
+//! Index file format versions and header extensions
+//!
+//! Format v0 header:
+//! - magic line `hash-index-v0`
+//! - key type line
+//! - description line
+//! - key size (`u8`) and payload size (`u8`)
+//!
+//! Format v1 (magic line `hash-index-v1`) appends:
+//! - flags (`u32`, big endian; see [`IndexFlags`])
+//! - if [`IndexFlags::METADATA`] is set: metadata section size (`u16`, big
+//!   endian) followed by metadata entries (tag `u8`, value size `u16` big
+//!   endian, value)
+//!
+//! If [`IndexFlags::LEADING_TABLE`] is set the header is followed by the
+//! size of the compressed table (`u32`, big endian) and the table; its
+//! offsets are relative to the end of the table.
+//!
+//! If [`IndexFlags::PAYLOAD_DICTIONARY`] is set entries store a `u8` index
+//! into the dictionary of their bucket instead of the payload. The
+//! dictionaries (distinct payloads of each bucket, in order of first use)
+//! follow the entries, and are located by a second table (same format as
+//! the main table) stored with its size before the main table.
+//!
+//! If [`IndexFlags::BUCKET_TABLES`] is set large buckets have a bucket
+//! table: the number of additional prefix bits (`u8`), followed by the
+//! index of the first entry (`u32`, big endian; relative to the bucket)
+//! for each value of these bits. The bucket tables follow the entries, and
+//! are located by a second table (same format as the main table; empty
+//! ranges for buckets without bucket table) stored with its size before
+//! the main table.
+//!
+//! If [`IndexFlags::CHECKSUMS`] is set the file ends with the SHA-256 of all
+//! preceding bytes (after the table size).
+//!
+//! Readers must reject files with unknown flags; unknown metadata tags are
+//! ignored.
+
+use alloc::vec::Vec;
+use chrono::TimeZone;
+
+/// Magic line of format v0
+pub const INDEX_V0_MAGIC: &str = "hash-index-v0";
+/// Magic line of format v1
+pub const INDEX_V1_MAGIC: &str = "hash-index-v1";
+/// Maximum size of the index header
+pub const INDEX_HEADER_LIMIT: u64 = 4096;
+/// Size of the checksum at the end of an index with [`IndexFlags::CHECKSUMS`]
+pub(super) const CHECKSUM_SIZE: usize = 32;
+
+/// Version of the index file format
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum FormatVersion {
+	/// Original format; readable by all versions
+	#[default]
+	V0,
+	/// Format with flags and metadata
+	V1,
+}
+
+impl FormatVersion {
+	/// Magic header line identifying the version
+	pub fn magic(self) -> &'static str {
+		match self {
+			Self::V0 => INDEX_V0_MAGIC,
+			Self::V1 => INDEX_V1_MAGIC,
+		}
+	}
+
+	pub(super) fn from_magic(magic: &str) -> Option<Self> {
+		match magic {
+			INDEX_V0_MAGIC => Some(Self::V0),
+			INDEX_V1_MAGIC => Some(Self::V1),
+			_ => None,
+		}
+	}
+}
+
+/// Optional features used by an index file (format v1)
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct IndexFlags(u32);
+
+impl IndexFlags {
+	/// Index ends with SHA-256 checksum of the whole file
+	pub const CHECKSUMS: Self = Self(1 << 0);
+	/// Table is compressed with zstd instead of deflate (requires feature `zstd`)
+	pub const COMPRESSION: Self = Self(1 << 1);
+	/// Payload contains (password) counts
+	pub const COUNTS: Self = Self(1 << 2);
+	/// Header contains metadata section
+	pub const METADATA: Self = Self(1 << 3);
+	/// Payloads are encrypted (see `PayloadKey`, requires feature `encryption`)
+	pub const ENCRYPTED_PAYLOAD: Self = Self(1 << 4);
+	/// Table is stored after the header instead of at the end (streamable)
+	pub const LEADING_TABLE: Self = Self(1 << 5);
+	/// Entries store an index into a per-bucket dictionary of payloads
+	///
+	/// Not supported together with [`IndexFlags::LEADING_TABLE`].
+	pub const PAYLOAD_DICTIONARY: Self = Self(1 << 6);
+	/// Large buckets have a table locating entries by additional prefix bits
+	///
+	/// Not supported together with [`IndexFlags::LEADING_TABLE`] or
+	/// [`IndexFlags::PAYLOAD_DICTIONARY`].
+	pub const BUCKET_TABLES: Self = Self(1 << 7);
+
+	/// Flags this implementation can read
+	pub const SUPPORTED: Self = Self(
+		Self::CHECKSUMS.0
+			| Self::COUNTS.0
+			| Self::METADATA.0
+			| Self::LEADING_TABLE.0
+			| Self::PAYLOAD_DICTIONARY.0
+			| Self::BUCKET_TABLES.0
+			| if cfg!(feature = "zstd") { Self::COMPRESSION.0 } else { 0 }
+			| if cfg!(feature = "encryption") { Self::ENCRYPTED_PAYLOAD.0 } else { 0 },
+	);
+
+	const NAMES: [(Self, &'static str); 8] = [
+		(Self::CHECKSUMS, "checksums"),
+		(Self::COMPRESSION, "compression"),
+		(Self::COUNTS, "counts"),
+		(Self::METADATA, "metadata"),
+		(Self::ENCRYPTED_PAYLOAD, "encrypted-payload"),
+		(Self::LEADING_TABLE, "leading-table"),
+		(Self::PAYLOAD_DICTIONARY, "payload-dictionary"),
+		(Self::BUCKET_TABLES, "bucket-tables"),
+	];
+
+	/// No flags set
+	pub const fn empty() -> Self {
+		Self(0)
+	}
+
+	/// Flags from raw bits (including unknown bits)
+	pub const fn from_bits(bits: u32) -> Self {
+		Self(bits)
+	}
+
+	/// Raw bits
+	pub const fn bits(self) -> u32 {
+		self.0
+	}
+
+	/// Whether all flags in `other` are set
+	pub const fn contains(self, other: Self) -> bool {
+		self.0 & other.0 == other.0
+	}
+
+	/// Whether no flag is set
+	pub const fn is_empty(self) -> bool {
+		self.0 == 0
+	}
+
+	/// Set all flags in `other`
+	pub fn insert(&mut self, other: Self) {
+		self.0 |= other.0;
+	}
+
+	/// Flags not supported by this implementation
+	pub const fn unsupported(self) -> Self {
+		Self(self.0 & !Self::SUPPORTED.0)
+	}
+}
+
+impl core::ops::BitOr for IndexFlags {
+	type Output = Self;
+
+	fn bitor(self, rhs: Self) -> Self {
+		Self(self.0 | rhs.0)
+	}
+}
+
+impl core::fmt::Display for IndexFlags {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+		if self.is_empty() {
+			return f.write_str("none");
+		}
+		let mut remaining = self.0;
+		let mut first = true;
+		for (flag, name) in Self::NAMES {
+			if self.contains(flag) {
+				if !first {
+					f.write_str(",")?;
+				}
+				first = false;
+				f.write_str(name)?;
+				remaining &= !flag.0;
+			}
+		}
+		if remaining != 0 {
+			if !first {
+				f.write_str(",")?;
+			}
+			write!(f, "{:#x}", remaining)?;
+		}
+		Ok(())
+	}
+}
+
+/// Compression of the table
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum TableCompression {
+	/// deflate; readable by all versions
+	#[default]
+	Deflate,
+	/// zstd; compresses deep tables better and decompresses faster (see [`IndexFlags::COMPRESSION`])
+	#[cfg(feature = "zstd")]
+	Zstd,
+}
+
+impl TableCompression {
+	/// Compression of table in index with given flags
+	#[cfg(feature = "std")]
+	pub(super) fn from_flags(flags: IndexFlags) -> Self {
+		#[cfg(feature = "zstd")]
+		if flags.contains(IndexFlags::COMPRESSION) {
+			return Self::Zstd;
+		}
+		debug_assert!(!flags.contains(IndexFlags::COMPRESSION), "unsupported flag");
+		Self::Deflate
+	}
+
+	/// Whether `level` is a valid compression level
+	pub fn valid_level(self, level: u32) -> bool {
+		match self {
+			Self::Deflate => level <= 9,
+			#[cfg(feature = "zstd")]
+			Self::Zstd => zstd::compression_level_range().contains(&(level as i32)),
+		}
+	}
+}
+
+/// Compression and compression level to write table with
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct TableEncoding {
+	pub(super) compression: TableCompression,
+	/// `None` for default level of compression
+	pub(super) level: Option<u32>,
+}
+
+/// Format version and features of an opened index
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Capabilities {
+	version: FormatVersion,
+	flags: IndexFlags,
+}
+
+impl Capabilities {
+	pub(super) fn new(version: FormatVersion, flags: IndexFlags) -> Self {
+		Self { version, flags }
+	}
+
+	/// Format version of index file
+	pub fn version(&self) -> FormatVersion {
+		self.version
+	}
+
+	/// Flags of index file (always empty for v0)
+	pub fn flags(&self) -> IndexFlags {
+		self.flags
+	}
+}
+
+/// Metadata entries stored in the header (format v1)
+///
+/// Each entry has a tag (`u8`) and a binary value; entries are stored in
+/// order of their tag.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Metadata {
+	entries: Vec<(u8, Vec<u8>)>,
+}
+
+impl Metadata {
+	/// Tag of modification time of the data (seconds since epoch, `i64` big endian)
+	pub const TAG_MTIME: u8 = 1;
+	/// Tag of source of the data (UTF-8 string)
+	pub const TAG_SOURCE: u8 = 2;
+	/// Tag of check value to detect wrong payload keys (8 bytes)
+	pub const TAG_PAYLOAD_KEY_CHECK: u8 = 3;
+	/// Tag of random salt to derive the payload subkey of the index with (16 bytes)
+	pub const TAG_PAYLOAD_KEY_SALT: u8 = 4;
+
+	/// Whether metadata contains no entries
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Value of entry with given tag
+	pub fn get(&self, tag: u8) -> Option<&[u8]> {
+		let pos = self.entries.binary_search_by_key(&tag, |(t, _)| *t).ok()?;
+		Some(&self.entries[pos].1)
+	}
+
+	/// Set value of entry with given tag (replacing existing value)
+	///
+	/// Panics if value is longer than 65535 bytes.
+	pub fn set(&mut self, tag: u8, value: Vec<u8>) {
+		assert!(value.len() <= u16::MAX as usize, "metadata value too long");
+		match self.entries.binary_search_by_key(&tag, |(t, _)| *t) {
+			Ok(pos) => self.entries[pos].1 = value,
+			Err(pos) => self.entries.insert(pos, (tag, value)),
+		}
+	}
+
+	/// Remove entry with given tag
+	pub fn remove(&mut self, tag: u8) -> Option<Vec<u8>> {
+		let pos = self.entries.binary_search_by_key(&tag, |(t, _)| *t).ok()?;
+		Some(self.entries.remove(pos).1)
+	}
+
+	/// Iterate over all entries (ordered by tag)
+	pub fn iter(&self) -> impl '_ + Iterator<Item = (u8, &[u8])> {
+		self.entries.iter().map(|(tag, value)| (*tag, value.as_slice()))
+	}
+
+	/// Modification time of the data
+	///
+	/// `None` if not present or invalid.
+	pub fn mtime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+		let value: [u8; 8] = self.get(Self::TAG_MTIME)?.try_into().ok()?;
+		chrono::Utc.timestamp_opt(i64::from_be_bytes(value), 0).single()
+	}
+
+	/// Set modification time of the data
+	pub fn set_mtime(&mut self, mtime: chrono::DateTime<chrono::Utc>) {
+		self.set(Self::TAG_MTIME, mtime.timestamp().to_be_bytes().to_vec());
+	}
+
+	/// Source of the data (e.g. name of the input file)
+	///
+	/// `None` if not present or invalid.
+	pub fn source(&self) -> Option<&str> {
+		core::str::from_utf8(self.get(Self::TAG_SOURCE)?).ok()
+	}
+
+	/// Set source of the data
+	///
+	/// Panics if source is longer than 65535 bytes.
+	pub fn set_source(&mut self, source: &str) {
+		self.set(Self::TAG_SOURCE, source.as_bytes().to_vec());
+	}
+
+	/// Whether values of all known tags are valid
+	#[cfg(feature = "std")]
+	pub(super) fn known_tags_valid(&self) -> bool {
+		(self.get(Self::TAG_MTIME).is_none() || self.mtime().is_some())
+			&& (self.get(Self::TAG_SOURCE).is_none() || self.source().is_some())
+	}
+
+	/// Append serialized entries (without section size)
+	#[cfg(feature = "std")]
+	pub(super) fn serialize(&self, buf: &mut Vec<u8>) {
+		for (tag, value) in &self.entries {
+			buf.push(*tag);
+			buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+			buf.extend_from_slice(value);
+		}
+	}
+
+	/// Parse serialized entries; returns `None` on invalid data
+	pub(super) fn parse(mut data: &[u8]) -> Option<Self> {
+		let mut entries: Vec<(u8, Vec<u8>)> = Vec::new();
+		while !data.is_empty() {
+			if data.len() < 3 {
+				return None;
+			}
+			let tag = data[0];
+			let len = u16::from_be_bytes([data[1], data[2]]) as usize;
+			if len > data.len() - 3 {
+				return None;
+			}
+			let (value, rest) = data[3..].split_at(len);
+			data = rest;
+			if let Some((last_tag, _)) = entries.last() {
+				if *last_tag >= tag {
+					return None;
+				}
+			}
+			entries.push((tag, value.to_vec()));
+		}
+		Some(Self { entries })
+	}
+}
+
+/// Options for the header of a new index
+#[derive(Clone, Debug, Default)]
+pub struct IndexOptions {
+	/// Format version to write
+	///
+	/// Defaults to v0, which older readers can open; all other options
+	/// require v1.
+	pub version: FormatVersion,
+	/// Metadata to store in header
+	pub metadata: Metadata,
+	/// Key to encrypt payloads with
+	#[cfg(feature = "encryption")]
+	pub payload_key: Option<super::PayloadKey>,
+	/// Append checksum of the whole file (see [`IndexFlags::CHECKSUMS`])
+	pub checksum: bool,
+	/// Mark payloads as (password) counts (see [`IndexFlags::COUNTS`])
+	pub counts: bool,
+	/// Compression of the table
+	pub table_compression: TableCompression,
+	/// Compression level of the table (default level if not set)
+	///
+	/// Deflate supports levels 0 to 9 (default 6), zstd 1 to 22 (default
+	/// 3). Higher levels result in smaller tables (mostly relevant for deep
+	/// tables), but take longer to build.
+	pub table_compression_level: Option<u32>,
+	/// Store the table after the header (see [`IndexFlags::LEADING_TABLE`])
+	///
+	/// Only supported by the two-pass builder, which knows the table before
+	/// writing the entries.
+	pub leading_table: bool,
+	/// Store payloads in per-bucket dictionaries (see [`IndexFlags::PAYLOAD_DICTIONARY`])
+	///
+	/// Reduces the file size if payloads are highly repetitive (e.g. small
+	/// counts); each bucket can contain at most 256 distinct payloads. Only
+	/// supported by the (seekable) builder without payload encryption.
+	pub payload_dictionary: bool,
+	/// Number of additional prefix bits of bucket tables (see [`IndexFlags::BUCKET_TABLES`])
+	///
+	/// Zero (the default) disables bucket tables; otherwise 1 to 8 bits.
+	/// Buckets with more entries than values of these bits get a table that
+	/// lookups use to skip most of the bucket. Useful for deep indexes of
+	/// large corpora (thousands of entries per bucket). Only supported by the
+	/// (seekable) builder without payload dictionary.
+	pub bucket_table_bits: u8,
+}
+
+impl IndexOptions {
+	/// Default options for format v1
+	pub fn v1() -> Self {
+		Self { version: FormatVersion::V1, ..Self::default() }
+	}
+
+	/// Options to create a new index with
+	///
+	/// With payload encryption a fresh random salt is stored in the metadata,
+	/// so each index gets its own payload subkey.
+	#[cfg(feature = "std")]
+	pub(super) fn for_new_index(&self) -> std::io::Result<alloc::borrow::Cow<'_, Self>> {
+		#[cfg(feature = "encryption")]
+		if self.payload_key.is_some() {
+			let mut options = self.clone();
+			let salt = super::PayloadKey::new_salt()?;
+			options.metadata.set(Metadata::TAG_PAYLOAD_KEY_SALT, salt.to_vec());
+			return Ok(alloc::borrow::Cow::Owned(options));
+		}
+		Ok(alloc::borrow::Cow::Borrowed(self))
+	}
+
+	/// Options for a builder with payload type `P`
+	///
+	/// Marks indexes with count payloads (see [`PayloadData::COUNTS`](crate::data::PayloadData::COUNTS)) with
+	/// [`IndexFlags::COUNTS`] (format v1 only, v0 has no flags).
+	#[cfg(feature = "std")]
+	pub(super) fn for_payload<P: crate::data::PayloadData>(&self) -> alloc::borrow::Cow<'_, Self> {
+		if P::COUNTS && !self.counts && self.version >= FormatVersion::V1 {
+			return alloc::borrow::Cow::Owned(Self { counts: true, ..self.clone() });
+		}
+		alloc::borrow::Cow::Borrowed(self)
+	}
+
+	/// Payload subkey of the index (salt set by [`IndexOptions::for_new_index`])
+	#[cfg(feature = "encryption")]
+	fn index_payload_key(&self) -> Option<super::encryption::IndexPayloadKey> {
+		let salt = self.metadata.get(Metadata::TAG_PAYLOAD_KEY_SALT);
+		Some(self.payload_key.as_ref()?.index_key(salt.expect("payload key salt")))
+	}
+
+	/// Metadata to write, including entries required by other options
+	#[cfg(feature = "std")]
+	pub(super) fn header_metadata(&self) -> alloc::borrow::Cow<'_, Metadata> {
+		#[cfg(feature = "encryption")]
+		if let Some(payload_key) = self.index_payload_key() {
+			let mut metadata = self.metadata.clone();
+			metadata.set(Metadata::TAG_PAYLOAD_KEY_CHECK, payload_key.check_value().to_vec());
+			return alloc::borrow::Cow::Owned(metadata);
+		}
+		alloc::borrow::Cow::Borrowed(&self.metadata)
+	}
+
+	/// Flags resulting from options
+	#[cfg(feature = "std")]
+	pub(super) fn flags(&self) -> IndexFlags {
+		let mut flags = IndexFlags::empty();
+		if self.checksum {
+			flags.insert(IndexFlags::CHECKSUMS);
+		}
+		if self.counts {
+			flags.insert(IndexFlags::COUNTS);
+		}
+		if self.leading_table {
+			flags.insert(IndexFlags::LEADING_TABLE);
+		}
+		if self.payload_dictionary {
+			flags.insert(IndexFlags::PAYLOAD_DICTIONARY);
+		}
+		if self.bucket_table_bits != 0 {
+			flags.insert(IndexFlags::BUCKET_TABLES);
+		}
+		if self.table_compression != TableCompression::Deflate {
+			flags.insert(IndexFlags::COMPRESSION);
+		}
+		if !self.header_metadata().is_empty() {
+			flags.insert(IndexFlags::METADATA);
+		}
+		#[cfg(feature = "encryption")]
+		if self.payload_key.is_some() {
+			flags.insert(IndexFlags::ENCRYPTED_PAYLOAD);
+		}
+		flags
+	}
+
+	/// Compression and level to write table with
+	#[cfg(feature = "std")]
+	pub(super) fn table_encoding(&self) -> TableEncoding {
+		TableEncoding { compression: self.table_compression, level: self.table_compression_level }
+	}
+
+	/// Key to encrypt payloads with (if enabled)
+	#[cfg(feature = "std")]
+	pub(super) fn payload_encryption(&self) -> PayloadEncryption {
+		#[cfg(feature = "encryption")]
+		{
+			PayloadEncryption(self.index_payload_key())
+		}
+		#[cfg(not(feature = "encryption"))]
+		{
+			PayloadEncryption::default()
+		}
+	}
+}
+
+/// Optional payload encryption state of builders and readers
+///
+/// Without feature `encryption` this is empty and does nothing.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub(super) struct PayloadEncryption(
+	#[cfg(feature = "encryption")] pub(super) Option<super::encryption::IndexPayloadKey>,
+);
+
+#[cfg(feature = "std")]
+impl PayloadEncryption {
+	/// Whether payloads need to be encrypted / decrypted
+	pub(super) fn is_enabled(&self) -> bool {
+		#[cfg(feature = "encryption")]
+		{
+			self.0.is_some()
+		}
+		#[cfg(not(feature = "encryption"))]
+		{
+			false
+		}
+	}
+
+	/// Encrypt or decrypt (a prefix of) the payload of the entry with given key
+	#[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+	pub(super) fn apply(&self, entry_key: &[u8], payload: &mut [u8]) {
+		#[cfg(feature = "encryption")]
+		if let Some(payload_key) = &self.0 {
+			payload_key.apply(entry_key, payload);
+		}
+	}
+}
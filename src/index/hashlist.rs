@@ -112,7 +112,7 @@ where
 	/// Open hash list
 	pub fn open(mut reader: R) -> Result<Self, HashListOpenError> {
 		let header = Header::open(&mut reader)?;
-		if header.key_type.as_known() != Some(K::KEY_TYPE) {
+		if header.key_type != K::KEY_TYPE {
 			return Err(HashListOpenError::InvalidKeyLength);
 		}
 		if header.key_size as usize != K::SIZE {
@@ -193,14 +193,7 @@ where
 	) -> Result<Self, HashListCreateError> {
 		assert!(K::SIZE < 256);
 		assert!(P::SIZE < 256);
-		Header::create(
-			&mut writer,
-			description,
-			K::KEY_TYPE.into(),
-			K::SIZE as u8,
-			P::SIZE as u8,
-			mtime,
-		)?;
+		Header::create(&mut writer, description, K::KEY_TYPE, K::SIZE as u8, P::SIZE as u8, mtime)?;
 		writer.write_u8(prefix.bits() as u8)?;
 		let prefix_byte_count = (prefix.bits() as usize + 7) / 8;
 		writer.write_all(&prefix.key().data()[..prefix_byte_count])?;
@@ -1,4 +1,5 @@
-use std::cmp::Ordering;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
 /// We often split a key into a prefix (of "depth" bits) and the remaining suffix
 ///
@@ -8,34 +9,42 @@ use std::cmp::Ordering;
 /// from the original key, and is stored locally.
 ///
 /// Therefore we can't return a slice to the full suffix.
+///
+/// If the prefix covers the full key the suffix is empty.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct KeySuffix<'key> {
 	depth: super::Depth,
-	key_first_byte: u8,
+	key_first_byte: Option<u8>,
 	key_suffix: &'key [u8],
 }
 
 impl<'key> KeySuffix<'key> {
-	pub(super) fn new(depth: super::Depth, key: &'key [u8]) -> Self {
+	/// Suffix of a full key as stored in an index with given depth
+	///
+	/// Panics if the key is shorter than the prefix bytes.
+	pub fn new(depth: super::Depth, key: &'key [u8]) -> Self {
 		let strip_key_prefix = depth.as_u8() as usize / 8;
 		// don't store unncessary bits; i.e. strip of "depth" bits of key
 		// don't shift bits though, so skip full bytes and zero partial bits.
 		// returns first (possibly masked byte) and remaining slice of key bytes.
-		let suffix = &key[strip_key_prefix..];
-		let partial_bits = depth.as_u8() & 0x7;
-		let mask_bits = 0xff >> partial_bits;
-		Self { depth, key_first_byte: suffix[0] & mask_bits, key_suffix: &suffix[1..] }
+		Self::new_from_entry(depth, &key[strip_key_prefix..])
 	}
 
-	pub(super) fn new_from_entry(depth: super::Depth, entry_key: &'key [u8]) -> Self {
+	/// Suffix from the key part of an entry stored in an index with given depth
+	pub fn new_from_entry(depth: super::Depth, entry_key: &'key [u8]) -> Self {
 		let partial_bits = depth.as_u8() & 0x7;
 		let mask_bits = 0xff >> partial_bits;
-		Self { depth, key_first_byte: entry_key[0] & mask_bits, key_suffix: &entry_key[1..] }
+		match entry_key.split_first() {
+			Some((&first, rest)) => {
+				Self { depth, key_first_byte: Some(first & mask_bits), key_suffix: rest }
+			},
+			None => Self { depth, key_first_byte: None, key_suffix: entry_key },
+		}
 	}
 
-	/// First byte of suffix (unused bits of original key cleared)
+	/// First byte of suffix (unused bits of original key cleared); empty if suffix is empty
 	pub fn first_byte(&self) -> &[u8] {
-		std::slice::from_ref(&self.key_first_byte)
+		self.key_first_byte.as_slice()
 	}
 
 	/// Remaining bytes of suffix (without first byte), reference to original key
@@ -44,15 +53,19 @@ impl<'key> KeySuffix<'key> {
 	}
 
 	/// Total length of suffix in bytes (including first possibly partial byte)
-	#[allow(clippy::len_without_is_empty)] // len is always > 0 -> never empty
 	pub fn len(&self) -> usize {
-		self.key_suffix.len() + 1
+		self.key_suffix.len() + self.key_first_byte.is_some() as usize
+	}
+
+	/// Whether suffix is empty (prefix covers the full key)
+	pub fn is_empty(&self) -> bool {
+		self.key_first_byte.is_none()
 	}
 
 	/// Store suffix in continuous memory
 	pub fn to_vec(&self) -> Vec<u8> {
 		let mut buf = Vec::with_capacity(self.len());
-		buf.push(self.key_first_byte);
+		buf.extend_from_slice(self.first_byte());
 		buf.extend_from_slice(self.key_suffix);
 		buf
 	}
@@ -69,8 +82,8 @@ impl<'key> KeySuffix<'key> {
 }
 
 /*
-impl std::fmt::Debug for KeySuffix<'_> {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for KeySuffix<'_> {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		TODO: (if 20 is the prefix length)
 		only show the significant nibbles from the first byte!
 		"<*20>suffixnibbles"
@@ -84,7 +97,7 @@ impl PartialOrd for KeySuffix<'_> {
 		if self.depth != other.depth {
 			return None;
 		}
-		if self.key_suffix.len() != other.key_suffix.len() {
+		if self.len() != other.len() {
 			return None;
 		}
 		Some(
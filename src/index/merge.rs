@@ -0,0 +1,114 @@
+use super::{reindex::copy_options, AtomicFile, TypedBuilder, TypedIndex};
+use crate::{
+	buf_read::ReadAt,
+	data::{KeyData, PayloadData},
+};
+use std::fs;
+use std::path::Path;
+
+/// Counters of a finished [`merge_into`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct MergeStats {
+	/// Entries copied from the base index
+	pub base: u64,
+	/// New entries from the additions
+	pub added: u64,
+	/// Entries of the base index replaced by additions with the same key
+	pub replaced: u64,
+}
+
+/// Add entry from additions (checking their order)
+fn add_entry<D, P, W>(
+	builder: &mut TypedBuilder<D, P, W>,
+	last_addition: &mut Option<D>,
+	key: D,
+	payload: &P,
+) -> anyhow::Result<()>
+where
+	D: KeyData,
+	P: PayloadData,
+	W: std::io::Write + std::io::Seek,
+{
+	if let Some(last) = last_addition {
+		if last.data() >= key.data() {
+			anyhow::bail!("Addition {} out of order (previous addition {})", key.hex(), last.hex());
+		}
+	}
+	builder.add_entry(&key, payload)?;
+	*last_addition = Some(key);
+	Ok(())
+}
+
+/// Write a new index at `dst` with the entries of `base` and the (ordered) `additions`
+///
+/// Base and additions are merged in a single pass, without sorting the
+/// combined entries; additions must be ordered by key (without duplicates).
+/// If a key is present in both the payload of the addition replaces the
+/// payload from `base`. Description, format version, metadata, checksum and
+/// table compression are taken from `base`; the table depth stays the same.
+/// The payload size of `base` must match `P` (larger payloads can't be
+/// copied without truncating them).
+///
+/// The new index is written atomically (see [`AtomicFile`](super::AtomicFile))
+/// and opened after the build.
+pub fn merge_into<D, P, R, I>(
+	base: &TypedIndex<D, P, R>,
+	additions: I,
+	dst: &Path,
+) -> anyhow::Result<(TypedIndex<D, P, fs::File>, MergeStats)>
+where
+	D: KeyData,
+	P: PayloadData,
+	R: ReadAt,
+	I: IntoIterator<Item = (D, P)>,
+{
+	let index = base.index();
+	if index.payload_size() as usize != P::SIZE {
+		anyhow::bail!(
+			"Payload size of base index ({} bytes) doesn't match payload type ({} bytes)",
+			index.payload_size(),
+			P::SIZE
+		);
+	}
+	let options = copy_options(index)?;
+	let mut builder = TypedBuilder::<D, P, _>::create_with_atomic_file(
+		AtomicFile::create_locked(dst)?,
+		index.description(),
+		index.depth(),
+		&options,
+	)?;
+	let mut stats = MergeStats::default();
+	let mut additions = additions.into_iter().peekable();
+	let mut last_addition: Option<D> = None;
+	let mut key = D::default();
+	let mut payload = P::default();
+	index.for_each_entry(|base_key, base_payload| -> anyhow::Result<()> {
+		while let Some((add_key, _)) = additions.peek() {
+			if add_key.data() > base_key {
+				break;
+			}
+			let (add_key, add_payload) = additions.next().expect("peeked");
+			if add_key.data() == base_key {
+				stats.replaced += 1;
+			} else {
+				stats.added += 1;
+			}
+			add_entry(&mut builder, &mut last_addition, add_key, &add_payload)?;
+		}
+		if last_addition.as_ref().is_some_and(|last| last.data() == base_key) {
+			// replaced by addition
+			return Ok(());
+		}
+		key.data_mut().copy_from_slice(base_key);
+		payload.data_mut().copy_from_slice(base_payload);
+		builder.add_entry(&key, &payload)?;
+		stats.base += 1;
+		Ok(())
+	})?;
+	for (add_key, add_payload) in additions {
+		stats.added += 1;
+		add_entry(&mut builder, &mut last_addition, add_key, &add_payload)?;
+	}
+	builder.finish()?;
+	Ok((TypedIndex::open(fs::File::open(dst)?)?, stats))
+}
@@ -8,22 +8,83 @@
 //! keys end at the start of the next prefix (table includes a final offset
 //! for end of all keys).
 
+#[cfg(feature = "std")]
+mod atomic_file;
+#[cfg(feature = "std")]
+mod bucket_table;
+#[cfg(feature = "std")]
 mod builder;
+#[cfg(feature = "std")]
+mod checksum;
 mod depth;
+#[cfg(feature = "std")]
+mod dictionary;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod format;
+#[cfg(feature = "std")]
 mod hashlist;
 mod key_suffix;
+#[cfg(feature = "std")]
+mod merge;
+#[cfg(feature = "std")]
+mod multi;
+#[cfg(feature = "std")]
+mod parallel;
+mod parse;
 mod prefix;
+#[cfg(feature = "std")]
 mod reader;
+#[cfg(feature = "std")]
+mod reindex;
+#[cfg(feature = "std")]
+mod reloading;
+pub mod search;
+#[cfg(feature = "signing")]
+mod signature;
+mod slice;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+pub mod switch;
+#[cfg(feature = "std")]
 mod table;
-mod table_helper;
+#[cfg(feature = "std")]
+mod table_cache;
+#[cfg(feature = "std")]
+mod text;
+#[cfg(feature = "std")]
+mod two_pass;
 
 use self::{depth::BucketIndexInner, prefix::BucketIndex};
 
 pub use self::{
-	builder::TypedBuilder,
 	depth::Depth,
-	hashlist::{TypedListReader, TypedListWriter},
+	format::{Capabilities, FormatVersion, IndexFlags, IndexOptions, Metadata, TableCompression},
 	key_suffix::KeySuffix,
 	prefix::{LimPrefix, LimPrefixRange},
-	reader::TypedIndex,
+	slice::{SliceIndex, TypedSliceIndex},
+};
+
+#[cfg(feature = "std")]
+pub(crate) use self::builder::parse_hibp_line_with_count;
+#[cfg(feature = "std")]
+pub use self::{
+	atomic_file::AtomicFile,
+	builder::{BuildProgress, TypedBuilder},
+	hashlist::{TypedListReader, TypedListWriter},
+	merge::{merge_into, MergeStats},
+	multi::MultiIndex,
+	parallel::build_parallel,
+	reader::{Index, LookupScratch, TypedIndex},
+	reindex::reindex,
+	reloading::ReloadingIndex,
+	stream::{verify_stream, StreamVerification, TypedStreamReader},
+	text::TextIndex,
+	two_pass::{BucketCounts, TypedTwoPassBuilder},
 };
+
+#[cfg(feature = "encryption")]
+pub use self::encryption::PayloadKey;
+#[cfg(feature = "signing")]
+pub use self::signature::{sign_index, verify_index, Signature, SigningKey, VerifyingKey};
@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::Path;
+
+use crate::{
+	buf_read::ReadAt,
+	data::{KeyData, KeyType, KnownKeyType, NT, SHA1, SHA512},
+	errors::{IndexOpenError, MultiLookupError},
+};
+
+use super::Index;
+
+/// Several (untyped) indexes of different key types
+///
+/// Lookups are routed to the index matching the key type; plaintext
+/// passwords are hashed for each loaded index of a known key type.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use hibp_index::data::NT;
+/// use hibp_index::index::MultiIndex;
+///
+/// let indexes = MultiIndex::open(["hibp-sha1.index", "hibp-ntlm.index"])?;
+/// let found = indexes.contains(&NT::hash("password"))?;
+/// let found_in = indexes.lookup_plaintext("password")?.map(|(key_type, _payload)| key_type);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MultiIndex<R = fs::File> {
+	indexes: Vec<Index<R>>,
+}
+
+impl<R> MultiIndex<R> {
+	/// Empty set of indexes
+	pub fn new() -> Self {
+		Self { indexes: Vec::new() }
+	}
+
+	/// Add index; replaces (and returns) index with the same key type
+	pub fn insert(&mut self, index: Index<R>) -> Option<Index<R>> {
+		match self.indexes.iter_mut().find(|i| i.key_type() == index.key_type()) {
+			Some(existing) => Some(std::mem::replace(existing, index)),
+			None => {
+				self.indexes.push(index);
+				None
+			},
+		}
+	}
+
+	/// Add index (see [`MultiIndex::insert`])
+	pub fn with_index(mut self, index: Index<R>) -> Self {
+		self.insert(index);
+		self
+	}
+
+	/// Index for key type
+	pub fn get(&self, key_type: &KeyType) -> Option<&Index<R>> {
+		self.indexes.iter().find(|index| index.key_type() == key_type)
+	}
+
+	/// Key types of loaded indexes (in order of insertion)
+	pub fn key_types(&self) -> impl Iterator<Item = &KeyType> {
+		self.indexes.iter().map(Index::key_type)
+	}
+
+	/// Loaded indexes (in order of insertion)
+	pub fn iter(&self) -> impl Iterator<Item = &Index<R>> {
+		self.indexes.iter()
+	}
+
+	/// Set page cache size (in bytes) of all loaded indexes (see [`Index::set_cache_size`])
+	pub fn set_cache_size(&mut self, cache_size: usize) {
+		for index in &mut self.indexes {
+			index.set_cache_size(cache_size);
+		}
+	}
+
+	/// Whether no index is loaded
+	pub fn is_empty(&self) -> bool {
+		self.indexes.is_empty()
+	}
+}
+
+impl<R> Default for MultiIndex<R> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl MultiIndex<fs::File> {
+	/// Open index files (key types are read from the files)
+	///
+	/// Later files replace earlier ones with the same key type.
+	pub fn open<I>(paths: I) -> Result<Self, IndexOpenError>
+	where
+		I: IntoIterator,
+		I::Item: AsRef<Path>,
+	{
+		let mut multi = Self::new();
+		for path in paths {
+			multi.insert(Index::open(fs::File::open(path)?)?);
+		}
+		Ok(multi)
+	}
+}
+
+impl<R> MultiIndex<R>
+where
+	R: ReadAt,
+{
+	/// Lookup raw key in index of given key type; returns payload if found
+	pub fn lookup_raw(
+		&self,
+		key_type: &KeyType,
+		key: &[u8],
+	) -> Result<Option<Vec<u8>>, MultiLookupError> {
+		let index = self
+			.get(key_type)
+			.ok_or_else(|| MultiLookupError::MissingIndex { key_type: key_type.clone() })?;
+		if key.len() != index.key_size() as usize {
+			return Err(MultiLookupError::InvalidKeyLength {
+				length: key.len(),
+				expected: index.key_size(),
+			});
+		}
+		Ok(index.lookup_raw(key)?)
+	}
+
+	/// Lookup key in index of its key type; returns payload if found
+	pub fn lookup<D: KeyData>(&self, key: &D) -> Result<Option<Vec<u8>>, MultiLookupError> {
+		self.lookup_raw(&D::KEY_TYPE, key.data())
+	}
+
+	/// Whether key is contained in index of its key type
+	pub fn contains<D: KeyData>(&self, key: &D) -> Result<bool, MultiLookupError> {
+		Ok(self.lookup(key)?.is_some())
+	}
+
+	/// Lookup plaintext password in all indexes of known key types (in order of insertion)
+	///
+	/// Returns key type and payload of the first index containing the password.
+	pub fn lookup_plaintext(
+		&self,
+		password: &str,
+	) -> Result<Option<(KnownKeyType, Vec<u8>)>, MultiLookupError> {
+		for index in &self.indexes {
+			let Some(key_type) = index.key_type().as_known() else {
+				continue;
+			};
+			let payload = match key_type {
+				KnownKeyType::SHA1 => self.lookup(&SHA1::hash(password.as_bytes()))?,
+				KnownKeyType::NT => self.lookup(&NT::hash(password))?,
+				KnownKeyType::SHA512 => self.lookup(&SHA512::hash(password.as_bytes()))?,
+				#[cfg(feature = "lm")]
+				KnownKeyType::LM => self.lookup(&crate::data::LM::hash(password))?,
+				#[allow(unreachable_patterns)] // LM hashing needs the `lm` feature
+				_ => continue,
+			};
+			if let Some(payload) = payload {
+				return Ok(Some((key_type, payload)));
+			}
+		}
+		Ok(None)
+	}
+}
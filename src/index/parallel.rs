@@ -0,0 +1,210 @@
+use super::{
+	builder::write_header,
+	checksum::ChecksumWriter,
+	format::{IndexOptions, PayloadEncryption},
+	table::Table,
+	Depth, LimPrefix,
+};
+use crate::{
+	data::{KeyData, PayloadData},
+	errors::BuilderCreateError,
+};
+use anyhow::Context;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Temporary file with the entries of a single shard; removed on drop
+struct ShardSegment {
+	path: PathBuf,
+	// number of entries for each bucket of the shard
+	counts: Vec<u64>,
+}
+
+impl Drop for ShardSegment {
+	fn drop(&mut self) {
+		let _ = fs::remove_file(&self.path);
+	}
+}
+
+fn build_shard<D, P, I>(
+	depth: Depth,
+	shard_depth: Depth,
+	shard: LimPrefix,
+	payload_encryption: &PayloadEncryption,
+	path: PathBuf,
+	entries: I,
+) -> anyhow::Result<ShardSegment>
+where
+	D: KeyData,
+	P: PayloadData,
+	I: Iterator<Item = anyhow::Result<(D, P)>>,
+{
+	let file = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+	let bucket_bits = depth.as_u8() - shard_depth.as_u8();
+	let mut segment = ShardSegment { path, counts: vec![0; 1 << bucket_bits] };
+	let first_bucket = shard.index().entry() << bucket_bits;
+	let mut writer = io::BufWriter::new(file);
+	let mut previous: Option<D> = None;
+	for entry in entries {
+		let (key, payload) = entry?;
+		if shard_depth.prefix(key.data()) != shard {
+			anyhow::bail!("Key {} doesn't belong to shard {:?}", key.hex(), shard);
+		}
+		if let Some(previous) = &previous {
+			if previous.data() >= key.data() {
+				anyhow::bail!(
+					"Key {} out of order (previous key {}) in shard {:?}",
+					key.hex(),
+					previous.hex(),
+					shard
+				);
+			}
+		}
+		segment.counts[depth.index(key.data()).entry() - first_bucket] += 1;
+		let k_suffix = depth.prepare_key(key.data());
+		writer.write_all(k_suffix.first_byte())?;
+		writer.write_all(k_suffix.remaining_bytes())?;
+		if payload_encryption.is_enabled() {
+			let mut payload = payload.clone();
+			payload_encryption.apply(key.data(), payload.data_mut());
+			writer.write_all(payload.data())?;
+		} else {
+			writer.write_all(payload.data())?;
+		}
+		previous = Some(key);
+	}
+	writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+	Ok(segment)
+}
+
+/// Build index from shards on multiple threads
+///
+/// The key space is split into `2^shard_bits` shards by the first `shard_bits`
+/// bits of the keys (must not exceed the table depth); `shard_input` is called
+/// (on a worker thread) with the prefix of a shard and must return the ordered
+/// entries with that prefix (e.g. from separate input files).
+///
+/// `threads` workers write the entries of the shards into temporary files in
+/// `work_dir`; afterwards the shards are concatenated into `database`,
+/// followed by the combined table. The temporary files need about as much
+/// space as the final index.
+///
+/// Supports the same `options` as [`TypedTwoPassBuilder`](super::TypedTwoPassBuilder)
+/// (including [`IndexOptions::leading_table`]); the result is identical to
+/// building the index from the same entries with it.
+#[allow(clippy::too_many_arguments)]
+pub fn build_parallel<D, P, W, F, I>(
+	database: W,
+	description: &str,
+	depth: Depth,
+	options: &IndexOptions,
+	shard_bits: u8,
+	threads: usize,
+	work_dir: &Path,
+	shard_input: F,
+) -> anyhow::Result<()>
+where
+	D: KeyData,
+	P: PayloadData,
+	W: io::Write,
+	F: Fn(LimPrefix) -> anyhow::Result<I> + Sync,
+	I: Iterator<Item = anyhow::Result<(D, P)>>,
+{
+	assert!(P::SIZE < 0x100);
+	assert!(threads > 0);
+	let shard_depth = match Depth::new(shard_bits) {
+		Some(shard_depth) if shard_depth <= depth => shard_depth,
+		_ => anyhow::bail!("Invalid number of shard bits {} for depth {:?}", shard_bits, depth),
+	};
+	if options.payload_dictionary {
+		return Err(BuilderCreateError::PayloadDictionaryUnsupported.into());
+	}
+	if options.bucket_table_bits != 0 {
+		return Err(BuilderCreateError::BucketTablesUnsupported.into());
+	}
+	let options = &*options.for_payload::<P>();
+	let options = &*options.for_new_index()?;
+	let mut database = ChecksumWriter::new(database, options.checksum);
+	let header_size = write_header(
+		&mut database,
+		&D::KEY_TYPE,
+		D::SIZE as u8,
+		description,
+		P::SIZE as u8,
+		depth,
+		options,
+	)?;
+	let payload_encryption = options.payload_encryption();
+	let entry_size = depth.entry_size(D::SIZE as u8, P::SIZE as u8);
+
+	let shards: Vec<LimPrefix> = shard_depth.prefix_range_raw(b"", 0).collect();
+	let segments: Mutex<Vec<Option<ShardSegment>>> =
+		Mutex::new(shards.iter().map(|_| None).collect());
+	let next_shard = AtomicUsize::new(0);
+	let abort = AtomicBool::new(false);
+	let worker = || -> anyhow::Result<()> {
+		while !abort.load(Ordering::Relaxed) {
+			let shard_ndx = next_shard.fetch_add(1, Ordering::Relaxed);
+			let Some(&shard) = shards.get(shard_ndx) else {
+				return Ok(());
+			};
+			let path =
+				work_dir.join(format!(".hibp-shard-{}-{}.tmp", std::process::id(), shard_ndx));
+			let segment = shard_input(shard)
+				.and_then(|entries| {
+					build_shard(depth, shard_depth, shard, &payload_encryption, path, entries)
+				})
+				.with_context(|| format!("Failed to build shard {:?}", shard));
+			match segment {
+				Ok(segment) => segments.lock().unwrap()[shard_ndx] = Some(segment),
+				Err(e) => {
+					abort.store(true, Ordering::Relaxed);
+					return Err(e);
+				},
+			}
+		}
+		Ok(())
+	};
+	std::thread::scope(|scope| {
+		let workers: Vec<_> = (0..threads).map(|_| scope.spawn(worker)).collect();
+		// join all workers (a panicked worker doesn't stop the others), report first failure
+		let mut result = Ok(());
+		for worker in workers {
+			let worker_result = worker.join().unwrap_or_else(|_| {
+				abort.store(true, Ordering::Relaxed);
+				Err(anyhow::anyhow!("Worker thread panicked"))
+			});
+			if result.is_ok() {
+				result = worker_result;
+			}
+		}
+		result
+	})?;
+
+	let segments: Vec<ShardSegment> = segments
+		.into_inner()
+		.map_err(|_| anyhow::anyhow!("Worker thread panicked"))?
+		.into_iter()
+		.map(|segment| segment.expect("all shards built"))
+		.collect();
+	let counts: Vec<u64> =
+		segments.iter().flat_map(|segment| segment.counts.iter().copied()).collect();
+	let table_encoding = options.table_encoding();
+	if options.leading_table {
+		// offsets are relative to the end of the leading table
+		let table = Table::from_bucket_counts(depth, 0, entry_size, &counts);
+		table.write_leading(&mut database, table_encoding)?;
+	}
+	for segment in &segments {
+		io::copy(&mut fs::File::open(&segment.path)?, &mut database)?;
+	}
+	if !options.leading_table {
+		let table = Table::from_bucket_counts(depth, header_size, entry_size, &counts);
+		table.write(&mut database, table_encoding)?;
+	}
+	database.finish()?;
+	Ok(())
+}
@@ -0,0 +1,223 @@
+//! Parsing of the index format (header and tables) shared by all readers
+//!
+//! Works on byte slices without `std`: [`SliceIndex`](super::SliceIndex)
+//! parses a complete index held in memory, the `std` readers parse the
+//! header and the (decompressed) tables they read from their input.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use super::{
+	format::{FormatVersion, IndexFlags, Metadata, CHECKSUM_SIZE, INDEX_HEADER_LIMIT},
+	Depth,
+};
+use crate::{
+	data::KeyType,
+	errors::{KeyTypeParseError, SliceIndexError},
+};
+
+/// Error parsing the header
+#[derive(Debug)]
+pub(super) enum HeaderError {
+	/// Data ends within the header; at least `needed` more bytes are required
+	Incomplete { needed: usize },
+	/// Invalid header
+	Invalid,
+	/// Invalid key type
+	KeyType(KeyTypeParseError),
+	/// Index uses flags not supported by this implementation
+	UnsupportedFlags(IndexFlags),
+}
+
+/// Split off next line (without newline)
+fn take_line<'a>(data: &mut &'a [u8]) -> Result<&'a str, HeaderError> {
+	let pos = data.iter().position(|&b| b == b'\n').ok_or(HeaderError::Incomplete { needed: 1 })?;
+	let line = core::str::from_utf8(&data[..pos]).map_err(|_| HeaderError::Invalid)?;
+	*data = &data[pos + 1..];
+	Ok(line)
+}
+
+fn take_bytes<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], HeaderError> {
+	let bytes = take_slice(data, N)?;
+	Ok(bytes.try_into().expect("N bytes"))
+}
+
+fn take_slice<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], HeaderError> {
+	if data.len() < len {
+		return Err(HeaderError::Incomplete { needed: len - data.len() });
+	}
+	let (bytes, rest) = data.split_at(len);
+	*data = rest;
+	Ok(bytes)
+}
+
+/// Parsed index header
+pub(super) struct IndexHeader<'a> {
+	pub(super) version: FormatVersion,
+	pub(super) key_type: KeyType,
+	pub(super) description: &'a str,
+	pub(super) key_size: u8,
+	pub(super) payload_size: u8,
+	pub(super) flags: IndexFlags,
+	pub(super) metadata: Metadata,
+	/// Size of header in bytes
+	pub(super) size: usize,
+}
+
+impl<'a> IndexHeader<'a> {
+	/// Parse header from start of `data`
+	///
+	/// Rejects flags not in [`IndexFlags::SUPPORTED`], invalid combinations
+	/// of flags and headers larger than [`INDEX_HEADER_LIMIT`].
+	pub(super) fn parse(data: &'a [u8]) -> Result<Self, HeaderError> {
+		let limit = core::cmp::min(data.len(), INDEX_HEADER_LIMIT as usize);
+		let mut header = &data[..limit];
+		match Self::parse_fields(&mut header) {
+			Ok(parsed) => Ok(Self { size: limit - header.len(), ..parsed }),
+			Err(HeaderError::Incomplete { needed })
+				if limit + needed > INDEX_HEADER_LIMIT as usize =>
+			{
+				Err(HeaderError::Invalid)
+			},
+			Err(e) => Err(e),
+		}
+	}
+
+	fn parse_fields(header: &mut &'a [u8]) -> Result<Self, HeaderError> {
+		let version = FormatVersion::from_magic(take_line(header)?).ok_or(HeaderError::Invalid)?;
+		let key_type = take_line(header)?;
+		let description = take_line(header)?;
+		let key_type = KeyType::try_from(alloc::string::String::from(key_type))
+			.map_err(HeaderError::KeyType)?;
+		let [key_size, payload_size] = take_bytes(header)?;
+		let mut flags = IndexFlags::empty();
+		let mut metadata = Metadata::default();
+		if version >= FormatVersion::V1 {
+			flags = IndexFlags::from_bits(u32::from_be_bytes(take_bytes(header)?));
+			let unsupported = flags.unsupported();
+			if !unsupported.is_empty() {
+				return Err(HeaderError::UnsupportedFlags(unsupported));
+			}
+			if flags.contains(IndexFlags::LEADING_TABLE | IndexFlags::PAYLOAD_DICTIONARY)
+				|| flags.contains(IndexFlags::LEADING_TABLE | IndexFlags::BUCKET_TABLES)
+				|| flags.contains(IndexFlags::PAYLOAD_DICTIONARY | IndexFlags::BUCKET_TABLES)
+			{
+				return Err(HeaderError::Invalid);
+			}
+			if flags.contains(IndexFlags::METADATA) {
+				let size = u16::from_be_bytes(take_bytes(header)?);
+				let buf = take_slice(header, size as usize)?;
+				metadata = Metadata::parse(buf).ok_or(HeaderError::Invalid)?;
+			}
+		}
+		Ok(Self {
+			version,
+			key_type,
+			description,
+			key_size,
+			payload_size,
+			flags,
+			metadata,
+			size: 0,
+		})
+	}
+}
+
+/// Size of the data following the table at the end of the index (the checksum)
+pub(super) fn trailer_size(flags: IndexFlags) -> usize {
+	if flags.contains(IndexFlags::CHECKSUMS) {
+		CHECKSUM_SIZE
+	} else {
+		0
+	}
+}
+
+/// Error decoding a table
+#[derive(Debug)]
+pub(super) enum TableError {
+	/// Invalid depth
+	#[cfg_attr(not(feature = "std"), allow(dead_code))]
+	InvalidDepth { depth: u8 },
+	/// Data ends within the table
+	Truncated,
+	/// Data continues after the table
+	TooMuchData,
+	/// Offsets not increasing
+	InvalidOffsets,
+}
+
+/// Size of uncompressed table with given depth
+pub(super) fn table_size(depth: Depth) -> usize {
+	1 + 8 * depth.table_entries()
+}
+
+/// Decode uncompressed table: depth (`u8`) followed by increasing file offsets (`u64`, big endian)
+pub(super) fn decode_table(data: &[u8]) -> Result<(Depth, Vec<u64>), TableError> {
+	let (&depth, offsets) = data.split_first().ok_or(TableError::Truncated)?;
+	let depth = Depth::new(depth).ok_or(TableError::InvalidDepth { depth })?;
+	let expected = table_size(depth) - 1;
+	if offsets.len() < expected {
+		return Err(TableError::Truncated);
+	} else if offsets.len() > expected {
+		return Err(TableError::TooMuchData);
+	}
+	let file_offsets: Vec<u64> = offsets
+		.chunks_exact(8)
+		.map(|chunk| u64::from_be_bytes(chunk.try_into().expect("chunk of 8 bytes")))
+		.collect();
+	if file_offsets.windows(2).any(|w| w[0] > w[1]) {
+		return Err(TableError::InvalidOffsets);
+	}
+	Ok((depth, file_offsets))
+}
+
+/// Check that buckets cover exactly `start..end`, each with a whole number of entries
+///
+/// Returns the failed check.
+pub(super) fn check_bucket_offsets(
+	offsets: &[u64],
+	start: u64,
+	end: u64,
+	entry_size: u64,
+) -> Result<(), &'static str> {
+	if offsets[0] != start || offsets[offsets.len() - 1] != end {
+		return Err("table doesn't cover data between header and table");
+	}
+	if offsets.windows(2).any(|w| !(w[1] - w[0]).is_multiple_of(entry_size)) {
+		return Err("bucket size not a multiple of entry size");
+	}
+	Ok(())
+}
+
+impl From<HeaderError> for SliceIndexError {
+	fn from(e: HeaderError) -> Self {
+		match e {
+			HeaderError::Incomplete { .. } | HeaderError::Invalid => Self::InvalidHeader,
+			HeaderError::KeyType(e) => Self::KeyTypeError(e),
+			HeaderError::UnsupportedFlags(flags) => Self::UnsupportedFlags { flags },
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl From<HeaderError> for crate::errors::IndexOpenError {
+	fn from(e: HeaderError) -> Self {
+		match e {
+			HeaderError::Incomplete { .. } | HeaderError::Invalid => Self::InvalidHeader,
+			HeaderError::KeyType(e) => Self::KeyTypeError(e),
+			HeaderError::UnsupportedFlags(flags) => Self::UnsupportedFlags { flags },
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl From<TableError> for crate::errors::TableReadError {
+	fn from(e: TableError) -> Self {
+		match e {
+			TableError::InvalidDepth { depth } => Self::InvalidDepth { depth },
+			TableError::Truncated => Self::IOError(std::io::ErrorKind::UnexpectedEof.into()),
+			TableError::TooMuchData => Self::TooMuchTableData,
+			TableError::InvalidOffsets => Self::InvalidTableOffsets,
+		}
+	}
+}
@@ -1,4 +1,7 @@
-use crate::data::{FixedByteArray, HexRange, KeyData};
+use crate::{
+	data::{split_prefix_str, FixedByteArray, HexRange, KeyData},
+	errors::PrefixParseError,
+};
 
 use super::{BucketIndexInner, Depth};
 
@@ -11,7 +14,7 @@ impl BucketIndex {
 	}
 }
 
-const KEY_BYTES: usize = std::mem::size_of::<BucketIndexInner>();
+const KEY_BYTES: usize = core::mem::size_of::<BucketIndexInner>();
 const KEY_BITS_U8: u8 = 8 * (KEY_BYTES as u8);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -48,7 +51,7 @@ impl LimPrefix {
 			return Self { raw, depth };
 		}
 		let mask: BucketIndexInner = (!0) << (KEY_BITS_U8 - depth.as_u8()); // zero depth would overflow shift
-		let raw_len = std::cmp::min(key.len(), raw.0.len());
+		let raw_len = core::cmp::min(key.len(), raw.0.len());
 		// copy data
 		// don't care if key was too short for depth... it just gets zero-padded.
 		raw.0[..raw_len].copy_from_slice(&key[..raw_len]);
@@ -57,6 +60,16 @@ impl LimPrefix {
 		Self { raw, depth }
 	}
 
+	/// Prefix of bucket with given index (in table)
+	#[cfg(feature = "std")]
+	pub(super) fn from_bucket(depth: Depth, bucket: usize) -> Self {
+		if depth.as_u8() == 0 {
+			return Self { raw: LimPrefixBytes([0u8; KEY_BYTES]), depth };
+		}
+		let raw = (bucket as BucketIndexInner) << (KEY_BITS_U8 - depth.as_u8());
+		Self { raw: LimPrefixBytes(raw.to_be_bytes()), depth }
+	}
+
 	/// Length of prefix
 	pub fn depth(self) -> Depth {
 		self.depth
@@ -64,7 +77,7 @@ impl LimPrefix {
 
 	/// Show (significant) nibbles of prefix
 	pub fn hex(&self) -> HexRange<[u8; KEY_BYTES * 2]> {
-		self.raw.hex_bit_range(0, self.depth.as_u8() as u32)
+		self.raw.hex_bit_range_masked(0, self.depth.as_u8() as u32)
 	}
 
 	pub(super) fn index(self) -> BucketIndex {
@@ -98,7 +111,10 @@ impl LimPrefix {
 		hex_suffix: &[u8],
 		key_data: &mut [u8],
 	) -> Result<(), hex::FromHexError> {
-		assert!((self.depth.as_u8() as usize + 7) / 8 < key_data.len(), "prefix too long for key");
+		assert!(
+			(self.depth.as_u8() as usize).div_ceil(8) <= key_data.len(),
+			"prefix too long for key"
+		);
 		let suffix_start = (self.depth.as_u8() as usize) / 8;
 		if self.depth.as_u8() & 0x7 >= 4 {
 			// suffix starts with the low nibble of a byte
@@ -128,12 +144,43 @@ impl LimPrefix {
 	}
 }
 
-impl std::fmt::Debug for LimPrefix {
-	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for LimPrefix {
+	fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
 		write!(f, "{}/{}", self.hex(), self.depth.as_u8())
 	}
 }
 
+/// Parse `"hex/depth"` (the [`Debug`](core::fmt::Debug) format) or just hex digits
+///
+/// Same format as for [`Prefix`](crate::data::Prefix); the length must be a
+/// valid [`Depth`].
+///
+/// ```
+/// use hibp_index::index::{Depth, LimPrefix};
+///
+/// let prefix: LimPrefix = "21BD1".parse().unwrap();
+/// assert_eq!(prefix.depth(), Depth::DEPTH20);
+/// assert_eq!(format!("{:?}", prefix), "21bd1/20");
+/// ```
+impl core::str::FromStr for LimPrefix {
+	type Err = PrefixParseError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (hex_digits, bits) = split_prefix_str(s)?;
+		let depth =
+			u8::try_from(bits).ok().and_then(Depth::new).ok_or(PrefixParseError::InvalidBits)?;
+		let hex_digits = hex_digits.as_bytes();
+		if hex_digits.len() != (bits as usize).div_ceil(4) {
+			return Err(PrefixParseError::InvalidHex(hex::FromHexError::InvalidStringLength));
+		}
+		let mut padded = [b'0'; 2 * KEY_BYTES];
+		padded[..hex_digits.len()].copy_from_slice(hex_digits);
+		let mut raw = [0u8; KEY_BYTES];
+		hex::decode_to_slice(padded, &mut raw).map_err(PrefixParseError::InvalidHex)?;
+		Ok(Self::new(depth, &raw))
+	}
+}
+
 /// When looking for keys with a certain (limited) prefix, we might need
 /// to iterate over multiple prefixes in the table
 #[derive(Clone, Copy, Debug)]
@@ -159,7 +206,7 @@ impl LimPrefixRange {
 			return Self { first: Some(0), last: mask, step, depth };
 		}
 		let mut raw = [0u8; KEY_BYTES];
-		let raw_len = std::cmp::min(key.len(), raw.len());
+		let raw_len = core::cmp::min(key.len(), raw.len());
 		// copy data
 		raw[..raw_len].copy_from_slice(&key[..raw_len]);
 		let ndx = u32::from_be_bytes(raw) & mask;
@@ -178,6 +225,42 @@ impl LimPrefixRange {
 			LimPrefixRange { first: Some(ndx), last: ndx, step, depth }
 		}
 	}
+
+	/// Split into (at most) `n` disjoint sub-ranges covering the remaining range
+	///
+	/// The sub-ranges are in order and their lengths differ by at most one;
+	/// if there are fewer than `n` prefixes left each sub-range contains a
+	/// single prefix. Useful to distribute scans or downloads over workers.
+	///
+	/// Panics if `n` is zero.
+	///
+	/// ```
+	/// use hibp_index::index::Depth;
+	///
+	/// let parts: Vec<_> = Depth::DEPTH20.prefix_range_raw(&[], 0).split(3).collect();
+	/// assert_eq!(parts.iter().map(|part| part.len()).collect::<Vec<_>>(), [349526, 349525, 349525]);
+	/// assert_eq!(format!("{:?}", parts[1].clone().next().unwrap()), "55556/20");
+	/// ```
+	pub fn split(self, n: usize) -> impl Iterator<Item = Self> {
+		assert!(n > 0, "can't split into zero ranges");
+		let (mut start, total) = match self.first {
+			Some(first) if first <= self.last => (first, self.len()),
+			_ => (0, 0),
+		};
+		let parts = n.min(total);
+		let (part_len, longer_parts) = match parts {
+			0 => (0, 0),
+			_ => (total / parts, total % parts),
+		};
+		(0..parts).map(move |part| {
+			let len = part_len + usize::from(part < longer_parts);
+			let last = start + self.step * (len - 1) as BucketIndexInner;
+			let range = Self { first: Some(start), last, step: self.step, depth: self.depth };
+			// wraps after the last part only
+			start = last.wrapping_add(self.step);
+			range
+		})
+	}
 }
 
 impl Iterator for LimPrefixRange {
@@ -222,10 +305,10 @@ impl DoubleEndedIterator for LimPrefixRange {
 
 impl ExactSizeIterator for LimPrefixRange {
 	fn len(&self) -> usize {
-		if let Some(first) = self.first {
-			1 + ((self.last - first) / self.step) as usize
-		} else {
-			0
+		match self.first {
+			// exhausted by `next` once `first` passed `last`
+			Some(first) if first <= self.last => 1 + ((self.last - first) / self.step) as usize,
+			_ => 0,
 		}
 	}
 }
@@ -1,67 +1,722 @@
-use byteorder::ReadBytesExt;
-use std::convert::TryFrom;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::io::{self, BufRead, Read, Seek};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use crate::{
-	buf_read::{BufReader, FileLen, ReadAt},
-	data::{KeyData, KeyType, PayloadData},
-	errors::{IndexOpenError, LookupError},
+	buf_read::{BufReader, CacheStats, FileLen, ReadAt, DEFAULT_CACHE_SIZE},
+	data::{KeyData, KeyType, PayloadData, Prefix},
+	errors::{
+		ChecksumError, FilterBuildError, IndexOpenError, LookupError, OrderError, TableReadError,
+	},
+	filter::{self, KeyFilter},
+	policy::BreachCount,
 };
 
 use super::{
+	bucket_table,
+	checksum::hash_prefix,
+	dictionary::DICTIONARY_LIMIT,
+	format::{
+		Capabilities, IndexFlags, Metadata, PayloadEncryption, TableCompression, CHECKSUM_SIZE,
+		INDEX_HEADER_LIMIT,
+	},
+	parse::{self, HeaderError, IndexHeader},
+	search::{ForwardRangeSearch, ForwardSearch, ForwardSearchResult},
+	stream::{SuffixOrder, SuffixOrderError},
 	table::Table,
-	table_helper::{ForwardRangeSearch, ForwardSearch, ForwardSearchResult},
-	LimPrefix, LimPrefixRange,
+	table_cache::{self, FileId, Tables},
+	Depth, LimPrefix, LimPrefixRange,
 };
 
-pub const INDEX_V0_MAGIC: &str = "hash-index-v0";
-pub const INDEX_V0_HEADER_LIMIT: u64 = 4096;
+/// Read header from start of index into `buf` (see [`IndexHeader::parse`])
+///
+/// Reads exactly the header; rejects unsupported flags.
+pub(super) fn read_header<R: BufRead>(
+	reader: R,
+	buf: &mut Vec<u8>,
+) -> Result<IndexHeader<'_>, IndexOpenError> {
+	let mut reader = reader.take(INDEX_HEADER_LIMIT);
+	buf.clear();
+	// magic, key type and description lines
+	for _ in 0..3 {
+		reader.read_until(b'\n', buf)?;
+	}
+	loop {
+		let needed = match IndexHeader::parse(buf) {
+			Ok(_) => break,
+			Err(HeaderError::Incomplete { needed }) => needed,
+			Err(e) => return Err(e.into()),
+		};
+		let start = buf.len();
+		buf.resize(start + needed, 0);
+		match reader.read_exact(&mut buf[start..]) {
+			Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+				return Err(IndexOpenError::InvalidHeader)
+			},
+			result => result?,
+		}
+	}
+	Ok(IndexHeader::parse(buf)?)
+}
 
 /// Reader for indexed database
-struct Index<R> {
+///
+/// Untyped view of an index: can be used to inspect the header of an unknown
+/// index file before choosing the matching [`TypedIndex`].
+pub struct Index<R> {
 	key_type: KeyType,
 	description: String,
 	key_size: u8,
 	payload_size: u8,
-	table: Table,
+	capabilities: Capabilities,
+	metadata: Metadata,
+	payload_encryption: PayloadEncryption,
+	// tables are shared with other readers of the same file (see `Index::open_shared`)
+	table: Arc<Table>,
+	// table of payload dictionaries (see `IndexFlags::PAYLOAD_DICTIONARY`)
+	dictionary: Option<Arc<Table>>,
+	// table of bucket tables (see `IndexFlags::BUCKET_TABLES`)
+	bucket_tables: Option<Arc<Table>>,
+	cache_size: usize,
+	cache_stats: SharedCacheStats,
 	database: R,
 }
 
 impl<R> Index<R>
 where
-	R: io::Read + io::Seek + ReadAt + FileLen,
+	R: ReadAt + FileLen,
 {
 	/// Open index from reader
-	fn open(mut database: R) -> Result<Self, IndexOpenError> {
-		let mut reader = io::BufReader::new(&mut database);
-		reader.rewind()?;
-		let mut header = reader.by_ref().take(INDEX_V0_HEADER_LIMIT);
-		let mut magic = String::new();
-		let mut key_type = String::new();
-		let mut description = String::new();
-		header.read_line(&mut magic)?;
-		header.read_line(&mut key_type)?;
-		header.read_line(&mut description)?;
-		if !magic.ends_with('\n') || !key_type.ends_with('\n') || !description.ends_with('\n') {
-			return Err(IndexOpenError::InvalidHeader);
-		}
-		magic.pop();
-		key_type.pop();
-		description.pop();
-		if magic != INDEX_V0_MAGIC {
-			return Err(IndexOpenError::InvalidHeader);
-		}
-		let key_type = KeyType::try_from(key_type)?;
-		let key_size = header.read_u8()?;
-		let payload_size = header.read_u8()?;
-		#[allow(clippy::drop_non_drop)]
-		drop(header); // must be done with header parsing here
-		let table = Table::open(reader.by_ref())?;
-		if !table.depth().valid_key_size(key_size) {
-			return Err(IndexOpenError::InvalidKeyLength);
+	///
+	/// Supports all format versions; see [`Index::capabilities`] for the
+	/// version and features of the opened file.
+	pub fn open(database: R) -> Result<Self, IndexOpenError> {
+		Self::open_with(database, false, None)
+	}
+
+	/// Open index from reader, rejecting anything suspicious
+	///
+	/// Intended for untrusted index files; in addition to the checks of
+	/// [`Index::open`] this requires:
+	/// - description without control characters
+	/// - known key type with matching key size
+	/// - table data plausible for its compressed size (allocations are
+	///   bounded by the file size)
+	/// - table covering exactly the data between header and table, with
+	///   each bucket containing a whole number of entries
+	/// - payload dictionaries (if used) covering exactly the data between
+	///   entries and dictionary table, with at most 256 payloads per bucket
+	/// - no payload size for an index without entries
+	/// - valid values for known metadata (e.g. [`Index::mtime`])
+	pub fn open_strict(database: R) -> Result<Self, IndexOpenError> {
+		Self::open_with(database, true, None)
+	}
+
+	fn open_with(
+		database: R,
+		strict: bool,
+		file_id: Option<FileId>,
+	) -> Result<Self, IndexOpenError> {
+		#[cfg(feature = "tracing")]
+		let span = tracing::debug_span!(
+			"Index::open",
+			strict,
+			key_type = tracing::field::Empty,
+			depth = tracing::field::Empty,
+			entries = tracing::field::Empty,
+		)
+		.entered();
+		let mut reader = BufReader::with_cache_size(&database, DEFAULT_CACHE_SIZE);
+		let mut header_buf = Vec::new();
+		let IndexHeader {
+			version,
+			key_type,
+			description,
+			key_size,
+			payload_size,
+			flags,
+			metadata,
+			size: header_size,
+		} = read_header(reader.by_ref(), &mut header_buf)?;
+		let header_size = header_size as u64;
+		if strict {
+			if description.chars().any(char::is_control) {
+				return Err(IndexOpenError::StrictValidation {
+					reason: "control characters in description",
+				});
+			}
+			match key_type.as_known() {
+				None => {
+					return Err(IndexOpenError::StrictValidation { reason: "unknown key type" })
+				},
+				Some(known) if known.key_bytes_length() != key_size => {
+					return Err(IndexOpenError::StrictValidation {
+						reason: "key size doesn't match key type",
+					})
+				},
+				Some(_) => (),
+			}
+		}
+		if strict && !metadata.known_tags_valid() {
+			return Err(IndexOpenError::StrictValidation { reason: "invalid metadata value" });
 		}
+		let (tables, data_range) = match file_id.as_ref().and_then(table_cache::lookup) {
+			Some(tables) => (tables, None),
+			None => {
+				let (tables, data_range) = Self::read_tables(
+					&mut reader,
+					strict,
+					header_size,
+					key_size,
+					payload_size,
+					flags,
+				)?;
+				if let Some(file_id) = file_id {
+					table_cache::insert(file_id, &tables);
+				}
+				(tables, Some(data_range))
+			},
+		};
 		drop(reader);
-		Ok(Self { key_type, description, key_size, payload_size, table, database })
+		let index = Self {
+			key_type,
+			description: description.to_string(),
+			key_size,
+			payload_size,
+			capabilities: Capabilities::new(version, flags),
+			metadata,
+			payload_encryption: PayloadEncryption::default(),
+			table: tables.table,
+			dictionary: tables.dictionary,
+			bucket_tables: tables.bucket_tables,
+			cache_size: DEFAULT_CACHE_SIZE,
+			cache_stats: SharedCacheStats::default(),
+			database,
+		};
+		if strict {
+			let data_range = data_range.expect("strict open doesn't use shared tables");
+			index.check_strict(data_range.start, data_range.end)?;
+		}
+		#[cfg(feature = "tracing")]
+		{
+			span.record("key_type", index.key_type.name());
+			span.record("depth", index.table.depth().as_u8());
+			if let Ok(entries) = index.entry_count() {
+				span.record("entries", entries);
+			}
+		}
+		Ok(index)
+	}
+
+	/// Read (main, dictionary and bucket) tables
+	///
+	/// Also returns the file range of entries, dictionaries and bucket tables.
+	fn read_tables(
+		reader: &mut BufReader<'_, R>,
+		strict: bool,
+		header_size: u64,
+		key_size: u8,
+		payload_size: u8,
+		flags: IndexFlags,
+	) -> Result<(Tables, std::ops::Range<u64>), IndexOpenError> {
+		let trailer_size = parse::trailer_size(flags) as u64;
+		let compression = TableCompression::from_flags(flags);
+		let (table, data_start, data_end) = if flags.contains(IndexFlags::LEADING_TABLE) {
+			let table = Table::read_leading(reader.by_ref(), header_size, strict, compression)?;
+			let data_start = header_size + 4 + table.compressed_size() as u64;
+			(table, data_start, reader.seek(io::SeekFrom::End(-(trailer_size as i64)))?)
+		} else {
+			let table = Table::open(reader.by_ref(), strict, trailer_size, compression)?;
+			let table_start = reader.seek(io::SeekFrom::End(-4 - trailer_size as i64))?
+				- table.compressed_size() as u64;
+			(table, header_size, table_start)
+		};
+		if !table.depth().valid_entry_layout(key_size, payload_size) {
+			return Err(IndexOpenError::InvalidKeyLength);
+		}
+		// dictionary table (or table of bucket tables) is stored (with its
+		// size) directly before the table
+		let mut secondary_end = data_end;
+		let mut open_secondary = |flag: IndexFlags| -> Result<Option<Table>, IndexOpenError> {
+			if !flags.contains(flag) {
+				return Ok(None);
+			}
+			let trailer_size = trailer_size + 4 + table.compressed_size() as u64;
+			let secondary = Table::open(reader.by_ref(), strict, trailer_size, compression)?;
+			if secondary.depth() != table.depth() {
+				let depth = secondary.depth().as_u8();
+				return Err(TableReadError::InvalidDepth { depth }.into());
+			}
+			secondary_end = data_end - 4 - secondary.compressed_size() as u64;
+			Ok(Some(secondary))
+		};
+		let dictionary = open_secondary(IndexFlags::PAYLOAD_DICTIONARY)?.map(Arc::new);
+		let bucket_tables = open_secondary(IndexFlags::BUCKET_TABLES)?.map(Arc::new);
+		let tables = Tables { table: Arc::new(table), dictionary, bucket_tables };
+		Ok((tables, data_start..secondary_end))
+	}
+}
+
+impl Index<std::fs::File> {
+	/// Open index file, sharing its tables with other readers of the same file
+	///
+	/// Opening the same file multiple times (e.g. once per worker thread)
+	/// only reads and decompresses the tables once; they are shared as long
+	/// as any index opened this way is alive. Files are identified by device,
+	/// inode, modification time and length, so a replaced file is read again.
+	/// On platforms without inode numbers this is the same as [`Index::open`].
+	pub fn open_shared(database: std::fs::File) -> Result<Self, IndexOpenError> {
+		let file_id = FileId::of(&database);
+		Self::open_with(database, false, file_id)
+	}
+}
+
+impl<R> Index<R> {
+	/// Entries (and payload dictionaries or bucket tables) must be stored exactly in `data_start..data_end`
+	fn check_strict(&self, data_start: u64, data_end: u64) -> Result<(), IndexOpenError> {
+		let entries_end = match self.dictionary.as_ref().or(self.bucket_tables.as_ref()) {
+			Some(secondary) => secondary.file_offsets()[0],
+			None => data_end,
+		};
+		let entry_size = self.entry_size() as u64;
+		parse::check_bucket_offsets(self.table.file_offsets(), data_start, entries_end, entry_size)
+			.map_err(|reason| IndexOpenError::StrictValidation { reason })?;
+		if let Some(dictionary) = &self.dictionary {
+			let offsets = dictionary.file_offsets();
+			if offsets[offsets.len() - 1] != data_end {
+				return Err(IndexOpenError::StrictValidation {
+					reason: "dictionary table doesn't cover data between entries and table",
+				});
+			}
+			let payload_size = self.payload_size as u64;
+			let max_size = DICTIONARY_LIMIT * payload_size;
+			let invalid_size = |size: u64| {
+				size > max_size || (payload_size > 0 && !size.is_multiple_of(payload_size))
+			};
+			if offsets.windows(2).any(|w| invalid_size(w[1] - w[0])) {
+				return Err(IndexOpenError::StrictValidation {
+					reason: "invalid payload dictionary size",
+				});
+			}
+		}
+		if let Some(bucket_tables) = &self.bucket_tables {
+			let offsets = bucket_tables.file_offsets();
+			if offsets[offsets.len() - 1] != data_end {
+				return Err(IndexOpenError::StrictValidation {
+					reason: "bucket tables don't cover data between entries and table",
+				});
+			}
+			if offsets.windows(2).any(|w| !bucket_table::valid_size(w[1] - w[0])) {
+				return Err(IndexOpenError::StrictValidation {
+					reason: "invalid bucket table size",
+				});
+			}
+		}
+		if self.payload_size > 0 && data_start == data_end {
+			return Err(IndexOpenError::StrictValidation {
+				reason: "payload size without entries",
+			});
+		}
+		Ok(())
+	}
+
+	/// Type of keys stored in index
+	pub fn key_type(&self) -> &KeyType {
+		&self.key_type
+	}
+
+	/// Description of database
+	pub fn description(&self) -> &str {
+		&self.description
+	}
+
+	/// Length (in bytes) of keys
+	pub fn key_size(&self) -> u8 {
+		self.key_size
+	}
+
+	/// Length (in bytes) of payload data of each entry
+	pub fn payload_size(&self) -> u8 {
+		self.payload_size
+	}
+
+	/// Depth of table (length of bucket prefix in bits)
+	pub fn depth(&self) -> Depth {
+		self.table.depth()
+	}
+
+	/// Page cache size (in bytes) of each lookup or scan
+	pub fn cache_size(&self) -> usize {
+		self.cache_size
+	}
+
+	/// Page cache counters of all lookups and scans finished so far
+	pub fn cache_stats(&self) -> CacheStats {
+		self.cache_stats.get()
+	}
+
+	/// Reset page cache counters (see [`Index::cache_stats`])
+	pub fn reset_cache_stats(&self) {
+		self.cache_stats.reset();
+	}
+
+	/// Set page cache size (in bytes) of each lookup or scan
+	///
+	/// Every running lookup or scan uses a separate cache of this size
+	/// (default [`DEFAULT_CACHE_SIZE`]); at least one page
+	/// ([`PAGE_SIZE`](crate::buf_read::PAGE_SIZE)) is always cached.
+	pub fn set_cache_size(&mut self, cache_size: usize) {
+		self.cache_size = cache_size;
+	}
+
+	/// Magic header line identifying the file format
+	pub fn magic(&self) -> &'static str {
+		self.capabilities.version().magic()
+	}
+
+	/// Format version and features used by the index file
+	pub fn capabilities(&self) -> Capabilities {
+		self.capabilities
+	}
+
+	/// Metadata stored in header (always empty for format v0)
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Set key to decrypt payloads (see [`IndexFlags::ENCRYPTED_PAYLOAD`])
+	///
+	/// Fails if the index doesn't use encrypted payloads or if the key
+	/// doesn't match.
+	#[cfg(feature = "encryption")]
+	pub fn set_payload_key(
+		&mut self,
+		payload_key: super::PayloadKey,
+	) -> Result<(), IndexOpenError> {
+		if !self.capabilities.flags().contains(IndexFlags::ENCRYPTED_PAYLOAD) {
+			return Err(IndexOpenError::PayloadNotEncrypted);
+		}
+		let salt = self
+			.metadata
+			.get(Metadata::TAG_PAYLOAD_KEY_SALT)
+			.ok_or(IndexOpenError::WrongPayloadKey)?;
+		let payload_key = payload_key.index_key(salt);
+		if self.metadata.get(Metadata::TAG_PAYLOAD_KEY_CHECK) != Some(&payload_key.check_value()) {
+			return Err(IndexOpenError::WrongPayloadKey);
+		}
+		self.payload_encryption = PayloadEncryption(Some(payload_key));
+		Ok(())
+	}
+
+	/// Whether payloads are encrypted and no key to decrypt them was set
+	pub fn needs_payload_key(&self) -> bool {
+		self.capabilities.flags().contains(IndexFlags::ENCRYPTED_PAYLOAD)
+			&& !self.payload_encryption.is_enabled()
+	}
+
+	/// Modification time of the indexed data (header field, not file metadata)
+	pub fn mtime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+		self.metadata.mtime()
+	}
+
+	/// Source of the indexed data
+	pub fn source(&self) -> Option<&str> {
+		self.metadata.source()
+	}
+
+	/// Size of compressed table in bytes
+	pub fn table_size(&self) -> u32 {
+		self.table.compressed_size()
+	}
+
+	/// Length (in bytes) of a stored entry (key suffix and payload)
+	///
+	/// With payload dictionaries entries only store a single byte instead
+	/// of the payload.
+	pub fn entry_size(&self) -> usize {
+		self.table.depth().entry_size(self.key_size, self.stored_payload_size())
+	}
+
+	/// Length (in bytes) of the payload stored in entries
+	fn stored_payload_size(&self) -> u8 {
+		if self.dictionary.is_some() {
+			1
+		} else {
+			self.payload_size
+		}
+	}
+
+	/// Total size (in bytes) of payload dictionaries (if used)
+	pub fn dictionary_size(&self) -> Option<u64> {
+		let offsets = self.dictionary.as_ref()?.file_offsets();
+		Some(offsets[offsets.len() - 1] - offsets[0])
+	}
+
+	/// Number of buckets with a bucket table (if used)
+	pub fn bucket_table_count(&self) -> Option<usize> {
+		let offsets = self.bucket_tables.as_ref()?.file_offsets();
+		Some(offsets.windows(2).filter(|w| w[0] != w[1]).count())
+	}
+
+	/// File offset ranges of all buckets (one for each prefix of table depth)
+	pub fn bucket_ranges(&self) -> impl '_ + ExactSizeIterator<Item = std::ops::Range<u64>> {
+		self.table.file_offsets().windows(2).map(|w| w[0]..w[1])
+	}
+
+	/// Indices of the non-empty buckets (i.e. of the populated prefixes of table depth)
+	pub fn populated_buckets(&self) -> impl '_ + Iterator<Item = u32> {
+		// at most 2^24 buckets
+		self.bucket_ranges().enumerate().filter(|(_, r)| !r.is_empty()).map(|(ndx, _)| ndx as u32)
+	}
+
+	/// Bitset of the non-empty buckets (see [`Index::populated_buckets`])
+	///
+	/// Bit `i % 8` (least significant bit first) of byte `i / 8` is set if
+	/// bucket `i` contains entries; comparing the bitsets of two indexes with
+	/// the same depth shows which prefixes are covered by only one of them.
+	pub fn populated_buckets_bitset(&self) -> Vec<u8> {
+		let mut bitset = vec![0u8; self.bucket_ranges().len().div_ceil(8)];
+		for ndx in self.populated_buckets() {
+			bitset[ndx as usize / 8] |= 1 << (ndx % 8);
+		}
+		bitset
+	}
+
+	/// Roaring bitmap of the non-empty buckets (see [`Index::populated_buckets`])
+	///
+	/// Can be serialized in the portable roaring format, e.g. for dashboards
+	/// (use [`Depth::prefix_range_raw`] to map bucket indices to prefixes).
+	#[cfg(feature = "roaring")]
+	pub fn populated_buckets_roaring(&self) -> roaring::RoaringBitmap {
+		self.populated_buckets().collect()
+	}
+
+	/// Total number of entries
+	///
+	/// Calculated from the table; fails if the buckets don't contain a whole
+	/// number of entries.
+	pub fn entry_count(&self) -> Result<u64, LookupError> {
+		let offsets = self.table.file_offsets();
+		let length = offsets[offsets.len() - 1] - offsets[0];
+		let entry_size = self.entry_size() as u64;
+		if !length.is_multiple_of(entry_size) {
+			return Err(LookupError::InvalidSegmentLength);
+		}
+		Ok(length / entry_size)
+	}
+}
+
+impl<R> Index<R>
+where
+	R: ReadAt + FileLen,
+{
+	/// Verify the checksum over the whole file (see [`IndexFlags::CHECKSUMS`])
+	///
+	/// Reads the complete database; intended to check the integrity of an
+	/// index after transferring it to another host.
+	pub fn verify_checksum(&self) -> Result<(), ChecksumError> {
+		if !self.capabilities.flags().contains(IndexFlags::CHECKSUMS) {
+			return Err(ChecksumError::MissingChecksum);
+		}
+		let data_size = self
+			.database
+			.file_len()?
+			.checked_sub(CHECKSUM_SIZE as u64)
+			.ok_or(ChecksumError::Mismatch)?;
+		let mut reader = BufReader::with_cache_size(&self.database, DEFAULT_CACHE_SIZE);
+		let checksum = hash_prefix(reader.by_ref(), data_size)?;
+		let mut stored = [0u8; CHECKSUM_SIZE];
+		reader.read_exact(&mut stored)?;
+		if checksum != stored {
+			return Err(ChecksumError::Mismatch);
+		}
+		Ok(())
+	}
+}
+
+impl<R> Index<R>
+where
+	R: ReadAt,
+{
+	/// Call `f` with key and payload of each entry (in key order)
+	///
+	/// Payloads are decrypted if a payload key was set (see
+	/// [`Index::needs_payload_key`]).
+	pub fn for_each_entry<F, E>(&self, mut f: F) -> Result<(), E>
+	where
+		F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+		E: From<LookupError>,
+	{
+		let mut walk = IndexWalk::new(self, &[], 0, false);
+		let mut key = vec![0u8; self.key_size as usize];
+		while let Some(payload) = walk.sync_walk(&mut key)? {
+			self.payload_encryption.apply(&key, payload);
+			f(&key, payload)?;
+		}
+		Ok(())
+	}
+
+	/// Check that the keys in each bucket are strictly increasing; returns number of entries
+	///
+	/// The lookup relies on this (keys in different buckets are ordered by
+	/// their prefix anyway); reports the bucket and file offset of the first
+	/// duplicate or misordered key. Also rejects stored key suffixes with bits
+	/// of the bucket prefix. Reads all entries (payloads are not inspected).
+	pub fn verify_order(&self) -> Result<u64, OrderError> {
+		let entry_size = self.entry_size();
+		let mut order = SuffixOrder::new(self.table.depth(), self.key_size);
+		let mut database = self.reader();
+		let mut entry = vec![0u8; entry_size];
+		let mut entries = 0;
+		for prefix in self.table.prefix_range(&[], 0) {
+			let std::ops::Range { start, end } = self.table.lookup_prefix(prefix);
+			if !(end - start).is_multiple_of(entry_size as u64) {
+				return Err(LookupError::InvalidSegmentLength.into());
+			}
+			database.seek_from_start(start);
+			order.start_bucket();
+			for offset in (start..end).step_by(entry_size) {
+				database.read_exact(&mut entry).map_err(LookupError::from)?;
+				match order.check(&entry) {
+					Ok(()) => (),
+					Err(SuffixOrderError::PrefixBits) => {
+						return Err(OrderError::InvalidKey { prefix, offset })
+					},
+					Err(SuffixOrderError::Duplicate) => {
+						return Err(OrderError::Duplicate { prefix, offset })
+					},
+					Err(SuffixOrderError::OutOfOrder) => {
+						return Err(OrderError::OutOfOrder { prefix, offset })
+					},
+				}
+				entries += 1;
+			}
+		}
+		Ok(entries)
+	}
+
+	/// Page reader for a single lookup or scan
+	fn reader(&self) -> IndexReader<'_, R> {
+		IndexReader {
+			cache_stats: &self.cache_stats,
+			reader: BufReader::with_cache_size(&self.database, self.cache_size),
+		}
+	}
+
+	/// Number of keys less than or equal to given key
+	fn rank_raw(&self, key: &[u8]) -> Result<u64, LookupError> {
+		let entry_size = self.entry_size() as u64;
+		let first = self.table.file_offsets()[0];
+		let std::ops::Range { start, end } = self.table.lookup(key);
+		if !(start - first).is_multiple_of(entry_size) || !(end - start).is_multiple_of(entry_size)
+		{
+			return Err(LookupError::InvalidSegmentLength);
+		}
+		let mut rank = (start - first) / entry_size;
+		// entries in bucket are sorted: count until the first greater one
+		let search = ForwardSearch::new(self.table.depth(), key);
+		let mut database = self.reader();
+		database.seek_from_start(start);
+		let mut entry_buf = vec![0u8; entry_size as usize];
+		for _ in 0..(end - start) / entry_size {
+			database.read_exact(&mut entry_buf)?;
+			match search.test_entry(&entry_buf) {
+				ForwardSearchResult::Match(_) => return Ok(rank + 1),
+				ForwardSearchResult::Continue => rank += 1,
+				ForwardSearchResult::Break => break,
+			}
+		}
+		Ok(rank)
+	}
+
+	/// File offsets of the buckets covering the given prefix
+	fn prefix_offsets(&self, key: &[u8], key_bits: u32) -> std::ops::Range<u64> {
+		let mut prefixes = self.table.prefix_range(key, key_bits);
+		let first = prefixes.next().expect("prefix range not empty");
+		let last = prefixes.next_back().unwrap_or(first);
+		self.table.lookup_prefix(first).start..self.table.lookup_prefix(last).end
+	}
+
+	/// Number of entries in the buckets covering the given prefix
+	fn bucket_count_raw(&self, key: &[u8], key_bits: u32) -> Result<u64, LookupError> {
+		let std::ops::Range { start, end } = self.prefix_offsets(key, key_bits);
+		let entry_size = self.entry_size() as u64;
+		if !(end - start).is_multiple_of(entry_size) {
+			return Err(LookupError::InvalidSegmentLength);
+		}
+		Ok((end - start) / entry_size)
+	}
+
+	/// Exact number of keys with given prefix
+	fn count_prefix_raw(&self, key: &[u8], key_bits: u32) -> Result<u64, LookupError> {
+		let depth = self.table.depth();
+		if key_bits <= depth.as_u8() as u32 {
+			// buckets contain exactly the keys with the prefix
+			return self.bucket_count_raw(key, key_bits);
+		}
+		// prefix is within a single bucket
+		let std::ops::Range { start, end } = self.prefix_offsets(key, key_bits);
+		let entry_size = self.entry_size();
+		if !(end - start).is_multiple_of(entry_size as u64) {
+			return Err(LookupError::InvalidSegmentLength);
+		}
+		let search = ForwardRangeSearch::new(key, key_bits);
+		let prefix = depth.prefix(key);
+		let strip_key_prefix = depth.as_u8() as usize / 8;
+		let mut database = self.reader();
+		database.seek_from_start(start);
+		let mut entry_buf = vec![0u8; entry_size];
+		let mut entry_key = vec![0u8; self.key_size as usize];
+		let mut count = 0;
+		for _ in 0..(end - start) / entry_size as u64 {
+			database.read_exact(&mut entry_buf)?;
+			entry_key[strip_key_prefix..]
+				.copy_from_slice(&entry_buf[..self.key_size as usize - strip_key_prefix]);
+			prefix.set_key_prefix(&mut entry_key);
+			match search.test_key(&entry_key) {
+				ForwardSearchResult::Match(_) => count += 1,
+				ForwardSearchResult::Continue => (),
+				ForwardSearchResult::Break => break,
+			}
+		}
+		Ok(count)
+	}
+
+	/// Read key of entry with given position (in index order) into `key`; returns `false` if out of range
+	fn select_raw(&self, position: u64, key: &mut [u8]) -> Result<bool, LookupError> {
+		let entry_size = self.entry_size() as u64;
+		let offsets = self.table.file_offsets();
+		let Some(offset) = position.checked_mul(entry_size).and_then(|o| o.checked_add(offsets[0]))
+		else {
+			return Ok(false);
+		};
+		if offset >= offsets[offsets.len() - 1] {
+			return Ok(false);
+		}
+		// last bucket starting at or before the entry (skips empty buckets)
+		let bucket = offsets.partition_point(|&start| start <= offset) - 1;
+		if !(offset - offsets[bucket]).is_multiple_of(entry_size) {
+			return Err(LookupError::InvalidSegmentLength);
+		}
+		let mut database = self.reader();
+		database.seek_from_start(offset);
+		let strip_key_prefix = self.table.depth().as_u8() as usize / 8;
+		database.read_exact(&mut key[strip_key_prefix..])?;
+		LimPrefix::from_bucket(self.table.depth(), bucket).set_key_prefix(key);
+		Ok(true)
+	}
+
+	/// Lookup entry with given key (must have `key_size` bytes); returns (decrypted) payload
+	pub(super) fn lookup_raw(&self, key: &[u8]) -> Result<Option<Vec<u8>>, LookupError> {
+		let mut payload = vec![0u8; self.payload_size as usize];
+		let mut scratch = LookupScratch::new(self);
+		if IndexLookup::new(&mut scratch, key).sync_lookup(&mut payload)?.is_none() {
+			return Ok(None);
+		}
+		self.payload_encryption.apply(key, &mut payload);
+		Ok(Some(payload))
 	}
 }
 
@@ -77,26 +732,106 @@ impl<D, P, R> TypedIndex<D, P, R>
 where
 	D: KeyData,
 	P: PayloadData,
-	R: io::Read + io::Seek + ReadAt + FileLen,
+	R: ReadAt + FileLen,
+{
+	/// Open an index database
+	pub fn open(database: R) -> Result<Self, IndexOpenError> {
+		Self::from_index(Index::open(database)?)
+	}
+
+	/// Open an untrusted index database (see [`Index::open_strict`])
+	pub fn open_strict(database: R) -> Result<Self, IndexOpenError> {
+		Self::from_index(Index::open_strict(database)?)
+	}
+
+	/// Open an index database after verifying its detached signature
+	///
+	/// Reads the complete database once to verify the signature (see
+	/// [`sign_index`](super::sign_index)) before parsing anything; the file
+	/// must not change size while verifying and opening.
+	#[cfg(feature = "signing")]
+	pub fn open_verified(
+		database: R,
+		key: &super::VerifyingKey,
+		signature: &super::Signature,
+	) -> Result<Self, crate::errors::IndexVerifyError> {
+		let len = database.file_len()?;
+		let reader = BufReader::with_cache_size(&database, DEFAULT_CACHE_SIZE);
+		super::verify_index(io::Read::take(reader, len), key, signature)?;
+		let index = Self::open(database)?;
+		if index.index.database.file_len()? != len {
+			return Err(crate::errors::IndexVerifyError::Modified);
+		}
+		Ok(index)
+	}
+
+	/// Open an index database with encrypted payloads
+	#[cfg(feature = "encryption")]
+	pub fn open_with_payload_key(
+		database: R,
+		payload_key: super::PayloadKey,
+	) -> Result<Self, IndexOpenError> {
+		let mut index = Index::open(database)?;
+		index.set_payload_key(payload_key)?;
+		Self::from_index(index)
+	}
+
+	/// Verify the checksum over the whole file (see [`Index::verify_checksum`])
+	pub fn verify_checksum(&self) -> Result<(), ChecksumError> {
+		self.index.verify_checksum()
+	}
+}
+
+impl<D, P, R> TypedIndex<D, P, R>
+where
+	D: KeyData,
+	P: PayloadData,
+	R: ReadAt,
 {
 	/// Try use the passed index with the specified types
-	fn new(index: Index<R>) -> Result<Self, IndexOpenError> {
-		if index.key_type != *D::KEY_TYPE {
-			return Err(IndexOpenError::InvalidKeyLength);
+	pub fn from_index(index: Index<R>) -> Result<Self, IndexOpenError> {
+		if index.key_type != D::KEY_TYPE {
+			return Err(IndexOpenError::KeyTypeMismatch {
+				expected: D::KEY_TYPE,
+				found: index.key_type,
+			});
 		}
-		if index.key_size != D::KEY_TYPE.key_bytes_length() {
-			return Err(IndexOpenError::InvalidKeyLength);
+		if index.key_size as usize != D::SIZE {
+			return Err(IndexOpenError::KeySizeMismatch {
+				expected: D::SIZE as u8,
+				found: index.key_size,
+			});
 		}
 		if (index.payload_size as usize) < P::SIZE {
-			// TODO: new enum?
-			return Err(IndexOpenError::InvalidKeyLength);
+			return Err(IndexOpenError::PayloadTooSmall {
+				expected: P::SIZE,
+				found: index.payload_size,
+			});
+		}
+		if index.needs_payload_key() {
+			return Err(IndexOpenError::MissingPayloadKey);
 		}
 		Ok(Self { index, _marker: std::marker::PhantomData })
 	}
 
-	/// Open an index database
-	pub fn open(database: R) -> Result<Self, IndexOpenError> {
-		Self::new(Index::open(database)?)
+	/// Untyped index
+	pub fn index(&self) -> &Index<R> {
+		&self.index
+	}
+
+	/// Page cache counters of all lookups and scans finished so far (see [`Index::cache_stats`])
+	pub fn cache_stats(&self) -> CacheStats {
+		self.index.cache_stats()
+	}
+
+	/// Set page cache size (in bytes) of each lookup or scan (see [`Index::set_cache_size`])
+	pub fn set_cache_size(&mut self, cache_size: usize) {
+		self.index.set_cache_size(cache_size);
+	}
+
+	/// Drop type information and return untyped index
+	pub fn into_index(self) -> Index<R> {
+		self.index
 	}
 
 	/// Description of database
@@ -104,6 +839,18 @@ where
 		&self.index.description
 	}
 
+	/// Modification time of the indexed data (header field, not file metadata)
+	///
+	/// Only stored in format v1 indexes.
+	pub fn mtime(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+		self.index.mtime()
+	}
+
+	/// Source of the indexed data (only stored in format v1 indexes)
+	pub fn source(&self) -> Option<&str> {
+		self.index.source()
+	}
+
 	/// Length (in bytes) of payload data of each entry
 	///
 	/// Might be larger than supplied PayloadData `P` type.
@@ -115,13 +862,173 @@ where
 	///
 	/// Return payload of entry if found.
 	pub fn lookup(&self, key: &D) -> Result<Option<P>, LookupError> {
+		self.lookup_with(&mut self.lookup_scratch(), key)
+	}
+
+	/// Buffers for [`TypedIndex::lookup_with`]
+	pub fn lookup_scratch(&self) -> LookupScratch<'_, R> {
+		LookupScratch::new(&self.index)
+	}
+
+	/// Lookup entry with given key in index, reusing the buffers of previous lookups
+	///
+	/// Like [`TypedIndex::lookup`], but doesn't allocate a new page reader and
+	/// entry buffer; intended for callers doing many lookups in a row.
+	///
+	/// Panics if `scratch` wasn't created by this index.
+	///
+	/// ```no_run
+	/// use hibp_index::{data::{NoPayload, SHA1}, index::TypedIndex};
+	///
+	/// let index = TypedIndex::<SHA1, NoPayload, _>::open(std::fs::File::open("hibp-sha1.index")?)?;
+	/// let mut scratch = index.lookup_scratch();
+	/// for password in ["password", "correct horse battery staple"] {
+	///     let found = index.lookup_with(&mut scratch, &SHA1::hash(password.as_bytes()))?.is_some();
+	///     println!("{}: {}", password, found);
+	/// }
+	/// # Ok::<(), Box<dyn std::error::Error>>(())
+	/// ```
+	pub fn lookup_with(
+		&self,
+		scratch: &mut LookupScratch<'_, R>,
+		key: &D,
+	) -> Result<Option<P>, LookupError> {
+		assert!(std::ptr::eq(scratch.index, &self.index), "scratch space of another index");
 		let mut payload = P::default();
-		if IndexLookup::new(&self.index, key.data()).sync_lookup(payload.data_mut())?.is_none() {
+		if IndexLookup::new(scratch, key.data()).sync_lookup(payload.data_mut())?.is_none() {
 			return Ok(None);
 		}
+		self.index.payload_encryption.apply(key.data(), payload.data_mut());
 		Ok(Some(payload))
 	}
 
+	/// Build an approximate membership filter of all keys with 8 or 16 bits per key
+	///
+	/// See [`filter`](crate::filter); reads all keys and needs about 50
+	/// bytes of memory per key while building.
+	pub fn build_filter(&self, bits_per_key: u8) -> Result<KeyFilter<D>, FilterBuildError> {
+		filter::check_bits_per_key(bits_per_key)?;
+		let mut key_hashes = Vec::with_capacity(self.index.entry_count()? as usize);
+		self.index.for_each_entry(|key, _| -> Result<(), LookupError> {
+			key_hashes.push(filter::key_hash(key));
+			Ok(())
+		})?;
+		KeyFilter::from_key_hashes(bits_per_key, key_hashes)
+	}
+
+	/// Lookup many keys, sharing one page reader (see [`TypedIndex::lookup_with`])
+	///
+	/// Returns each key with its payload (if found) in the order of `keys`;
+	/// keys don't need to be sorted, but lookups of nearby keys benefit from
+	/// the shared page cache.
+	pub fn lookup_many<'a, I>(
+		&'a self,
+		keys: I,
+	) -> impl 'a + Iterator<Item = Result<(D, Option<P>), LookupError>>
+	where
+		I: IntoIterator<Item = D>,
+		I::IntoIter: 'a,
+	{
+		let mut scratch = self.lookup_scratch();
+		keys.into_iter().map(move |key| {
+			let payload = self.lookup_with(&mut scratch, &key)?;
+			Ok((key, payload))
+		})
+	}
+
+	/// Lookup entry with given key in index
+	///
+	/// Return complete (raw) payload of entry if found, i.e. all
+	/// [`payload_size`](TypedIndex::payload_size) bytes (`P` might use less).
+	pub fn lookup_payload(&self, key: &D) -> Result<Option<Vec<u8>>, LookupError> {
+		self.index.lookup_raw(key.data())
+	}
+
+	/// Number of keys in index less than or equal to given key
+	///
+	/// Computed from the table offsets; only the bucket of the key is read
+	/// (up to the first greater key).
+	pub fn rank(&self, key: &D) -> Result<u64, LookupError> {
+		self.index.rank_raw(key.data())
+	}
+
+	/// Key at given position (starting at 0) in index order
+	///
+	/// Returns `None` if the position is not less than the number of entries
+	/// (see [`Index::entry_count`]). Reads only the single entry (the bucket
+	/// is found in the table). Together with [`TypedIndex::rank`] this allows
+	/// e.g. percentile queries over the key space.
+	pub fn select(&self, position: u64) -> Result<Option<D>, LookupError> {
+		let mut key = D::default();
+		if !self.index.select_raw(position, key.data_mut())? {
+			return Ok(None);
+		}
+		Ok(Some(key))
+	}
+
+	/// Approximate number of keys with given prefix, computed from the table only
+	///
+	/// Doesn't read any entries. If the prefix is not longer than the depth
+	/// of the index (see [`Index::depth`]) the result is exact. Otherwise the
+	/// count of the single bucket containing the prefix is scaled down by
+	/// `2^(prefix bits - depth)`, assuming uniformly distributed keys (fine
+	/// for hashes); the real count is somewhere between 0 and the count of
+	/// the bucket, i.e. the absolute error is less than the bucket size.
+	pub fn approx_count_prefix(&self, prefix: &Prefix<D>) -> Result<u64, LookupError> {
+		let count = self.index.bucket_count_raw(prefix.key().data(), prefix.bits())?;
+		let extra_bits = prefix.bits().saturating_sub(self.index.depth().as_u8() as u32);
+		if extra_bits == 0 {
+			return Ok(count);
+		}
+		// round to nearest
+		let scaled = (count as f64) / 2f64.powi(extra_bits as i32);
+		Ok(scaled.round() as u64)
+	}
+
+	/// Number of keys with given prefix
+	///
+	/// Prefixes not longer than the depth of the index are counted from the
+	/// table only; otherwise only the single bucket containing the prefix is
+	/// scanned (up to the first key beyond the prefix). Entries aren't
+	/// decoded (unlike counting the results of [`TypedIndex::lookup_range`]).
+	pub fn count_prefix(&self, prefix: &Prefix<D>) -> Result<u64, LookupError> {
+		self.index.count_prefix_raw(prefix.key().data(), prefix.bits())
+	}
+
+	/// The `k` entries with the highest breach counts (highest first)
+	///
+	/// Scans all entries but only keeps `k` of them (in a heap); entries
+	/// without count are skipped. Entries with equal counts are ordered by
+	/// key.
+	pub fn top_k(&self, k: usize) -> Result<Vec<(D, u64)>, LookupError>
+	where
+		D: Ord,
+		P: BreachCount,
+	{
+		if k == 0 {
+			return Ok(Vec::new());
+		}
+		// min-heap of the current top entries
+		let mut heap = BinaryHeap::with_capacity(k + 1);
+		for entry in self.lookup_range(&[], 0) {
+			let (key, payload) = entry?;
+			let Some(count) = payload.breach_count() else {
+				continue;
+			};
+			if heap.len() == k && heap.peek().is_some_and(|Reverse((min, _))| count <= *min) {
+				continue;
+			}
+			heap.push(Reverse((count, key)));
+			if heap.len() > k {
+				heap.pop();
+			}
+		}
+		let mut top: Vec<(D, u64)> =
+			heap.into_iter().map(|Reverse((count, key))| (key, count)).collect();
+		top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+		Ok(top)
+	}
+
 	/// Loop over all entries with given key prefix.
 	///
 	/// Iterator returns key and payload for each entry.
@@ -130,13 +1037,82 @@ where
 		key: &'a [u8],
 		key_bits: u32,
 	) -> impl 'a + Iterator<Item = Result<(D, P), LookupError>> {
-		let mut walk = IndexWalk::new(&self.index, key, key_bits);
+		self.walk(key, key_bits, false)
+	}
+
+	/// Loop over all entries with given key prefix in reverse order (greatest key first).
+	///
+	/// Same as [`TypedIndex::lookup_range`], but walks buckets (and entries
+	/// within buckets) backward; e.g. to fetch the last matching entries.
+	pub fn lookup_range_rev<'a>(
+		&'a self,
+		key: &'a [u8],
+		key_bits: u32,
+	) -> impl 'a + Iterator<Item = Result<(D, P), LookupError>> {
+		self.walk(key, key_bits, true)
+	}
+
+	/// Call `visitor` with key and payload of each entry, scanning on multiple threads
+	///
+	/// The buckets are split into parts that are scanned in parallel on the
+	/// [`rayon`] thread pool, each part with its own page reader. Entries are
+	/// in key order within a part, but parts are visited concurrently. Stops
+	/// at the first error (of the index or returned by `visitor`).
+	///
+	/// ```no_run
+	/// use hibp_index::{data::{Count32, SHA1}, errors::LookupError, index::TypedIndex};
+	/// use std::sync::atomic::{AtomicU64, Ordering};
+	///
+	/// let index = TypedIndex::<SHA1, Count32, _>::open(std::fs::File::open("hibp-sha1.index")?)?;
+	/// let total = AtomicU64::new(0);
+	/// index.par_scan(|_key, count| -> Result<(), LookupError> {
+	///     total.fetch_add(count.value(), Ordering::Relaxed);
+	///     Ok(())
+	/// })?;
+	/// println!("{} breaches", total.into_inner());
+	/// # Ok::<(), Box<dyn std::error::Error>>(())
+	/// ```
+	#[cfg(feature = "rayon")]
+	pub fn par_scan<F, E>(&self, visitor: F) -> Result<(), E>
+	where
+		D: Sync,
+		P: Sync,
+		R: Sync,
+		F: Fn(&D, &P) -> Result<(), E> + Sync,
+		E: From<LookupError> + Send,
+	{
+		use rayon::prelude::*;
+
+		// more parts than threads to balance uneven buckets
+		let parts: Vec<LimPrefixRange> =
+			self.index.table.prefix_range(&[], 0).split(4 * rayon::current_num_threads()).collect();
+		parts.into_par_iter().try_for_each(|prefixes| {
+			let mut walk = IndexWalk::new_buckets(&self.index, prefixes);
+			let mut key = D::default();
+			let mut payload = P::default();
+			while let Some(full_payload) = walk.sync_walk(key.data_mut())? {
+				payload.data_mut().copy_from_slice(&full_payload[..P::SIZE]);
+				self.index.payload_encryption.apply(key.data(), payload.data_mut());
+				visitor(&key, &payload)?;
+			}
+			Ok(())
+		})
+	}
+
+	fn walk<'a>(
+		&'a self,
+		key: &'a [u8],
+		key_bits: u32,
+		reverse: bool,
+	) -> impl 'a + Iterator<Item = Result<(D, P), LookupError>> {
+		let mut walk = IndexWalk::new(&self.index, key, key_bits, reverse);
 		let mut key = D::default();
 		std::iter::from_fn(move || match walk.sync_walk(key.data_mut()) {
 			Ok(None) => None,
 			Ok(Some(full_payload)) => {
 				let mut payload = P::default();
 				payload.data_mut().copy_from_slice(&full_payload[..P::SIZE]);
+				self.index.payload_encryption.apply(key.data(), payload.data_mut());
 				Some(Ok((key.clone(), payload)))
 			},
 			Err(e) => Some(Err(e)),
@@ -144,94 +1120,264 @@ where
 	}
 }
 
-struct IndexLookup<'r, 'key, R> {
-	database: BufReader<'r, R>,
+impl<D, P> TypedIndex<D, P, std::fs::File>
+where
+	D: KeyData,
+	P: PayloadData,
+{
+	/// Open index file, sharing its tables with other readers of the same file
+	///
+	/// See [`Index::open_shared`].
+	pub fn open_shared(database: std::fs::File) -> Result<Self, IndexOpenError> {
+		Self::from_index(Index::open_shared(database)?)
+	}
+}
+
+/// Reusable buffers for repeated lookups (see [`TypedIndex::lookup_with`])
+///
+/// Keeps the page reader (with its cache) and the entry buffer of the
+/// lookups, so they don't need to be allocated for each lookup; pages used by
+/// previous lookups stay cached. The counters of the page reader are added
+/// to [`Index::cache_stats`] when the scratch space is dropped.
+pub struct LookupScratch<'r, R> {
+	index: &'r Index<R>,
+	database: IndexReader<'r, R>,
 	entry_buf: Vec<u8>,
+}
+
+impl<'r, R> LookupScratch<'r, R>
+where
+	R: ReadAt,
+{
+	fn new(index: &'r Index<R>) -> Self {
+		Self { index, database: index.reader(), entry_buf: vec![0u8; index.entry_size()] }
+	}
+}
+
+struct IndexLookup<'s, 'r, 'key, R> {
+	scratch: &'s mut LookupScratch<'r, R>,
+	// payload dictionary of bucket
+	dictionary: Option<std::ops::Range<u64>>,
+	payload_size: u8,
 	forward_search: ForwardSearch<'key>,
 	num_entries: u64,
 	err: Option<LookupError>,
+	#[cfg(feature = "tracing")]
+	span: tracing::Span,
+	// bytes read by the (shared) page reader before this lookup
+	#[cfg(feature = "tracing")]
+	bytes_read_before: u64,
 }
 
-impl<'r, 'key, R> IndexLookup<'r, 'key, R>
+impl<'s, 'r, 'key, R> IndexLookup<'s, 'r, 'key, R>
 where
-	R: io::Read + io::Seek + ReadAt + FileLen,
+	R: ReadAt,
 {
-	fn new(index: &'r Index<R>, key: &'key [u8]) -> Self {
+	fn new(scratch: &'s mut LookupScratch<'r, R>, key: &'key [u8]) -> Self {
+		let index = scratch.index;
 		assert_ne!(index.key_size, 0);
 		assert_eq!(key.len(), index.key_size as usize);
-		let mut database = BufReader::new(&index.database, 16);
+		let database = &mut scratch.database;
+		#[cfg(feature = "tracing")]
+		let bytes_read_before = database.stats().bytes_read;
 
 		let forward_search = ForwardSearch::new(index.table.depth(), key);
 
-		let entry_size = index.table.depth().entry_size(index.key_size, index.payload_size);
-		let mut entry_buf = Vec::new();
-		entry_buf.resize(entry_size, 0u8);
+		let entry_size = index.entry_size();
 
-		let std::ops::Range { start, end } = index.table.lookup(key);
+		let dictionary = index.dictionary.as_ref().map(|dictionary| dictionary.lookup(key));
+
+		let mut bucket = index.table.lookup(key);
+		let mut err: Option<LookupError> = None;
+		if let Some(bucket_tables) = &index.bucket_tables {
+			// jump to the entries with the additional prefix bits of the key
+			let depth = index.table.depth();
+			let bucket_table = bucket_tables.lookup(key);
+			match bucket_table::narrow(database, depth, key, entry_size, bucket, bucket_table) {
+				Ok(range) => bucket = range,
+				Err(e) => {
+					bucket = 0..0;
+					err = Some(e);
+				},
+			}
+		}
+		let std::ops::Range { start, end } = bucket;
 		database.seek_from_start(start);
 
 		let length = end - start;
 		let num_entries: u64;
-		let err: Option<LookupError>;
 		if length % entry_size as u64 != 0 {
 			num_entries = 0;
 			err = Some(LookupError::InvalidSegmentLength);
 		} else {
 			num_entries = length / entry_size as u64;
-			err = None
 		}
 
-		Self { database, entry_buf, forward_search, num_entries, err }
+		#[cfg(feature = "tracing")]
+		let span = tracing::debug_span!(
+			"lookup",
+			prefix = %index.table.depth().prefix(key).hex(),
+			bucket_entries = num_entries,
+			found = tracing::field::Empty,
+			bytes_read = tracing::field::Empty,
+		);
+
+		Self {
+			scratch,
+			dictionary,
+			payload_size: index.payload_size,
+			forward_search,
+			num_entries,
+			err,
+			#[cfg(feature = "tracing")]
+			span,
+			#[cfg(feature = "tracing")]
+			bytes_read_before,
+		}
 	}
 }
 
-impl<R> IndexLookup<'_, '_, R>
+impl<R> IndexLookup<'_, '_, '_, R>
 where
-	R: io::Read + io::Seek + ReadAt + FileLen,
+	R: ReadAt,
 {
 	pub(super) fn sync_lookup<'a>(
 		&mut self,
 		payload: &'a mut [u8],
 	) -> Result<Option<&'a mut [u8]>, LookupError> {
+		#[cfg(feature = "tracing")]
+		let _enter = self.span.enter();
 		if let Some(err) = self.err.take() {
 			return Err(err);
 		}
+		let LookupScratch { database, entry_buf, .. } = &mut *self.scratch;
 		for _ in 0..self.num_entries {
 			// read (partial) key with payload in one operation
-			self.database.read_exact(&mut self.entry_buf)?;
-			match self.forward_search.test_entry(&self.entry_buf) {
+			database.read_exact(entry_buf)?;
+			match self.forward_search.test_entry(entry_buf) {
 				ForwardSearchResult::Match(data) => {
-					let p_len = std::cmp::min(payload.len(), data.len());
+					#[cfg(feature = "tracing")]
+					self.span.record("found", true);
+					let Some(dictionary) = &self.dictionary else {
+						let p_len = std::cmp::min(payload.len(), data.len());
+						let payload = &mut payload[..p_len];
+						payload.copy_from_slice(&data[..p_len]);
+						return Ok(Some(payload));
+					};
+					let payload_size = self.payload_size as u64;
+					let offset = dictionary.start + data[0] as u64 * payload_size;
+					if offset + payload_size > dictionary.end {
+						return Err(LookupError::InvalidDictionary);
+					}
+					let p_len = std::cmp::min(payload.len(), self.payload_size as usize);
 					let payload = &mut payload[..p_len];
-					payload.copy_from_slice(&data[..p_len]);
+					database.seek_from_start(offset);
+					database.read_exact(payload)?;
 					return Ok(Some(payload));
 				},
 				ForwardSearchResult::Continue => (),
 				ForwardSearchResult::Break => break,
 			}
 		}
+		#[cfg(feature = "tracing")]
+		self.span.record("found", false);
 		Ok(None)
 	}
 }
 
+#[cfg(feature = "tracing")]
+impl<R> Drop for IndexLookup<'_, '_, '_, R> {
+	fn drop(&mut self) {
+		let bytes_read = self.scratch.database.stats().bytes_read - self.bytes_read_before;
+		self.span.record("bytes_read", bytes_read);
+	}
+}
+
+/// Counters of finished page readers
+#[derive(Default)]
+struct SharedCacheStats {
+	pages_loaded: AtomicU64,
+	cache_hits: AtomicU64,
+	bytes_read: AtomicU64,
+}
+
+impl SharedCacheStats {
+	fn add(&self, stats: CacheStats) {
+		self.pages_loaded.fetch_add(stats.pages_loaded, Ordering::Relaxed);
+		self.cache_hits.fetch_add(stats.cache_hits, Ordering::Relaxed);
+		self.bytes_read.fetch_add(stats.bytes_read, Ordering::Relaxed);
+	}
+
+	fn get(&self) -> CacheStats {
+		CacheStats {
+			pages_loaded: self.pages_loaded.load(Ordering::Relaxed),
+			cache_hits: self.cache_hits.load(Ordering::Relaxed),
+			bytes_read: self.bytes_read.load(Ordering::Relaxed),
+		}
+	}
+
+	fn reset(&self) {
+		self.pages_loaded.store(0, Ordering::Relaxed);
+		self.cache_hits.store(0, Ordering::Relaxed);
+		self.bytes_read.store(0, Ordering::Relaxed);
+	}
+}
+
+/// Page reader of an index; adds its counters to the index when dropped
+struct IndexReader<'r, R> {
+	cache_stats: &'r SharedCacheStats,
+	reader: BufReader<'r, R>,
+}
+
+impl<'r, R> std::ops::Deref for IndexReader<'r, R> {
+	type Target = BufReader<'r, R>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.reader
+	}
+}
+
+impl<R> std::ops::DerefMut for IndexReader<'_, R> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.reader
+	}
+}
+
+impl<R> Drop for IndexReader<'_, R> {
+	fn drop(&mut self) {
+		self.cache_stats.add(self.reader.stats());
+	}
+}
+
 struct IndexWalk<'r, 'key, R> {
 	index: &'r Index<R>,
-	database: BufReader<'r, R>,
+	database: IndexReader<'r, R>,
 	forward_search: ForwardRangeSearch<'key>,
 	prefixes: LimPrefixRange,
 	payload_buf: Vec<u8>,
+	// payload dictionary of current bucket
+	dictionary_buf: Vec<u8>,
 	entry_size: usize,
+	// walk prefixes and entries backward
+	reverse: bool,
+	// file offset of current bucket
+	bucket_start: u64,
 	current_prefix_num_entries: Option<(LimPrefix, u64)>,
+	#[cfg(feature = "tracing")]
+	span: tracing::Span,
+	// number of returned entries
+	#[cfg(feature = "tracing")]
+	matches: u64,
 }
 
 impl<'r, 'key, R> IndexWalk<'r, 'key, R>
 where
-	R: io::Read + io::Seek + ReadAt + FileLen,
+	R: ReadAt,
 {
-	fn new(index: &'r Index<R>, key: &'key [u8], key_bits: u32) -> Self {
+	fn new(index: &'r Index<R>, key: &'key [u8], key_bits: u32, reverse: bool) -> Self {
 		assert_ne!(index.key_size, 0);
 
-		let database = BufReader::new(&index.database, 16);
+		let database = index.reader();
 
 		let forward_search = ForwardRangeSearch::new(key, key_bits);
 		let prefixes = index.table.prefix_range(key, key_bits);
@@ -239,7 +1385,17 @@ where
 		let mut payload_buf = Vec::new();
 		payload_buf.resize(index.payload_size as usize, 0u8);
 
-		let entry_size = index.table.depth().entry_size(index.key_size, index.payload_size);
+		let entry_size = index.entry_size();
+
+		#[cfg(feature = "tracing")]
+		let span = tracing::debug_span!(
+			"lookup_range",
+			key = %hex::encode(key),
+			key_bits,
+			reverse,
+			entries = tracing::field::Empty,
+			bytes_read = tracing::field::Empty,
+		);
 
 		Self {
 			index,
@@ -247,57 +1403,120 @@ where
 			forward_search,
 			prefixes,
 			payload_buf,
+			dictionary_buf: Vec::new(),
 			entry_size,
+			reverse,
+			bucket_start: 0,
 			current_prefix_num_entries: None,
+			#[cfg(feature = "tracing")]
+			span,
+			#[cfg(feature = "tracing")]
+			matches: 0,
 		}
 	}
+
+	/// Walk all entries of the buckets in `prefixes`
+	#[cfg(feature = "rayon")]
+	fn new_buckets(index: &'r Index<R>, prefixes: LimPrefixRange) -> Self {
+		let mut walk = Self::new(index, &[], 0, false);
+		walk.prefixes = prefixes;
+		walk
+	}
 }
 
 impl<R> IndexWalk<'_, '_, R>
 where
-	R: io::Read + io::Seek + ReadAt + FileLen,
+	R: ReadAt,
 {
 	pub(super) fn sync_walk<'a>(
 		&'a mut self,
 		key: &mut [u8],
 	) -> Result<Option<&'a mut [u8]>, LookupError> {
 		assert_eq!(key.len(), self.index.key_size as usize);
+		#[cfg(feature = "tracing")]
+		let _enter = self.span.clone().entered();
 
 		loop {
 			if let Some((prefix, mut num_entries)) = self.current_prefix_num_entries.take() {
 				// if all entires are done (num_entries == 0) we just don't write state back;
 				// next (outer) loop iteration will load next prefix.
+				let strip_key_prefix = self.index.table.depth().as_u8() as usize / 8;
 				while num_entries > 0 {
-					self.database.read_exact(key)?;
-					self.database.read_exact(&mut self.payload_buf)?;
+					if self.reverse {
+						let offset = (num_entries - 1) * self.entry_size as u64;
+						self.database.seek_from_start(self.bucket_start + offset);
+					}
+					// entries don't store the full prefix bytes; set_key_prefix restores them
+					self.database.read_exact(&mut key[strip_key_prefix..])?;
+					if self.index.dictionary.is_some() {
+						let mut dictionary_index = [0u8];
+						self.database.read_exact(&mut dictionary_index)?;
+						let start = dictionary_index[0] as usize * self.payload_buf.len();
+						let value = self
+							.dictionary_buf
+							.get(start..start + self.payload_buf.len())
+							.ok_or(LookupError::InvalidDictionary)?;
+						self.payload_buf.copy_from_slice(value);
+					} else {
+						self.database.read_exact(&mut self.payload_buf)?;
+					}
 					num_entries -= 1;
 					prefix.set_key_prefix(key);
 					match self.forward_search.test_key(key) {
 						ForwardSearchResult::Match(_) => {
+							#[cfg(feature = "tracing")]
+							{
+								self.matches += 1;
+							}
 							// remember state
 							self.current_prefix_num_entries = Some((prefix, num_entries));
 							return Ok(Some(&mut self.payload_buf));
 						},
+						// entry smaller than searched keys; in reverse all following are too
+						ForwardSearchResult::Continue if self.reverse => return Ok(None),
 						ForwardSearchResult::Continue => (),
+						ForwardSearchResult::Break if self.reverse => (),
 						ForwardSearchResult::Break => return Ok(None),
 					}
 				}
 			} else {
 				// currently no prefix active, load next one
-				let prefix = match self.prefixes.next() {
+				let prefix =
+					if self.reverse { self.prefixes.next_back() } else { self.prefixes.next() };
+				let prefix = match prefix {
 					None => return Ok(None),
 					Some(prefix) => prefix,
 				};
+				if let Some(dictionary) = &self.index.dictionary {
+					let std::ops::Range { start, end } = dictionary.lookup_prefix(prefix);
+					if end - start > DICTIONARY_LIMIT * self.index.payload_size as u64 {
+						return Err(LookupError::InvalidDictionary);
+					}
+					self.dictionary_buf.resize((end - start) as usize, 0);
+					self.database.seek_from_start(start);
+					self.database.read_exact(&mut self.dictionary_buf)?;
+				}
 				let std::ops::Range { start, end } = self.index.table.lookup_prefix(prefix);
 				self.database.seek_from_start(start);
+				self.bucket_start = start;
 
 				let length = end - start;
 				if length % self.entry_size as u64 != 0 {
 					return Err(LookupError::InvalidSegmentLength);
 				}
 				let num_entries = length / self.entry_size as u64;
+				#[cfg(feature = "tracing")]
+				tracing::trace!(prefix = %prefix.hex(), bucket_entries = num_entries, "bucket");
 				self.current_prefix_num_entries = Some((prefix, num_entries));
 			}
 		}
 	}
 }
+
+#[cfg(feature = "tracing")]
+impl<R> Drop for IndexWalk<'_, '_, R> {
+	fn drop(&mut self) {
+		self.span.record("entries", self.matches);
+		self.span.record("bytes_read", self.database.stats().bytes_read);
+	}
+}
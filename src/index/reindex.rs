@@ -0,0 +1,61 @@
+use super::{
+	builder::Builder,
+	format::{IndexFlags, IndexOptions, Metadata, TableCompression},
+	AtomicFile, Depth, Index,
+};
+use crate::buf_read::ReadAt;
+use std::path::Path;
+
+/// Options to write a copy of `src` with (unencrypted payloads)
+pub(super) fn copy_options<R>(src: &Index<R>) -> anyhow::Result<IndexOptions> {
+	if src.needs_payload_key() {
+		anyhow::bail!("Can't copy encrypted payloads without payload key");
+	}
+	let flags = src.capabilities().flags();
+	let mut options = IndexOptions {
+		version: src.capabilities().version(),
+		metadata: src.metadata().clone(),
+		checksum: flags.contains(IndexFlags::CHECKSUMS),
+		counts: flags.contains(IndexFlags::COUNTS),
+		table_compression: TableCompression::from_flags(flags),
+		..IndexOptions::default()
+	};
+	options.metadata.remove(Metadata::TAG_PAYLOAD_KEY_CHECK);
+	options.metadata.remove(Metadata::TAG_PAYLOAD_KEY_SALT);
+	Ok(options)
+}
+
+/// Write all entries of `src` into a new index at `dst` with table depth `new_depth`
+///
+/// Entries are copied in binary form (no text round trip) in a single pass;
+/// description, format version, metadata, checksum and table compression are
+/// taken from `src`. Payloads of encrypted indexes are only copied if the
+/// payload key was set (`Index::set_payload_key`, feature `encryption`);
+/// they are stored unencrypted. The new index is written atomically (see
+/// [`AtomicFile`](super::AtomicFile)); returns the number of copied entries.
+///
+/// Useful to re-tune the depth after looking at the bucket statistics
+/// (e.g. with `hibp-inspect`).
+pub fn reindex<R>(src: &Index<R>, dst: &Path, new_depth: Depth) -> anyhow::Result<u64>
+where
+	R: ReadAt,
+{
+	let options = copy_options(src)?;
+	let mut builder = Builder::create_atomic(
+		AtomicFile::create_locked(dst)?,
+		src.key_type(),
+		src.key_size(),
+		src.description(),
+		src.payload_size(),
+		new_depth,
+		&options,
+	)?;
+	let mut entries = 0;
+	src.for_each_entry(|key, payload| -> anyhow::Result<()> {
+		builder.add_entry(key, payload)?;
+		entries += 1;
+		Ok(())
+	})?;
+	builder.finish()?;
+	Ok(entries)
+}
@@ -0,0 +1,125 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{table_cache::FileId, TypedIndex};
+use crate::{
+	data::{KeyData, PayloadData},
+	errors::{IndexOpenError, LookupError},
+};
+
+/// How often [`ReloadingIndex`] checks the file by default
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Version of a file as far as it can be detected from its metadata
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct FileVersion {
+	id: Option<FileId>,
+	mtime: Option<SystemTime>,
+	len: u64,
+}
+
+impl FileVersion {
+	fn new(metadata: &fs::Metadata) -> Self {
+		Self {
+			id: FileId::from_metadata(metadata),
+			mtime: metadata.modified().ok(),
+			len: metadata.len(),
+		}
+	}
+}
+
+struct Current<D, P> {
+	index: Arc<TypedIndex<D, P, fs::File>>,
+	version: FileVersion,
+	last_check: Instant,
+}
+
+/// Index that is reopened when its file is replaced
+///
+/// The file at `path` is checked (with `stat`) at most once per check
+/// interval; when it changed (e.g. replaced by a build with
+/// [`AtomicFile`](super::AtomicFile)) the new file is opened and used for
+/// all following lookups. Lookups already running keep using the old index.
+///
+/// If the new file can't be opened the old index stays in use (and the
+/// file is checked again after the next interval); [`ReloadingIndex::reload_if_changed`]
+/// reports such errors.
+pub struct ReloadingIndex<D, P> {
+	path: PathBuf,
+	check_interval: Duration,
+	current: Mutex<Current<D, P>>,
+}
+
+impl<D, P> ReloadingIndex<D, P>
+where
+	D: KeyData,
+	P: PayloadData,
+{
+	/// Open index at `path` (checking for changes every 10 seconds)
+	pub fn open<Q: Into<PathBuf>>(path: Q) -> Result<Self, IndexOpenError> {
+		let path = path.into();
+		let (index, version) = Self::open_file(&path)?;
+		Ok(Self {
+			path,
+			check_interval: DEFAULT_CHECK_INTERVAL,
+			current: Mutex::new(Current {
+				index: Arc::new(index),
+				version,
+				last_check: Instant::now(),
+			}),
+		})
+	}
+
+	/// Check for changes of the file at most once per `interval` (zero: on every access)
+	pub fn with_check_interval(mut self, interval: Duration) -> Self {
+		self.check_interval = interval;
+		self
+	}
+
+	fn open_file(path: &Path) -> Result<(TypedIndex<D, P, fs::File>, FileVersion), IndexOpenError> {
+		let file = fs::File::open(path)?;
+		// version of the opened file (the path might have been replaced again already)
+		let version = FileVersion::new(&file.metadata()?);
+		Ok((TypedIndex::open_shared(file)?, version))
+	}
+
+	/// Path of the index file
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Current index (reopened first if the file changed and the check interval passed)
+	pub fn current(&self) -> Arc<TypedIndex<D, P, fs::File>> {
+		let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+		if current.last_check.elapsed() >= self.check_interval {
+			// errors are reported by `reload_if_changed`; keep serving the old index
+			let _ = self.reload_locked(&mut current);
+		}
+		current.index.clone()
+	}
+
+	/// Check file now and reopen it if it changed; returns whether it was reopened
+	pub fn reload_if_changed(&self) -> Result<bool, IndexOpenError> {
+		let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+		self.reload_locked(&mut current)
+	}
+
+	fn reload_locked(&self, current: &mut Current<D, P>) -> Result<bool, IndexOpenError> {
+		current.last_check = Instant::now();
+		let version = FileVersion::new(&fs::metadata(&self.path)?);
+		if version == current.version {
+			return Ok(false);
+		}
+		let (index, version) = Self::open_file(&self.path)?;
+		current.index = Arc::new(index);
+		current.version = version;
+		Ok(true)
+	}
+
+	/// Search for key in the current index
+	pub fn lookup(&self, key: &D) -> Result<Option<P>, LookupError> {
+		self.current().lookup(key)
+	}
+}
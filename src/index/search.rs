@@ -0,0 +1,148 @@
+//! Comparison helpers for entries stored in an index
+//!
+//! Entries of a bucket don't store the full bytes of the bucket prefix (table
+//! depth); if the depth is not a multiple of 8 the first stored byte contains
+//! some prefix bits, which are ignored (masked). The helpers in this module
+//! implement exactly the comparisons the index reader uses, so code doing its
+//! own I/O (e.g. on other storage) gets the same semantics.
+//!
+//! Entries in a bucket are sorted by key, so a forward scan through a bucket
+//! can stop as soon as [`ForwardSearchResult::Break`] is returned.
+//!
+//! ```
+//! use hibp_index::index::search::{ForwardSearch, ForwardSearchResult, KeySuffix};
+//! use hibp_index::index::Depth;
+//!
+//! // depth 20: first two bytes are stripped, high nibble of third byte is masked
+//! let key = [0x12, 0x34, 0x56, 0x78];
+//! let suffix = KeySuffix::new(Depth::DEPTH20, &key);
+//! assert_eq!(suffix.to_vec(), [0x06, 0x78]);
+//! assert_eq!(suffix.len(), 2);
+//!
+//! // entries are the stored suffix followed by the payload
+//! let search = ForwardSearch::new(Depth::DEPTH20, &key);
+//! assert_eq!(search.test_entry(&[0x06, 0x78, 0xaa]), ForwardSearchResult::Match(&[0xaa]));
+//! // masked prefix bits are ignored
+//! assert_eq!(search.test_entry(&[0x56, 0x78, 0xaa]), ForwardSearchResult::Match(&[0xaa]));
+//! assert_eq!(search.test_entry(&[0x06, 0x77, 0xaa]), ForwardSearchResult::Continue);
+//! assert_eq!(search.test_entry(&[0x07, 0x00, 0xaa]), ForwardSearchResult::Break);
+//! ```
+//!
+//! Searching for all keys with a given prefix (of any length) works on full
+//! keys (i.e. after recombining bucket prefix and stored suffix):
+//!
+//! ```
+//! use hibp_index::index::search::{ForwardRangeSearch, ForwardSearchResult};
+//!
+//! // all keys starting with the 12 bits 0x123
+//! let search = ForwardRangeSearch::new(&[0x12, 0x30], 12);
+//! assert_eq!(search.test_key(&[0x12, 0x3f, 0xff]), ForwardSearchResult::Match(&[0x12, 0x3f, 0xff]));
+//! assert_eq!(search.test_key(&[0x12, 0x2f, 0xff]), ForwardSearchResult::Continue);
+//! assert_eq!(search.test_key(&[0x12, 0x40, 0x00]), ForwardSearchResult::Break);
+//!
+//! // zero bits match everything
+//! let search = ForwardRangeSearch::new(&[], 0);
+//! assert_eq!(search.test_key(&[0xff]), ForwardSearchResult::Match(&[0xff]));
+//! ```
+
+use core::cmp::Ordering;
+
+use super::Depth;
+
+pub use super::key_suffix::KeySuffix;
+
+/// Result of testing an entry during a forward scan
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ForwardSearchResult<'data> {
+	/// Entry matches; contains the payload (or the full key for range searches)
+	Match(&'data [u8]),
+	/// Entry is smaller than the searched key; continue with next entry
+	Continue,
+	/// Entry is greater than the searched key; all following entries will be too
+	Break,
+}
+
+/// Search for a single key by scanning the entries of its bucket
+pub struct ForwardSearch<'key> {
+	suffix: KeySuffix<'key>,
+}
+
+impl<'key> ForwardSearch<'key> {
+	/// Prepare search for full key in an index with given depth
+	pub fn new(depth: Depth, key: &'key [u8]) -> Self {
+		Self { suffix: depth.prepare_key(key) }
+	}
+
+	/// Stored suffix of the searched key
+	pub fn suffix(&self) -> &KeySuffix<'key> {
+		&self.suffix
+	}
+
+	/// Compare stored entry (key suffix followed by payload) with searched key
+	///
+	/// Panics if the entry is shorter than the key suffix.
+	pub fn test_entry<'data>(&self, entry: &'data [u8]) -> ForwardSearchResult<'data> {
+		let (entry_key, entry_payload) = entry.split_at(self.suffix.len());
+		match self.suffix.compare_entry(entry_key) {
+			Ordering::Equal => ForwardSearchResult::Match(entry_payload),
+			// key we're looking for still greater than entry from file
+			Ordering::Greater => ForwardSearchResult::Continue,
+			// now entry in file greater than our search key - only getting worse now.
+			Ordering::Less => ForwardSearchResult::Break,
+		}
+	}
+}
+
+/// Search for all keys with a given prefix (bitstring) in sorted full keys
+pub struct ForwardRangeSearch<'key> {
+	prefix: &'key [u8],
+	prefix_end: u8,
+	mask_end_bits: u8,
+}
+
+impl<'key> ForwardRangeSearch<'key> {
+	/// Prepare search for keys starting with the first `key_bits` bits of `key`
+	///
+	/// Panics if `key` is shorter than `key_bits`.
+	pub fn new(key: &'key [u8], key_bits: u32) -> Self {
+		if key_bits == 0 {
+			return Self { prefix: b"", prefix_end: 0, mask_end_bits: 0 };
+		}
+		let len_clipped = (key_bits / 8) as usize;
+		let partial_bits = key_bits & 0x7;
+		if partial_bits > 0 {
+			let mask_end_bits = !(0xff >> partial_bits);
+			Self {
+				prefix: &key[..len_clipped],
+				prefix_end: key[len_clipped] & mask_end_bits,
+				mask_end_bits,
+			}
+		} else {
+			// fully match last byte with mask
+			Self {
+				prefix: &key[..len_clipped - 1],
+				prefix_end: key[len_clipped - 1],
+				mask_end_bits: 0xff,
+			}
+			// this should work too:
+			// Self { prefix: &key[..len_clipped], prefix_end: 0, mask_end_bits: 0 }
+		}
+	}
+
+	/// Compare full key with searched prefix
+	///
+	/// Panics if the key is shorter than the searched prefix.
+	pub fn test_key<'data>(&self, key: &'data [u8]) -> ForwardSearchResult<'data> {
+		match self
+			.prefix
+			.cmp(&key[..self.prefix.len()])
+			.then_with(|| self.prefix_end.cmp(&(key[self.prefix.len()] & self.mask_end_bits)))
+		{
+			Ordering::Equal => ForwardSearchResult::Match(key),
+			// key we're looking for still greater than entry from file
+			Ordering::Greater => ForwardSearchResult::Continue,
+			// now entry in file greater than our search key - only getting worse now.
+			Ordering::Less => ForwardSearchResult::Break,
+		}
+	}
+}
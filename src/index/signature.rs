@@ -0,0 +1,34 @@
+use crate::errors::IndexVerifyError;
+use sha2::{Digest, Sha512};
+use std::io;
+
+pub use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+
+/// Domain separation context for index signatures
+const SIGNATURE_CONTEXT: &[u8] = b"hibp-index";
+
+fn hash_index<R: io::Read>(mut reader: R) -> io::Result<Sha512> {
+	let mut hasher = Sha512::new();
+	io::copy(&mut reader, &mut hasher)?;
+	Ok(hasher)
+}
+
+/// Create detached signature of a complete index file
+///
+/// Signs the full file content (header, entries and table) with Ed25519ph
+/// (SHA-512 prehash), so the file only needs to be read once.
+pub fn sign_index<R: io::Read>(reader: R, key: &SigningKey) -> io::Result<Signature> {
+	let hash = hash_index(reader)?;
+	Ok(key.sign_prehashed(hash, Some(SIGNATURE_CONTEXT)).expect("context is short enough"))
+}
+
+/// Verify detached signature of a complete index file (see [`sign_index`])
+pub fn verify_index<R: io::Read>(
+	reader: R,
+	key: &VerifyingKey,
+	signature: &Signature,
+) -> Result<(), IndexVerifyError> {
+	let hash = hash_index(reader)?;
+	key.verify_prehashed_strict(hash, Some(SIGNATURE_CONTEXT), signature)
+		.map_err(|_| IndexVerifyError::InvalidSignature)
+}
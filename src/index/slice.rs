@@ -0,0 +1,281 @@
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use crate::{
+	data::{KeyData, KeyType, PayloadData},
+	errors::SliceIndexError,
+};
+
+use super::{
+	format::{Capabilities, IndexFlags, Metadata},
+	parse::{self, IndexHeader},
+	Depth, KeySuffix,
+};
+
+/// Features the lookup of [`SliceIndex`] doesn't implement
+const NOT_IMPLEMENTED: IndexFlags = IndexFlags::from_bits(
+	IndexFlags::PAYLOAD_DICTIONARY.bits()
+		| IndexFlags::BUCKET_TABLES.bits()
+		| IndexFlags::ENCRYPTED_PAYLOAD.bits(),
+);
+
+/// Read size (`u32`, big endian) of compressed table at `pos`
+fn table_size_at(data: &[u8], pos: usize) -> Result<usize, SliceIndexError> {
+	let size = data.get(pos..pos + 4).ok_or(SliceIndexError::InvalidTable)?;
+	Ok(u32::from_be_bytes(size.try_into().expect("4 bytes")) as usize)
+}
+
+/// Decompress table (bounded by the size of the largest possible table)
+fn decompress_table(
+	flags: IndexFlags,
+	compressed: &[u8],
+) -> Result<alloc::vec::Vec<u8>, SliceIndexError> {
+	let max_size = parse::table_size(Depth::MAX);
+	#[cfg(feature = "zstd")]
+	if flags.contains(IndexFlags::COMPRESSION) {
+		return zstd::bulk::decompress(compressed, max_size)
+			.map_err(|_| SliceIndexError::InvalidTable);
+	}
+	debug_assert!(!flags.contains(IndexFlags::COMPRESSION), "unsupported flag");
+	miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, max_size)
+		.map_err(|_| SliceIndexError::InvalidTable)
+}
+
+/// Index stored completely in memory (usable with `no_std` + `alloc`)
+///
+/// Parses the header and decompresses the table once; lookups only access
+/// the borrowed data (e.g. an index blob embedded in a firmware image with
+/// `include_bytes!`).
+///
+/// Supports all format features except payload dictionaries, bucket
+/// tables and encrypted payloads; checksums are not verified.
+/// As all data is available upfront the table is validated completely:
+/// buckets must cover exactly the data between header and table, each with
+/// a whole number of entries.
+///
+/// ```
+/// # #[cfg(feature = "std")]
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use hibp_index::data::{KeyData, NoPayload, SHA1};
+/// use hibp_index::index::{Depth, TypedBuilder, TypedSliceIndex};
+///
+/// let mut data = std::io::Cursor::new(Vec::new());
+/// let mut builder = TypedBuilder::<SHA1, NoPayload, _>::create(&mut data, "example", Depth::DEPTH16)?;
+/// builder.add_entry(&SHA1::hash(b"password"), &NoPayload)?;
+/// builder.finish()?;
+/// let data = data.into_inner();
+///
+/// let index = TypedSliceIndex::<SHA1, NoPayload>::open(&data)?;
+/// assert!(index.lookup(&SHA1::hash(b"password")).is_some());
+/// assert!(index.lookup(&SHA1::hash(b"correct horse battery staple")).is_none());
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "std"))]
+/// # fn main() {}
+/// ```
+pub struct SliceIndex<'a> {
+	key_type: KeyType,
+	description: &'a str,
+	key_size: u8,
+	payload_size: u8,
+	capabilities: Capabilities,
+	metadata: Metadata,
+	depth: Depth,
+	file_offsets: alloc::vec::Vec<usize>,
+	data: &'a [u8],
+}
+
+impl<'a> SliceIndex<'a> {
+	/// Open index from complete file contents
+	pub fn open(data: &'a [u8]) -> Result<Self, SliceIndexError> {
+		let IndexHeader {
+			version,
+			key_type,
+			description,
+			key_size,
+			payload_size,
+			flags,
+			metadata,
+			size: header_size,
+		} = IndexHeader::parse(data)?;
+		let not_implemented = IndexFlags::from_bits(flags.bits() & NOT_IMPLEMENTED.bits());
+		if !not_implemented.is_empty() {
+			return Err(SliceIndexError::UnsupportedFlags { flags: not_implemented });
+		}
+
+		let data_end = data
+			.len()
+			.checked_sub(parse::trailer_size(flags))
+			.ok_or(SliceIndexError::InvalidTable)?;
+		// compressed table and range of entries
+		let (table, entries): (Range<usize>, Range<usize>) =
+			if flags.contains(IndexFlags::LEADING_TABLE) {
+				// size of compressed table followed by the table
+				let table_start = header_size + 4;
+				let table_end = table_start
+					.checked_add(table_size_at(data, header_size)?)
+					.filter(|&end| end <= data_end)
+					.ok_or(SliceIndexError::InvalidTable)?;
+				(table_start..table_end, table_end..data_end)
+			} else {
+				// compressed table followed by its size
+				let table_end = data_end.checked_sub(4).ok_or(SliceIndexError::InvalidTable)?;
+				let table_start = table_end
+					.checked_sub(table_size_at(data, table_end)?)
+					.filter(|&start| start >= header_size)
+					.ok_or(SliceIndexError::InvalidTable)?;
+				(table_start..table_end, header_size..table_start)
+			};
+		let table = decompress_table(flags, &data[table])?;
+		let (depth, mut file_offsets) =
+			parse::decode_table(&table).map_err(|_| SliceIndexError::InvalidTable)?;
+		if flags.contains(IndexFlags::LEADING_TABLE) {
+			// offsets are relative to the end of the table
+			for offset in &mut file_offsets {
+				*offset = offset
+					.checked_add(entries.start as u64)
+					.ok_or(SliceIndexError::InvalidTable)?;
+			}
+		}
+		if !depth.valid_entry_layout(key_size, payload_size) {
+			return Err(SliceIndexError::InvalidKeyLength);
+		}
+		let entry_size = depth.entry_size(key_size, payload_size) as u64;
+		parse::check_bucket_offsets(
+			&file_offsets,
+			entries.start as u64,
+			entries.end as u64,
+			entry_size,
+		)
+		.map_err(|_| SliceIndexError::InvalidTable)?;
+		// all offsets are within `entries`
+		let file_offsets = file_offsets.into_iter().map(|offset| offset as usize).collect();
+
+		Ok(Self {
+			key_type,
+			description,
+			key_size,
+			payload_size,
+			capabilities: Capabilities::new(version, flags),
+			metadata,
+			depth,
+			file_offsets,
+			data,
+		})
+	}
+
+	/// Type of keys stored in index
+	pub fn key_type(&self) -> &KeyType {
+		&self.key_type
+	}
+
+	/// Description of database
+	pub fn description(&self) -> &'a str {
+		self.description
+	}
+
+	/// Length (in bytes) of keys
+	pub fn key_size(&self) -> u8 {
+		self.key_size
+	}
+
+	/// Length (in bytes) of payload data of each entry
+	pub fn payload_size(&self) -> u8 {
+		self.payload_size
+	}
+
+	/// Depth of table (length of bucket prefix in bits)
+	pub fn depth(&self) -> Depth {
+		self.depth
+	}
+
+	/// Format version and features used by the index
+	pub fn capabilities(&self) -> Capabilities {
+		self.capabilities
+	}
+
+	/// Metadata stored in header (always empty for format v0)
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Length (in bytes) of a stored entry (key suffix and payload)
+	pub fn entry_size(&self) -> usize {
+		self.depth.entry_size(self.key_size, self.payload_size)
+	}
+
+	/// Total number of entries
+	pub fn entry_count(&self) -> usize {
+		(self.file_offsets[self.file_offsets.len() - 1] - self.file_offsets[0]) / self.entry_size()
+	}
+
+	/// Search for key; returns (full) payload of entry if found
+	///
+	/// Panics if the key doesn't have the key size of the index.
+	pub fn lookup_raw(&self, key: &[u8]) -> Option<&'a [u8]> {
+		assert_eq!(key.len(), self.key_size as usize, "invalid key length");
+		let bucket = self.depth.index(key).entry();
+		let bucket = &self.data[self.file_offsets[bucket]..self.file_offsets[bucket + 1]];
+		let suffix = KeySuffix::new(self.depth, key);
+		let entry_size = self.entry_size();
+		let (mut low, mut high) = (0, bucket.len() / entry_size);
+		while low < high {
+			let mid = low + (high - low) / 2;
+			let entry = &bucket[mid * entry_size..][..entry_size];
+			let (entry_key, payload) = entry.split_at(suffix.len());
+			match suffix.compare_entry(entry_key) {
+				core::cmp::Ordering::Less => high = mid,
+				core::cmp::Ordering::Greater => low = mid + 1,
+				core::cmp::Ordering::Equal => return Some(payload),
+			}
+		}
+		None
+	}
+}
+
+/// Typed wrapper for [`SliceIndex`]
+pub struct TypedSliceIndex<'a, D, P> {
+	index: SliceIndex<'a>,
+	_marker: PhantomData<fn() -> (D, P)>,
+}
+
+impl<'a, D, P> TypedSliceIndex<'a, D, P>
+where
+	D: KeyData,
+	P: PayloadData,
+{
+	/// Wrap untyped index; fails if key type or payload size don't match
+	pub fn from_index(index: SliceIndex<'a>) -> Result<Self, SliceIndexError> {
+		if index.key_type != D::KEY_TYPE || index.key_size as usize != D::SIZE {
+			return Err(SliceIndexError::KeyTypeMismatch {
+				expected: D::KEY_TYPE,
+				found: index.key_type,
+			});
+		}
+		if (index.payload_size as usize) < P::SIZE {
+			return Err(SliceIndexError::PayloadTooSmall {
+				expected: P::SIZE,
+				found: index.payload_size,
+			});
+		}
+		Ok(Self { index, _marker: PhantomData })
+	}
+
+	/// Open index from complete file contents
+	pub fn open(data: &'a [u8]) -> Result<Self, SliceIndexError> {
+		Self::from_index(SliceIndex::open(data)?)
+	}
+
+	/// Access untyped index
+	pub fn index(&self) -> &SliceIndex<'a> {
+		&self.index
+	}
+
+	/// Search for key
+	pub fn lookup(&self, key: &D) -> Option<P> {
+		let full_payload = self.index.lookup_raw(key.data())?;
+		let mut payload = P::default();
+		payload.data_mut().copy_from_slice(&full_payload[..P::SIZE]);
+		Some(payload)
+	}
+}
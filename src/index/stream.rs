@@ -0,0 +1,362 @@
+use std::io::{self, Read};
+
+use super::{
+	checksum::ChecksumReader,
+	format::{Capabilities, IndexFlags, Metadata, TableCompression, CHECKSUM_SIZE},
+	reader::read_header,
+	table::Table,
+	Depth, LimPrefix,
+};
+use crate::{
+	data::{KeyData, KeyType, PayloadData},
+	errors::{IndexOpenError, LookupError, StreamVerifyError},
+};
+
+/// Read all entries of an index from a stream (e.g. stdin or a HTTP download)
+///
+/// Requires an index with the table stored after the header
+/// ([`IndexFlags::LEADING_TABLE`], written by
+/// [`TypedTwoPassBuilder`](super::TypedTwoPassBuilder) with
+/// [`IndexOptions::leading_table`](super::IndexOptions::leading_table)); the
+/// input is read once from start to end without seeking. Entries are
+/// returned in key order.
+///
+/// Encrypted payloads are not supported; checksums are not verified.
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use hibp_index::data::{KeyData, NoPayload, SHA1};
+/// use hibp_index::index::{BucketCounts, Depth, IndexOptions, TypedStreamReader, TypedTwoPassBuilder};
+///
+/// let mut keys = vec![SHA1::hash(b"password"), SHA1::hash(b"123456")];
+/// keys.sort();
+/// let mut counts = BucketCounts::new(Depth::DEPTH16);
+/// keys.iter().for_each(|key| counts.count(key));
+/// let options = IndexOptions { leading_table: true, ..IndexOptions::v1() };
+/// let mut data = Vec::new();
+/// let mut builder = TypedTwoPassBuilder::<SHA1, NoPayload, _>::create_with_options(&mut data, "example", counts, &options)?;
+/// for key in &keys {
+///     builder.add_entry(key, &NoPayload)?;
+/// }
+/// builder.finish()?;
+///
+/// let reader = TypedStreamReader::<SHA1, NoPayload, _>::open(data.as_slice())?;
+/// let entries = reader.map(|entry| entry.map(|(key, _)| key)).collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(entries, keys);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TypedStreamReader<D, P, R> {
+	input: io::BufReader<R>,
+	description: String,
+	capabilities: Capabilities,
+	metadata: Metadata,
+	table: Table,
+	payload_size: usize,
+	// stream position
+	position: u64,
+	// next bucket to read
+	next_bucket: usize,
+	// bucket of current entries and number of remaining entries in it
+	current: Option<(LimPrefix, u64)>,
+	payload_buf: Vec<u8>,
+	_marker: std::marker::PhantomData<(D, P)>,
+}
+
+impl<D, P, R> TypedStreamReader<D, P, R>
+where
+	D: KeyData,
+	P: PayloadData,
+	R: io::Read,
+{
+	/// Read header and table from start of input
+	pub fn open(input: R) -> Result<Self, IndexOpenError> {
+		let mut input = io::BufReader::new(input);
+		let mut header_buf = Vec::new();
+		let header = read_header(&mut input, &mut header_buf)?;
+		if header.key_type != D::KEY_TYPE {
+			return Err(IndexOpenError::KeyTypeMismatch {
+				expected: D::KEY_TYPE,
+				found: header.key_type,
+			});
+		}
+		if header.key_size as usize != D::SIZE {
+			return Err(IndexOpenError::KeySizeMismatch {
+				expected: D::SIZE as u8,
+				found: header.key_size,
+			});
+		}
+		if (header.payload_size as usize) < P::SIZE {
+			return Err(IndexOpenError::PayloadTooSmall {
+				expected: P::SIZE,
+				found: header.payload_size,
+			});
+		}
+		if header.flags.contains(IndexFlags::ENCRYPTED_PAYLOAD) {
+			return Err(IndexOpenError::MissingPayloadKey);
+		}
+		if !header.flags.contains(IndexFlags::LEADING_TABLE) {
+			return Err(IndexOpenError::TableNotLeading);
+		}
+		let table = Table::read_leading(
+			&mut input,
+			header.size as u64,
+			true,
+			TableCompression::from_flags(header.flags),
+		)?;
+		if !table.depth().valid_entry_layout(header.key_size, header.payload_size) {
+			return Err(IndexOpenError::InvalidKeyLength);
+		}
+		Ok(Self {
+			input,
+			description: header.description.to_string(),
+			capabilities: Capabilities::new(header.version, header.flags),
+			metadata: header.metadata,
+			position: header.size as u64 + 4 + table.compressed_size() as u64,
+			table,
+			payload_size: header.payload_size as usize,
+			next_bucket: 0,
+			current: None,
+			payload_buf: vec![0u8; header.payload_size as usize],
+			_marker: std::marker::PhantomData,
+		})
+	}
+
+	/// Description of database
+	pub fn description(&self) -> &str {
+		&self.description
+	}
+
+	/// Format version and features used by the index
+	pub fn capabilities(&self) -> Capabilities {
+		self.capabilities
+	}
+
+	/// Metadata stored in header
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	/// Depth of table (length of bucket prefix in bits)
+	pub fn depth(&self) -> Depth {
+		self.table.depth()
+	}
+
+	fn next_entry(&mut self) -> Result<Option<(D, P)>, LookupError> {
+		let depth = self.table.depth();
+		let entry_size = depth.entry_size(D::SIZE as u8, self.payload_size as u8);
+		let (prefix, remaining) = loop {
+			match self.current {
+				Some((prefix, remaining)) if remaining > 0 => break (prefix, remaining),
+				_ => (),
+			}
+			let offsets = self.table.file_offsets();
+			if self.next_bucket + 1 >= offsets.len() {
+				return Ok(None);
+			}
+			let (start, end) = (offsets[self.next_bucket], offsets[self.next_bucket + 1]);
+			if (end - start) % entry_size as u64 != 0 {
+				return Err(LookupError::InvalidSegmentLength);
+			}
+			// skip unused data before bucket (offsets are never decreasing)
+			if start > self.position {
+				let skip = start - self.position;
+				io::copy(&mut self.input.by_ref().take(skip), &mut io::sink())?;
+				self.position = start;
+			}
+			let prefix = LimPrefix::from_bucket(depth, self.next_bucket);
+			self.current = Some((prefix, (end - start) / entry_size as u64));
+			self.next_bucket += 1;
+		};
+		let mut key = D::default();
+		// entries don't store the full prefix bytes; set_key_prefix restores them
+		let strip_key_prefix = depth.as_u8() as usize / 8;
+		self.input.read_exact(&mut key.data_mut()[strip_key_prefix..])?;
+		self.input.read_exact(&mut self.payload_buf)?;
+		prefix.set_key_prefix(key.data_mut());
+		self.position += entry_size as u64;
+		self.current = Some((prefix, remaining - 1));
+		let mut payload = P::default();
+		payload.data_mut().copy_from_slice(&self.payload_buf[..P::SIZE]);
+		Ok(Some((key, payload)))
+	}
+}
+
+impl<D, P, R> Iterator for TypedStreamReader<D, P, R>
+where
+	D: KeyData,
+	P: PayloadData,
+	R: io::Read,
+{
+	type Item = Result<(D, P), LookupError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.next_entry().transpose()
+	}
+}
+
+/// Violation found by [`SuffixOrder::check`]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(super) enum SuffixOrderError {
+	/// Stored suffix contains bits of the bucket prefix
+	PrefixBits,
+	/// Same suffix as the previous entry of the bucket
+	Duplicate,
+	/// Suffix less than the previous entry of the bucket
+	OutOfOrder,
+}
+
+/// Checks the stored key suffixes of the entries of a bucket
+///
+/// Shared by [`verify_stream`] and [`Index::verify_order`](super::Index::verify_order):
+/// suffixes must not contain bits of the prefix, and must be strictly
+/// increasing within each bucket.
+pub(super) struct SuffixOrder {
+	// bits of the first suffix byte belonging to the prefix
+	prefix_bits: u8,
+	previous: Vec<u8>,
+	first_in_bucket: bool,
+}
+
+impl SuffixOrder {
+	pub(super) fn new(depth: Depth, key_size: u8) -> Self {
+		Self {
+			prefix_bits: !(0xffu8 >> (depth.as_u8() & 0x7)),
+			previous: vec![0u8; depth.entry_size(key_size, 0)],
+			first_in_bucket: true,
+		}
+	}
+
+	/// Following entries belong to the next bucket
+	pub(super) fn start_bucket(&mut self) {
+		self.first_in_bucket = true;
+	}
+
+	/// Check the next entry (only its leading key suffix is inspected)
+	pub(super) fn check(&mut self, entry: &[u8]) -> Result<(), SuffixOrderError> {
+		let suffix = &entry[..self.previous.len()];
+		if suffix.first().is_some_and(|first| first & self.prefix_bits != 0) {
+			return Err(SuffixOrderError::PrefixBits);
+		}
+		if !self.first_in_bucket {
+			match self.previous.as_slice().cmp(suffix) {
+				std::cmp::Ordering::Less => (),
+				std::cmp::Ordering::Equal => return Err(SuffixOrderError::Duplicate),
+				std::cmp::Ordering::Greater => return Err(SuffixOrderError::OutOfOrder),
+			}
+		}
+		self.previous.copy_from_slice(suffix);
+		self.first_in_bucket = false;
+		Ok(())
+	}
+}
+
+/// Summary of an index verified with [`verify_stream`]
+#[derive(Clone, Debug)]
+pub struct StreamVerification {
+	/// Type of keys stored in index
+	pub key_type: KeyType,
+	/// Description of database
+	pub description: String,
+	/// Format version and features used by the index
+	pub capabilities: Capabilities,
+	/// Depth of table
+	pub depth: Depth,
+	/// Number of entries
+	pub entries: u64,
+	/// Whether the index contains a checksum (which matched)
+	pub checksum: bool,
+}
+
+/// Verify an index read sequentially from a stream (e.g. while downloading it)
+///
+/// Like [`TypedStreamReader`] this requires an index with the table stored
+/// after the header ([`IndexFlags::LEADING_TABLE`]); other indexes fail with
+/// [`IndexOpenError::TableNotLeading`]. The input is read once to the end
+/// without seeking or buffering more than a bucket; checks:
+/// - header and table (like [`Index::open_strict`](super::Index::open_strict))
+/// - table covering exactly the data after the table, with each bucket
+///   containing a whole number of entries
+/// - stored key suffixes without bits of the prefix, strictly increasing
+///   within each bucket (i.e. keys are ordered and unique)
+/// - the checksum (if present), and nothing following it
+///
+/// Payloads are not inspected (encrypted payloads don't need a key).
+pub fn verify_stream<R: io::Read>(input: R) -> Result<StreamVerification, StreamVerifyError> {
+	let mut input = io::BufReader::new(ChecksumReader::new(input));
+	let mut header_buf = Vec::new();
+	let header = read_header(&mut input, &mut header_buf)?;
+	if !header.flags.contains(IndexFlags::LEADING_TABLE) {
+		return Err(IndexOpenError::TableNotLeading.into());
+	}
+	match header.key_type.as_known() {
+		Some(known) if known.key_bytes_length() == header.key_size => (),
+		Some(_) => {
+			let reason = "key size doesn't match key type";
+			return Err(IndexOpenError::StrictValidation { reason }.into());
+		},
+		None => {
+			let reason = "unknown key type";
+			return Err(IndexOpenError::StrictValidation { reason }.into());
+		},
+	}
+	let table = Table::read_leading(
+		&mut input,
+		header.size as u64,
+		true,
+		TableCompression::from_flags(header.flags),
+	)
+	.map_err(IndexOpenError::from)?;
+	let depth = table.depth();
+	if !depth.valid_entry_layout(header.key_size, header.payload_size) {
+		return Err(IndexOpenError::InvalidKeyLength.into());
+	}
+	let offsets = table.file_offsets();
+	if offsets[0] != header.size as u64 + 4 + table.compressed_size() as u64 {
+		return Err(StreamVerifyError::InvalidTable);
+	}
+	let entry_size = depth.entry_size(header.key_size, header.payload_size);
+	let mut order = SuffixOrder::new(depth, header.key_size);
+	let mut entry = vec![0u8; entry_size];
+	let mut entries = 0;
+	for (bucket, range) in offsets.windows(2).enumerate() {
+		let length = range[1] - range[0];
+		if !length.is_multiple_of(entry_size as u64) {
+			return Err(StreamVerifyError::InvalidSegmentLength { bucket });
+		}
+		order.start_bucket();
+		for _ in 0..length / entry_size as u64 {
+			input.read_exact(&mut entry)?;
+			entries += 1;
+			match order.check(&entry) {
+				Ok(()) => (),
+				Err(SuffixOrderError::PrefixBits) => {
+					return Err(StreamVerifyError::InvalidKey { bucket, entry: entries })
+				},
+				Err(SuffixOrderError::Duplicate | SuffixOrderError::OutOfOrder) => {
+					return Err(StreamVerifyError::OutOfOrder { bucket, entry: entries })
+				},
+			}
+		}
+	}
+	let checksum = header.flags.contains(IndexFlags::CHECKSUMS);
+	let mut trailer = Vec::new();
+	input.by_ref().take(CHECKSUM_SIZE as u64 + 1).read_to_end(&mut trailer)?;
+	if trailer.len() != if checksum { CHECKSUM_SIZE } else { 0 } {
+		return Err(StreamVerifyError::TrailingData);
+	}
+	if checksum {
+		let (hash, stored) = input.into_inner().finish();
+		if hash[..] != stored[..] {
+			return Err(StreamVerifyError::ChecksumMismatch);
+		}
+	}
+	Ok(StreamVerification {
+		key_type: header.key_type,
+		description: header.description.to_string(),
+		capabilities: Capabilities::new(header.version, header.flags),
+		depth,
+		entries,
+		checksum,
+	})
+}
@@ -0,0 +1,144 @@
+//! Switch between versions of an index file
+//!
+//! Replacing an index in place (even atomically with
+//! [`AtomicFile`](super::AtomicFile)) means readers can't tell which version
+//! they opened, and rolling back needs a copy of the old file. Instead new
+//! versions are written beside the old one (`<name>.<version>`), and a small
+//! marker file `<name>.current` names the version in use:
+//!
+//! 1. build the new index at [`VersionedIndex::new_version_path`] (e.g. with
+//!    [`TypedBuilder::create_atomic`](super::TypedBuilder::create_atomic),
+//!    which syncs it to disk before it shows up under its name)
+//! 2. [`VersionedIndex::switch`] replaces the marker atomically and then
+//!    removes the previous version
+//! 3. readers open the index with [`VersionedIndex::open`], which follows
+//!    the marker
+//!
+//! On Unix readers that still have the previous version open keep reading
+//! it after it was removed.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::data::{NoPayload, SHA1};
+//! use hibp_index::index::{switch::VersionedIndex, Depth, TypedBuilder};
+//!
+//! let versions = VersionedIndex::new("/var/lib/hibp", "sha1.index");
+//! let path = versions.new_version_path()?;
+//! let builder = TypedBuilder::<SHA1, NoPayload, _>::create_atomic(&path, "hibp", Depth::DEPTH20)?;
+//! // ... add entries ...
+//! builder.finish()?;
+//! versions.switch(&path)?;
+//!
+//! let index = versions.open::<SHA1, NoPayload>()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{AtomicFile, TypedIndex};
+use crate::{
+	data::{KeyData, PayloadData},
+	errors::IndexOpenError,
+};
+
+/// Versions of an index file in a directory, with a marker naming the current one
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct VersionedIndex {
+	dir: PathBuf,
+	name: String,
+}
+
+impl VersionedIndex {
+	/// Versions of index `name` in directory `dir`
+	pub fn new<Q: Into<PathBuf>>(dir: Q, name: &str) -> Self {
+		Self { dir: dir.into(), name: name.to_string() }
+	}
+
+	/// Path of the marker file naming the current version
+	pub fn marker_path(&self) -> PathBuf {
+		self.dir.join(format!("{}.current", self.name))
+	}
+
+	/// Unused path for a new version (named after the current time)
+	pub fn new_version_path(&self) -> io::Result<PathBuf> {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+		let mut path = self.dir.join(format!("{}.{}", self.name, now));
+		let mut counter = 0;
+		while fs::symlink_metadata(&path).is_ok() {
+			counter += 1;
+			path = self.dir.join(format!("{}.{}-{}", self.name, now, counter));
+		}
+		Ok(path)
+	}
+
+	/// Path of the current version; `None` if no version was activated yet
+	pub fn current_path(&self) -> io::Result<Option<PathBuf>> {
+		let marker = match fs::read_to_string(self.marker_path()) {
+			Ok(marker) => marker,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(e),
+		};
+		let file_name = marker.trim_end_matches('\n');
+		// only file names in the directory (a corrupt marker must not point elsewhere)
+		if file_name.is_empty() || Path::new(file_name).file_name() != Some(OsStr::new(file_name)) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("invalid index marker {:?}", self.marker_path()),
+			));
+		}
+		Ok(Some(self.dir.join(file_name)))
+	}
+
+	/// Make `path` (in the directory of the versions) the current version and remove the previous one
+	///
+	/// The new version is synced to disk before the marker is replaced
+	/// (atomically); the previous version is only removed afterwards, so
+	/// readers always find a complete index. Returns the path of the removed
+	/// previous version.
+	pub fn switch(&self, path: &Path) -> io::Result<Option<PathBuf>> {
+		let file_name = path
+			.file_name()
+			.and_then(|name| name.to_str())
+			.filter(|name| self.dir.join(name) == path)
+			.ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::InvalidInput,
+					format!("{:?} is not a version in {:?}", path, self.dir),
+				)
+			})?;
+		fs::File::open(path)?.sync_all()?;
+		let previous = self.current_path()?;
+		let mut marker = AtomicFile::create(self.marker_path())?;
+		writeln!(marker, "{}", file_name)?;
+		marker.commit()?;
+		match previous {
+			Some(previous) if previous != path => {
+				fs::remove_file(&previous)?;
+				Ok(Some(previous))
+			},
+			_ => Ok(None),
+		}
+	}
+
+	/// Open the current version
+	///
+	/// Fails with an IO error (`NotFound`) if no version was activated yet.
+	pub fn open<D, P>(&self) -> Result<TypedIndex<D, P, fs::File>, IndexOpenError>
+	where
+		D: KeyData,
+		P: PayloadData,
+	{
+		let path = self.current_path()?.ok_or_else(|| {
+			io::Error::new(
+				io::ErrorKind::NotFound,
+				format!("no current version in {:?}", self.marker_path()),
+			)
+		})?;
+		TypedIndex::open(fs::File::open(path)?)
+	}
+}
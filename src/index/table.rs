@@ -1,24 +1,38 @@
 use byteorder::{ReadBytesExt, WriteBytesExt, BE};
-use std::io::{self, Read, Write};
+use std::io::{self, Read};
 use std::ops::Range;
 
-use super::{BucketIndex, Depth, LimPrefix, LimPrefixRange};
-use crate::errors::TableReadError;
+use super::{
+	format::{TableCompression, TableEncoding},
+	parse, BucketIndex, Depth, LimPrefix, LimPrefixRange,
+};
+use crate::errors::{BuilderAddError, TableReadError};
 
 pub(super) struct Table {
 	depth: Depth,
 	file_offsets: Vec<u64>,
+	compressed_size: u32,
 }
 
 impl Table {
 	fn new(depth: Depth, file_offsets: Vec<u64>) -> Self {
-		Self { depth, file_offsets }
+		Self { depth, file_offsets, compressed_size: 0 }
 	}
 
 	pub(super) fn depth(&self) -> Depth {
 		self.depth
 	}
 
+	/// Size of compressed table in file (without trailing size field)
+	pub(super) fn compressed_size(&self) -> u32 {
+		self.compressed_size
+	}
+
+	/// File offsets of all buckets (including final end offset)
+	pub(super) fn file_offsets(&self) -> &[u64] {
+		&self.file_offsets
+	}
+
 	pub(super) fn lookup(&self, key: &[u8]) -> Range<u64> {
 		let start = self.depth.index(key);
 		self.file_offsets[start.entry()]..self.file_offsets[start.entry() + 1]
@@ -34,29 +48,202 @@ impl Table {
 		self.file_offsets[start.entry()]..self.file_offsets[start.entry() + 1]
 	}
 
-	pub(super) fn open<R>(mut input: R) -> Result<Self, TableReadError>
+	/// Create table from number of entries in each bucket
+	///
+	/// Offsets start at `start` (i.e. end of header).
+	pub(super) fn from_bucket_counts(
+		depth: Depth,
+		start: u64,
+		entry_size: usize,
+		counts: &[u64],
+	) -> Self {
+		assert_eq!(counts.len() + 1, depth.table_entries());
+		let mut file_offsets = Vec::with_capacity(depth.table_entries());
+		let mut offset = start;
+		file_offsets.push(offset);
+		for &count in counts {
+			offset += count * entry_size as u64;
+			file_offsets.push(offset);
+		}
+		Self::new(depth, file_offsets)
+	}
+
+	/// Add `base` to all file offsets
+	///
+	/// Tables stored after the header contain offsets relative to the end of
+	/// the table, as the table size isn't known before compressing it.
+	pub(super) fn relocate(&mut self, base: u64) {
+		for offset in &mut self.file_offsets {
+			*offset += base;
+		}
+	}
+
+	fn encode<W: io::Write>(&self, mut output: W) -> io::Result<()> {
+		output.write_u8(self.depth.as_u8())?;
+		for &p in &self.file_offsets {
+			output.write_u64::<BE>(p)?;
+		}
+		Ok(())
+	}
+
+	/// Compressed table
+	fn compress(&self, encoding: TableEncoding) -> io::Result<Vec<u8>> {
+		let compressed = match encoding.compression {
+			TableCompression::Deflate => {
+				let level = encoding.level.map_or(flate2::Compression::default(), |level| {
+					flate2::Compression::new(level)
+				});
+				let mut tbl_writer = flate2::write::DeflateEncoder::new(Vec::new(), level);
+				self.encode(&mut tbl_writer)?;
+				tbl_writer.finish()?
+			},
+			#[cfg(feature = "zstd")]
+			TableCompression::Zstd => {
+				// level 0 selects the default level
+				let level = encoding.level.map_or(0, |level| level as i32);
+				let tbl_writer = zstd::Encoder::new(Vec::new(), level)?;
+				let mut tbl_writer = io::BufWriter::new(tbl_writer);
+				self.encode(&mut tbl_writer)?;
+				tbl_writer.into_inner().map_err(io::IntoInnerError::into_error)?.finish()?
+			},
+		};
+		// due to limited table depth this shouldn't exceed u32 ever.
+		assert!(compressed.len() < (u32::MAX as usize));
+		Ok(compressed)
+	}
+
+	/// Write (compressed) table and its size
+	pub(super) fn write<W: io::Write>(
+		&self,
+		database: &mut W,
+		encoding: TableEncoding,
+	) -> io::Result<()> {
+		let compressed = self.compress(encoding)?;
+		database.write_all(&compressed)?;
+		database.write_u32::<BE>(compressed.len() as u32)?;
+		database.flush()?;
+		Ok(())
+	}
+
+	/// Write size of (compressed) table followed by the table
+	///
+	/// Returns number of written bytes.
+	pub(super) fn write_leading<W: io::Write>(
+		&self,
+		database: &mut W,
+		encoding: TableEncoding,
+	) -> io::Result<u64> {
+		let compressed = self.compress(encoding)?;
+		database.write_u32::<BE>(compressed.len() as u32)?;
+		database.write_all(&compressed)?;
+		Ok(4 + compressed.len() as u64)
+	}
+
+	/// Read table from end of input, followed by `trailer_size` other bytes
+	/// (e.g. a checksum)
+	///
+	/// In `strict` mode the compressed size must fit into the input, and the
+	/// uncompressed table must not exceed what the compression could possibly
+	/// produce from the compressed size (bounds allocation by input size).
+	pub(super) fn open<R>(
+		mut input: R,
+		strict: bool,
+		trailer_size: u64,
+		compression: TableCompression,
+	) -> Result<Self, TableReadError>
 	where
 		R: io::Read + io::Seek,
 	{
-		input.seek(io::SeekFrom::End(-4))?;
-		let table_size = input.read_u32::<BE>()? as u64;
-		input.seek(io::SeekFrom::End(-4 - table_size as i64))?;
-		let mut tbl_reader = flate2::read::DeflateDecoder::new(input.take(table_size));
-		let depth = tbl_reader.read_u8()?;
-		let depth = Depth::new(depth).ok_or(TableReadError::InvalidDepth { depth })?;
-		let entries = depth.table_entries();
-		let mut file_offsets: Vec<u64> = Vec::new();
-		file_offsets.resize(entries, 0);
-		tbl_reader.read_u64_into::<BE>(&mut file_offsets)?;
-		if 1 == tbl_reader.read(&mut [0])? {
-			return Err(TableReadError::TooMuchTableData);
+		#[cfg(feature = "tracing")]
+		let span = tracing::debug_span!(
+			"Table::open",
+			compressed_size = tracing::field::Empty,
+			depth = tracing::field::Empty,
+		)
+		.entered();
+		let size_offset = -4 - trailer_size as i64;
+		let table_end = input.seek(io::SeekFrom::End(size_offset))?;
+		let compressed_size = input.read_u32::<BE>()?;
+		let table_size = compressed_size as u64;
+		if strict && table_size > table_end {
+			return Err(TableReadError::SuspiciousTableSize);
 		}
-		for i in 0..(entries - 1) {
-			if file_offsets[i] > file_offsets[i + 1] {
-				return Err(TableReadError::InvalidTableOffsets);
-			}
+		input.seek(io::SeekFrom::End(size_offset - table_size as i64))?;
+		let table = Self::read_compressed(input, compressed_size, strict, compression)?;
+		#[cfg(feature = "tracing")]
+		{
+			span.record("compressed_size", compressed_size);
+			span.record("depth", table.depth.as_u8());
+		}
+		Ok(table)
+	}
+
+	/// Read table size and table stored directly after the header (of `header_size` bytes)
+	///
+	/// The input doesn't need to be seekable; afterwards it is positioned at
+	/// the first entry. See [`Table::open`] for `strict`.
+	pub(super) fn read_leading<R: io::Read>(
+		mut input: R,
+		header_size: u64,
+		strict: bool,
+		compression: TableCompression,
+	) -> Result<Self, TableReadError> {
+		let compressed_size = input.read_u32::<BE>()?;
+		let mut table = Self::read_compressed(input, compressed_size, strict, compression)?;
+		let base = header_size + 4 + compressed_size as u64;
+		if table.file_offsets[table.file_offsets.len() - 1].checked_add(base).is_none() {
+			return Err(TableReadError::InvalidTableOffsets);
+		}
+		table.relocate(base);
+		Ok(table)
+	}
+
+	fn read_compressed<R: io::Read>(
+		input: R,
+		compressed_size: u32,
+		strict: bool,
+		compression: TableCompression,
+	) -> Result<Self, TableReadError> {
+		let table_size = compressed_size as u64;
+		let mut input = input.take(table_size);
+		let (depth, file_offsets) = match compression {
+			TableCompression::Deflate => {
+				// deflate can't compress better than about 1:1032
+				let max_size = strict.then(|| table_size * 1032);
+				Self::decode(flate2::read::DeflateDecoder::new(input.by_ref()), max_size)?
+			},
+			#[cfg(feature = "zstd")]
+			TableCompression::Zstd => {
+				// zstd needs at least 4 bytes for a (RLE) block of up to 128 KiB
+				let max_size = strict.then(|| table_size * 32 * 1024);
+				Self::decode(zstd::Decoder::new(input.by_ref())?, max_size)?
+			},
+		};
+		// skip remaining compressed data (e.g. final empty deflate block)
+		io::copy(&mut input, &mut io::sink())?;
+		Ok(Self { depth, file_offsets, compressed_size })
+	}
+
+	/// Decode uncompressed table (must not be longer than `max_size` bytes)
+	fn decode<R: io::Read>(
+		tbl_reader: R,
+		max_size: Option<u64>,
+	) -> Result<(Depth, Vec<u64>), TableReadError> {
+		let table_limit = parse::table_size(Depth::MAX) as u64;
+		let limit = max_size.map_or(table_limit, |max_size| max_size.min(table_limit));
+		// one more byte to detect data after the table
+		let mut data = Vec::new();
+		tbl_reader.take(limit + 1).read_to_end(&mut data)?;
+		match parse::decode_table(&data) {
+			Ok(table) => Ok(table),
+			// stopped reading at `max_size`, but depth requires a larger table
+			Err(parse::TableError::Truncated)
+				if max_size.is_some_and(|max_size| data.len() as u64 > max_size) =>
+			{
+				Err(TableReadError::SuspiciousTableSize)
+			},
+			Err(e) => Err(e.into()),
 		}
-		Ok(Table::new(depth, file_offsets))
 	}
 }
 
@@ -64,6 +251,7 @@ pub(super) struct TableBuilder {
 	table: Table,
 	current_index: Option<BucketIndex>,
 	previous_entry: Vec<u8>,
+	entries: u64,
 }
 
 impl TableBuilder {
@@ -72,9 +260,19 @@ impl TableBuilder {
 			table: Table::new(depth, Vec::new()),
 			current_index: None,
 			previous_entry: Vec::new(),
+			entries: 0,
 		}
 	}
 
+	pub(super) fn depth(&self) -> Depth {
+		self.table.depth
+	}
+
+	/// Number of keys written
+	pub(super) fn entries(&self) -> u64 {
+		self.entries
+	}
+
 	fn fill_index<W: io::Seek>(&mut self, database: &mut W, index: BucketIndex) -> io::Result<()> {
 		if let Some(cur_ndx) = self.current_index {
 			debug_assert!(self.table.file_offsets.len() == cur_ndx.entry() + 1);
@@ -93,14 +291,20 @@ impl TableBuilder {
 		&mut self,
 		database: &mut W,
 		key: &[u8],
-	) -> io::Result<()> {
+	) -> Result<(), BuilderAddError> {
 		if self.previous_entry.is_empty() {
 			debug_assert!(self.current_index.is_none());
 			self.previous_entry = key.to_vec();
 		} else {
 			debug_assert!(self.current_index.is_some());
 			debug_assert!(self.previous_entry.len() == key.len());
-			assert!(self.previous_entry.as_slice() < key);
+			if self.previous_entry.as_slice() >= key {
+				return Err(BuilderAddError::OutOfOrder {
+					previous: self.previous_entry.clone(),
+					key: key.to_vec(),
+					line_hint: self.entries + 1,
+				});
+			}
 			self.previous_entry.copy_from_slice(key);
 		}
 		let ndx = self.table.depth.index(key);
@@ -108,26 +312,24 @@ impl TableBuilder {
 		let k_suffix = self.table.depth.prepare_key(key);
 		database.write_all(k_suffix.first_byte())?;
 		database.write_all(k_suffix.remaining_bytes())?;
+		self.entries += 1;
 		Ok(())
 	}
 
-	pub(super) fn close<W: io::Write + io::Seek>(&mut self, database: &mut W) -> io::Result<()> {
-		let table_start = database.stream_position()?;
+	/// End all remaining buckets at the current position (end of entries)
+	pub(super) fn finish_entries<W: io::Seek>(&mut self, database: &mut W) -> io::Result<()> {
+		let end = database.stream_position()?;
 		let entries = self.table.depth.table_entries();
-		self.table.file_offsets.resize(entries, table_start);
-		let mut tbl_writer =
-			flate2::write::DeflateEncoder::new(database.by_ref(), flate2::Compression::default());
-		tbl_writer.write_u8(self.table.depth.as_u8())?;
-		for &p in &self.table.file_offsets {
-			tbl_writer.write_u64::<BE>(p)?;
-		}
-		tbl_writer.flush()?;
-		drop(tbl_writer);
-		let table_size = database.stream_position()? - table_start;
-		// due to limited table depth this shouldn't exceed u32 ever.
-		assert!(table_size < (u32::MAX as u64));
-		database.write_u32::<BE>(table_size as u32)?;
-		database.flush()?;
+		self.table.file_offsets.resize(entries, end);
 		Ok(())
 	}
+
+	pub(super) fn close<W: io::Write + io::Seek>(
+		&mut self,
+		database: &mut W,
+		encoding: TableEncoding,
+	) -> io::Result<()> {
+		self.finish_entries(database)?;
+		self.table.write(database, encoding)
+	}
 }
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::SystemTime;
+
+use super::table::Table;
+
+/// Identity of an index file: device, inode, modification time and length
+///
+/// Replacing the file (e.g. with [`AtomicFile`](super::AtomicFile)) changes
+/// the inode, so a new index is never mixed up with an old one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(super) struct FileId {
+	dev: u64,
+	ino: u64,
+	mtime: SystemTime,
+	len: u64,
+}
+
+impl FileId {
+	/// Identity of an open file; `None` if not available on this platform
+	pub(super) fn of(file: &fs::File) -> Option<Self> {
+		Self::from_metadata(&file.metadata().ok()?)
+	}
+
+	/// Identity of a file from its metadata; `None` if not available on this platform
+	pub(super) fn from_metadata(metadata: &fs::Metadata) -> Option<Self> {
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::MetadataExt;
+			Some(Self {
+				dev: metadata.dev(),
+				ino: metadata.ino(),
+				mtime: metadata.modified().ok()?,
+				len: metadata.len(),
+			})
+		}
+		#[cfg(not(unix))]
+		{
+			let _ = metadata;
+			None
+		}
+	}
+}
+
+/// Parsed tables of an index
+#[derive(Clone)]
+pub(super) struct Tables {
+	pub(super) table: Arc<Table>,
+	pub(super) dictionary: Option<Arc<Table>>,
+	pub(super) bucket_tables: Option<Arc<Table>>,
+}
+
+// only weak references: tables are dropped with the last index using them
+struct CachedTables {
+	table: Weak<Table>,
+	dictionary: Option<Weak<Table>>,
+	bucket_tables: Option<Weak<Table>>,
+}
+
+static CACHE: Mutex<Option<HashMap<FileId, CachedTables>>> = Mutex::new(None);
+
+/// Tables of a file opened before (and still open somewhere in the process)
+pub(super) fn lookup(id: &FileId) -> Option<Tables> {
+	let cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+	let cached = cache.as_ref()?.get(id)?;
+	let upgrade = |table: &Option<Weak<Table>>| match table {
+		Some(table) => table.upgrade().map(Some),
+		None => Some(None),
+	};
+	Some(Tables {
+		table: cached.table.upgrade()?,
+		dictionary: upgrade(&cached.dictionary)?,
+		bucket_tables: upgrade(&cached.bucket_tables)?,
+	})
+}
+
+/// Remember tables of a file; also forgets tables no longer in use
+pub(super) fn insert(id: FileId, tables: &Tables) {
+	let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+	let cache = cache.get_or_insert_with(HashMap::new);
+	cache.retain(|_, cached| cached.table.strong_count() > 0);
+	cache.insert(
+		id,
+		CachedTables {
+			table: Arc::downgrade(&tables.table),
+			dictionary: tables.dictionary.as_ref().map(Arc::downgrade),
+			bucket_tables: tables.bucket_tables.as_ref().map(Arc::downgrade),
+		},
+	);
+}
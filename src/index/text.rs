@@ -0,0 +1,154 @@
+use std::io::{self, BufRead, Read};
+use std::marker::PhantomData;
+
+use crate::{
+	buf_read::{BufReader, FileLen, ReadAt, DEFAULT_CACHE_SIZE},
+	data::KeyData,
+	errors::TextLookupError,
+};
+
+/// Upper limit for the length of a line (including line break)
+const MAX_LINE_LENGTH: u64 = 1024;
+
+/// Parsed line of a HIBP text file
+struct Line<D> {
+	key: D,
+	count: u64,
+	// file offset of the next line
+	next: u64,
+}
+
+/// Lookups in a HIBP text file ordered by hash, without building an index
+///
+/// Uses a binary search over the `HASH:count` lines of the original
+/// `pwned-passwords-*-ordered-by-hash-*.txt` download (uncompressed; empty
+/// lines and `\r\n` line breaks are fine), so a lookup needs about
+/// `log2(file size)` random reads instead of one or two. Use it until a
+/// [`TypedIndex`](super::TypedIndex) is built from the file.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use hibp_index::data::SHA1;
+/// use hibp_index::index::TextIndex;
+///
+/// let text = TextIndex::<SHA1, _>::open(std::fs::File::open(
+///     "pwned-passwords-sha1-ordered-by-hash-v7.txt",
+/// )?)?;
+/// let count = text.lookup(&SHA1::hash(b"password"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TextIndex<D, R> {
+	database: R,
+	len: u64,
+	_marker: PhantomData<D>,
+}
+
+impl<D, R> TextIndex<D, R>
+where
+	D: KeyData,
+	R: ReadAt + FileLen,
+{
+	/// Open HIBP text file (file must be ordered by hash)
+	pub fn open(database: R) -> io::Result<Self> {
+		let len = database.file_len()?;
+		Ok(Self { database, len, _marker: PhantomData })
+	}
+}
+
+impl<D, R> TextIndex<D, R>
+where
+	D: KeyData,
+	R: ReadAt,
+{
+	/// Size of the text file
+	pub fn file_len(&self) -> u64 {
+		self.len
+	}
+
+	/// Get back the underlying reader
+	pub fn into_inner(self) -> R {
+		self.database
+	}
+
+	/// Lookup key; returns its count if found
+	pub fn lookup(&self, key: &D) -> Result<Option<u64>, TextLookupError> {
+		let mut reader = BufReader::with_cache_size(&self.database, DEFAULT_CACHE_SIZE);
+		let key = key.data();
+		// `low` is always the start of a line; all lines starting before it
+		// have smaller keys, all lines starting at `high` or later larger keys
+		let mut low = 0;
+		let mut high = self.len;
+		while low < high {
+			let mid = low + (high - low) / 2;
+			let Some((start, line)) = self.next_line(&mut reader, mid)? else {
+				high = mid;
+				continue;
+			};
+			if start >= high {
+				high = mid;
+				continue;
+			}
+			match line.key.data().cmp(key) {
+				std::cmp::Ordering::Less => low = line.next,
+				std::cmp::Ordering::Equal => return Ok(Some(line.count)),
+				std::cmp::Ordering::Greater => high = mid,
+			}
+		}
+		Ok(None)
+	}
+
+	/// First non-empty line starting at `position` or later (with its start offset)
+	fn next_line(
+		&self,
+		reader: &mut BufReader<'_, R>,
+		position: u64,
+	) -> Result<Option<(u64, Line<D>)>, TextLookupError> {
+		let mut buf = Vec::new();
+		if position > 0 {
+			// skip the rest of the line containing `position - 1`
+			reader.seek_from_start(position - 1);
+			self.read_line(reader, &mut buf)?;
+		} else {
+			reader.seek_from_start(0);
+		}
+		loop {
+			let start = reader.tell();
+			if start >= self.len {
+				return Ok(None);
+			}
+			self.read_line(reader, &mut buf)?;
+			let line = buf.trim_ascii_end();
+			if line.is_empty() {
+				continue;
+			}
+			let (key, count) =
+				parse_line::<D>(line).ok_or(TextLookupError::InvalidLine { offset: start })?;
+			return Ok(Some((start, Line { key, count, next: reader.tell() })));
+		}
+	}
+
+	/// Read line (including line break) into `buf`
+	fn read_line(
+		&self,
+		reader: &mut BufReader<'_, R>,
+		buf: &mut Vec<u8>,
+	) -> Result<(), TextLookupError> {
+		let start = reader.tell();
+		buf.clear();
+		(&mut *reader).take(MAX_LINE_LENGTH).read_until(b'\n', buf)?;
+		if buf.last() != Some(&b'\n') && start + (buf.len() as u64) < self.len {
+			return Err(TextLookupError::InvalidLine { offset: start });
+		}
+		Ok(())
+	}
+}
+
+/// Parse `HASH:count` line (without line break)
+fn parse_line<D: KeyData>(line: &[u8]) -> Option<(D, u64)> {
+	let colon = line.iter().position(|&c| c == b':')?;
+	let mut key = D::default();
+	hex::decode_to_slice(&line[..colon], key.data_mut()).ok()?;
+	let count = std::str::from_utf8(&line[colon + 1..]).ok()?.parse().ok()?;
+	Some((key, count))
+}
@@ -0,0 +1,281 @@
+use super::{
+	builder::{
+		parse_hibp_line, parse_hibp_line_with_count, write_header, BuildProgress, ProgressCallback,
+	},
+	checksum::ChecksumWriter,
+	format::{IndexOptions, PayloadEncryption, TableEncoding},
+	table::Table,
+	Depth, LimPrefix,
+};
+use crate::{
+	data::{Count32, KeyData, NoPayload, PayloadData},
+	errors::{BuilderAddError, BuilderCreateError},
+};
+use anyhow::Context;
+use std::io::{self, Write};
+
+/// Number of entries in each bucket of a table
+///
+/// Result of the first pass of a two-pass build (see [`TypedTwoPassBuilder`]);
+/// only needs to see the keys, ordering is verified in the second pass.
+pub struct BucketCounts {
+	depth: Depth,
+	counts: Vec<u64>,
+}
+
+impl BucketCounts {
+	/// Create empty counts for a table with given depth
+	pub fn new(depth: Depth) -> Self {
+		Self { depth, counts: vec![0; depth.table_entries() - 1] }
+	}
+
+	/// Depth of table
+	pub fn depth(&self) -> Depth {
+		self.depth
+	}
+
+	/// Count raw key
+	pub fn count_raw(&mut self, key: &[u8]) {
+		self.counts[self.depth.index(key).entry()] += 1;
+	}
+
+	/// Count key
+	pub fn count<D: KeyData>(&mut self, key: &D) {
+		self.count_raw(key.data());
+	}
+
+	/// Count key from HIBP file line (see [`TypedBuilder::add_entry_from_hibp_line`](super::TypedBuilder::add_entry_from_hibp_line))
+	pub fn count_hibp_line<D>(&mut self, line: &str) -> anyhow::Result<()>
+	where
+		D: KeyData + std::str::FromStr,
+		<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+	{
+		if let Some(hash) = parse_hibp_line::<D>(line)? {
+			self.count(&hash);
+		}
+		Ok(())
+	}
+
+	/// Total number of counted entries
+	pub fn total(&self) -> u64 {
+		self.counts.iter().sum()
+	}
+}
+
+/// Builder writing an index with a table computed in advance
+///
+/// The input needs to be read twice: first all keys are counted with
+/// [`BucketCounts`], then the entries are added (in order) to this builder.
+///
+/// As the table is known in advance the database only needs to be
+/// [`io::Write`] (no seeking), and the total number of entries is known
+/// for progress reports. The second pass must provide exactly the
+/// counted entries; otherwise [`BuilderAddError::CountMismatch`] is returned.
+///
+/// Only this builder can store the table after the header (see
+/// [`IndexOptions::leading_table`]), which allows reading the index from a
+/// stream (see [`TypedStreamReader`](super::TypedStreamReader)).
+pub struct TypedTwoPassBuilder<D, P, W> {
+	table: Table,
+	database: ChecksumWriter<W>,
+	// table was already written after the header
+	leading_table: bool,
+	table_encoding: TableEncoding,
+	entry_size: usize,
+	position: u64,
+	entries: u64,
+	total: u64,
+	previous_entry: Option<D>,
+	current_prefix: Option<LimPrefix>,
+	progress: Option<ProgressCallback>,
+	payload_encryption: PayloadEncryption,
+	_marker: std::marker::PhantomData<P>,
+}
+
+impl<D, P, W> TypedTwoPassBuilder<D, P, W>
+where
+	D: KeyData,
+	P: PayloadData,
+	W: io::Write,
+{
+	/// Create a new builder from entry counts of the first pass
+	pub fn create(
+		database: W,
+		description: &str,
+		counts: BucketCounts,
+	) -> Result<Self, BuilderCreateError> {
+		Self::create_with_options(database, description, counts, &IndexOptions::default())
+	}
+
+	/// Create a new builder with header options (e.g. format version)
+	pub fn create_with_options(
+		database: W,
+		description: &str,
+		counts: BucketCounts,
+		options: &IndexOptions,
+	) -> Result<Self, BuilderCreateError> {
+		assert!(P::SIZE < 0x100);
+		if options.payload_dictionary {
+			return Err(BuilderCreateError::PayloadDictionaryUnsupported);
+		}
+		if options.bucket_table_bits != 0 {
+			return Err(BuilderCreateError::BucketTablesUnsupported);
+		}
+		let options = &*options.for_payload::<P>();
+		let options = &*options.for_new_index()?;
+		let mut database = ChecksumWriter::new(database, options.checksum);
+		let depth = counts.depth;
+		let header_size = write_header(
+			&mut database,
+			&D::KEY_TYPE,
+			D::SIZE as u8,
+			description,
+			P::SIZE as u8,
+			depth,
+			options,
+		)?;
+		let entry_size = depth.entry_size(D::SIZE as u8, P::SIZE as u8);
+		let total = counts.total();
+		let mut data_start = header_size;
+		let table = if options.leading_table {
+			let mut table = Table::from_bucket_counts(depth, 0, entry_size, &counts.counts);
+			data_start += table.write_leading(&mut database, options.table_encoding())?;
+			table.relocate(data_start);
+			table
+		} else {
+			Table::from_bucket_counts(depth, header_size, entry_size, &counts.counts)
+		};
+		drop(counts);
+		Ok(Self {
+			table,
+			database,
+			leading_table: options.leading_table,
+			table_encoding: options.table_encoding(),
+			entry_size,
+			position: data_start,
+			entries: 0,
+			total,
+			previous_entry: None,
+			current_prefix: None,
+			progress: None,
+			payload_encryption: options.payload_encryption(),
+			_marker: std::marker::PhantomData,
+		})
+	}
+
+	/// Total number of entries (as counted in first pass)
+	pub fn total_entries(&self) -> u64 {
+		self.total
+	}
+
+	/// Set callback to report progress
+	///
+	/// The callback is called whenever entries for a new bucket (prefix of the
+	/// table depth) are started, and a final time when finishing the build.
+	pub fn set_progress<F>(&mut self, callback: F)
+	where
+		F: FnMut(&BuildProgress) + Send + 'static,
+	{
+		self.progress = Some(Box::new(callback));
+	}
+
+	fn report_progress(&mut self) {
+		if let (Some(callback), Some(prefix)) = (&mut self.progress, self.current_prefix) {
+			callback(&BuildProgress {
+				entries: self.entries,
+				bytes: self.position,
+				prefix,
+				total: Some(self.total),
+			});
+		}
+	}
+
+	/// Add entry to database (must be added in order)
+	pub fn add_entry(&mut self, key: &D, payload: &P) -> Result<(), BuilderAddError> {
+		if let Some(previous) = &self.previous_entry {
+			if previous.data() >= key.data() {
+				return Err(BuilderAddError::OutOfOrder {
+					previous: previous.data().to_vec(),
+					key: key.data().to_vec(),
+					line_hint: self.entries + 1,
+				});
+			}
+		}
+		// entry must be placed within its bucket
+		let bucket = self.table.lookup(key.data());
+		if !bucket.contains(&self.position) {
+			return Err(BuilderAddError::CountMismatch { line_hint: self.entries + 1 });
+		}
+		let k_suffix = self.table.depth().prepare_key(key.data());
+		self.database.write_all(k_suffix.first_byte())?;
+		self.database.write_all(k_suffix.remaining_bytes())?;
+		if self.payload_encryption.is_enabled() {
+			let mut payload = payload.clone();
+			self.payload_encryption.apply(key.data(), payload.data_mut());
+			self.database.write_all(payload.data())?;
+		} else {
+			self.database.write_all(payload.data())?;
+		}
+		self.position += self.entry_size as u64;
+		self.entries += 1;
+		self.previous_entry = Some(key.clone());
+		if self.progress.is_some() {
+			let prefix = self.table.depth().prefix(key.data());
+			if self.current_prefix != Some(prefix) {
+				self.current_prefix = Some(prefix);
+				self.report_progress();
+			}
+		}
+		Ok(())
+	}
+
+	/// Write index table (and checksum) for database
+	///
+	/// Fails if fewer entries were added than counted in the first pass.
+	pub fn finish(mut self) -> Result<(), BuilderAddError> {
+		if self.entries != self.total {
+			return Err(BuilderAddError::CountMismatch { line_hint: self.entries + 1 });
+		}
+		self.report_progress();
+		if !self.leading_table {
+			self.table.write(&mut self.database, self.table_encoding)?;
+		}
+		self.database.finish()?;
+		Ok(())
+	}
+}
+
+impl<D, W> TypedTwoPassBuilder<D, NoPayload, W>
+where
+	D: KeyData + std::str::FromStr,
+	<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+	W: io::Write,
+{
+	/// Add entry from HIBP file line
+	///
+	/// See [`TypedBuilder::add_entry_from_hibp_line`](super::TypedBuilder::add_entry_from_hibp_line).
+	pub fn add_entry_from_hibp_line(&mut self, line: &str) -> anyhow::Result<()> {
+		if let Some(hash) = parse_hibp_line::<D>(line)? {
+			self.add_entry(&hash, &NoPayload).context("Failed to add hash to index")?;
+		}
+		Ok(())
+	}
+}
+
+impl<D, W> TypedTwoPassBuilder<D, Count32, W>
+where
+	D: KeyData + std::str::FromStr,
+	<D as std::str::FromStr>::Err: std::error::Error + Sync + Send + 'static,
+	W: io::Write,
+{
+	/// Add entry from HIBP file line, storing the password count as payload
+	///
+	/// See [`TypedBuilder::add_entry_from_hibp_line`](super::TypedBuilder::add_entry_from_hibp_line).
+	pub fn add_entry_from_hibp_line(&mut self, line: &str) -> anyhow::Result<()> {
+		if let Some((hash, count)) = parse_hibp_line_with_count::<D>(line)? {
+			self.add_entry(&hash, &Count32::saturating_from(count))
+				.context("Failed to add hash to index")?;
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,100 @@
+//! Open (possibly compressed) input files
+//!
+//! The compression is detected from the first bytes of the input, so it
+//! works for stdin too:
+//!
+//! - gzip (always supported)
+//! - zstd (requires feature `zstd`)
+//! - xz (requires feature `xz`)
+//!
+//! ```no_run
+//! # fn main() -> std::io::Result<()> {
+//! use std::io::BufRead;
+//!
+//! let input = hibp_index::input::open("pwned-passwords-sha1-ordered-by-hash-v7.txt.gz")?;
+//! for line in input.lines() {
+//!     let line = line?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// Compression format of input data
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Compression {
+	/// Not compressed (or unknown format)
+	None,
+	/// gzip
+	Gzip,
+	/// Zstandard
+	Zstd,
+	/// xz
+	Xz,
+	/// 7-Zip archive (not supported; extract with `7z x -so` and pipe to stdin)
+	SevenZip,
+}
+
+impl Compression {
+	/// Detect format from (at least 6) first bytes of data
+	pub fn detect(data: &[u8]) -> Self {
+		if data.starts_with(&[0x1f, 0x8b]) {
+			Self::Gzip
+		} else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+			Self::Zstd
+		} else if data.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+			Self::Xz
+		} else if data.starts_with(&[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c]) {
+			Self::SevenZip
+		} else {
+			Self::None
+		}
+	}
+}
+
+fn unsupported(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+/// Wrap reader to transparently decompress data
+///
+/// Fails if the compression format isn't supported (in this build).
+pub fn decompress<R>(mut reader: R) -> io::Result<Box<dyn BufRead + Send>>
+where
+	R: BufRead + Send + 'static,
+{
+	// peek at buffered data without consuming it
+	let compression = Compression::detect(reader.fill_buf()?);
+	match compression {
+		Compression::None => Ok(Box::new(reader)),
+		Compression::Gzip => {
+			Ok(Box::new(io::BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))))
+		},
+		#[cfg(feature = "zstd")]
+		Compression::Zstd => Ok(Box::new(io::BufReader::new(zstd::Decoder::with_buffer(reader)?))),
+		#[cfg(not(feature = "zstd"))]
+		Compression::Zstd => Err(unsupported("zstd compressed input requires feature `zstd`")),
+		#[cfg(feature = "xz")]
+		Compression::Xz => {
+			Ok(Box::new(io::BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(reader))))
+		},
+		#[cfg(not(feature = "xz"))]
+		Compression::Xz => Err(unsupported("xz compressed input requires feature `xz`")),
+		Compression::SevenZip => {
+			Err(unsupported("7z archives are not supported; extract with `7z x -so` to stdin"))
+		},
+	}
+}
+
+/// Open file (or stdin for `-`) and transparently decompress it
+pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn BufRead + Send>> {
+	let path = path.as_ref();
+	if path == Path::new("-") {
+		decompress(io::BufReader::new(io::stdin()))
+	} else {
+		decompress(io::BufReader::new(fs::File::open(path)?))
+	}
+}
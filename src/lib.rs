@@ -1,11 +1,46 @@
 #![warn(missing_docs)]
+#![cfg_attr(not(feature = "std"), no_std)]
 //! # HIPB Index
 //!
 //! Lots of code to build (indexed) lists of password hashes for quick lookup.
 //!
 //! Tries to use data from <https://haveibeenpwned.com/> and might offer similar APIs one day.
 
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
 pub mod buf_read;
+#[cfg(feature = "std")]
+pub mod checker;
+#[cfg(feature = "cli")]
+pub mod config;
 pub mod data;
+#[cfg(feature = "std")]
+pub mod email;
 pub mod errors;
+#[cfg(feature = "std")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod import;
 pub mod index;
+#[cfg(feature = "std")]
+pub mod input;
+#[cfg(feature = "online")]
+pub mod online;
+pub mod policy;
+pub mod pwdump;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+pub mod range_dir;
+
+// used by `#[derive(KeyData)]`
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+	pub use hex;
+}
@@ -0,0 +1,203 @@
+use std::time::Duration;
+
+use super::{retry::RateLimiter, send_request, RetryPolicy};
+use crate::errors::BreachApiError;
+
+/// Base URL of the breach API (version 3)
+pub const DEFAULT_BREACH_API_URL: &str = "https://haveibeenpwned.com/api/v3/";
+
+/// Breach as returned by the breach API
+///
+/// See the [API documentation](https://haveibeenpwned.com/API/v3#BreachModel)
+/// for the meaning of the fields; fields missing in a response get their
+/// default value.
+#[derive(Clone, PartialEq, Eq, Debug, Default, serde::Deserialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct Breach {
+	/// Unique name of the breach (used to look it up with [`BreachClient::breach`])
+	pub name: String,
+	/// Descriptive title
+	pub title: String,
+	/// Domain of the breached site (might be empty)
+	pub domain: String,
+	/// Date of the breach
+	pub breach_date: chrono::NaiveDate,
+	/// When the breach was added to the service
+	pub added_date: chrono::DateTime<chrono::Utc>,
+	/// When the breach was last modified
+	pub modified_date: chrono::DateTime<chrono::Utc>,
+	/// Number of accounts in the breach
+	pub pwn_count: u64,
+	/// Description (HTML)
+	pub description: String,
+	/// URL of the logo
+	pub logo_path: String,
+	/// Kinds of data in the breach (e.g. "Email addresses", "Passwords")
+	pub data_classes: Vec<String>,
+	/// Whether the breach is verified
+	pub is_verified: bool,
+	/// Whether the breach is likely fabricated
+	pub is_fabricated: bool,
+	/// Whether the breach is sensitive (not returned in public searches)
+	pub is_sensitive: bool,
+	/// Whether the breach was retired
+	pub is_retired: bool,
+	/// Whether the data is a spam list
+	pub is_spam_list: bool,
+	/// Whether the data comes from malware
+	pub is_malware: bool,
+	/// Whether the breach can be searched without a subscription
+	pub is_subscription_free: bool,
+	/// Whether the data comes from info-stealer logs
+	pub is_stealer_log: bool,
+}
+
+/// Client for the breach API of Have I Been Pwned (feature `breaches`)
+///
+/// Searching breaches of an account needs an
+/// [API key](https://haveibeenpwned.com/API/Key); listing breaches doesn't.
+/// Requests are retried like those of [`RangeClient`](super::RangeClient)
+/// (including `429 Too Many Requests` with `Retry-After`); set the rate
+/// limit of the API key with [`BreachClient::with_rate_limit`] to avoid
+/// running into it in the first place.
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use hibp_index::online::BreachClient;
+///
+/// let client = BreachClient::new().with_api_key("0123456789abcdef").with_rate_limit(10);
+/// for breach in client.breached_account("test@example.com")? {
+///     println!("{} ({}): {} accounts", breach.title, breach.breach_date, breach.pwn_count);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct BreachClient {
+	agent: ureq::Agent,
+	base_url: String,
+	api_key: Option<String>,
+	retry: RetryPolicy,
+	rate_limiter: Option<RateLimiter>,
+}
+
+impl BreachClient {
+	/// Client for the public API ([`DEFAULT_BREACH_API_URL`])
+	pub fn new() -> Self {
+		Self::with_base_url(DEFAULT_BREACH_API_URL)
+	}
+
+	/// Client for a compatible API; paths like `breaches` are appended to `base_url`
+	pub fn with_base_url(base_url: &str) -> Self {
+		let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+		Self {
+			agent: ureq::AgentBuilder::new().user_agent(user_agent).build(),
+			base_url: base_url.to_string(),
+			api_key: None,
+			retry: RetryPolicy::default(),
+			rate_limiter: None,
+		}
+	}
+
+	/// Set API key (sent as `hibp-api-key` header)
+	pub fn with_api_key(mut self, api_key: &str) -> Self {
+		self.api_key = Some(api_key.to_string());
+		self
+	}
+
+	/// Set user agent (default: crate name and version)
+	///
+	/// The API rejects requests without a user agent; it should identify the
+	/// application using the API.
+	pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+		self.agent = ureq::AgentBuilder::new().user_agent(user_agent).build();
+		self
+	}
+
+	/// Set how to retry failed requests (default: [`RetryPolicy::default`])
+	pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+		self.retry = retry;
+		self
+	}
+
+	/// Send at most `requests_per_minute` requests (shared by all clones of the client)
+	///
+	/// The limit depends on the subscription of the API key. Panics if
+	/// `requests_per_minute` is zero.
+	pub fn with_rate_limit(mut self, requests_per_minute: u32) -> Self {
+		assert!(requests_per_minute > 0, "rate limit must be positive");
+		let interval = Duration::from_secs(60) / requests_per_minute;
+		self.rate_limiter = Some(RateLimiter::with_interval(interval));
+		self
+	}
+
+	/// Base URL paths are appended to
+	pub fn base_url(&self) -> &str {
+		&self.base_url
+	}
+
+	/// All breaches an account (email address or username) appears in (needs API key)
+	///
+	/// Returns full breach records (including unverified breaches); empty if
+	/// the account wasn't found.
+	pub fn breached_account(&self, account: &str) -> Result<Vec<Breach>, BreachApiError> {
+		let api_key = self.api_key.as_deref().ok_or(BreachApiError::MissingApiKey)?;
+		let url = format!("{}breachedaccount/{}", self.base_url, encode_path_segment(account));
+		let request = self
+			.agent
+			.get(&url)
+			.set("hibp-api-key", api_key)
+			.query("truncateResponse", "false")
+			.query("includeUnverified", "true");
+		Ok(self.get_json(&request)?.unwrap_or_default())
+	}
+
+	/// All breaches in the system
+	pub fn breaches(&self) -> Result<Vec<Breach>, BreachApiError> {
+		let request = self.agent.get(&format!("{}breaches", self.base_url));
+		Ok(self.get_json(&request)?.unwrap_or_default())
+	}
+
+	/// All breaches of a domain (e.g. `adobe.com`)
+	pub fn breaches_for_domain(&self, domain: &str) -> Result<Vec<Breach>, BreachApiError> {
+		let request = self.agent.get(&format!("{}breaches", self.base_url)).query("domain", domain);
+		Ok(self.get_json(&request)?.unwrap_or_default())
+	}
+
+	/// Single breach by its name (`None` if not found)
+	pub fn breach(&self, name: &str) -> Result<Option<Breach>, BreachApiError> {
+		let url = format!("{}breach/{}", self.base_url, encode_path_segment(name));
+		self.get_json(&self.agent.get(&url))
+	}
+
+	/// Send request and parse the JSON response (`None` for `404 Not Found`)
+	fn get_json<T>(&self, request: &ureq::Request) -> Result<Option<T>, BreachApiError>
+	where
+		T: serde::de::DeserializeOwned,
+	{
+		let response = send_request(request, &self.retry, self.rate_limiter.as_ref(), &[404], Ok)?;
+		if response.status == 404 {
+			return Ok(None);
+		}
+		Ok(Some(serde_json::from_str(&response.body)?))
+	}
+}
+
+impl Default for BreachClient {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Percent-encode everything but unreserved characters (RFC 3986)
+fn encode_path_segment(value: &str) -> String {
+	let mut encoded = String::with_capacity(value.len());
+	for &byte in value.as_bytes() {
+		if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+			encoded.push(byte as char);
+		} else {
+			encoded.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	encoded
+}
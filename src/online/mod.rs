@@ -0,0 +1,449 @@
+//! Client for the online range API of Pwned Passwords (feature `online`)
+//!
+//! Uses the [k-anonymity model](https://haveibeenpwned.com/API/v3#SearchingPwnedPasswordsByRange):
+//! only the first 5 hex digits (20 bits) of a hash are sent; all hashes with
+//! this prefix are returned and searched locally. Responses are padded (with
+//! entries of count 0, which are skipped) to hide their size.
+//!
+//! Fetched ranges can be cached as hash lists (see
+//! [`RangeClient::with_cache_dir`]), mirrored in a local directory (see
+//! [`RangeUpdater`]) or streamed into a new index (see
+//! [`RangeClient::build_index`]).
+//!
+//! With the `breaches` feature [`BreachClient`] also queries the breach API
+//! (breaches of an email address or username, and the list of all breaches).
+//!
+//! Can be used instead of a local index (or when none is available):
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::data::SHA1;
+//! use hibp_index::online::RangeClient;
+//!
+//! let client = RangeClient::new();
+//! let found = client.lookup(&SHA1::hash(b"password"))?.is_some();
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "breaches")]
+mod breaches;
+mod retry;
+mod stream;
+mod updater;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::TimeZone;
+
+use crate::{
+	data::{Count64, KeyData, KnownKeyType, NoPayload},
+	errors::LookupError,
+	index::{AtomicFile, Depth, LimPrefix, TypedListReader, TypedListWriter},
+};
+
+pub use self::{
+	retry::RetryPolicy,
+	updater::{DownloadProgress, RangeFailure, RangeUpdater, UpdateSummary},
+};
+
+#[cfg(feature = "breaches")]
+pub use self::breaches::{Breach, BreachClient, DEFAULT_BREACH_API_URL};
+
+use self::retry::RateLimiter;
+
+/// Base URL of the range API; the 5 hex digit prefix is appended
+pub const DEFAULT_RANGE_API_URL: &str = "https://api.pwnedpasswords.com/range/";
+
+/// Length of the prefix sent to the API (in bits)
+const PREFIX_DEPTH: Depth = Depth::DEPTH20;
+
+/// Version of a range as reported by the server
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct RangeVersion {
+	/// `ETag` header
+	pub etag: Option<String>,
+	/// `Last-Modified` header
+	pub last_modified: Option<String>,
+}
+
+impl RangeVersion {
+	fn from_response(response: &ureq::Response) -> Self {
+		Self {
+			etag: response.header("ETag").map(str::to_string),
+			last_modified: response.header("Last-Modified").map(str::to_string),
+		}
+	}
+
+	/// Whether the server sent any version information
+	pub fn is_known(&self) -> bool {
+		self.etag.is_some() || self.last_modified.is_some()
+	}
+}
+
+/// Client for the online range API
+///
+/// Supports SHA-1 and NT hashes; returns the same result types as
+/// [`TypedIndex::lookup`](crate::index::TypedIndex::lookup), with network and
+/// protocol errors reported as IO errors.
+#[derive(Clone, Debug)]
+pub struct RangeClient {
+	agent: ureq::Agent,
+	base_url: String,
+	cache_dir: Option<PathBuf>,
+	cache_max_age: Option<Duration>,
+	retry: RetryPolicy,
+	rate_limiter: Option<RateLimiter>,
+}
+
+impl RangeClient {
+	/// Client for the public API ([`DEFAULT_RANGE_API_URL`])
+	pub fn new() -> Self {
+		Self::with_base_url(DEFAULT_RANGE_API_URL)
+	}
+
+	/// Client for a compatible API (e.g. a mirror); the prefix is appended to `base_url`
+	pub fn with_base_url(base_url: &str) -> Self {
+		let user_agent = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+		Self {
+			agent: ureq::AgentBuilder::new().user_agent(user_agent).build(),
+			base_url: base_url.to_string(),
+			cache_dir: None,
+			cache_max_age: None,
+			retry: RetryPolicy::default(),
+			rate_limiter: None,
+		}
+	}
+
+	/// Set how to retry failed requests (default: [`RetryPolicy::default`])
+	pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+		self.retry = retry;
+		self
+	}
+
+	/// Send at most `requests_per_second` requests (shared by all clones of the client)
+	///
+	/// Panics if `requests_per_second` is zero.
+	pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+		self.rate_limiter = Some(RateLimiter::new(requests_per_second));
+		self
+	}
+
+	/// Cache fetched ranges as hash lists (see [`TypedListReader`]) in given directory
+	///
+	/// Repeated queries for a prefix are served from the cache; the directory
+	/// must exist.
+	pub fn with_cache_dir<P: Into<PathBuf>>(mut self, cache_dir: P) -> Self {
+		self.cache_dir = Some(cache_dir.into());
+		self
+	}
+
+	/// Fetch ranges again if cached longer than `max_age` (default: cache never expires)
+	pub fn with_cache_max_age(mut self, max_age: Duration) -> Self {
+		self.cache_max_age = Some(max_age);
+		self
+	}
+
+	/// Base URL the prefix is appended to
+	pub fn base_url(&self) -> &str {
+		&self.base_url
+	}
+
+	/// Fetch all hashes (and their breach counts) with the same 20-bit prefix as `key`
+	///
+	/// Only the prefix of `key` is transmitted.
+	pub fn fetch_range<D>(&self, key: &D) -> Result<Vec<(D, u64)>, LookupError>
+	where
+		D: KeyData,
+	{
+		self.fetch_prefix(PREFIX_DEPTH.prefix(key.data()))
+	}
+
+	/// Fetch all hashes (and their breach counts) with given 20-bit prefix
+	///
+	/// Uses (and fills) the cache if enabled.
+	pub fn fetch_prefix<D>(&self, prefix: LimPrefix) -> Result<Vec<(D, u64)>, LookupError>
+	where
+		D: KeyData,
+	{
+		assert_eq!(prefix.depth(), PREFIX_DEPTH, "range API needs 20-bit prefixes");
+		let Some(cache_dir) = &self.cache_dir else {
+			return self.download_range(prefix);
+		};
+		let path = cache_dir.join(range_file_name::<D>(prefix));
+		if let Some(range) = read_range_file(&path)? {
+			let age = unix_now().saturating_sub(range.mtime);
+			if self.cache_max_age.is_none_or(|max_age| age <= max_age.as_secs() as i64) {
+				return Ok(range.entries);
+			}
+		}
+		let entries = self.download_range(prefix)?;
+		write_range_file(&path, prefix, &entries)?;
+		Ok(entries)
+	}
+
+	fn range_url<D>(&self, prefix: LimPrefix) -> Result<String, LookupError>
+	where
+		D: KeyData,
+	{
+		let mode = match D::KEY_TYPE.as_known() {
+			Some(KnownKeyType::SHA1) => "",
+			Some(KnownKeyType::NT) => "?mode=ntlm",
+			_ => {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					format!("range API doesn't support key type {}", D::KEY_TYPE.name()),
+				)
+				.into())
+			},
+		};
+		Ok(format!("{}{}{}", self.base_url, prefix.hex(), mode))
+	}
+
+	fn download_range<D>(&self, prefix: LimPrefix) -> Result<Vec<(D, u64)>, LookupError>
+	where
+		D: KeyData,
+	{
+		let download = self.download_prefix(prefix, None)?.expect("unconditional request");
+		Ok(download.entries)
+	}
+
+	/// Download range
+	///
+	/// With a `known` version sends a conditional request and returns `None`
+	/// if the range didn't change (`304 Not Modified`).
+	fn download_prefix<D>(
+		&self,
+		prefix: LimPrefix,
+		known: Option<&RangeVersion>,
+	) -> Result<Option<RangeDownload<D>>, LookupError>
+	where
+		D: KeyData,
+	{
+		let url = self.range_url::<D>(prefix)?;
+		let mut request = self.agent.get(&url).set("Add-Padding", "true");
+		if let Some(known) = known {
+			if let Some(etag) = &known.etag {
+				request = request.set("If-None-Match", etag);
+			}
+			if let Some(last_modified) = &known.last_modified {
+				request = request.set("If-Modified-Since", last_modified);
+			}
+		}
+		// corrupt (e.g. truncated) responses are retried like failed requests
+		let download =
+			send_request(&request, &self.retry, self.rate_limiter.as_ref(), &[], |response| {
+				if response.status == 304 {
+					return Ok(None);
+				}
+				let entries = parse_range(prefix, &response.body)?;
+				let bytes = response.body.len() as u64;
+				Ok(Some(RangeDownload { entries, version: response.version, bytes }))
+			})?;
+		Ok(download)
+	}
+
+	/// Breach count of hash (`None` if not found)
+	pub fn lookup_count<D>(&self, key: &D) -> Result<Option<u64>, LookupError>
+	where
+		D: KeyData,
+	{
+		let entries = self.fetch_range(key)?;
+		Ok(entries
+			.into_iter()
+			.find(|(entry, _)| entry.data() == key.data())
+			.map(|(_, count)| count))
+	}
+
+	/// Search for hash
+	pub fn lookup<D>(&self, key: &D) -> Result<Option<NoPayload>, LookupError>
+	where
+		D: KeyData,
+	{
+		Ok(self.lookup_count(key)?.map(|_| NoPayload))
+	}
+}
+
+/// Parse and validate range response
+///
+/// Each line must contain a hex suffix of the right length and a count;
+/// suffixes (except for padding entries with count 0) must be strictly
+/// increasing.
+fn parse_range<D>(prefix: LimPrefix, body: &str) -> Result<Vec<(D, u64)>, String>
+where
+	D: KeyData,
+{
+	let mut entries: Vec<(D, u64)> = Vec::new();
+	for line in body.lines() {
+		let line = line.trim_end();
+		if line.is_empty() {
+			continue;
+		}
+		let invalid = || format!("invalid range line {:?}", line);
+		let (suffix, count) = line.split_once(':').ok_or_else(invalid)?;
+		let count = count.parse::<u64>().map_err(|_| invalid())?;
+		let key = prefix.read_key_from_suffix_hex::<D>(suffix.as_bytes()).map_err(|_| invalid())?;
+		if count == 0 {
+			// padding
+			continue;
+		}
+		if let Some((last, _)) = entries.last() {
+			if last.data() >= key.data() {
+				return Err(format!("range not ordered at line {:?}", line));
+			}
+		}
+		entries.push((key, count));
+	}
+	Ok(entries)
+}
+
+/// All prefixes of the range API
+fn all_prefixes() -> impl Iterator<Item = LimPrefix> {
+	PREFIX_DEPTH.prefix_range_raw(&[], 0)
+}
+
+fn unix_now() -> i64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64)
+}
+
+/// File name of cached range (hash list)
+fn range_file_name<D>(prefix: LimPrefix) -> String
+where
+	D: KeyData,
+{
+	format!("{}-{}.hashlist", D::KEY_TYPE.name(), prefix.hex())
+}
+
+/// Send request (rate limited, retrying on failures) and parse the response
+///
+/// Client errors in `accept` (e.g. `404 Not Found`) are passed to `parse`
+/// instead of returning an error. Responses `parse` rejects count as failed
+/// attempts (all attempts share the limit of the retry policy).
+fn send_request<T, F>(
+	request: &ureq::Request,
+	retry_policy: &RetryPolicy,
+	rate_limiter: Option<&RateLimiter>,
+	accept: &[u16],
+	mut parse: F,
+) -> io::Result<T>
+where
+	F: FnMut(Response) -> Result<T, String>,
+{
+	let mut retry = 0;
+	loop {
+		if let Some(rate_limiter) = rate_limiter {
+			rate_limiter.wait();
+		}
+		let result = match request.clone().call() {
+			Err(ureq::Error::Status(status, response)) if accept.contains(&status) => Ok(response),
+			result => result,
+		};
+		let (error, retry_after) = match result {
+			Ok(response) => {
+				let status = response.status();
+				let version = RangeVersion::from_response(&response);
+				match response.into_string() {
+					Ok(body) => match parse(Response { status, version, body }) {
+						Ok(value) => return Ok(value),
+						Err(e) => (
+							io::Error::new(
+								io::ErrorKind::InvalidData,
+								format!("{}: {}", request.url(), e),
+							),
+							None,
+						),
+					},
+					Err(e) => (e, None),
+				}
+			},
+			Err(ureq::Error::Status(status, response)) if status == 429 || status >= 500 => {
+				let retry_after = response
+					.header("Retry-After")
+					.and_then(|value| value.parse::<u64>().ok())
+					.map(Duration::from_secs);
+				let error = io::Error::other(format!("{}: status code {}", request.url(), status));
+				(error, retry_after)
+			},
+			Err(e @ ureq::Error::Status(..)) => return Err(io::Error::other(e.to_string())),
+			Err(e @ ureq::Error::Transport(_)) => (io::Error::other(e.to_string()), None),
+		};
+		retry += 1;
+		if retry >= retry_policy.max_attempts {
+			return Err(error);
+		}
+		std::thread::sleep(retry_after.unwrap_or_else(|| retry_policy.backoff(retry)));
+	}
+}
+
+/// Response of an API request
+struct Response {
+	status: u16,
+	version: RangeVersion,
+	body: String,
+}
+
+/// Downloaded range
+struct RangeDownload<D> {
+	entries: Vec<(D, u64)>,
+	version: RangeVersion,
+	/// Size of response body
+	bytes: u64,
+}
+
+/// Range stored as hash list
+struct RangeFile<D> {
+	/// Unix timestamp of download
+	mtime: i64,
+	entries: Vec<(D, u64)>,
+}
+
+/// Read cached range (`None` if the file doesn't exist)
+fn read_range_file<D>(path: &Path) -> Result<Option<RangeFile<D>>, LookupError>
+where
+	D: KeyData,
+{
+	let file = match fs::File::open(path) {
+		Ok(file) => file,
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		Err(e) => return Err(e.into()),
+	};
+	let mut reader = TypedListReader::<D, Count64, _>::open(io::BufReader::new(file))
+		.map_err(io::Error::other)?;
+	let mut entries = Vec::new();
+	while let Some(entry) = reader.next_entry() {
+		let (key, count) = entry?;
+		entries.push((key, count.value()));
+	}
+	Ok(Some(RangeFile { mtime: reader.mtime().timestamp(), entries }))
+}
+
+fn write_range_file<D>(path: &Path, prefix: LimPrefix, entries: &[(D, u64)]) -> io::Result<()>
+where
+	D: KeyData,
+{
+	let mut key = D::default();
+	prefix.set_key_prefix(key.data_mut());
+	let mut file = io::BufWriter::new(AtomicFile::create(path)?);
+	let mut writer = TypedListWriter::<D, Count64, _>::create(
+		&mut file,
+		"Pwned Passwords range",
+		chrono::Utc.timestamp_opt(unix_now(), 0).single().expect("valid timestamp"),
+		key.prefix(PREFIX_DEPTH.as_u8() as u32),
+	)
+	.map_err(io::Error::other)?;
+	for (key, count) in entries {
+		writer.add(key, &Count64::new(*count))?;
+	}
+	drop(writer);
+	file.flush()?;
+	file.into_inner().map_err(|e| e.into_error())?.commit()
+}
+
+impl Default for RangeClient {
+	fn default() -> Self {
+		Self::new()
+	}
+}
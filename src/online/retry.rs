@@ -0,0 +1,70 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often and how fast to retry failed requests
+///
+/// Network errors, `429 Too Many Requests` and server errors (5xx) are
+/// retried; the delay doubles after each attempt (a `Retry-After` header
+/// with seconds overrides it).
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct RetryPolicy {
+	/// Maximum number of attempts (including the first)
+	pub max_attempts: u32,
+	/// Delay before the first retry
+	pub initial_backoff: Duration,
+	/// Upper limit for the delay between attempts
+	pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+	/// Don't retry failed requests
+	pub fn none() -> Self {
+		Self { max_attempts: 1, ..Self::default() }
+	}
+
+	/// Delay before given retry (starting with 1)
+	pub(super) fn backoff(&self, retry: u32) -> Duration {
+		let factor = 1u32.checked_shl(retry - 1).unwrap_or(u32::MAX);
+		self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+	}
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: 5,
+			initial_backoff: Duration::from_secs(1),
+			max_backoff: Duration::from_secs(60),
+		}
+	}
+}
+
+/// Limits the request rate; shared by all clones of a client
+#[derive(Clone, Debug)]
+pub(super) struct RateLimiter {
+	interval: Duration,
+	next: Arc<Mutex<Instant>>,
+}
+
+impl RateLimiter {
+	pub(super) fn new(requests_per_second: u32) -> Self {
+		assert!(requests_per_second > 0, "rate limit must be positive");
+		Self::with_interval(Duration::from_secs(1) / requests_per_second)
+	}
+
+	/// Allow one request per `interval`
+	pub(super) fn with_interval(interval: Duration) -> Self {
+		Self { interval, next: Arc::new(Mutex::new(Instant::now())) }
+	}
+
+	/// Wait until the next request is allowed
+	pub(super) fn wait(&self) {
+		// keep lock while sleeping so waiting requests are queued
+		let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+		let now = Instant::now();
+		if *next > now {
+			std::thread::sleep(*next - now);
+		}
+		*next = std::cmp::max(now, *next) + self.interval;
+	}
+}
@@ -0,0 +1,84 @@
+use std::io;
+use std::thread;
+
+use crate::{
+	data::{KeyData, NoPayload},
+	errors::{LookupError, RangeUpdateError},
+	index::{LimPrefix, TypedBuilder},
+};
+
+use super::{all_prefixes, RangeClient};
+
+/// Number of ranges fetched per worker before they are added to the builder
+const RANGES_PER_WORKER: usize = 16;
+
+type RangeResult<D> = Result<Vec<(D, u64)>, LookupError>;
+
+impl RangeClient {
+	/// Download all ranges and add them to builder (then finish it)
+	///
+	/// Ranges are fetched in prefix order and directly added to the builder,
+	/// i.e. they aren't stored on disk (unlike [`RangeUpdater`](super::RangeUpdater),
+	/// which can skip unchanged ranges instead). `workers` ranges are fetched
+	/// concurrently; a range failing after all retries aborts the build.
+	///
+	/// Use [`TypedBuilder::set_progress`] to track progress.
+	pub fn build_index<D, W>(
+		&self,
+		mut builder: TypedBuilder<D, NoPayload, W>,
+		workers: usize,
+	) -> Result<(), RangeUpdateError>
+	where
+		D: KeyData + Send,
+		W: io::Write + io::Seek,
+	{
+		let workers = workers.max(1);
+		let mut prefixes = all_prefixes();
+		loop {
+			let batch: Vec<LimPrefix> =
+				prefixes.by_ref().take(workers * RANGES_PER_WORKER).collect();
+			if batch.is_empty() {
+				break;
+			}
+			for (prefix, result) in batch.iter().zip(self.download_batch::<D>(&batch, workers)) {
+				let entries = result.map_err(|error| RangeUpdateError::DownloadFailed {
+					prefix: prefix.hex().to_string(),
+					error,
+				})?;
+				for (key, _count) in entries {
+					builder.add_entry(&key, &NoPayload)?;
+				}
+			}
+		}
+		builder.finish()?;
+		Ok(())
+	}
+
+	/// Download ranges concurrently; results in order of `prefixes`
+	fn download_batch<D>(&self, prefixes: &[LimPrefix], workers: usize) -> Vec<RangeResult<D>>
+	where
+		D: KeyData + Send,
+	{
+		let chunk_size = prefixes.len().div_ceil(workers);
+		thread::scope(|scope| {
+			let handles: Vec<_> = prefixes
+				.chunks(chunk_size)
+				.map(|chunk| {
+					scope.spawn(move || {
+						chunk
+							.iter()
+							.map(|&prefix| {
+								let download = self.download_prefix::<D>(prefix, None)?;
+								Ok(download.expect("unconditional request").entries)
+							})
+							.collect::<Vec<_>>()
+					})
+				})
+				.collect();
+			handles
+				.into_iter()
+				.flat_map(|handle| handle.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+				.collect()
+		})
+	}
+}
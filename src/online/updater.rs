@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{
+	data::{KeyData, NoPayload},
+	errors::RangeUpdateError,
+	index::{AtomicFile, LimPrefix, TypedBuilder},
+};
+
+use super::{
+	all_prefixes, range_file_name, read_range_file, write_range_file, RangeClient, RangeVersion,
+};
+
+/// File name of the manifest in the range directory
+const MANIFEST_FILE: &str = "manifest.tsv";
+
+/// File name suffix of the checkpoint (of an unfinished update) in the range
+/// directory; prefixed with the key type, as the range directory is shared
+const CHECKPOINT_FILE_SUFFIX: &str = "-update.checkpoint";
+
+/// Default number of ranges between checkpoints
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 1000;
+
+/// Default maximum age of an unfinished update to resume
+const DEFAULT_RESUME_MAX_AGE: Duration = Duration::from_secs(24 * 3600);
+
+/// Start of first line of a checkpoint, followed by the start time of its update
+const CHECKPOINT_HEADER: &str = "started ";
+
+/// Seconds since the Unix epoch
+fn unix_now() -> u64 {
+	std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Ranges already checked by an unfinished update
+struct Checkpoint {
+	/// Start of the update (seconds since the Unix epoch)
+	started: u64,
+	/// Prefixes (in hex) of the checked ranges
+	prefixes: BTreeSet<String>,
+}
+
+/// Progress of a running update
+///
+/// Passed to the progress callback of a [`RangeUpdater`] after each range.
+#[derive(Clone, Copy, Debug)]
+pub struct DownloadProgress {
+	/// Number of ranges processed so far (including failed and resumed ones)
+	pub completed: u64,
+	/// Number of ranges skipped because an interrupted update already checked them
+	pub resumed: u64,
+	/// Total number of ranges if known in advance
+	pub total: Option<u64>,
+	/// Number of ranges (re-)downloaded so far
+	pub changed: u64,
+	/// Number of failed ranges so far
+	pub failed: u64,
+	/// Number of (response body) bytes fetched so far
+	pub bytes: u64,
+	/// Prefix of the last processed range
+	pub prefix: LimPrefix,
+	/// Time since start of update
+	pub elapsed: Duration,
+}
+
+impl DownloadProgress {
+	/// Estimated time until all ranges are processed (extrapolated from the rate so far)
+	pub fn eta(&self) -> Option<Duration> {
+		let remaining = self.total?.checked_sub(self.completed)?;
+		// resumed ranges are skipped without requests
+		let processed = self.completed - self.resumed;
+		if processed == 0 {
+			return None;
+		}
+		let per_range = self.elapsed.as_secs_f64() / processed as f64;
+		Some(Duration::from_secs_f64(per_range * remaining as f64))
+	}
+}
+
+type ProgressCallback = Box<dyn FnMut(&DownloadProgress) + Send>;
+
+/// Range that couldn't be updated
+#[derive(Debug)]
+pub struct RangeFailure {
+	/// Prefix of range
+	pub prefix: LimPrefix,
+	/// Why the update failed
+	pub error: RangeUpdateError,
+}
+
+/// Result of [`RangeUpdater::update`]
+#[derive(Debug, Default)]
+pub struct UpdateSummary {
+	/// Number of ranges checked successfully
+	pub checked: u64,
+	/// Number of ranges (re-)downloaded
+	pub changed: u64,
+	/// Number of ranges skipped because an interrupted update already checked them
+	///
+	/// Included in `checked`; whether they changed is unknown.
+	pub resumed: u64,
+	/// Ranges that couldn't be updated
+	pub failures: Vec<RangeFailure>,
+}
+
+impl UpdateSummary {
+	/// Whether all ranges were updated
+	pub fn is_complete(&self) -> bool {
+		self.failures.is_empty()
+	}
+}
+
+/// Mirror of all ranges of the online API in a local directory
+///
+/// Ranges are stored as hash lists (the same files [`RangeClient::with_cache_dir`]
+/// uses), and their `ETag` / `Last-Modified` headers are recorded in a
+/// manifest (`manifest.tsv`). Updates only download ranges whose version
+/// changed (using conditional requests, i.e. `If-None-Match` and
+/// `If-Modified-Since`); afterwards a new index can be
+/// built from the local ranges with [`RangeUpdater::build_index`].
+///
+/// While updating, the manifest is saved regularly and the checked ranges are
+/// recorded in a checkpoint file (e.g. `sha1-update.checkpoint`) together
+/// with the start time of the update; an interrupted update resumes with the
+/// remaining ranges if it started recently enough (see
+/// [`RangeUpdater::set_resume_max_age`]). The checkpoint is removed once an
+/// update finished without failures.
+pub struct RangeUpdater<D> {
+	client: RangeClient,
+	dir: PathBuf,
+	manifest: BTreeMap<String, RangeVersion>,
+	/// Whether the manifest changed since it was loaded / saved
+	dirty: bool,
+	checkpoint_interval: u64,
+	resume_max_age: Duration,
+	progress: Option<ProgressCallback>,
+	_marker: std::marker::PhantomData<fn() -> D>,
+}
+
+impl<D> RangeUpdater<D>
+where
+	D: KeyData,
+{
+	/// Open range directory (must exist); loads manifest if present
+	pub fn open<P: Into<PathBuf>>(client: RangeClient, dir: P) -> Result<Self, RangeUpdateError> {
+		let dir = dir.into();
+		let manifest = match fs::File::open(dir.join(MANIFEST_FILE)) {
+			Ok(file) => read_manifest(io::BufReader::new(file))?,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+			Err(e) => return Err(e.into()),
+		};
+		Ok(Self {
+			client,
+			dir,
+			manifest,
+			dirty: false,
+			checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+			resume_max_age: DEFAULT_RESUME_MAX_AGE,
+			progress: None,
+			_marker: std::marker::PhantomData,
+		})
+	}
+
+	/// Set number of ranges between checkpoints (default: 1000)
+	///
+	/// Lower values lose less work when interrupted, but write the manifest
+	/// more often.
+	pub fn set_checkpoint_interval(&mut self, ranges: u64) {
+		self.checkpoint_interval = ranges.max(1);
+	}
+
+	/// Set maximum age of an unfinished update to resume (default: 1 day)
+	///
+	/// The checkpoint of an update started longer ago is discarded: a new
+	/// update checks all ranges again instead of skipping ranges that were
+	/// checked by the old one (their data might be outdated by now). Zero
+	/// disables resuming.
+	pub fn set_resume_max_age(&mut self, max_age: Duration) {
+		self.resume_max_age = max_age;
+	}
+
+	/// Discard the checkpoint of an unfinished update (the next update checks all ranges)
+	pub fn clear_checkpoint(&self) -> io::Result<()> {
+		match fs::remove_file(self.checkpoint_path()) {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+			_ => Ok(()),
+		}
+	}
+
+	/// Set callback to report progress
+	///
+	/// Called after each range; throttle expensive output yourself.
+	pub fn set_progress<F>(&mut self, callback: F)
+	where
+		F: FnMut(&DownloadProgress) + Send + 'static,
+	{
+		self.progress = Some(Box::new(callback));
+	}
+
+	/// Range directory
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Path of the manifest
+	///
+	/// The manifest is only written if a range changed; its modification time
+	/// can be compared with an index built from the ranges.
+	pub fn manifest_path(&self) -> PathBuf {
+		self.dir.join(MANIFEST_FILE)
+	}
+
+	/// Recorded version of a range
+	pub fn version(&self, prefix: LimPrefix) -> Option<&RangeVersion> {
+		self.manifest.get(&range_file_name::<D>(prefix))
+	}
+
+	/// Update single range; returns whether it was (re-)downloaded
+	///
+	/// Doesn't save the manifest.
+	pub fn update_prefix(&mut self, prefix: LimPrefix) -> Result<bool, RangeUpdateError> {
+		Ok(self.update_range(prefix)?.is_some())
+	}
+
+	/// Returns size of downloaded data if the range changed
+	fn update_range(&mut self, prefix: LimPrefix) -> Result<Option<u64>, RangeUpdateError> {
+		let name = range_file_name::<D>(prefix);
+		let path = self.dir.join(&name);
+		// only send conditional request if the range is still there
+		let known = self.manifest.get(&name).filter(|known| known.is_known() && path.is_file());
+		let Some(download) = self.client.download_prefix::<D>(prefix, known)? else {
+			return Ok(None);
+		};
+		write_range_file(&path, prefix, &download.entries)?;
+		self.manifest.insert(name, download.version);
+		self.dirty = true;
+		Ok(Some(download.bytes))
+	}
+
+	/// Update given ranges and save manifest
+	///
+	/// Failing ranges (after the retries of the client) don't abort the
+	/// update; they are reported in [`UpdateSummary::failures`] and are
+	/// downloaded again in the next update (e.g. with the failed prefixes
+	/// only), while unchanged ranges are skipped.
+	///
+	/// Ranges recorded in the checkpoint of an unfinished update are skipped
+	/// (see [`UpdateSummary::resumed`]) unless the checkpoint is too old (see
+	/// [`RangeUpdater::set_resume_max_age`]).
+	pub fn update<I>(&mut self, prefixes: I) -> Result<UpdateSummary, RangeUpdateError>
+	where
+		I: IntoIterator<Item = LimPrefix>,
+	{
+		let prefixes = prefixes.into_iter();
+		let total = match prefixes.size_hint() {
+			(lower, Some(upper)) if lower == upper => Some(upper as u64),
+			_ => None,
+		};
+		let now = unix_now();
+		let (started, resume) = match self.read_checkpoint()? {
+			Some(checkpoint)
+				if Duration::from_secs(now.saturating_sub(checkpoint.started))
+					< self.resume_max_age =>
+			{
+				(checkpoint.started, checkpoint.prefixes)
+			},
+			Some(_) => {
+				// stale checkpoint of an old update: start over
+				self.clear_checkpoint()?;
+				(now, BTreeSet::new())
+			},
+			None => (now, BTreeSet::new()),
+		};
+		let start = Instant::now();
+		let mut summary = UpdateSummary::default();
+		let mut pending = Vec::new();
+		let mut bytes = 0;
+		for (done, prefix) in prefixes.enumerate() {
+			if resume.contains(prefix.hex().as_str()) {
+				summary.checked += 1;
+				summary.resumed += 1;
+				continue;
+			}
+			match self.update_range(prefix) {
+				Ok(downloaded) => {
+					summary.checked += 1;
+					if let Some(size) = downloaded {
+						summary.changed += 1;
+						bytes += size;
+					}
+					pending.push(prefix);
+					if pending.len() as u64 >= self.checkpoint_interval {
+						self.checkpoint(started, &mut pending)?;
+					}
+				},
+				Err(error) => summary.failures.push(RangeFailure { prefix, error }),
+			}
+			if let Some(callback) = &mut self.progress {
+				callback(&DownloadProgress {
+					completed: done as u64 + 1,
+					resumed: summary.resumed,
+					total,
+					changed: summary.changed,
+					failed: summary.failures.len() as u64,
+					bytes,
+					prefix,
+					elapsed: start.elapsed(),
+				});
+			}
+		}
+		if summary.is_complete() {
+			self.save_changed_manifest()?;
+			self.clear_checkpoint()?;
+		} else {
+			self.checkpoint(started, &mut pending)?;
+		}
+		Ok(summary)
+	}
+
+	/// Update all ranges (see [`RangeUpdater::update`])
+	pub fn update_all(&mut self) -> Result<UpdateSummary, RangeUpdateError> {
+		self.update(all_prefixes())
+	}
+
+	/// Write manifest to range directory
+	pub fn save_manifest(&self) -> io::Result<()> {
+		let mut file = io::BufWriter::new(AtomicFile::create(self.dir.join(MANIFEST_FILE))?);
+		for (name, version) in &self.manifest {
+			writeln!(
+				file,
+				"{}\t{}\t{}",
+				name,
+				version.etag.as_deref().unwrap_or(""),
+				version.last_modified.as_deref().unwrap_or("")
+			)?;
+		}
+		file.flush()?;
+		file.into_inner().map_err(|e| e.into_error())?.commit()
+	}
+
+	fn save_changed_manifest(&mut self) -> io::Result<()> {
+		if self.dirty {
+			self.save_manifest()?;
+			self.dirty = false;
+		}
+		Ok(())
+	}
+
+	/// Path of the checkpoint of an unfinished update of this key type
+	fn checkpoint_path(&self) -> PathBuf {
+		self.dir.join(format!("{}{}", D::KEY_TYPE.name(), CHECKPOINT_FILE_SUFFIX))
+	}
+
+	/// Save manifest, then record checked ranges in checkpoint of update started at `started`
+	fn checkpoint(&mut self, started: u64, pending: &mut Vec<LimPrefix>) -> io::Result<()> {
+		self.save_changed_manifest()?;
+		let file = fs::OpenOptions::new().create(true).append(true).open(self.checkpoint_path())?;
+		let is_new = file.metadata()?.len() == 0;
+		let mut file = io::BufWriter::new(file);
+		if is_new {
+			writeln!(file, "{}{}", CHECKPOINT_HEADER, started)?;
+		}
+		for prefix in pending.drain(..) {
+			writeln!(file, "{}", prefix.hex())?;
+		}
+		file.into_inner().map_err(|e| e.into_error())?.sync_data()
+	}
+
+	/// Checkpoint of an unfinished update (if any)
+	///
+	/// Checkpoints without (valid) start time count as started at the epoch,
+	/// i.e. are never resumed.
+	fn read_checkpoint(&self) -> io::Result<Option<Checkpoint>> {
+		let file = match fs::File::open(self.checkpoint_path()) {
+			Ok(file) => file,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(e),
+		};
+		let mut lines = io::BufReader::new(file).lines();
+		let started = match lines.next().transpose()? {
+			Some(line) => line
+				.strip_prefix(CHECKPOINT_HEADER)
+				.and_then(|started| started.parse().ok())
+				.unwrap_or(0),
+			None => 0,
+		};
+		Ok(Some(Checkpoint { started, prefixes: lines.collect::<io::Result<_>>()? }))
+	}
+
+	/// Add entries of all (local) ranges to builder and finish it
+	///
+	/// All ranges must have been downloaded.
+	pub fn build_index<W>(
+		&self,
+		mut builder: TypedBuilder<D, NoPayload, W>,
+	) -> Result<(), RangeUpdateError>
+	where
+		W: io::Write + io::Seek,
+	{
+		for prefix in all_prefixes() {
+			let path = self.dir.join(range_file_name::<D>(prefix));
+			let range = read_range_file::<D>(&path)?.ok_or_else(|| {
+				RangeUpdateError::MissingRange { prefix: prefix.hex().to_string() }
+			})?;
+			for (key, _count) in range.entries {
+				builder.add_entry(&key, &NoPayload)?;
+			}
+		}
+		builder.finish()?;
+		Ok(())
+	}
+}
+
+fn read_manifest<R: BufRead>(
+	reader: R,
+) -> Result<BTreeMap<String, RangeVersion>, RangeUpdateError> {
+	let mut manifest = BTreeMap::new();
+	for line in reader.lines() {
+		let line = line?;
+		let mut fields = line.split('\t');
+		let (Some(name), Some(etag), Some(last_modified), None) =
+			(fields.next(), fields.next(), fields.next(), fields.next())
+		else {
+			return Err(RangeUpdateError::InvalidManifest { line });
+		};
+		let field = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+		let version = RangeVersion { etag: field(etag), last_modified: field(last_modified) };
+		manifest.insert(name.to_string(), version);
+	}
+	Ok(manifest)
+}
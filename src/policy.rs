@@ -0,0 +1,132 @@
+//! Password policies based on breach counts
+//!
+//! A [`Policy`] maps the result of a lookup (not found, or found with a
+//! number of times the password was seen in breaches) to an [`Action`]:
+//!
+//! ```
+//! use hibp_index::policy::{Action, Policy};
+//!
+//! // reject if seen at least 10 times, warn if seen at all
+//! let policy = Policy::reject_at(10).with_warn_at(1);
+//! assert_eq!(policy.evaluate(None).action, Action::Accept);
+//! assert_eq!(policy.evaluate(Some(3)).action, Action::Warn);
+//! assert_eq!(policy.evaluate(Some(10)).action, Action::Reject);
+//! ```
+
+use crate::data::{Count32, Count64, NoPayload, PayloadData};
+
+/// Payload types that (might) store how often a password was seen in breaches
+pub trait BreachCount: PayloadData {
+	/// Number of times the password was seen; `None` if unknown
+	fn breach_count(&self) -> Option<u64>;
+}
+
+impl BreachCount for NoPayload {
+	fn breach_count(&self) -> Option<u64> {
+		None
+	}
+}
+
+impl BreachCount for Count32 {
+	fn breach_count(&self) -> Option<u64> {
+		Some(self.value())
+	}
+}
+
+impl BreachCount for Count64 {
+	fn breach_count(&self) -> Option<u64> {
+		Some(self.value())
+	}
+}
+
+/// Breach count stored as raw payload (4 or 8 bytes big endian); `None` for other payload sizes
+pub fn raw_breach_count(payload: &[u8]) -> Option<u64> {
+	if let Ok(count) = <[u8; 4]>::try_from(payload) {
+		Some(u32::from_be_bytes(count) as u64)
+	} else if let Ok(count) = <[u8; 8]>::try_from(payload) {
+		Some(u64::from_be_bytes(count))
+	} else {
+		None
+	}
+}
+
+/// What to do with a password
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Action {
+	/// Password is fine
+	Accept,
+	/// Password can be used, but the user should be warned
+	Warn,
+	/// Password must not be used
+	Reject,
+}
+
+/// Result of evaluating a [`Policy`]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Verdict {
+	/// Resulting action
+	pub action: Action,
+	/// Whether the password was found
+	pub found: bool,
+	/// Breach count used for the decision (`None` if not found; `1` if found without count)
+	pub count: Option<u64>,
+}
+
+/// Thresholds (on the breach count) to warn about or reject passwords
+///
+/// Entries without breach count (e.g. [`NoPayload`]) are treated as seen
+/// once.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Policy {
+	/// Reject passwords seen at least this often (`None`: never reject)
+	pub reject_threshold: Option<u64>,
+	/// Warn about passwords seen at least this often (`None`: never warn)
+	pub warn_threshold: Option<u64>,
+}
+
+impl Policy {
+	/// Policy rejecting passwords seen at least `count` times
+	pub fn reject_at(count: u64) -> Self {
+		Self { reject_threshold: Some(count), warn_threshold: None }
+	}
+
+	/// Policy only warning about passwords seen at least `count` times
+	pub fn warn_at(count: u64) -> Self {
+		Self { reject_threshold: None, warn_threshold: Some(count) }
+	}
+
+	/// Also warn about passwords seen at least `count` times
+	pub fn with_warn_at(mut self, count: u64) -> Self {
+		self.warn_threshold = Some(count);
+		self
+	}
+
+	/// Evaluate lookup result given as breach count (`None` if not found)
+	pub fn evaluate(&self, count: Option<u64>) -> Verdict {
+		let found = count.is_some();
+		let reached = |threshold: Option<u64>| match (threshold, count) {
+			(Some(threshold), Some(count)) => count >= threshold,
+			_ => false,
+		};
+		let action = if reached(self.reject_threshold) {
+			Action::Reject
+		} else if reached(self.warn_threshold) {
+			Action::Warn
+		} else {
+			Action::Accept
+		};
+		Verdict { action, found, count }
+	}
+
+	/// Evaluate lookup result (e.g. from [`TypedIndex::lookup`](crate::index::TypedIndex::lookup))
+	pub fn evaluate_lookup<P: BreachCount>(&self, result: Option<&P>) -> Verdict {
+		self.evaluate(result.map(|payload| payload.breach_count().unwrap_or(1)))
+	}
+}
+
+impl Default for Policy {
+	/// Reject all passwords found in breaches
+	fn default() -> Self {
+		Self::reject_at(1)
+	}
+}
@@ -0,0 +1,75 @@
+//! Parse password dumps in pwdump format
+//!
+//! Tools like `secretsdump.py` or `pwdump` export the accounts of a Windows
+//! domain (or local SAM) as lines of the form `user:rid:lmhash:nthash:::`
+//! (`secretsdump.py` might append a ` (status=...)` suffix):
+//!
+//! ```
+//! use hibp_index::pwdump::PwdumpEntry;
+//!
+//! let line = "CORP\\alice:1104:aad3b435b51404eeaad3b435b51404ee:31d6cfe0d16ae931b73c59d7e0c089c0:::";
+//! let entry = PwdumpEntry::parse(line).unwrap();
+//! assert_eq!(entry.user, "CORP\\alice");
+//! assert_eq!(entry.rid, 1104);
+//! assert_eq!(entry.nt.to_string(), "31d6cfe0d16ae931b73c59d7e0c089c0");
+//! assert!(entry.lm.is_none());
+//! ```
+
+use crate::{
+	data::{LM, NT},
+	errors::PwdumpParseError,
+};
+
+/// LM hash of the empty password (i.e. no LM hash stored)
+pub const EMPTY_LM_HASH: &str = "aad3b435b51404eeaad3b435b51404ee";
+
+/// Account line of a pwdump file
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PwdumpEntry<'a> {
+	/// User name (possibly with domain: `DOMAIN\user`)
+	pub user: &'a str,
+	/// Relative identifier of the account
+	pub rid: u32,
+	/// LM hash (hex encoded; usually [`EMPTY_LM_HASH`])
+	pub lm_hash: &'a str,
+	/// NT hash (aka NTLM)
+	pub nt: NT,
+	/// Parsed LM hash (`None` if empty or [`EMPTY_LM_HASH`])
+	pub lm: Option<LM>,
+}
+
+impl<'a> PwdumpEntry<'a> {
+	/// Parse line `user:rid:lmhash:nthash:::`
+	///
+	/// The user name may contain colons; anything after the last three
+	/// colons is ignored.
+	pub fn parse(line: &'a str) -> Result<Self, PwdumpParseError> {
+		let line = line.trim_end_matches(['\r', '\n']);
+		// split from the end: user names may contain colons
+		let mut fields = line.rsplitn(7, ':');
+		let _status = fields.next();
+		let (Some(""), Some(""), Some(nt_hash), Some(lm_hash), Some(rid), Some(user)) = (
+			fields.next(),
+			fields.next(),
+			fields.next(),
+			fields.next(),
+			fields.next(),
+			fields.next(),
+		) else {
+			return Err(PwdumpParseError::InvalidFormat);
+		};
+		let rid = rid.parse().map_err(|_| PwdumpParseError::InvalidRid)?;
+		let nt = nt_hash.parse().map_err(|_| PwdumpParseError::InvalidNtHash)?;
+		let lm = if lm_hash.is_empty() || lm_hash.eq_ignore_ascii_case(EMPTY_LM_HASH) {
+			None
+		} else {
+			Some(lm_hash.parse().map_err(|_| PwdumpParseError::InvalidLmHash)?)
+		};
+		Ok(Self { user, rid, lm_hash, nt, lm })
+	}
+
+	/// Whether an LM hash is stored (LM hashes are weak and should be disabled)
+	pub fn has_lm_hash(&self) -> bool {
+		self.lm.is_some()
+	}
+}
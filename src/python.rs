@@ -0,0 +1,146 @@
+//! Python extension module (feature `python`)
+//!
+//! Build with [maturin](https://www.maturin.rs/) (see `pyproject.toml`):
+//!
+//! ```python
+//! import hibp_index
+//! index = hibp_index.Index("hibp-sha1.index")
+//! index.lookup_password("password")  # True
+//! index.lookup("5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8")  # True
+//! index.lookup_range("5BAA6")  # list of hex encoded hashes
+//! ```
+
+use crate::{
+	data::{FixedByteArray, KeyData, KnownKeyType, NoPayload, NT, SHA1},
+	index::{Index as UntypedIndex, TypedIndex},
+};
+use pyo3::{
+	exceptions::{PyIOError, PyValueError},
+	prelude::*,
+};
+use std::fs;
+
+fn io_error<E: std::fmt::Display>(e: E) -> PyErr {
+	PyIOError::new_err(e.to_string())
+}
+
+fn lookup<D>(index: &TypedIndex<D, NoPayload, fs::File>, hash: &D) -> PyResult<bool>
+where
+	D: KeyData,
+{
+	Ok(index.lookup(hash).map_err(io_error)?.is_some())
+}
+
+fn lookup_range<D>(
+	index: &TypedIndex<D, NoPayload, fs::File>,
+	prefix: &[u8],
+	bits: u32,
+) -> PyResult<Vec<String>>
+where
+	D: KeyData,
+{
+	index
+		.lookup_range(prefix, bits)
+		.map(|entry| Ok(entry.map_err(io_error)?.0.hex_upper().to_string()))
+		.collect()
+}
+
+enum Inner {
+	Sha1(TypedIndex<SHA1, NoPayload, fs::File>),
+	Nt(TypedIndex<NT, NoPayload, fs::File>),
+}
+
+/// Index of SHA-1 or NT password hashes
+#[pyclass(name = "Index", module = "hibp_index")]
+struct PyIndex {
+	inner: Inner,
+}
+
+#[pymethods]
+impl PyIndex {
+	/// Open index file (key type is detected from header)
+	#[new]
+	fn new(path: std::path::PathBuf) -> PyResult<Self> {
+		let index = UntypedIndex::open(fs::File::open(&path)?).map_err(io_error)?;
+		let inner = match index.key_type().as_known() {
+			Some(KnownKeyType::SHA1) => {
+				Inner::Sha1(TypedIndex::from_index(index).map_err(io_error)?)
+			},
+			Some(KnownKeyType::NT) => Inner::Nt(TypedIndex::from_index(index).map_err(io_error)?),
+			_ => {
+				return Err(PyValueError::new_err(format!(
+					"unsupported key type {}",
+					index.key_type().name()
+				)))
+			},
+		};
+		Ok(Self { inner })
+	}
+
+	/// Key type of index ("sha1" or "nt")
+	#[getter]
+	fn key_type(&self) -> &'static str {
+		match &self.inner {
+			Inner::Sha1(_) => KnownKeyType::SHA1.name(),
+			Inner::Nt(_) => KnownKeyType::NT.name(),
+		}
+	}
+
+	/// Description of index
+	#[getter]
+	fn description(&self) -> String {
+		match &self.inner {
+			Inner::Sha1(index) => index.description().to_string(),
+			Inner::Nt(index) => index.description().to_string(),
+		}
+	}
+
+	/// Whether hex encoded hash is contained in index
+	fn lookup(&self, hash: &str) -> PyResult<bool> {
+		match &self.inner {
+			Inner::Sha1(index) => {
+				lookup(index, &hash.parse().map_err(|e| PyValueError::new_err(format!("{}", e)))?)
+			},
+			Inner::Nt(index) => {
+				lookup(index, &hash.parse().map_err(|e| PyValueError::new_err(format!("{}", e)))?)
+			},
+		}
+	}
+
+	/// Whether hash of plaintext password is contained in index
+	fn lookup_password(&self, password: &str) -> PyResult<bool> {
+		match &self.inner {
+			Inner::Sha1(index) => lookup(index, &SHA1::hash(password.as_bytes())),
+			Inner::Nt(index) => lookup(index, &NT::hash(password)),
+		}
+	}
+
+	/// All hashes (upper case hex) starting with given hex prefix
+	fn lookup_range(&self, prefix: &str) -> PyResult<Vec<String>> {
+		let mut padded = prefix.to_string();
+		if padded.len() % 2 == 1 {
+			padded.push('0');
+		}
+		let prefix_bytes =
+			hex::decode(&padded).map_err(|e| PyValueError::new_err(e.to_string()))?;
+		let bits = prefix.len() as u32 * 4;
+		let max_bits = match &self.inner {
+			Inner::Sha1(_) => SHA1::SIZE,
+			Inner::Nt(_) => NT::SIZE,
+		} as u32 * 8;
+		if bits > max_bits {
+			return Err(PyValueError::new_err("prefix longer than hash"));
+		}
+		match &self.inner {
+			Inner::Sha1(index) => lookup_range(index, &prefix_bytes, bits),
+			Inner::Nt(index) => lookup_range(index, &prefix_bytes, bits),
+		}
+	}
+}
+
+/// Lookup password hashes in indexed databases
+#[pymodule]
+fn hibp_index(m: &Bound<'_, PyModule>) -> PyResult<()> {
+	m.add_class::<PyIndex>()?;
+	Ok(())
+}
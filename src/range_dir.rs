@@ -0,0 +1,160 @@
+//! Ranges downloaded by the official `haveibeenpwned-downloader`
+//!
+//! The [downloader](https://github.com/HaveIBeenPwned/PwnedPasswordsDownloader)
+//! can store each range of the online API (all hashes with the same 5 hex
+//! digit prefix) in its own file (`ABCDE.txt`, with `SUFFIX:count` lines as
+//! returned by the API). [`RangeDir`] looks up hashes directly in such a
+//! directory (reading the single file of the range), and adds all ranges to a
+//! builder to convert them into an index:
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hibp_index::data::{Count32, SHA1};
+//! use hibp_index::index::{Depth, TypedBuilder};
+//! use hibp_index::range_dir::RangeDir;
+//!
+//! let ranges = RangeDir::<SHA1>::open("pwnedpasswords")?;
+//! let count = ranges.lookup(&SHA1::hash(b"password"))?;
+//!
+//! let mut builder =
+//!     TypedBuilder::<SHA1, Count32, _>::create_atomic("hibp-sha1.index", "pwned-passwords", Depth::DEPTH20)?;
+//! ranges.add_entries(&mut builder, Count32::saturating_from)?;
+//! builder.finish()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use crate::{
+	data::{HexCase, KeyData, PayloadData},
+	errors::RangeDirError,
+	index::{Depth, LimPrefix, TypedBuilder},
+};
+
+/// Length of the prefix in the file names (in bits)
+const PREFIX_DEPTH: Depth = Depth::DEPTH20;
+
+/// Directory with one text file per range (see [module documentation](self))
+pub struct RangeDir<D> {
+	dir: PathBuf,
+	_marker: PhantomData<D>,
+}
+
+impl<D> RangeDir<D>
+where
+	D: KeyData,
+{
+	/// Open range directory (must exist)
+	pub fn open<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+		let dir = dir.into();
+		if !fs::metadata(&dir)?.is_dir() {
+			return Err(io::Error::other(format!("{:?} is not a directory", dir)));
+		}
+		Ok(Self { dir, _marker: PhantomData })
+	}
+
+	/// Range directory
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Path of the file of a range (upper case hex digits; lower case if only that exists)
+	pub fn path(&self, prefix: LimPrefix) -> PathBuf {
+		assert_eq!(prefix.depth(), PREFIX_DEPTH, "ranges have 20-bit prefixes");
+		let upper = self.dir.join(format!("{}.txt", prefix.hex().with_case(HexCase::Upper)));
+		if !upper.exists() {
+			let lower = self.dir.join(format!("{}.txt", prefix.hex()));
+			if lower.exists() {
+				return lower;
+			}
+		}
+		upper
+	}
+
+	/// Breach count of hash (`None` if not found)
+	///
+	/// Scans the file of the range of the hash.
+	pub fn lookup(&self, key: &D) -> Result<Option<u64>, RangeDirError> {
+		for entry in self.entries(PREFIX_DEPTH.prefix(key.data()))? {
+			let (entry, count) = entry?;
+			match entry.data().cmp(key.data()) {
+				std::cmp::Ordering::Less => (),
+				std::cmp::Ordering::Equal => return Ok(Some(count)),
+				std::cmp::Ordering::Greater => break,
+			}
+		}
+		Ok(None)
+	}
+
+	/// All hashes (and their breach counts) of a range
+	pub fn read_range(&self, prefix: LimPrefix) -> Result<Vec<(D, u64)>, RangeDirError> {
+		self.entries(prefix)?.collect()
+	}
+
+	/// Add all ranges (in order) to builder; returns number of added entries
+	///
+	/// `payload` builds the payload for an entry from its count. Fails if the
+	/// file of any range is missing.
+	pub fn add_entries<P, W, F>(
+		&self,
+		builder: &mut TypedBuilder<D, P, W>,
+		mut payload: F,
+	) -> Result<u64, RangeDirError>
+	where
+		P: PayloadData,
+		W: io::Write + io::Seek,
+		F: FnMut(u64) -> P,
+	{
+		let mut added = 0;
+		for prefix in PREFIX_DEPTH.prefix_range_raw(&[], 0) {
+			for entry in self.entries(prefix)? {
+				let (key, count) = entry?;
+				builder.add_entry(&key, &payload(count))?;
+				added += 1;
+			}
+		}
+		Ok(added)
+	}
+
+	/// Entries of a range (in file order; padding entries are skipped)
+	fn entries(
+		&self,
+		prefix: LimPrefix,
+	) -> Result<impl Iterator<Item = Result<(D, u64), RangeDirError>>, RangeDirError> {
+		let file = match fs::File::open(self.path(prefix)) {
+			Ok(file) => file,
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				return Err(RangeDirError::MissingRange { prefix: prefix.hex().to_string() });
+			},
+			Err(e) => return Err(e.into()),
+		};
+		Ok(io::BufReader::new(file).lines().filter_map(move |line| match line {
+			Ok(line) => parse_range_line(prefix, &line).transpose(),
+			Err(e) => Some(Err(e.into())),
+		}))
+	}
+}
+
+/// Parse `SUFFIX:count` line; `None` for empty lines and padding (count 0)
+fn parse_range_line<D>(prefix: LimPrefix, line: &str) -> Result<Option<(D, u64)>, RangeDirError>
+where
+	D: KeyData,
+{
+	let line = line.trim_end();
+	if line.is_empty() {
+		return Ok(None);
+	}
+	let invalid =
+		|| RangeDirError::InvalidLine { prefix: prefix.hex().to_string(), line: line.to_string() };
+	let (suffix, count) = line.split_once(':').ok_or_else(invalid)?;
+	let count = count.parse::<u64>().map_err(|_| invalid())?;
+	let key = prefix.read_key_from_suffix_hex::<D>(suffix.as_bytes()).map_err(|_| invalid())?;
+	if count == 0 {
+		return Ok(None);
+	}
+	Ok(Some((key, count)))
+}
@@ -0,0 +1,40 @@
+//! Replacing (or not replacing) existing files with `AtomicFile`
+
+use hibp_index::index::AtomicFile;
+use std::fs;
+use std::io::{self, Write};
+
+#[test]
+fn overwrite_by_default() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("test.index");
+	fs::write(&path, b"old").unwrap();
+	let mut file = AtomicFile::create_locked(&path).unwrap();
+	file.write_all(b"new").unwrap();
+	file.commit().unwrap();
+	assert_eq!(fs::read(&path).unwrap(), b"new");
+}
+
+#[test]
+fn no_overwrite_existing() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("test.index");
+	fs::write(&path, b"old").unwrap();
+	let err = AtomicFile::create_locked(&path).unwrap().no_overwrite().err().unwrap();
+	assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+	assert_eq!(fs::read(&path).unwrap(), b"old");
+}
+
+#[test]
+fn no_overwrite_created_while_building() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("test.index");
+	let mut file = AtomicFile::create_locked(&path).unwrap().no_overwrite().unwrap();
+	file.write_all(b"new").unwrap();
+	fs::write(&path, b"other").unwrap();
+	let err = file.commit().unwrap_err();
+	assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+	assert_eq!(fs::read(&path).unwrap(), b"other");
+	// temporary file was removed (only target and lock file are left)
+	assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
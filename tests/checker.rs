@@ -0,0 +1,73 @@
+//! Password checks routed through `MultiIndex`
+
+use hibp_index::checker::{InputMode, PasswordChecker, PasswordHash};
+use hibp_index::data::{KeyData, KnownKeyType, NoPayload, NT, SHA1, SHA512};
+use hibp_index::errors::PasswordCheckError;
+use hibp_index::index::{Depth, MultiIndex, TypedBuilder};
+use std::path::{Path, PathBuf};
+
+const PASSWORDS: [&str; 3] = ["password", "123456", "letmein"];
+
+fn build<D: KeyData>(dir: &Path, name: &str, hash: impl Fn(&str) -> D) -> PathBuf {
+	let path = dir.join(name);
+	let mut keys: Vec<D> = PASSWORDS.iter().map(|password| hash(password)).collect();
+	keys.sort_by(|a, b| a.data().cmp(b.data()));
+	let mut builder =
+		TypedBuilder::<D, NoPayload, _>::create_atomic(&path, "test", Depth::DEPTH16).unwrap();
+	for key in &keys {
+		builder.add_entry(key, &NoPayload).unwrap();
+	}
+	builder.finish().unwrap();
+	path
+}
+
+#[test]
+fn from_indexes() {
+	let dir = tempfile::tempdir().unwrap();
+	let nt = build(dir.path(), "nt.index", NT::hash);
+	let sha1 = build(dir.path(), "sha1.index", |password| SHA1::hash(password.as_bytes()));
+	let checker = PasswordChecker::from_indexes(MultiIndex::open([&nt, &sha1]).unwrap()).unwrap();
+	// SHA-1 first, independent of the order of the files
+	assert_eq!(checker.key_types().collect::<Vec<_>>(), [KnownKeyType::SHA1, KnownKeyType::NT]);
+
+	assert!(checker.check_plaintext("letmein").unwrap());
+	assert!(!checker.check_plaintext("correct horse battery staple").unwrap());
+	let results = checker.check_input_all("123456", InputMode::Auto).unwrap();
+	assert_eq!(results.len(), 2);
+	assert!(results.iter().all(|(_, found)| *found));
+	let nt_hash = NT::hash("password").to_string();
+	let (hash, found) = checker.check_input(&nt_hash, InputMode::HashOnly).unwrap();
+	assert!(matches!(hash, PasswordHash::NT(_)));
+	assert!(found);
+}
+
+#[test]
+fn missing_index() {
+	let dir = tempfile::tempdir().unwrap();
+	let nt = build(dir.path(), "nt.index", NT::hash);
+	let checker = PasswordChecker::open(None, Some(&nt)).unwrap();
+	assert!(checker.check_plaintext("password").unwrap());
+	assert!(matches!(
+		checker.check_sha1(&SHA1::hash(b"password")),
+		Err(PasswordCheckError::MissingIndex { key_type: KnownKeyType::SHA1 })
+	));
+	assert!(matches!(
+		PasswordChecker::from_indexes(MultiIndex::<std::fs::File>::new()),
+		Err(PasswordCheckError::NoIndex)
+	));
+}
+
+#[test]
+fn unsupported_key_type() {
+	let dir = tempfile::tempdir().unwrap();
+	let sha512 = build(dir.path(), "sha512.index", |password| SHA512::hash(password.as_bytes()));
+	assert!(matches!(
+		PasswordChecker::from_indexes(MultiIndex::open([&sha512]).unwrap()),
+		Err(PasswordCheckError::UnsupportedKeyType { .. })
+	));
+	// opening with an explicit key type checks the file
+	assert!(matches!(
+		PasswordChecker::open(Some(&sha512), None),
+		Err(PasswordCheckError::OpenError(_))
+	));
+}
@@ -0,0 +1,83 @@
+//! Test indexes shared by the integration tests
+
+// each test crate only uses some of the helpers
+#![allow(dead_code)]
+
+use hibp_index::data::{KeyData, PayloadData, SHA1};
+use hibp_index::errors::BuilderAddError;
+use hibp_index::index::{BucketCounts, Depth, IndexOptions, TypedBuilder, TypedTwoPassBuilder};
+use std::io;
+use std::ops::Range;
+
+/// SHA-1 hash of the `i`-th test password
+pub fn password_hash(i: u32) -> SHA1 {
+	SHA1::hash(format!("password{}", i).as_bytes())
+}
+
+/// Sorted SHA-1 hashes of the test passwords in `range`
+pub fn keys(range: Range<u32>) -> Vec<SHA1> {
+	let mut keys: Vec<SHA1> = range.map(password_hash).collect();
+	keys.sort();
+	keys
+}
+
+/// Sorted SHA-1 hashes of the test passwords in `range`, with payload `payload(i)`
+pub fn entries<P>(range: Range<u32>, payload: impl Fn(u32) -> P) -> Vec<(SHA1, P)> {
+	let mut entries: Vec<(SHA1, P)> = range.map(|i| (password_hash(i), payload(i))).collect();
+	entries.sort_by_key(|(key, _)| *key);
+	entries
+}
+
+/// Write index of (ordered) `entries` with the seekable builder
+pub fn write_index<D, P, W>(
+	database: W,
+	entries: &[(D, P)],
+	depth: Depth,
+	options: &IndexOptions,
+) -> Result<(), BuilderAddError>
+where
+	D: KeyData,
+	P: PayloadData,
+	W: io::Write + io::Seek,
+{
+	let mut builder =
+		TypedBuilder::<D, P, _>::create_with_options(database, "test", depth, options).unwrap();
+	for (key, payload) in entries {
+		builder.add_entry(key, payload)?;
+	}
+	builder.finish()?;
+	Ok(())
+}
+
+/// Write index of (ordered) `entries` with the two-pass builder
+pub fn write_index_two_pass<D, P, W>(
+	database: W,
+	entries: &[(D, P)],
+	depth: Depth,
+	options: &IndexOptions,
+) where
+	D: KeyData,
+	P: PayloadData,
+	W: io::Write,
+{
+	let mut counts = BucketCounts::new(depth);
+	entries.iter().for_each(|(key, _)| counts.count(key));
+	let mut builder =
+		TypedTwoPassBuilder::<D, P, _>::create_with_options(database, "test", counts, options)
+			.unwrap();
+	for (key, payload) in entries {
+		builder.add_entry(key, payload).unwrap();
+	}
+	builder.finish().unwrap();
+}
+
+/// Temporary file with index of (ordered) `entries` (seekable builder)
+pub fn build_file<D: KeyData, P: PayloadData>(
+	entries: &[(D, P)],
+	depth: Depth,
+	options: &IndexOptions,
+) -> std::fs::File {
+	let file = tempfile::tempfile().unwrap();
+	write_index(file.try_clone().unwrap(), entries, depth, options).unwrap();
+	file
+}
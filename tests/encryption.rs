@@ -0,0 +1,62 @@
+//! Indexes with encrypted payloads
+#![cfg(feature = "encryption")]
+
+use hibp_index::data::{Count32, SHA1};
+use hibp_index::errors::{IndexOpenError, LookupError};
+use hibp_index::index::{Depth, Index, IndexOptions, PayloadKey, TypedIndex};
+
+mod common;
+
+fn entries() -> Vec<(SHA1, Count32)> {
+	common::entries(0..100, |i| Count32::new(i + 1))
+}
+
+fn build(key: [u8; 32]) -> std::fs::File {
+	let mut options = IndexOptions::v1();
+	options.payload_key = Some(PayloadKey::new(key));
+	common::build_file(&entries(), Depth::DEPTH16, &options)
+}
+
+/// Stored (encrypted) payloads
+fn raw_payloads(file: std::fs::File) -> Vec<Vec<u8>> {
+	let index = Index::open(file).unwrap();
+	assert!(index.needs_payload_key());
+	let mut payloads = Vec::new();
+	index
+		.for_each_entry(|_key, payload| {
+			payloads.push(payload.to_vec());
+			Ok::<_, LookupError>(())
+		})
+		.unwrap();
+	payloads
+}
+
+#[test]
+fn round_trip() {
+	let file = build([1; 32]);
+	let index =
+		TypedIndex::<SHA1, Count32, _>::open_with_payload_key(file, PayloadKey::new([1; 32]))
+			.unwrap();
+	for (key, count) in &entries() {
+		assert_eq!(index.lookup(key).unwrap(), Some(*count));
+	}
+}
+
+#[test]
+fn wrong_key() {
+	let file = build([1; 32]);
+	let err = TypedIndex::<SHA1, Count32, _>::open_with_payload_key(file, PayloadKey::new([2; 32]))
+		.err()
+		.unwrap();
+	assert!(matches!(err, IndexOpenError::WrongPayloadKey));
+}
+
+#[test]
+fn keystream_not_reused() {
+	let plain: Vec<Vec<u8>> = entries().iter().map(|(_, count)| count.0.to_vec()).collect();
+	let first = raw_payloads(build([1; 32]));
+	let second = raw_payloads(build([1; 32]));
+	assert_ne!(first, plain);
+	// same key, same entries: payloads must still be encrypted differently
+	assert_ne!(first, second);
+}
@@ -0,0 +1,33 @@
+//! Building and querying key filters
+
+use hibp_index::data::SHA1;
+use hibp_index::filter::KeyFilter;
+
+fn keys(range: std::ops::Range<u32>) -> Vec<SHA1> {
+	range.map(|i| SHA1::hash(format!("password{}", i).as_bytes())).collect()
+}
+
+#[test]
+fn contains_and_false_positives() {
+	let keys = keys(0..20_000);
+	let others = self::keys(20_000..120_000);
+	for (bits, max_rate) in [(8, 2.0 / 256.0), (16, 2.0 / 65536.0)] {
+		let filter = KeyFilter::<SHA1>::build_raw(bits, keys.iter().map(|key| key.0)).unwrap();
+		assert!(keys.iter().all(|key| filter.contains(key)));
+		let false_positives = others.iter().filter(|key| filter.contains(key)).count();
+		assert!((false_positives as f64) / (others.len() as f64) < max_rate);
+
+		let mut file = Vec::new();
+		filter.write(&mut file).unwrap();
+		let read = KeyFilter::<SHA1>::read(&file[..]).unwrap();
+		assert!(keys.iter().all(|key| read.contains(key)));
+	}
+}
+
+#[test]
+fn duplicate_keys() {
+	let mut keys = keys(0..1000);
+	keys.extend_from_within(..100);
+	let filter = KeyFilter::<SHA1>::build_raw(8, keys.iter().map(|key| key.0)).unwrap();
+	assert!(keys.iter().all(|key| filter.contains(key)));
+}
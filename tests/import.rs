@@ -0,0 +1,50 @@
+//! Importing HIBP text files in any order
+
+use hibp_index::data::{Count32, FixedByteArray, SHA1};
+use hibp_index::import::{UnorderedHibpImport, MAX_SHARD_BITS};
+use hibp_index::index::{Depth, TypedBuilder, TypedIndex};
+use std::fmt::Write;
+
+fn input() -> String {
+	let mut input = String::new();
+	for i in (0..500u32).rev() {
+		let key = SHA1::hash(format!("password{}", i).as_bytes());
+		writeln!(input, "{}:{}", key.hex(), i + 1).unwrap();
+	}
+	input
+}
+
+fn import(
+	import: &UnorderedHibpImport,
+) -> anyhow::Result<TypedIndex<SHA1, Count32, std::fs::File>> {
+	let file = tempfile::tempfile()?;
+	let mut builder =
+		TypedBuilder::<SHA1, Count32, _>::create(file.try_clone()?, "test", Depth::DEPTH16)?;
+	import.add_entries(input().as_bytes(), &mut builder, Count32::saturating_from)?;
+	builder.finish()?;
+	Ok(TypedIndex::open(file)?)
+}
+
+#[test]
+fn unordered() {
+	let temp_dir = tempfile::tempdir().unwrap();
+	// stale directory of an earlier (crashed) import with the same pid
+	std::fs::create_dir(temp_dir.path().join(format!(".hibp-sort-{}-0", std::process::id())))
+		.unwrap();
+	let index = import(&UnorderedHibpImport::new(temp_dir.path())).unwrap();
+	for i in 0..500u32 {
+		let key = SHA1::hash(format!("password{}", i).as_bytes());
+		assert_eq!(index.lookup(&key).unwrap(), Some(Count32::new(i + 1)));
+	}
+	// only the stale directory is left
+	assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn shard_bits_limit() {
+	let temp_dir = tempfile::tempdir().unwrap();
+	let import_with =
+		|bits| import(&UnorderedHibpImport::new(temp_dir.path()).with_shard_bits(bits));
+	assert!(import_with(MAX_SHARD_BITS).is_ok());
+	assert!(import_with(MAX_SHARD_BITS + 1).is_err());
+}
@@ -0,0 +1,43 @@
+//! Lookups and range walks on small indexes built in temporary files
+
+use hibp_index::data::{FixedByteArray, NoPayload, SHA1};
+use hibp_index::index::{Depth, IndexOptions, TypedIndex};
+
+mod common;
+
+fn build(keys: &[SHA1], depth: Depth) -> TypedIndex<SHA1, NoPayload, std::fs::File> {
+	let entries: Vec<(SHA1, NoPayload)> = keys.iter().map(|key| (*key, NoPayload)).collect();
+	TypedIndex::open(common::build_file(&entries, depth, &IndexOptions::default())).unwrap()
+}
+
+// entries only store the key suffix; splitting them at the full key length
+// panicked for the last entry of a bucket
+#[test]
+fn lookup_without_payload() {
+	let keys = common::keys(0..1000);
+	for depth in [Depth::DEPTH16, Depth::DEPTH20] {
+		let index = build(&keys, depth);
+		for key in &keys {
+			assert_eq!(index.lookup(key).unwrap(), Some(NoPayload));
+		}
+		assert_eq!(index.lookup(&SHA1::hash(b"not in index")).unwrap(), None);
+	}
+}
+
+// range walks read full keys instead of the stored suffixes
+#[test]
+fn walk_returns_all_keys() {
+	let keys = common::keys(0..1000);
+	for depth in [Depth::DEPTH16, Depth::DEPTH20] {
+		let index = build(&keys, depth);
+		let walked: Vec<SHA1> = index.lookup_range(&[], 0).map(|entry| entry.unwrap().0).collect();
+		assert_eq!(walked, keys);
+
+		let prefix = keys[500].data();
+		let expected: Vec<SHA1> =
+			keys.iter().filter(|key| key.data()[..2] == prefix[..2]).cloned().collect();
+		let walked: Vec<SHA1> =
+			index.lookup_range(prefix, 16).map(|entry| entry.unwrap().0).collect();
+		assert_eq!(walked, expected);
+	}
+}
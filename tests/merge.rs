@@ -0,0 +1,41 @@
+//! Merging additions into an existing index
+
+use hibp_index::data::{Count32, Count64, SHA1};
+use hibp_index::index::{merge_into, Depth, IndexOptions, TypedIndex};
+
+mod common;
+
+fn build<P: hibp_index::data::PayloadData>(entries: &[(SHA1, P)]) -> std::fs::File {
+	common::build_file(entries, Depth::DEPTH16, &IndexOptions::default())
+}
+
+#[test]
+fn merge() {
+	let base = common::entries(0..100, Count32::new);
+	let base = TypedIndex::<SHA1, Count32, _>::open(build(&base)).unwrap();
+	let additions = common::entries(50..150, |i| Count32::new(i + 1000));
+
+	let dir = tempfile::tempdir().unwrap();
+	let dst = dir.path().join("merged.idx");
+	let (merged, stats) = merge_into(&base, additions, &dst).unwrap();
+	assert_eq!((stats.base, stats.added, stats.replaced), (50, 50, 50));
+	for (key, i) in common::entries(0..150, |i| i) {
+		let expected = if i < 50 { i } else { i + 1000 };
+		assert_eq!(merged.lookup(&key).unwrap(), Some(Count32::new(expected)));
+	}
+}
+
+// a typed index may read a prefix of larger payloads; merging must not
+// truncate them
+#[test]
+fn merge_larger_base_payload() {
+	let base = common::entries(0..100, |i| Count64::new(u64::from(i) << 32 | 1));
+	let base = TypedIndex::<SHA1, Count32, _>::open(build(&base)).unwrap();
+
+	let dir = tempfile::tempdir().unwrap();
+	let dst = dir.path().join("merged.idx");
+	let additions = vec![(SHA1::hash(b"addition"), Count32::new(1))];
+	let err = merge_into(&base, additions, &dst).err().unwrap();
+	assert!(err.to_string().contains("Payload size"), "{}", err);
+	assert!(!dst.exists());
+}
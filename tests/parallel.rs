@@ -0,0 +1,96 @@
+//! Parallel builds produce the same index as sequential builds
+
+use hibp_index::data::{Count32, FixedByteArray, SHA1};
+use hibp_index::index::{build_parallel, Depth, IndexOptions, LimPrefix, TableCompression};
+
+mod common;
+
+fn entries(count: u32) -> Vec<(SHA1, Count32)> {
+	common::entries(0..count, |i| Count32::new(i % 7))
+}
+
+fn build_sequential(entries: &[(SHA1, Count32)], depth: Depth, options: &IndexOptions) -> Vec<u8> {
+	let mut data = Vec::new();
+	common::write_index_two_pass(&mut data, entries, depth, options);
+	data
+}
+
+fn build_with_threads(
+	entries: &[(SHA1, Count32)],
+	depth: Depth,
+	options: &IndexOptions,
+	shard_bits: u8,
+) -> anyhow::Result<Vec<u8>> {
+	let work_dir = tempfile::tempdir().unwrap();
+	let shard_depth = Depth::new(shard_bits).unwrap();
+	let mut data = Vec::new();
+	build_parallel(&mut data, "test", depth, options, shard_bits, 3, work_dir.path(), |shard| {
+		let shard_entries: Vec<anyhow::Result<(SHA1, Count32)>> = entries
+			.iter()
+			.filter(|(key, _)| shard_depth.prefix(key.data()) == shard)
+			.map(|entry| Ok(*entry))
+			.collect();
+		Ok(shard_entries.into_iter())
+	})?;
+	// all temporary shard files are removed
+	assert_eq!(std::fs::read_dir(work_dir.path()).unwrap().count(), 0);
+	Ok(data)
+}
+
+#[test]
+fn parallel_matches_sequential() {
+	let entries = entries(3000);
+	let depth = Depth::new(12).unwrap();
+	let option_sets = [
+		IndexOptions::default(),
+		IndexOptions { checksum: true, ..IndexOptions::v1() },
+		IndexOptions { leading_table: true, ..IndexOptions::v1() },
+		IndexOptions {
+			table_compression: TableCompression::Deflate,
+			table_compression_level: Some(9),
+			..IndexOptions::v1()
+		},
+	];
+	for options in &option_sets {
+		let sequential = build_sequential(&entries, depth, options);
+		for shard_bits in [0, 4, 12] {
+			let parallel = build_with_threads(&entries, depth, options, shard_bits).unwrap();
+			assert!(parallel == sequential, "options {:?}, shard bits {}", options, shard_bits);
+		}
+	}
+}
+
+#[test]
+fn unsupported_options() {
+	let entries = entries(10);
+	let depth = Depth::new(8).unwrap();
+	for options in [
+		IndexOptions { payload_dictionary: true, ..IndexOptions::v1() },
+		IndexOptions { bucket_table_bits: 2, ..IndexOptions::v1() },
+	] {
+		assert!(build_with_threads(&entries, depth, &options, 4).is_err());
+	}
+}
+
+#[test]
+fn worker_panic() {
+	let work_dir = tempfile::tempdir().unwrap();
+	let failing = Depth::new(4).unwrap().prefix(&[0x50; 20]);
+	let result = build_parallel::<SHA1, Count32, _, _, _>(
+		Vec::new(),
+		"test",
+		Depth::new(8).unwrap(),
+		&IndexOptions::default(),
+		4,
+		2,
+		work_dir.path(),
+		|shard: LimPrefix| {
+			if shard == failing {
+				panic!("failing shard");
+			}
+			Ok(std::iter::empty())
+		},
+	);
+	let error = result.unwrap_err();
+	assert!(error.to_string().contains("panicked"), "{:#}", error);
+}
@@ -0,0 +1,59 @@
+//! Range API requests against a local HTTP server
+#![cfg(feature = "online")]
+
+use hibp_index::data::SHA1;
+use hibp_index::index::Depth;
+use hibp_index::online::{RangeClient, RetryPolicy};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Alternately fail requests and serve corrupt ranges; returns base URL and request counter
+fn serve_corrupt() -> (String, Arc<AtomicUsize>) {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let url = format!("http://{}/range/", listener.local_addr().unwrap());
+	let requests = Arc::new(AtomicUsize::new(0));
+	let counter = requests.clone();
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			let mut stream = stream.unwrap();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			loop {
+				let mut line = String::new();
+				if reader.read_line(&mut line).unwrap() <= 2 {
+					break;
+				}
+			}
+			let (status, body) = match counter.fetch_add(1, Ordering::SeqCst) % 2 {
+				0 => ("500 Internal Server Error", ""),
+				_ => ("200 OK", "not a range\r\n"),
+			};
+			let _ = write!(
+				stream,
+				"HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+				status,
+				body.len(),
+				body
+			);
+		}
+	});
+	(url, requests)
+}
+
+// corrupt responses count against the same attempt limit as failed requests
+// (instead of restarting the retries of failed requests)
+#[test]
+fn corrupt_range_attempts() {
+	let (url, requests) = serve_corrupt();
+	let retry = RetryPolicy {
+		max_attempts: 3,
+		initial_backoff: Duration::from_millis(1),
+		max_backoff: Duration::from_millis(1),
+	};
+	let client = RangeClient::with_base_url(&url).with_retry(retry);
+	let prefix = Depth::DEPTH20.prefix(&[0, 0, 0]);
+	assert!(client.fetch_prefix::<SHA1>(prefix).is_err());
+	assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,345 @@
+//! Build → open → lookup / `for_each_entry` round trips for all layout options
+
+use hibp_index::data::{Count32, FixedByteArrayImpl, KeyData, KeyType, NoPayload, SHA1};
+use hibp_index::errors::{BuilderAddError, BuilderCreateError, SliceIndexError};
+use hibp_index::index::{
+	Depth, IndexFlags, IndexOptions, SliceIndex, TableCompression, TypedBuilder, TypedIndex,
+	TypedSliceIndex,
+};
+use std::fmt::Debug;
+use std::fs::File;
+
+mod common;
+
+/// Sorted SHA-1 hashes of `count` distinct passwords with small (repetitive) counts
+fn entries(count: u32) -> Vec<(SHA1, Count32)> {
+	common::entries(0..count, |i| Count32::new(i % 7))
+}
+
+fn compressions() -> Vec<TableCompression> {
+	vec![
+		TableCompression::Deflate,
+		#[cfg(feature = "zstd")]
+		TableCompression::Zstd,
+	]
+}
+
+fn build<D: KeyData, P: hibp_index::data::PayloadData>(
+	entries: &[(D, P)],
+	depth: Depth,
+	options: &IndexOptions,
+) -> Result<TypedIndex<D, P, File>, BuilderAddError> {
+	let file = tempfile::tempfile().unwrap();
+	common::write_index(file.try_clone().unwrap(), entries, depth, options)?;
+	Ok(open(file))
+}
+
+fn build_two_pass<D: KeyData, P: hibp_index::data::PayloadData>(
+	entries: &[(D, P)],
+	depth: Depth,
+	options: &IndexOptions,
+) -> TypedIndex<D, P, File> {
+	let file = tempfile::tempfile().unwrap();
+	common::write_index_two_pass(file.try_clone().unwrap(), entries, depth, options);
+	open(file)
+}
+
+/// Open with strict validation (which rejects unknown key types)
+fn open<D: KeyData, P: hibp_index::data::PayloadData>(file: File) -> TypedIndex<D, P, File> {
+	if D::KEY_TYPE.as_known().is_some() {
+		TypedIndex::open_strict(file).unwrap()
+	} else {
+		TypedIndex::open(file).unwrap()
+	}
+}
+
+/// Every entry is found by `lookup` and `for_each_entry` returns exactly the entries
+fn check<D, P>(index: &TypedIndex<D, P, File>, entries: &[(D, P)], missing: &D)
+where
+	D: KeyData + Debug,
+	P: hibp_index::data::PayloadData + PartialEq + Debug,
+{
+	for (key, payload) in entries {
+		assert_eq!(index.lookup(key).unwrap().as_ref(), Some(payload), "key {:?}", key);
+	}
+	assert_eq!(index.lookup(missing).unwrap(), None);
+
+	let mut walked = Vec::new();
+	index
+		.index()
+		.for_each_entry(|key, payload| {
+			walked.push((key.to_vec(), payload.to_vec()));
+			Ok::<_, hibp_index::errors::LookupError>(())
+		})
+		.unwrap();
+	let expected: Vec<(Vec<u8>, Vec<u8>)> = entries
+		.iter()
+		.map(|(key, payload)| (key.data().to_vec(), payload.data().to_vec()))
+		.collect();
+	assert_eq!(walked, expected);
+	if index.index().capabilities().flags().contains(IndexFlags::CHECKSUMS) {
+		index.verify_checksum().unwrap();
+	}
+}
+
+#[test]
+fn seekable_builder_options() {
+	let entries = entries(3000);
+	let missing = SHA1::hash(b"not in index");
+	for compression in compressions() {
+		for checksum in [false, true] {
+			for payload_dictionary in [false, true] {
+				for bucket_table_bits in [0, 2] {
+					let options = IndexOptions {
+						checksum,
+						table_compression: compression,
+						payload_dictionary,
+						bucket_table_bits,
+						..IndexOptions::v1()
+					};
+					let depth = Depth::new(8).unwrap();
+					if payload_dictionary && bucket_table_bits != 0 {
+						let file = tempfile::tempfile().unwrap();
+						assert!(matches!(
+							TypedBuilder::<SHA1, Count32, _>::create_with_options(
+								file, "test", depth, &options
+							),
+							Err(BuilderCreateError::BucketTablesUnsupported)
+						));
+						continue;
+					}
+					let index = build(&entries, depth, &options).unwrap();
+					assert_eq!(index.index().dictionary_size().is_some(), payload_dictionary);
+					// ~12 entries per bucket: most buckets get a table
+					assert_eq!(
+						index.index().bucket_table_count().is_some(),
+						bucket_table_bits != 0
+					);
+					check(&index, &entries, &missing);
+				}
+			}
+		}
+	}
+}
+
+#[test]
+fn two_pass_builder_options() {
+	let entries = entries(3000);
+	let missing = SHA1::hash(b"not in index");
+	for compression in compressions() {
+		for checksum in [false, true] {
+			for leading_table in [false, true] {
+				let options = IndexOptions {
+					checksum,
+					table_compression: compression,
+					leading_table,
+					..IndexOptions::v1()
+				};
+				let index = build_two_pass(&entries, Depth::new(8).unwrap(), &options);
+				check(&index, &entries, &missing);
+			}
+		}
+	}
+}
+
+/// SHA-1 key with the given first two bytes (and the rest derived from `n`)
+fn key_in_bucket(first: [u8; 2], n: u32) -> SHA1 {
+	let mut key = SHA1::hash(&n.to_be_bytes());
+	key.0[..2].copy_from_slice(&first);
+	key
+}
+
+// buckets with more entries than sub-buckets get a table; check both sides of
+// the threshold and keys in every sub-bucket
+#[test]
+fn bucket_table_threshold() {
+	let bits = 3;
+	let mut entries: Vec<(SHA1, Count32)> = Vec::new();
+	// bucket 0x00: exactly 8 entries (no table); bucket 0x01: 9 entries (table)
+	for (bucket, count) in [(0x00u8, 8u32), (0x01, 9)] {
+		for n in 0..count {
+			// spread keys over all sub-buckets (next 3 bits)
+			let second = ((n % 8) as u8) << 5;
+			entries.push((key_in_bucket([bucket, second], n), Count32::new(n)));
+		}
+	}
+	entries.sort_by_key(|(key, _)| *key);
+	let options = IndexOptions { bucket_table_bits: bits, ..IndexOptions::v1() };
+	let index = build(&entries, Depth::new(8).unwrap(), &options).unwrap();
+	assert_eq!(index.index().bucket_table_count(), Some(1));
+	check(&index, &entries, &key_in_bucket([0x01, 0xff], 0));
+	check(&index, &entries, &key_in_bucket([0x01, 0x00], 1000));
+}
+
+// each bucket can store at most 256 distinct payloads in its dictionary
+#[test]
+fn payload_dictionary_limit() {
+	let options = IndexOptions { payload_dictionary: true, ..IndexOptions::v1() };
+	let depth = Depth::new(0).unwrap();
+	let mut entries: Vec<(SHA1, Count32)> =
+		(0..257u32).map(|i| (SHA1::hash(&i.to_be_bytes()), Count32::new(1000 + i))).collect();
+	entries.sort_by_key(|(key, _)| *key);
+
+	let index = build(&entries[..256], depth, &options).unwrap();
+	check(&index, &entries[..256], &entries[256].0);
+
+	assert!(matches!(
+		build(&entries, depth, &options),
+		Err(BuilderAddError::DictionaryFull { line_hint: 257 })
+	));
+}
+
+/// Two byte key: fully covered by a table of depth 16
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+struct Short([u8; 2]);
+
+impl AsRef<[u8; 2]> for Short {
+	fn as_ref(&self) -> &[u8; 2] {
+		&self.0
+	}
+}
+
+impl AsMut<[u8; 2]> for Short {
+	fn as_mut(&mut self) -> &mut [u8; 2] {
+		&mut self.0
+	}
+}
+
+impl FixedByteArrayImpl for Short {
+	type ByteArray = [u8; 2];
+	type HexArray = [u8; 4];
+}
+
+impl KeyData for Short {
+	const KEY_TYPE: KeyType = KeyType::custom("short");
+}
+
+// with depth == key bits entries only store the payload
+#[test]
+fn empty_suffix() {
+	let depth = Depth::new(16).unwrap();
+	let entries: Vec<(Short, Count32)> = (0..0x10000u32)
+		.step_by(3)
+		.map(|i| (Short((i as u16).to_be_bytes()), Count32::new(i)))
+		.collect();
+	let missing = Short([0x00, 0x01]);
+	for compression in compressions() {
+		for checksum in [false, true] {
+			for payload_dictionary in [false, true] {
+				let options = IndexOptions {
+					checksum,
+					table_compression: compression,
+					payload_dictionary,
+					..IndexOptions::v1()
+				};
+				let index = build(&entries, depth, &options).unwrap();
+				check(&index, &entries, &missing);
+			}
+			for leading_table in [false, true] {
+				let options = IndexOptions {
+					checksum,
+					table_compression: compression,
+					leading_table,
+					..IndexOptions::v1()
+				};
+				let index = build_two_pass(&entries, depth, &options);
+				check(&index, &entries, &missing);
+			}
+		}
+	}
+
+	// entries without key bytes need a payload
+	let file = tempfile::tempfile().unwrap();
+	assert!(matches!(
+		TypedBuilder::<Short, NoPayload, _>::create(file, "test", depth),
+		Err(BuilderCreateError::InvalidKeyLength)
+	));
+}
+
+/// Flags of an index built in memory from `entries`
+fn built_flags<P: hibp_index::data::PayloadData>(
+	entries: &[(SHA1, P)],
+	options: &IndexOptions,
+) -> IndexFlags {
+	let mut data = Vec::new();
+	common::write_index(std::io::Cursor::new(&mut data), entries, Depth::new(8).unwrap(), options)
+		.unwrap();
+	SliceIndex::open(&data).unwrap().capabilities().flags()
+}
+
+// the counts flag is informational: readers accept it and report it
+#[test]
+fn counts_flag() {
+	let entries = entries(100);
+	let no_payload: Vec<(SHA1, NoPayload)> =
+		entries.iter().map(|(key, _)| (*key, NoPayload)).collect();
+	for counts in [false, true] {
+		let options = IndexOptions { counts, ..IndexOptions::v1() };
+		assert_eq!(built_flags(&no_payload, &options).contains(IndexFlags::COUNTS), counts);
+	}
+
+	// count payloads are always marked (v0 has no flags)
+	let index = build(&entries, Depth::new(8).unwrap(), &IndexOptions::v1()).unwrap();
+	assert!(index.index().capabilities().flags().contains(IndexFlags::COUNTS));
+	check(&index, &entries, &SHA1::hash(b"not in index"));
+	assert!(built_flags(&entries, &IndexOptions::v1()).contains(IndexFlags::COUNTS));
+	assert!(built_flags(&entries, &IndexOptions::default()).is_empty());
+}
+
+// counts parsed from HIBP lines mark the index
+#[test]
+fn counts_flag_from_hibp_lines() {
+	let mut data = Vec::new();
+	let mut builder = TypedBuilder::<SHA1, Count32, _>::create_with_options(
+		std::io::Cursor::new(&mut data),
+		"test",
+		Depth::new(8).unwrap(),
+		&IndexOptions::v1(),
+	)
+	.unwrap();
+	builder.add_entry_from_hibp_line("000000005AD76BD555C1D6D771DE417A4B87E4B4:10").unwrap();
+	builder.finish().unwrap();
+	let index = SliceIndex::open(&data).unwrap();
+	assert!(index.capabilities().flags().contains(IndexFlags::COUNTS));
+}
+
+// the in-memory index shares the format parsing with the other readers
+#[test]
+fn slice_index_options() {
+	let entries = entries(1000);
+	for compression in compressions() {
+		for checksum in [false, true] {
+			for leading_table in [false, true] {
+				let options = IndexOptions {
+					checksum,
+					table_compression: compression,
+					leading_table,
+					..IndexOptions::v1()
+				};
+				let mut data = Vec::new();
+				common::write_index_two_pass(&mut data, &entries, Depth::new(8).unwrap(), &options);
+				let index = TypedSliceIndex::<SHA1, Count32>::open(&data).unwrap();
+				for (key, payload) in &entries {
+					assert_eq!(index.lookup(key).as_ref(), Some(payload));
+				}
+				assert_eq!(index.lookup(&SHA1::hash(b"not in index")), None);
+				assert_eq!(index.index().entry_count(), entries.len());
+			}
+		}
+	}
+
+	// lookups in payload dictionaries aren't implemented
+	let options = IndexOptions { payload_dictionary: true, ..IndexOptions::v1() };
+	let mut data = Vec::new();
+	common::write_index(
+		std::io::Cursor::new(&mut data),
+		&entries,
+		Depth::new(8).unwrap(),
+		&options,
+	)
+	.unwrap();
+	assert!(matches!(
+		SliceIndex::open(&data),
+		Err(SliceIndexError::UnsupportedFlags { flags }) if flags == IndexFlags::PAYLOAD_DICTIONARY
+	));
+}
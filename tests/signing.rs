@@ -0,0 +1,68 @@
+//! Opening signed index files
+#![cfg(feature = "signing")]
+
+use hibp_index::data::{NoPayload, SHA1};
+use hibp_index::errors::IndexVerifyError;
+use hibp_index::index::{sign_index, Depth, IndexOptions, SigningKey, TypedIndex};
+use std::io::{Seek, Write};
+
+mod common;
+
+fn build() -> std::fs::File {
+	let entries = common::entries(0..100, |_| NoPayload);
+	common::build_file(&entries, Depth::DEPTH20, &IndexOptions::default())
+}
+
+#[test]
+fn open_verified() {
+	let mut file = build();
+	let key = SigningKey::from_bytes(&[7; 32]);
+	file.rewind().unwrap();
+	let signature = sign_index(&file, &key).unwrap();
+
+	let index = TypedIndex::<SHA1, NoPayload, _>::open_verified(
+		file.try_clone().unwrap(),
+		&key.verifying_key(),
+		&signature,
+	)
+	.unwrap();
+	assert_eq!(index.lookup(&common::password_hash(42)).unwrap(), Some(NoPayload));
+
+	let other_key = SigningKey::from_bytes(&[8; 32]);
+	let err = TypedIndex::<SHA1, NoPayload, _>::open_verified(
+		file.try_clone().unwrap(),
+		&other_key.verifying_key(),
+		&signature,
+	)
+	.err()
+	.unwrap();
+	assert!(matches!(err, IndexVerifyError::InvalidSignature));
+
+	// the signature covers the complete file
+	file.seek(std::io::SeekFrom::End(0)).unwrap();
+	file.write_all(b"x").unwrap();
+	let err = TypedIndex::<SHA1, NoPayload, _>::open_verified(
+		file.try_clone().unwrap(),
+		&key.verifying_key(),
+		&signature,
+	)
+	.err()
+	.unwrap();
+	assert!(matches!(err, IndexVerifyError::InvalidSignature));
+}
+
+// the signature is checked before the file is parsed
+#[test]
+fn open_verified_malformed() {
+	let mut file = tempfile::tempfile().unwrap();
+	file.write_all(b"not an index\n").unwrap();
+	let key = SigningKey::from_bytes(&[7; 32]);
+	let mut index = build();
+	index.rewind().unwrap();
+	let signature = sign_index(&index, &key).unwrap();
+	let err =
+		TypedIndex::<SHA1, NoPayload, _>::open_verified(file, &key.verifying_key(), &signature)
+			.err()
+			.unwrap();
+	assert!(matches!(err, IndexVerifyError::InvalidSignature));
+}
@@ -0,0 +1,108 @@
+//! Range updates against a local HTTP server
+#![cfg(feature = "online")]
+
+use hibp_index::data::{NT, SHA1};
+use hibp_index::index::{Depth, LimPrefix};
+use hibp_index::online::{RangeClient, RangeUpdater, RetryPolicy};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+/// Serve empty ranges; requests for range `00002` fail while `fail` is set
+fn serve(fail: bool) -> String {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let url = format!("http://{}/range/", listener.local_addr().unwrap());
+	std::thread::spawn(move || {
+		for stream in listener.incoming() {
+			let mut stream = stream.unwrap();
+			let mut reader = BufReader::new(stream.try_clone().unwrap());
+			let mut request_line = String::new();
+			reader.read_line(&mut request_line).unwrap();
+			loop {
+				let mut line = String::new();
+				if reader.read_line(&mut line).unwrap() <= 2 {
+					break;
+				}
+			}
+			let status = if fail && request_line.contains("/range/00002") {
+				"500 Internal Server Error"
+			} else {
+				"200 OK"
+			};
+			let _ = write!(
+				stream,
+				"HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+				status
+			);
+		}
+	});
+	url
+}
+
+fn prefixes() -> Vec<LimPrefix> {
+	(0..3u8).map(|i| Depth::DEPTH20.prefix(&[0, 0, i << 4])).collect()
+}
+
+fn client(fail: bool) -> RangeClient {
+	RangeClient::with_base_url(&serve(fail)).with_retry(RetryPolicy::none())
+}
+
+// interrupted updates of one key type must not skip ranges of another
+#[test]
+fn checkpoint_per_key_type() {
+	let dir = tempfile::tempdir().unwrap();
+
+	let mut updater = RangeUpdater::<SHA1>::open(client(true), dir.path()).unwrap();
+	let summary = updater.update(prefixes()).unwrap();
+	assert!(!summary.is_complete());
+	assert_eq!(summary.checked, 2);
+
+	let mut updater = RangeUpdater::<NT>::open(client(false), dir.path()).unwrap();
+	let summary = updater.update(prefixes()).unwrap();
+	assert!(summary.is_complete());
+	assert_eq!(summary.resumed, 0);
+	assert_eq!(summary.checked, 3);
+
+	let mut updater = RangeUpdater::<SHA1>::open(client(false), dir.path()).unwrap();
+	let summary = updater.update(prefixes()).unwrap();
+	assert!(summary.is_complete());
+	assert_eq!(summary.resumed, 2);
+	assert_eq!(summary.checked, 3);
+}
+
+// a new update must not skip ranges checked by an old, partially failed one
+#[test]
+fn stale_checkpoint() {
+	let dir = tempfile::tempdir().unwrap();
+
+	let mut updater = RangeUpdater::<SHA1>::open(client(true), dir.path()).unwrap();
+	let summary = updater.update(prefixes()).unwrap();
+	assert!(!summary.is_complete());
+
+	// too old to resume
+	let mut updater = RangeUpdater::<SHA1>::open(client(true), dir.path()).unwrap();
+	updater.set_resume_max_age(Duration::ZERO);
+	let summary = updater.update(prefixes()).unwrap();
+	assert!(!summary.is_complete());
+	assert_eq!(summary.resumed, 0);
+	assert_eq!(summary.checked, 2);
+
+	// explicitly started over
+	let mut updater = RangeUpdater::<SHA1>::open(client(false), dir.path()).unwrap();
+	updater.clear_checkpoint().unwrap();
+	let summary = updater.update(prefixes()).unwrap();
+	assert!(summary.is_complete());
+	assert_eq!(summary.resumed, 0);
+	assert_eq!(summary.checked, 3);
+}
+
+// checkpoints without start time (or from another format) are never resumed
+#[test]
+fn checkpoint_without_start_time() {
+	let dir = tempfile::tempdir().unwrap();
+	std::fs::write(dir.path().join("sha1-update.checkpoint"), "00000\n00001\n").unwrap();
+	let mut updater = RangeUpdater::<SHA1>::open(client(false), dir.path()).unwrap();
+	let summary = updater.update(prefixes()).unwrap();
+	assert_eq!(summary.resumed, 0);
+	assert_eq!(summary.checked, 3);
+}
@@ -0,0 +1,62 @@
+//! Order checks of stored entries (`Index::verify_order`, `verify_stream`)
+
+use hibp_index::buf_read::SeekReader;
+use hibp_index::data::NoPayload;
+use hibp_index::errors::{OrderError, StreamVerifyError};
+use hibp_index::index::{verify_stream, Depth, Index, IndexOptions};
+use std::io::Cursor;
+
+mod common;
+
+/// Index with leading table (so it can be verified as stream); depth 4 keeps
+/// prefix bits in the first stored byte
+fn build() -> Vec<u8> {
+	let entries = common::entries(0..2000, |_| NoPayload);
+	let options = IndexOptions { leading_table: true, ..IndexOptions::v1() };
+	let mut data = Vec::new();
+	common::write_index_two_pass(&mut data, &entries, Depth::new(4).unwrap(), &options);
+	data
+}
+
+fn open(data: &[u8]) -> Index<SeekReader<Cursor<Vec<u8>>>> {
+	Index::open(SeekReader::new(Cursor::new(data.to_vec()))).unwrap()
+}
+
+#[test]
+fn ordered() {
+	let data = build();
+	assert_eq!(open(&data).verify_order().unwrap(), 2000);
+	assert_eq!(verify_stream(data.as_slice()).unwrap().entries, 2000);
+}
+
+#[test]
+fn out_of_order_and_duplicate() {
+	let data = build();
+	let index = open(&data);
+	let entry_size = index.entry_size();
+	let bucket = index.bucket_ranges().nth(3).unwrap();
+	assert!(bucket.end - bucket.start >= 2 * entry_size as u64);
+	let first = bucket.start as usize;
+	let second = first + entry_size;
+
+	let mut swapped = data.clone();
+	swapped[first..second + entry_size].rotate_left(entry_size);
+	let err = open(&swapped).verify_order().unwrap_err();
+	assert!(matches!(err, OrderError::OutOfOrder { offset, .. } if offset == second as u64));
+	let err = verify_stream(swapped.as_slice()).unwrap_err();
+	assert!(matches!(err, StreamVerifyError::OutOfOrder { bucket: 3, .. }));
+
+	let mut duplicate = data.clone();
+	duplicate.copy_within(first..second, second);
+	let err = open(&duplicate).verify_order().unwrap_err();
+	assert!(matches!(err, OrderError::Duplicate { offset, .. } if offset == second as u64));
+	let err = verify_stream(duplicate.as_slice()).unwrap_err();
+	assert!(matches!(err, StreamVerifyError::OutOfOrder { bucket: 3, .. }));
+
+	let mut prefix_bits = data.clone();
+	prefix_bits[first] |= 0xf0;
+	let err = open(&prefix_bits).verify_order().unwrap_err();
+	assert!(matches!(err, OrderError::InvalidKey { offset, .. } if offset == first as u64));
+	let err = verify_stream(prefix_bits.as_slice()).unwrap_err();
+	assert!(matches!(err, StreamVerifyError::InvalidKey { bucket: 3, .. }));
+}